@@ -0,0 +1,108 @@
+// SPDX-License-Identifier: MIT or GPL-2.0
+
+#![allow(unused_variables)]
+#![allow(dead_code)]
+#![deny(clippy::implicit_return)]
+#![allow(clippy::needless_return)]
+
+use serde_json::json;
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::{debug, info};
+use crate::stdio;
+
+// how long unsaved changes can sit before they're written out, short enough
+// that not much is lost to a dropped ssh session
+pub const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(15);
+
+// what a session had that wasn't saved, which is only ever read back by the
+// next session for the same config
+pub struct Autosave {
+	pub config: String,
+	pub total_system_memory: u64,
+	// a whole config, as "save" would have written it, but always yaml
+	pub contents: String,
+}
+
+pub fn session_file(config_file: &Path) -> Option<PathBuf>
+{
+	// a config on stdin is gone once read, so there's nothing to restore into
+	if stdio::is_stdio(config_file) {
+		return None
+	}
+
+	// one per config, so that sessions on different configs don't trample
+	// over each other, wherever they were started from
+	let config_file = fs::canonicalize(config_file).unwrap_or_else(|_| {
+		return config_file.to_path_buf()
+	});
+	let mut hasher = DefaultHasher::new();
+	config_file.hash(&mut hasher);
+	let name = format!("seg-configurator-{:016x}.session.json", hasher.finish());
+
+	return Some(std::env::temp_dir().join(name))
+}
+
+pub fn write(config_file: &Path, autosave: &Autosave) -> io::Result<()>
+{
+	let session_file = match session_file(config_file) {
+		Some(session_file) => session_file,
+		None => return Ok(()),
+	};
+
+	let session = json!({
+		"config": autosave.config,
+		"total-system-memory": autosave.total_system_memory,
+		"contents": autosave.contents,
+	});
+
+	// written alongside & moved over, so that a session that goes away
+	// halfway through writing doesn't leave half a file to be restored
+	let partial_file = session_file.with_extension("partial");
+	fs::write(&partial_file, session.to_string() + "\n")?;
+	fs::rename(&partial_file, &session_file)?;
+	debug!("autosaved to {}", session_file.display());
+
+	return Ok(())
+}
+
+pub fn read(config_file: &Path) -> Result<Option<Autosave>, String>
+{
+	let session_file = match session_file(config_file) {
+		Some(session_file) => session_file,
+		None => return Ok(None),
+	};
+	let contents = match fs::read_to_string(&session_file) {
+		Ok(contents) => contents,
+		Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+		Err(error) => return Err(format!("{}: {}", session_file.display(), error)),
+	};
+
+	let error = |what: &str| return format!("{}: {}", session_file.display(), what);
+	let session: serde_json::Value = serde_json::from_str(&contents)
+		.map_err(|parse_error| return error(&parse_error.to_string()))?;
+	let total_system_memory = session["total-system-memory"].as_u64()
+		.ok_or_else(|| return error("total-system-memory is missing or not a number"))?;
+	let contents = session["contents"].as_str()
+		.ok_or_else(|| return error("contents is missing"))?;
+
+	return Ok(Some(Autosave {
+		config: session["config"].as_str().unwrap_or_default().to_string(),
+		total_system_memory,
+		contents: contents.to_string(),
+	}))
+}
+
+pub fn remove(config_file: &Path)
+{
+	// there being nothing to remove is the usual case
+	if let Some(session_file) = session_file(config_file) {
+		if fs::remove_file(&session_file).is_ok() {
+			info!("removed {}", session_file.display());
+		}
+	}
+}
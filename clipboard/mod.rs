@@ -0,0 +1,95 @@
+// SPDX-License-Identifier: MIT or GPL-2.0
+
+#![allow(unused_variables)]
+#![allow(dead_code)]
+#![deny(clippy::implicit_return)]
+#![allow(clippy::needless_return)]
+
+use std::env;
+use std::io;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use tracing::debug;
+
+// whichever of these is installed & has a display to talk to, tried in order
+const TOOLS: [(&str, &[&str]); 4] = [
+	("pbcopy", &[]),
+	("wl-copy", &[]),
+	("xclip", &["-selection", "clipboard"]),
+	("xsel", &["--clipboard", "--input"]),
+];
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+// puts text on the clipboard, returning how it got there
+pub fn copy(text: &str) -> io::Result<&'static str>
+{
+	// a clipboard tool on the far end of an ssh session would only fill the
+	// clipboard of a machine nobody is sitting at, the terminal's is the one
+	// that's wanted
+	let remote = env::var_os("SSH_CONNECTION").is_some() || env::var_os("SSH_TTY").is_some();
+	if !remote {
+		for (tool, tool_args) in TOOLS {
+			if copy_with(tool, tool_args, text).is_ok() {
+				return Ok(tool)
+			}
+		}
+	}
+
+	osc52(text)?;
+	return Ok("the terminal")
+}
+
+fn copy_with(tool: &str, tool_args: &[&str], text: &str) -> io::Result<()>
+{
+	let mut child = Command::new(tool)
+		.args(tool_args)
+		.stdin(Stdio::piped())
+		.stdout(Stdio::null())
+		.stderr(Stdio::null())
+		.spawn()?;
+	child.stdin.take().unwrap().write_all(text.as_bytes())?;
+	let status = child.wait()?;
+	debug!("{} exited with {}", tool, status);
+	if !status.success() {
+		return Err(io::Error::other(format!("{} failed", tool)))
+	}
+
+	return Ok(())
+}
+
+fn osc52(text: &str) -> io::Result<()>
+{
+	// asks the terminal itself to set the clipboard, which works over ssh but
+	// is quietly ignored by terminals that don't support it. tmux only passes
+	// it through to the terminal outside when wrapped up
+	let sequence = format!("\x1b]52;c;{}\x07", base64(text.as_bytes()));
+	let sequence = match env::var_os("TMUX") {
+		Some(_) => format!("\x1bPtmux;{}\x1b\\", sequence.replace('\x1b', "\x1b\x1b")),
+		None => sequence,
+	};
+
+	let mut stdout = io::stdout();
+	stdout.write_all(sequence.as_bytes())?;
+	return stdout.flush()
+}
+
+fn base64(bytes: &[u8]) -> String
+{
+	let mut encoded = String::new();
+	for chunk in bytes.chunks(3) {
+		let word = chunk.iter()
+			.enumerate()
+			.fold(0u32, |word, (i, byte)| return word | (*byte as u32) << (16 - 8 * i));
+		// a partial chunk is padded out with "=" in place of its missing bytes
+		for i in 0..4 {
+			let sextet = (word >> (18 - 6 * i) & 0x3f) as usize;
+			match i <= chunk.len() {
+				true => encoded.push(BASE64[sextet] as char),
+				false => encoded.push('='),
+			}
+		}
+	}
+
+	return encoded
+}
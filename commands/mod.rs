@@ -0,0 +1,297 @@
+// SPDX-License-Identifier: MIT or GPL-2.0
+
+#![allow(unused_variables)]
+#![allow(dead_code)]
+#![deny(clippy::implicit_return)]
+#![allow(clippy::needless_return)]
+
+use crate::config::ConfigFormat;
+use crate::export;
+use crate::export::ExportFormat;
+use crate::units;
+use std::path::PathBuf;
+
+pub struct Command {
+	pub name: &'static str,
+	pub syntax: &'static str,
+	pub example: &'static str,
+	pub description: &'static str,
+}
+
+pub const COMMANDS: [Command; 17] = [
+	Command {
+		name: "set",
+		syntax: "set <register> <address>",
+		example: "set seg0_1 0x02000000",
+		description: "set the hardware start address of an aperture",
+	},
+	Command {
+		name: "setseg",
+		syntax: "setseg <register> <seg value>",
+		example: "setseg seg0_1 0x7f00",
+		description: "set an aperture from a raw seg register value, showing how it decodes",
+	},
+	Command {
+		name: "size",
+		syntax: "size <register> <size>",
+		example: "size seg1_2 512M",
+		description: "resize an aperture, offering to reflow the others",
+	},
+	Command {
+		name: "mem",
+		syntax: "mem <size>",
+		example: "mem 2G",
+		description: "set the total system memory",
+	},
+	Command {
+		name: "save",
+		syntax: "save [--dry-run] [--format <f>] [<file>]",
+		example: "save --format json board.json",
+		description: "write the seg-reg-config to the output file, or change where it goes",
+	},
+	Command {
+		name: "load",
+		syntax: "load <file>",
+		example: "load config.yaml",
+		description: "replace the seg values with those from a config file",
+	},
+	Command {
+		name: "dtb",
+		syntax: "dtb <file>",
+		example: "dtb board.dtb",
+		description: "replace the dt memory nodes & reserved memory with those from a dtb",
+	},
+	Command {
+		name: "reset",
+		syntax: "reset",
+		example: "reset",
+		description: "put every aperture back to its default start address",
+	},
+	Command {
+		name: "via",
+		syntax: "via <dt node> <register>",
+		example: "via memory@1000000000 seg0_1",
+		description: "pick the aperture a dt node goes through, if several could",
+	},
+	Command {
+		name: "node",
+		syntax: "node <dt node> <address> <size>",
+		example: "node memory@1000000000 0x1000000000 1G",
+		description: "add a dt memory node, or move & resize an existing one",
+	},
+	Command {
+		name: "rmnode",
+		syntax: "rmnode <dt node>",
+		example: "rmnode memory@1000000000",
+		description: "delete a dt memory node",
+	},
+	Command {
+		name: "overlay",
+		syntax: "overlay <file>",
+		example: "overlay memory.dtbo",
+		description: "write a dt overlay of the memory nodes that differ from the dtb",
+	},
+	Command {
+		name: "auto",
+		syntax: "auto [<memory size>]",
+		example: "auto 2G",
+		description: "lay every aperture out back to back from address 0",
+	},
+	Command {
+		name: "place",
+		syntax: "place",
+		example: "place",
+		description: "place the named regions in ddr, behind the kind of aperture asked for",
+	},
+	Command {
+		name: "export",
+		syntax: "export <format> [<file>]",
+		example: "export uboot boot.cmd",
+		description: "write bootloader settings matching the apertures, or show them",
+	},
+	Command {
+		name: "copy",
+		syntax: "copy",
+		example: "copy",
+		description: "copy the seg-reg-config line to the clipboard",
+	},
+	Command {
+		name: "fix",
+		syntax: "fix [<n>|all]",
+		example: "fix all",
+		description: "apply the suggested fix for a problem, or pick one from a list",
+	},
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FixChoice {
+	Pick,
+	// counted from 1, as they're listed
+	One(usize),
+	All,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParsedCommand {
+	Set { reg_name: String, addr: u64 },
+	SetSeg { reg_name: String, seg: u64 },
+	Size { reg_name: String, size: u64 },
+	Mem(u64),
+	Save { dry_run: bool, file: Option<PathBuf>, format: Option<ConfigFormat> },
+	Load(PathBuf),
+	Dtb(PathBuf),
+	Copy,
+	Reset,
+	Via { label: String, reg_name: String },
+	Node { label: String, address: u64, size: u64 },
+	RemoveNode(String),
+	Overlay(PathBuf),
+	Auto(Option<u64>),
+	Place,
+	Export { format: ExportFormat, file: Option<PathBuf> },
+	Fix(FixChoice),
+}
+
+fn parse_seg(input: &str) -> Result<u64, String>
+{
+	// the register is only 15 bits wide
+	let seg = units::parse_size(input).map_err(|error| return error.to_string())?;
+	if seg > 0x7FFF {
+		return Err(format!("{:#x} doesn't fit in a 15 bit seg register.", seg))
+	}
+
+	return Ok(seg)
+}
+
+fn parse_save(words: &[&str], usage: &str) -> Result<ParsedCommand, String>
+{
+	// the options & the file can come in any order, but only once each
+	let (mut dry_run, mut file, mut format) = (false, None, None);
+	let mut words = words.iter();
+	while let Some(word) = words.next() {
+		match *word {
+			"--dry-run" if !dry_run => dry_run = true,
+			"--format" if format.is_none() => {
+				let name = words.next().ok_or(usage)?;
+				let parsed = clap::ArgEnum::from_str(name, true);
+				format = Some(parsed.map_err(|_| {
+					return format!("unknown format {}, expected yaml or json",
+						       name)
+				})?);
+			}
+			_ if file.is_none() && !word.starts_with("--") => {
+				file = Some(PathBuf::from(word));
+			}
+			_ => return Err(usage.to_string()),
+		}
+	}
+
+	return Ok(ParsedCommand::Save { dry_run, file, format })
+}
+
+pub fn parse(input: &str) -> Option<Result<ParsedCommand, String>>
+{
+	// returns None if the input doesn't start with a known command, so that
+	// it can be handed on to whatever prompt is currently being shown
+	let words: Vec<&str> = input.split_whitespace().collect();
+	let command = find_command(words.first()?)?;
+	let usage = format!("usage: {}", command.syntax);
+
+	let parsed = match (command.name, &words[1..]) {
+		("set", [reg_name, addr]) => units::parse_size(addr)
+			.map(|addr| return ParsedCommand::Set {
+				reg_name: reg_name.to_string(),
+				addr
+			})
+			.map_err(|error| return error.to_string()),
+		("setseg", [reg_name, seg]) => parse_seg(seg)
+			.map(|seg| return ParsedCommand::SetSeg {
+				reg_name: reg_name.to_string(),
+				seg
+			}),
+		("size", [reg_name, size]) => units::parse_size(size)
+			.map(|size| return ParsedCommand::Size {
+				reg_name: reg_name.to_string(),
+				size
+			})
+			.map_err(|error| return error.to_string()),
+		("mem", [size]) => units::parse_size(size)
+			.map(ParsedCommand::Mem)
+			.map_err(|error| return error.to_string()),
+		("save", options) => parse_save(options, &usage),
+		("load", [file]) => Ok(ParsedCommand::Load(PathBuf::from(file))),
+		("dtb", [file]) => Ok(ParsedCommand::Dtb(PathBuf::from(file))),
+		("reset", []) => Ok(ParsedCommand::Reset),
+		("copy", []) => Ok(ParsedCommand::Copy),
+		("via", [label, reg_name]) => Ok(ParsedCommand::Via {
+			label: label.to_string(),
+			reg_name: reg_name.to_string()
+		}),
+		("node", [label, address, size]) => units::parse_size(address)
+			.and_then(|address| return units::parse_size(size)
+				.map(|size| return ParsedCommand::Node {
+					label: label.to_string(),
+					address,
+					size
+				}))
+			.map_err(|error| return error.to_string()),
+		("rmnode", [label]) => Ok(ParsedCommand::RemoveNode(label.to_string())),
+		("overlay", [file]) => Ok(ParsedCommand::Overlay(PathBuf::from(file))),
+		("auto", []) => Ok(ParsedCommand::Auto(None)),
+		("auto", [size]) => units::parse_size(size)
+			.map(|size| return ParsedCommand::Auto(Some(size)))
+			.map_err(|error| return error.to_string()),
+		("place", []) => Ok(ParsedCommand::Place),
+		("export", [format]) | ("export", [format, _]) => export::parse_format(format)
+			.map(|format| return ParsedCommand::Export {
+				format,
+				file: words.get(2).map(PathBuf::from)
+			})
+			.ok_or(format!("unknown format {}, expected one of {}", format,
+				       export::format_names())),
+		("fix", []) => Ok(ParsedCommand::Fix(FixChoice::Pick)),
+		("fix", ["all"]) => Ok(ParsedCommand::Fix(FixChoice::All)),
+		("fix", [number]) => number.parse::<usize>()
+			.ok()
+			.filter(|number| return *number > 0)
+			.map(|number| return ParsedCommand::Fix(FixChoice::One(number)))
+			.ok_or(usage),
+		_ => Err(usage),
+	};
+
+	return Some(parsed)
+}
+
+pub fn find_command(name: &str) -> Option<&'static Command>
+{
+	return COMMANDS.iter().find(|command| return command.name == name)
+}
+
+pub fn help_for(input: &str) -> Option<String>
+{
+	// "<command>?" asks for help on that command
+	let name = input.trim().strip_suffix('?')?.trim();
+	let command = find_command(name)?;
+
+	return Some(format!("{}: {} (e.g. \"{}\") - {}",
+			    command.name, command.syntax, command.example,
+			    command.description))
+}
+
+pub fn complete(input: &str) -> Option<String>
+{
+	// only the command name itself is completed, and only when there's
+	// exactly one command it could be
+	if input.contains(' ') {
+		return None
+	}
+
+	let mut candidates = COMMANDS.iter()
+		.filter(|command| return command.name.starts_with(input));
+	let candidate = candidates.next()?;
+	if candidates.next().is_some() {
+		return None
+	}
+
+	return Some(format!("{} ", candidate.name))
+}
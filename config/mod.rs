@@ -0,0 +1,768 @@
+// SPDX-License-Identifier: MIT or GPL-2.0
+
+#![allow(unused_variables)]
+#![allow(dead_code)]
+#![deny(clippy::implicit_return)]
+#![allow(clippy::needless_return)]
+
+use serde_yaml::Mapping;
+use serde_yaml::Value;
+use std::path::Path;
+
+use crate::soc;
+use crate::soc::SoC;
+use crate::units;
+
+#[derive(clap::ArgEnum, Clone, Copy, Debug, PartialEq)]
+pub enum ConfigFormat {
+	Yaml,
+	Json,
+}
+
+pub fn detect_format(file: &Path, forced_format: Option<ConfigFormat>) -> ConfigFormat
+{
+	if let Some(format) = forced_format {
+		return format
+	}
+
+	let extension = file.extension();
+	if extension.is_some() && extension.unwrap().eq_ignore_ascii_case("json") {
+		return ConfigFormat::Json
+	}
+
+	return ConfigFormat::Yaml
+}
+
+pub fn parse_config(contents: &str, format: ConfigFormat)
+-> Result<Value, Box<dyn std::error::Error>>
+{
+	// the yaml Value is used for both, so that the rest of the program
+	// doesn't need to care where the config came from
+	match format {
+		ConfigFormat::Yaml => return Ok(serde_yaml::from_str(contents)?),
+		ConfigFormat::Json => return Ok(serde_json::from_str(contents)?),
+	}
+}
+
+pub fn serialize_config(config: &Value, format: ConfigFormat)
+-> Result<String, Box<dyn std::error::Error>>
+{
+	match format {
+		ConfigFormat::Yaml => return Ok(serde_yaml::to_string(config)?),
+		ConfigFormat::Json => return Ok(serde_json::to_string_pretty(config)? + "\n"),
+	}
+}
+
+fn find_top_level_key(contents: &str, key: &str) -> Option<usize>
+{
+	// returns the offset just past the "key:" of an unindented mapping key
+	let mut offset = 0;
+	for line in contents.split_inclusive('\n') {
+		if let Some(rest) = line.strip_prefix(key) {
+			if rest.trim_start().starts_with(':') {
+				let colon = rest.find(':').unwrap();
+				return Some(offset + key.len() + colon + 1)
+			}
+		}
+		offset += line.len();
+	}
+
+	return None
+}
+
+fn value_span(contents: &str, start: usize, terminators: &[char]) -> (usize, usize)
+{
+	// the span of a scalar value starting at (or after whitespace following)
+	// start, including any quotes but not trailing whitespace or comments
+	let rest = &contents[start..];
+	let leading = rest.len() - rest.trim_start_matches([' ', '\t']).len();
+	let value_start = start + leading;
+	let value = &contents[value_start..];
+
+	if value.starts_with('\'') || value.starts_with('"') {
+		let quote = value.chars().next().unwrap();
+		let closing = value[1..].find(quote).map(|i| return i + 2).unwrap_or(value.len());
+		return (value_start, value_start + closing)
+	}
+
+	let mut end = value.find(|c: char| return terminators.contains(&c) || c == '\n')
+		.unwrap_or(value.len());
+	if let Some(comment) = value[..end].find(" #") {
+		end = comment;
+	}
+
+	return (value_start, value_start + value[..end].trim_end().len())
+}
+
+fn quote_like(old_value: &str, new_value: &str) -> String
+{
+	if old_value.starts_with('"') {
+		return format!("\"{}\"", new_value)
+	}
+	if old_value.is_empty() || old_value.starts_with('\'') {
+		return format!("'{}'", new_value)
+	}
+
+	return new_value.to_string()
+}
+
+fn find_flow_key(contents: &str, start: usize, end: usize, key: &str) -> Option<usize>
+{
+	// find "key:" inside a flow mapping, making sure that it's not just the
+	// tail end of some longer key
+	let mut search_from = start;
+	while let Some(found) = contents[search_from..end].find(key) {
+		let key_start = search_from + found;
+		let key_end = key_start + key.len();
+		let before = contents[..key_start].trim_end_matches([' ', '\t', '\n']);
+		let after = contents[key_end..end].trim_start_matches([' ', '\t']);
+		if (before.ends_with('{') || before.ends_with(',')) && after.starts_with(':') {
+			return Some(key_end + (contents[key_end..end].len() - after.len()) + 1)
+		}
+		search_from = key_end;
+	}
+
+	return None
+}
+
+fn update_flow_mapping(contents: &mut String, open_brace: usize, segs: &[(String, String)])
+-> Option<()>
+{
+	for (reg_name, seg_value) in segs {
+		let close_brace = open_brace + contents[open_brace..].find('}')?;
+		match find_flow_key(contents, open_brace, close_brace, reg_name) {
+			Some(value_start) => {
+				let (start, end) = value_span(contents, value_start, &[',', '}']);
+				let new_value = quote_like(&contents[start..end], seg_value);
+				contents.replace_range(start..end, &new_value);
+			}
+			None => {
+				// tack missing registers onto the end of the mapping,
+				// keeping any trailing comma style that's already there
+				let inner = contents[open_brace + 1..close_brace].trim_end();
+				let insert_at = open_brace + 1 + inner.len();
+				let entry = format!("{}: '{}'", reg_name, seg_value);
+				if inner.is_empty() {
+					contents.insert_str(insert_at, &entry);
+				} else if inner.ends_with(',') {
+					contents.insert_str(insert_at, &format!(" {},", entry));
+				} else {
+					contents.insert_str(insert_at, &format!(", {}", entry));
+				}
+			}
+		}
+	}
+
+	return Some(())
+}
+
+fn update_block_mapping(contents: &mut String, key_end: usize, segs: &[(String, String)])
+-> Option<()>
+{
+	let block_start = key_end + contents[key_end..].find('\n')? + 1;
+	let mut indent = "  ".to_string();
+
+	for (reg_name, seg_value) in segs {
+		let mut offset = block_start;
+		let mut block_end = contents.len();
+		let mut found = false;
+
+		for line in contents[block_start..].split_inclusive('\n') {
+			let trimmed = line.trim_start_matches([' ', '\t']);
+			let is_blank = trimmed.trim().is_empty() || trimmed.starts_with('#');
+			if !is_blank && trimmed.len() == line.len() {
+				// back at the top level, so the block is over
+				block_end = offset;
+				break;
+			}
+
+			if !is_blank {
+				indent = line[..line.len() - trimmed.len()].to_string();
+			}
+
+			if let Some(rest) = trimmed.strip_prefix(reg_name.as_str()) {
+				if rest.trim_start().starts_with(':') {
+					let value_start = offset + (line.len() - rest.len())
+							  + rest.find(':').unwrap() + 1;
+					let (start, end) = value_span(contents, value_start, &[]);
+					let new_value = quote_like(&contents[start..end], seg_value);
+					let leading = if start == value_start { " " } else { "" };
+					contents.replace_range(start..end,
+							       &format!("{}{}", leading, new_value));
+					found = true;
+					break;
+				}
+			}
+			offset += line.len();
+		}
+
+		if !found {
+			let mut prefix = "";
+			if block_end > 0 && !contents[..block_end].ends_with('\n') {
+				prefix = "\n";
+			}
+			contents.insert_str(block_end,
+					    &format!("{}{}{}: '{}'\n", prefix, indent,
+						     reg_name, seg_value));
+		}
+	}
+
+	return Some(())
+}
+
+pub fn update_schema_version(contents: &str) -> Option<String>
+{
+	// the version is a plain top level scalar, so either swap its value or
+	// tack it onto the end like a missing seg-reg-config would be
+	let mut updated = contents.to_string();
+	let version = SCHEMA_VERSION.to_string();
+
+	match find_top_level_key(contents, "schema-version") {
+		Some(key_end) => {
+			let (start, end) = value_span(contents, key_end, &[]);
+			if start == key_end {
+				updated.replace_range(start..end, &format!(" {}", version));
+			} else {
+				updated.replace_range(start..end, &version);
+			}
+		}
+		None => {
+			if !updated.is_empty() && !updated.ends_with('\n') {
+				updated.push('\n');
+			}
+			updated += &format!("schema-version: {}\n", version);
+		}
+	}
+
+	return Some(updated)
+}
+
+pub fn update_seg_values(contents: &str, segs: &[(String, String)]) -> Option<String>
+{
+	return update_mapping_values(contents, "seg-reg-config", segs)
+}
+
+pub fn update_mapping_values(contents: &str, mapping: &str, segs: &[(String, String)])
+-> Option<String>
+{
+	// rewrite only the given values in the original text, so that comments,
+	// key order and formatting all survive a round trip through the tool.
+	// returns None if the layout isn't understood & a full re-serialise is
+	// the only option
+	let mut updated = contents.to_string();
+	if segs.is_empty() {
+		return Some(updated)
+	}
+
+	let key_end = find_top_level_key(contents, mapping);
+	if key_end.is_none() {
+		if !updated.is_empty() && !updated.ends_with('\n') {
+			updated.push('\n');
+		}
+		let entries: Vec<String> = segs.iter()
+			.map(|(reg_name, seg_value)| return format!("{}: '{}'", reg_name, seg_value))
+			.collect();
+		updated += &format!("{}: {{{}}}\n", mapping, entries.join(", "));
+		return Some(updated)
+	}
+
+	let key_end = key_end.unwrap();
+	let after_key = &contents[key_end..];
+	let value = after_key.trim_start_matches([' ', '\t']);
+
+	if value.starts_with('{') {
+		let open_brace = key_end + (after_key.len() - value.len());
+		update_flow_mapping(&mut updated, open_brace, segs)?;
+	} else if value.starts_with('\n') || value.starts_with('#') || value.is_empty() {
+		update_block_mapping(&mut updated, key_end, segs)?;
+	} else {
+		return None
+	}
+
+	return Some(updated)
+}
+
+pub fn parse_seg_value(value: &Value) -> Result<Option<u64>, String>
+{
+	// seg values turn up quoted or not, in upper or lower case and with or
+	// without leading zeros, all of which mean the same thing. unquoted hex
+	// has already been turned into a number by the yaml parser
+	if value.is_null() {
+		return Ok(None)
+	}
+
+	if let Some(number) = value.as_u64() {
+		return Ok(Some(number))
+	}
+
+	let seg_string = value.as_str()
+		.ok_or(format!("{:?} is not a valid seg value", value))?;
+	let seg_trimmed = seg_string.trim();
+	let seg_hex = seg_trimmed.strip_prefix("0x")
+		.or(seg_trimmed.strip_prefix("0X"))
+		.unwrap_or(seg_trimmed);
+
+	return u64::from_str_radix(seg_hex, 16)
+		.map(Some)
+		.map_err(|error| return format!("\"{}\" is not a valid seg value: {}",
+						 seg_string, error))
+}
+
+// bumped whenever the layout of the config changes, with a migration in
+// migrate_config() to bring older configs up to date
+pub const SCHEMA_VERSION: u64 = 1;
+
+// how seg-reg-config was spelt by older releases
+const OLD_SEG_KEYS: [&str; 2] = ["seg_reg_config", "seg-regs"];
+
+pub fn migrate_config(config: &mut Value) -> Result<Vec<String>, String>
+{
+	// returns a description of each change made, so that they can be warned
+	// about rather than somebody wondering where their seg values went
+	let mut changes: Vec<String> = Vec::new();
+	let version = match &config["schema-version"] {
+		Value::Null => 0,
+		value => value.as_u64()
+			.ok_or(format!("schema-version {:?} is not a number", value))?,
+	};
+	if version > SCHEMA_VERSION {
+		return Err(format!("schema-version {} is newer than the {} this version \
+				    understands", version, SCHEMA_VERSION))
+	}
+	if version == SCHEMA_VERSION {
+		return Ok(changes)
+	}
+
+	let mapping = config.as_mapping_mut();
+	if mapping.is_none() {
+		return Ok(changes)
+	}
+
+	let mapping = mapping.unwrap();
+	let seg_key = Value::String("seg-reg-config".to_string());
+	for old_key in OLD_SEG_KEYS {
+		let old_segs = mapping.remove(&Value::String(old_key.to_string()));
+		if old_segs.is_none() {
+			continue;
+		}
+
+		if mapping.contains_key(&seg_key) {
+			changes.push(format!("dropped {}, as seg-reg-config is set too", old_key));
+			continue;
+		}
+		mapping.insert(seg_key.clone(), old_segs.unwrap());
+		changes.push(format!("renamed {} to seg-reg-config", old_key));
+	}
+
+	// a list of single entry mappings, rather than one mapping
+	if let Some(Value::Sequence(entries)) = mapping.get(&seg_key).cloned() {
+		let mut segs = Mapping::new();
+		for entry in entries {
+			let entry = entry.as_mapping()
+				.ok_or(format!("{:?} is not a seg register & value", entry))?;
+			for (reg_name, seg) in entry {
+				segs.insert(reg_name.clone(), seg.clone());
+			}
+		}
+		mapping.insert(seg_key.clone(), Value::Mapping(segs));
+		changes.push("turned the seg-reg-config list into a mapping".to_string());
+	}
+
+	// register names in upper case, like the libero defines
+	if let Some(Value::Mapping(segs)) = mapping.get_mut(&seg_key) {
+		let mut renamed = Mapping::new();
+		for (reg_name, seg) in segs.iter() {
+			let lower_case = reg_name.as_str().map(|name| return name.to_ascii_lowercase());
+			if lower_case.is_none() || reg_name.as_str() == lower_case.as_deref() {
+				renamed.insert(reg_name.clone(), seg.clone());
+				continue;
+			}
+
+			let lower_case = lower_case.unwrap();
+			changes.push(format!("renamed {} to {}", reg_name.as_str().unwrap(),
+					     lower_case));
+			renamed.insert(Value::String(lower_case), seg.clone());
+		}
+		*segs = renamed;
+	}
+
+	return Ok(changes)
+}
+
+// the config is shared with the hss payload generator, so its keys are just
+// as valid as the ones that this tool adds
+pub const KNOWN_KEYS: [&str; 11] = [
+	"schema-version",
+	"theme",
+	"set-name",
+	"hart-entry-points",
+	"payloads",
+	"seg-reg-config",
+	"contexts",
+	"bus-masters",
+	"dt-node-apertures",
+	"aperture-access",
+	"memory-banks",
+];
+
+pub fn key_line(contents: &str, key: &str, from_line: usize) -> Option<usize>
+{
+	// the (1 based) line number of the first "key:" on or after from_line,
+	// quoted or not. good enough to point somebody at the right place, for
+	// yaml & json alike, without needing a parser that keeps track of marks
+	let is_key_char = |c: char| return c.is_ascii_alphanumeric() || c == '_' || c == '-';
+
+	for (index, line) in contents.lines().enumerate().skip(from_line.saturating_sub(1)) {
+		let mut search_from = 0;
+		while let Some(found) = line[search_from..].find(key) {
+			let key_start = search_from + found;
+			let key_end = key_start + key.len();
+			search_from = key_end;
+
+			let before = line[..key_start].trim_end_matches(['"', '\'']);
+			if before.ends_with(is_key_char) {
+				continue;
+			}
+
+			let after = line[key_end..].trim_start_matches(['"', '\''])
+				.trim_start_matches([' ', '\t']);
+			if after.starts_with(':') {
+				return Some(index + 1)
+			}
+		}
+	}
+
+	return None
+}
+
+fn at_line(file: &str, line: Option<usize>, message: String) -> String
+{
+	match line {
+		Some(line) => return format!("{}:{}: {}", file, line, message),
+		None => return format!("{}: {}", file, message),
+	}
+}
+
+pub fn check_strict(file: &str, contents: &str, format: ConfigFormat, reg_names: &[&str])
+-> Result<(), String>
+{
+	// everything that a normal load would quietly skip over, reported all at
+	// once rather than making somebody fix them one at a time
+	let mut config = parse_config(contents, format)
+		.map_err(|error| return format!("{}: {}", file, error))?;
+	migrate_config(&mut config).map_err(|error| return format!("{}: {}", file, error))?;
+	if config.is_null() {
+		return Ok(())
+	}
+
+	let mapping = config.as_mapping()
+		.ok_or(format!("{}: the config should be a mapping", file))?;
+	let mut errors: Vec<String> = Vec::new();
+
+	for (key, _) in mapping {
+		let name = key.as_str();
+		if name.is_none() {
+			errors.push(format!("{}: {:?} is not a valid key", file, key));
+			continue;
+		}
+
+		let name = name.unwrap();
+		if !KNOWN_KEYS.contains(&name) {
+			errors.push(at_line(file, key_line(contents, name, 1),
+					    format!("unknown key \"{}\"", name)));
+		}
+	}
+
+	let seg_config = &config["seg-reg-config"];
+	let seg_config_line = key_line(contents, "seg-reg-config", 1);
+	if !seg_config.is_null() && !seg_config.is_mapping() {
+		errors.push(at_line(file, seg_config_line,
+				    "seg-reg-config should be a mapping of register to seg \
+				     value".to_string()));
+	}
+
+	for (reg_name, seg) in seg_config.as_mapping().into_iter().flatten() {
+		let reg_name = reg_name.as_str().unwrap_or_default();
+		let line = key_line(contents, reg_name, seg_config_line.unwrap_or(1));
+		if !reg_names.contains(&reg_name) {
+			errors.push(at_line(file, line,
+					    format!("seg value for unknown register \"{}\"",
+						    reg_name)));
+			continue;
+		}
+
+		match parse_seg_value(seg) {
+			Ok(None) => errors.push(at_line(file, line,
+							format!("{} has no value", reg_name))),
+			Err(error) => errors.push(at_line(file, line,
+							  format!("{}: {}", reg_name, error))),
+			Ok(Some(_)) => {}
+		}
+	}
+
+	if !errors.is_empty() {
+		return Err(errors.join("\n"))
+	}
+
+	return Ok(())
+}
+
+pub fn merge(base: &str, base_format: ConfigFormat, overlay: &str, overlay_format: ConfigFormat,
+	     output_format: ConfigFormat, reg_names: &[&str])
+-> Result<(String, Vec<String>), String>
+{
+	// for keeping a small set of changes on top of somebody else's config:
+	// the overlay's seg values win & everything else is the base's, edited
+	// in place where possible like a save. also returns the overlay's keys
+	// that were left out
+	let mut config = parse_config(base, base_format)
+		.map_err(|error| return format!("base: {}", error))?;
+	let migrated = !migrate_config(&mut config)
+		.map_err(|error| return format!("base: {}", error))?
+		.is_empty();
+	let mut overlay = parse_config(overlay, overlay_format)
+		.map_err(|error| return format!("overlay: {}", error))?;
+	migrate_config(&mut overlay).map_err(|error| return format!("overlay: {}", error))?;
+	config["schema-version"] = Value::from(SCHEMA_VERSION);
+
+	let mut segs: Vec<(String, String)> = Vec::new();
+	for (reg_name, seg) in overlay["seg-reg-config"].as_mapping().into_iter().flatten() {
+		let reg_name = reg_name.as_str().unwrap_or_default();
+		if !reg_names.contains(&reg_name) {
+			return Err(format!("overlay: seg value for unknown register \"{}\"",
+					   reg_name))
+		}
+
+		let seg = parse_seg_value(seg)
+			.map_err(|error| return format!("overlay: {}: {}", reg_name, error))?
+			.ok_or(format!("overlay: {} has no value", reg_name))?;
+		if let Ok(Some(existing)) = parse_seg_value(&config["seg-reg-config"][reg_name]) {
+			if existing == seg {
+				continue;
+			}
+		}
+
+		let seg_value = format!("{:#x?}", seg);
+		config["seg-reg-config"][reg_name] = Value::String(seg_value.clone());
+		segs.push((reg_name.to_string(), seg_value));
+	}
+
+	let ignored: Vec<String> = overlay.as_mapping().into_iter().flatten()
+		.filter_map(|(key, _)| return key.as_str())
+		.filter(|key| return *key != "seg-reg-config" && *key != "schema-version")
+		.map(|key| return key.to_string())
+		.collect();
+
+	let mut output = None;
+	if base_format == ConfigFormat::Yaml && output_format == ConfigFormat::Yaml && !migrated {
+		output = update_seg_values(base, &segs)
+			.and_then(|updated| return update_schema_version(&updated));
+	}
+	if output.is_none() {
+		output = Some(serialize_config(&config, output_format)
+			.map_err(|error| return error.to_string())?);
+	}
+
+	return Ok((output.unwrap(), ignored))
+}
+
+fn scaffold_contexts(board: &soc::MPFS) -> String
+{
+	let mut output = "contexts:\n".to_string();
+	for context in &board.contexts {
+		let memory: Vec<String> = context.memory.iter()
+			.map(|(start, size)| return format!("{{start: {:#x}, size: {:#x}}}", start,
+							    size))
+			.collect();
+		output += &format!("  {}:\n    harts: [{}]\n    memory: [{}]\n", context.name,
+				   context.harts.join(", "), memory.join(", "));
+	}
+
+	return output
+}
+
+fn scaffold_bus_masters(board: &soc::MPFS) -> String
+{
+	let mut output = "bus-masters:\n".to_string();
+	for master in &board.bus_masters {
+		let windows: Vec<String> = master.windows.iter()
+			.map(|(start, size)| return format!("{{start: {:#x}, size: {:#x}}}", start,
+							    size))
+			.collect();
+		output += &format!("  {}:\n    windows: [{}]\n", master.name, windows.join(", "));
+	}
+
+	return output
+}
+
+pub fn scaffold(board: &soc::MPFS, source: &str) -> String
+{
+	// a config to start from, with every section that the tool reads &
+	// comments saying what goes in them. the optional ones are left commented
+	// out, so that loading it straight away doesn't claim things about the
+	// board that nobody has said are true, unless the board already has them
+
+	let mut output = "#\n# seg-configurator config\n#\n\n".to_string();
+	output += &format!("# the layout version of this file, so that it can be upgraded if that changes\n\
+			    schema-version: {}\n\n", SCHEMA_VERSION);
+	output += "# the same file is read by the HSS payload generator, so its keys (set-name,\n\
+		   # hart-entry-points & payloads) can live here too\n\
+		   #set-name: 'PolarFire-SoC-HSS::Image'\n\n";
+
+	output += &format!("# the value of each seg register, as hex, from {}.\n\
+			    # a seg value of 0x0 leaves the aperture's hardware address the same as its\n\
+			    # bus address, otherwise bit 14 has to be set\n\
+			    seg-reg-config:\n", source);
+	for aperture in board.apertures() {
+		output += &format!("  # {}, bus address {:#x}, {:#x} long, hardware address {:#x}\n",
+				   aperture.description(), aperture.bus_addr,
+				   aperture.aperture_size, aperture.hardware_addr);
+		output += &format!("  {}: '{:#x}'\n", aperture.reg_name,
+				   aperture.seg().unwrap_or_default());
+	}
+
+	output += "\n# the banks of physical memory, by hardware address, for a design with more\n\
+		   # than one memory controller. leave it out for a single bank from 0 up\n\
+		   #memory-banks:\n\
+		   #  ddr4: {base: 0x0, size: 2G}\n\
+		   #  lpddr4: {base: 0x100000000, size: 2G}\n";
+
+	output += "\n# software contexts sharing the board, each with its harts & the DDR that it\n\
+		   # owns, by hardware address. sizes can be hex, decimal or have a unit suffix\n";
+	match board.contexts.is_empty() {
+		true => output += "#contexts:\n\
+				   #  linux:\n\
+				   #    harts: [u54_1, u54_2, u54_3]\n\
+				   #    memory: [{start: 0x0, size: 1G}]\n\
+				   #  rtos:\n\
+				   #    harts: [u54_4]\n\
+				   #    memory: [{start: 0x40000000, size: 256M}]\n\n",
+		false => output += &(scaffold_contexts(board) + "\n"),
+	}
+
+	output += "# bus masters other than the harts, e.g. dma in the fabric, by the bus\n\
+		   # addresses that they put out\n";
+	match board.bus_masters.is_empty() {
+		true => output += "#bus-masters:\n\
+				   #  fic0-dma:\n\
+				   #    windows: [{start: 0x80000000, size: 512M}]\n\n",
+		false => output += &(scaffold_bus_masters(board) + "\n"),
+	}
+
+	output += "# which aperture a devicetree memory node goes through, by node name, for\n\
+		   # when more than one could reach it. the \"via\" command fills these in\n\
+		   #dt-node-apertures:\n\
+		   #  memory@1000000000: seg0_1\n";
+
+	return output
+}
+
+pub fn parse_number(value: &Value) -> Result<u64, String>
+{
+	// sizes & addresses other than seg values are whatever parse_size()
+	// takes, or a plain number if the yaml parser got there first
+	if let Some(number) = value.as_u64() {
+		return Ok(number)
+	}
+
+	let number = value.as_str()
+		.ok_or(format!("{:?} is not a valid size or address", value))?;
+
+	return units::parse_size(number).map_err(|error| return error.to_string())
+}
+
+pub fn parse_bus_masters(value: &Value) -> Result<Vec<soc::BusMaster>, String>
+{
+	// bus-masters:
+	//   fic0-dma: {windows: [{start: 0xc0000000, size: 256M}]}
+	let mut bus_masters: Vec<soc::BusMaster> = Vec::new();
+	if value.is_null() {
+		return Ok(bus_masters)
+	}
+
+	let mapping = value.as_mapping()
+		.ok_or("bus-masters should be a mapping of name to bus master")?;
+	for (name, master) in mapping {
+		let name = name.as_str()
+			.ok_or(format!("{:?} is not a valid bus master name", name))?
+			.to_string();
+
+		let mut windows: Vec<(u64, u64)> = Vec::new();
+		for window in master["windows"].as_sequence().into_iter().flatten() {
+			let start = parse_number(&window["start"])
+				.map_err(|error| return format!("{}: start: {}", name, error))?;
+			let size = parse_number(&window["size"])
+				.map_err(|error| return format!("{}: size: {}", name, error))?;
+			windows.push((start, size));
+		}
+
+		bus_masters.push(soc::BusMaster { name, windows });
+	}
+
+	return Ok(bus_masters)
+}
+
+pub fn parse_memory_banks(value: &Value) -> Result<Vec<soc::MemoryBank>, String>
+{
+	// memory-banks:
+	//   ddr4: {base: 0x0, size: 2G}
+	let mut banks: Vec<soc::MemoryBank> = Vec::new();
+	if value.is_null() {
+		return Ok(banks)
+	}
+
+	let mapping = value.as_mapping()
+		.ok_or("memory-banks should be a mapping of name to bank")?;
+	for (name, bank) in mapping {
+		let name = name.as_str()
+			.ok_or(format!("{:?} is not a valid memory bank name", name))?
+			.to_string();
+		let base = parse_number(&bank["base"])
+			.map_err(|error| return format!("{}: base: {}", name, error))?;
+		let size = parse_number(&bank["size"])
+			.map_err(|error| return format!("{}: size: {}", name, error))?;
+		if size == 0 {
+			return Err(format!("{}: a memory bank can't be empty", name))
+		}
+
+		// the end is worked out everywhere else without checking, so a bank
+		// that runs off the top of the address space has to stop here
+		let end = base.checked_add(size)
+			.ok_or(format!("{}: a bank of {:#x} at {:#x} runs past the end of the \
+					address space", name, size, base))?;
+		let overlapping = banks.iter()
+			.find(|other| return base < other.end() && other.base < end);
+		if let Some(other) = overlapping {
+			return Err(format!("{} overlaps memory bank {}", name, other.name))
+		}
+
+		banks.push(soc::MemoryBank { name, base, size });
+	}
+
+	return Ok(banks)
+}
+
+pub fn parse_aperture_access(value: &Value, reg_names: &[&str])
+-> Result<Vec<(String, soc::Access)>, String>
+{
+	// aperture-access: {seg0_1: rwx, seg1_4: rw}
+	let mut accesses: Vec<(String, soc::Access)> = Vec::new();
+	if value.is_null() {
+		return Ok(accesses)
+	}
+
+	let mapping = value.as_mapping()
+		.ok_or("aperture-access should be a mapping of register to access")?;
+	for (reg_name, access) in mapping {
+		let reg_name = reg_name.as_str()
+			.filter(|reg_name| return reg_names.contains(reg_name))
+			.ok_or(format!("aperture-access: {:?} is not a seg register", reg_name))?;
+		let access = access.as_str()
+			.ok_or(format!("{}: access should be a string, e.g. rwx", reg_name))
+			.and_then(|access| return soc::Access::parse(access)
+				.map_err(|error| return format!("{}: {}", reg_name, error)))?;
+		accesses.push((reg_name.to_string(), access));
+	}
+
+	return Ok(accesses)
+}
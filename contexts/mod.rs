@@ -0,0 +1,168 @@
+// SPDX-License-Identifier: MIT or GPL-2.0
+
+#![allow(unused_variables)]
+#![allow(dead_code)]
+#![deny(clippy::implicit_return)]
+#![allow(clippy::needless_return)]
+
+use serde_yaml::Value;
+
+use crate::config;
+use crate::soc;
+use crate::soc::PerformanceClass;
+
+// a piece of software, e.g. linux on the u54s or an rtos on one of them, that
+// owns some of ddr for itself. described by hardware address, as that is
+// what has to be kept apart, whichever apertures they go through
+#[derive(Debug, Clone)]
+pub struct Context {
+	pub name: String,
+	pub harts: Vec<String>,
+	// (hardware start, size)
+	pub memory: Vec<(u64, u64)>,
+}
+
+impl Context {
+	// the memory as (start, exclusive end), leaving out anything empty or that
+	// would run off the top of the address space, which the config can't
+	// give but a context made some other way might
+	pub fn ranges(&self) -> Vec<(u64, u64)>
+	{
+		return self.memory.iter()
+			.filter(|(_, size)| return *size > 0)
+			.filter_map(|(start, size)| return Some((*start, start.checked_add(*size)?)))
+			.collect()
+	}
+}
+
+pub fn parse_contexts(value: &Value) -> Result<Vec<Context>, String>
+{
+	// contexts:
+	//   linux: {harts: [u54_1, u54_2], memory: [{start: 0x0, size: 1G}]}
+	let mut contexts: Vec<Context> = Vec::new();
+	if value.is_null() {
+		return Ok(contexts)
+	}
+
+	let mapping = value.as_mapping()
+		.ok_or("contexts should be a mapping of name to context")?;
+	for (name, context) in mapping {
+		let name = name.as_str()
+			.ok_or(format!("{:?} is not a valid context name", name))?
+			.to_string();
+
+		let harts = context["harts"].as_sequence()
+			.map(|harts| return harts.iter()
+				.filter_map(|hart| return hart.as_str())
+				.map(|hart| return hart.to_string())
+				.collect())
+			.unwrap_or_default();
+
+		let mut memory: Vec<(u64, u64)> = Vec::new();
+		for region in context["memory"].as_sequence().into_iter().flatten() {
+			let start = config::parse_number(&region["start"])
+				.map_err(|error| return format!("{}: start: {}", name, error))?;
+			let size = config::parse_number(&region["size"])
+				.map_err(|error| return format!("{}: size: {}", name, error))?;
+			if size == 0 {
+				return Err(format!("{}: memory at {:#x} is empty", name, start))
+			}
+			if start.checked_add(size).is_none() {
+				return Err(format!("{}: {:#x} of memory at {:#x} runs past the end \
+						    of the address space", name, size, start))
+			}
+			memory.push((start, size));
+		}
+
+		contexts.push(Context { name, harts, memory });
+	}
+
+	return Ok(contexts)
+}
+
+pub fn check_contexts(contexts: &[Context]) -> Vec<String>
+{
+	// contexts are only any use if they keep out of each other's way, both
+	// in ddr & in which harts they run on
+	let mut problems: Vec<String> = Vec::new();
+
+	for (index, context) in contexts.iter().enumerate() {
+		for other in &contexts[index + 1..] {
+			for (start, end) in context.ranges() {
+				for (other_start, other_end) in other.ranges() {
+					if start < other_end && other_start < end {
+						problems.push(format!(
+							"contexts {} & {} both use hardware addresses \
+							 {:#x}-{:#x}.",
+							context.name, other.name,
+							start.max(other_start),
+							end.min(other_end) - 1
+						));
+					}
+				}
+			}
+
+			for hart in &context.harts {
+				if other.harts.contains(hart) {
+					problems.push(format!("contexts {} & {} both run on {}.",
+							      context.name, other.name, hart));
+				}
+			}
+		}
+	}
+
+	return problems
+}
+
+// a piece of a context's memory, as seen through one of the apertures
+#[derive(Debug, Clone)]
+pub struct ContextBank {
+	pub reg_name: String,
+	pub bus_addr: u64,
+	pub size: u64,
+}
+
+pub fn context_banks(board: &soc::MPFS, context: &Context)
+-> (Vec<ContextBank>, Vec<(u64, u64)>)
+{
+	// software runs out of the cached apertures, so the context's memory is
+	// mapped through whichever of them reaches it, the first one winning if
+	// more than one does. anything none of them reach is returned as
+	// (start, end) alongside
+	let cached: Vec<(&soc::MemoryAperture, u64, u64)> = board.hw_ranges()
+		.into_iter()
+		.filter(|(aperture, _, _)| return aperture.performance_class == PerformanceClass::CachedAxi)
+		.collect();
+
+	let mut banks: Vec<ContextBank> = Vec::new();
+	let mut unreachable: Vec<(u64, u64)> = Vec::new();
+	for (start, end) in context.ranges() {
+		let mut cursor = start;
+		while cursor < end {
+			let host = cached.iter()
+				.find(|(_, host_start, host_end)| return *host_start <= cursor
+								    && cursor < *host_end);
+			if let Some((aperture, host_start, host_end)) = host {
+				let piece_end = end.min(*host_end);
+				banks.push(ContextBank {
+					reg_name: aperture.reg_name.clone(),
+					bus_addr: aperture.bus_addr + (cursor - host_start),
+					size: piece_end - cursor,
+				});
+				cursor = piece_end;
+				continue;
+			}
+
+			let next_host = cached.iter()
+				.map(|(_, host_start, _)| return *host_start)
+				.filter(|host_start| return *host_start > cursor)
+				.min()
+				.unwrap_or(end)
+				.min(end);
+			unreachable.push((cursor, next_host));
+			cursor = next_host;
+		}
+	}
+
+	return (banks, unreachable)
+}
@@ -0,0 +1,111 @@
+// SPDX-License-Identifier: MIT or GPL-2.0
+
+#![allow(unused_variables)]
+#![allow(dead_code)]
+#![deny(clippy::implicit_return)]
+#![allow(clippy::needless_return)]
+
+// lines of unchanged text kept either side of a change, the same as diff -u
+const CONTEXT: usize = 3;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Op {
+	Equal,
+	Delete,
+	Insert,
+}
+
+// one line of the edit script, with where it falls in each of the texts
+struct Edit<'a> {
+	op: Op,
+	line: &'a str,
+	old_line: usize,
+	new_line: usize,
+}
+
+fn edits<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<Edit<'a>>
+{
+	// a plain longest common subsequence. configs are a few hundred lines at
+	// most, so the table is small enough not to bother with anything cleverer
+	let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+	for i in (0..old.len()).rev() {
+		for j in (0..new.len()).rev() {
+			common[i][j] = match old[i] == new[j] {
+				true => common[i + 1][j + 1] + 1,
+				false => common[i + 1][j].max(common[i][j + 1]),
+			};
+		}
+	}
+
+	let mut edits: Vec<Edit> = Vec::new();
+	let (mut i, mut j) = (0, 0);
+	while i < old.len() || j < new.len() {
+		let edit = |op, line| return Edit { op, line, old_line: i, new_line: j };
+		if i < old.len() && j < new.len() && old[i] == new[j] {
+			edits.push(edit(Op::Equal, old[i]));
+			i += 1;
+			j += 1;
+		} else if i < old.len() && (j == new.len() || common[i + 1][j] >= common[i][j + 1]) {
+			// removed lines go before the ones replacing them
+			edits.push(edit(Op::Delete, old[i]));
+			i += 1;
+		} else {
+			edits.push(edit(Op::Insert, new[j]));
+			j += 1;
+		}
+	}
+
+	return edits
+}
+
+fn hunk_range(start: usize, count: usize) -> String
+{
+	// an empty side is given as the line before it, as diff does
+	if count == 0 {
+		return format!("{},0", start)
+	}
+
+	return format!("{},{}", start + 1, count)
+}
+
+pub fn unified(old: &str, new: &str, old_name: &str, new_name: &str) -> String
+{
+	// the same format as diff -u, empty if nothing has changed
+	let old_lines: Vec<&str> = old.lines().collect();
+	let new_lines: Vec<&str> = new.lines().collect();
+	let edits = edits(&old_lines, &new_lines);
+
+	// each change with its context, merging any that run into each other
+	let mut hunks: Vec<(usize, usize)> = Vec::new();
+	for (index, _) in edits.iter().enumerate().filter(|(_, edit)| return edit.op != Op::Equal) {
+		let start = index.saturating_sub(CONTEXT);
+		let end = (index + 1 + CONTEXT).min(edits.len());
+		match hunks.last_mut() {
+			Some(last) if start <= last.1 => last.1 = end,
+			_ => hunks.push((start, end)),
+		}
+	}
+
+	if hunks.is_empty() {
+		return String::new()
+	}
+
+	let mut diff = format!("--- {}\n+++ {}\n", old_name, new_name);
+	for (start, end) in hunks {
+		let hunk = &edits[start..end];
+		let old_count = hunk.iter().filter(|edit| return edit.op != Op::Insert).count();
+		let new_count = hunk.iter().filter(|edit| return edit.op != Op::Delete).count();
+		diff += &format!("@@ -{} +{} @@\n", hunk_range(hunk[0].old_line, old_count),
+				 hunk_range(hunk[0].new_line, new_count));
+		for edit in hunk {
+			let prefix = match edit.op {
+				Op::Equal => ' ',
+				Op::Delete => '-',
+				Op::Insert => '+',
+			};
+			diff += &format!("{}{}\n", prefix, edit.line);
+		}
+	}
+
+	return diff
+}
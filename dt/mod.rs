@@ -5,86 +5,475 @@
 #![deny(clippy::implicit_return)]
 #![allow(clippy::needless_return)]
 
-use std::io::Read;
 use std::fs;
+use std::path::Path;
 
+use crate::dts;
+use crate::messages;
+use crate::stdio;
 use crate::soc::Aperture;
+use crate::soc::in_one_bank;
 use crate::soc::MemoryAperture;
 use crate::soc::MPFS;
 use crate::soc::SegError;
+use crate::soc::SoC;
+use crate::units;
+use crate::units::Radix;
+
+// big endian, at the very start of every dtb
+const DTB_MAGIC: [u8; 4] = [0xd0, 0x0d, 0xfe, 0xed];
 
 #[derive(Clone, Debug)]
-pub struct MemoryNode {
+pub struct MemoryRegion {
 	pub address: u64,
 	pub size: u64,
+	// the aperture picked for this region when more than one could host it
+	pub aperture: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct MemoryNode {
+	pub regions: Vec<MemoryRegion>,
 	pub label: String,
+	pub enabled: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct ReservedMemoryNode {
+	pub regions: Vec<MemoryRegion>,
+	pub label: String,
+	pub no_map: bool,
+	pub reusable: bool,
+}
+
+impl ReservedMemoryNode {
+	pub fn flags(&self) -> String
+	{
+		let mut flags: Vec<&str> = Vec::new();
+		if self.no_map {
+			flags.push("no-map");
+		}
+		if self.reusable {
+			flags.push("reusable");
+		}
+
+		return flags.join(" ")
+	}
+}
+
+impl MemoryRegion {
+	pub fn host_aperture<'a>
+	(&self, apertures: &'a [MemoryAperture]) -> Option<&'a MemoryAperture>
+	{
+		// an aperture that has been picked beats whichever happens to match
+		// first, so long as it can still host the region
+		let picked = apertures.iter()
+			.find(|aperture| return Some(&aperture.reg_name) == self.aperture.as_ref())
+			.filter(|aperture| return aperture.check_region_in_aperture(self.address,
+										    self.size));
+		if picked.is_some() {
+			return picked
+		}
+
+		return apertures.iter()
+			.find(|aperture| return aperture.check_region_in_aperture(self.address,
+										  self.size))
+	}
+
+	pub fn get_hw_start_addr
+	(&self, apertures: &[MemoryAperture]) -> Result<u64, SegError>
+	{
+		let hw_start_addr = self.host_aperture(apertures)
+			.and_then(|aperture| return aperture.get_region_hw_start_addr(self.address,
+										      self.size));
+		if let Some(hw_start_addr) = hw_start_addr {
+			return Ok(hw_start_addr)
+		}
+
+		// this gets asked every time the screen is drawn, so it's left to
+		// whoever changed the apertures to say that a region fell out
+		return Err(SegError::Unreachable(self.address))
+	}
+
+	// where the region ends in hardware, if it starts at hw_start_addr.
+	// sizes come straight from the dtb, so an empty region or one that runs
+	// past the end of the address space has no end
+	pub fn hw_end(&self, hw_start_addr: u64) -> Option<u64>
+	{
+		if self.size == 0 {
+			return None
+		}
+
+		return hw_start_addr.checked_add(self.size)
+	}
+
+	pub fn is_uncached(&self, apertures: &[MemoryAperture]) -> bool
+	{
+		return self.host_aperture(apertures)
+			.map(|aperture| return !aperture.performance_class.is_cached())
+			.unwrap_or(false)
+	}
+
+	pub fn is_mapped
+	(&self, apertures: &[MemoryAperture], banks: &[(u64, u64)]) -> bool
+	{
+		// a region is only really mapped if it is reachable through one of
+		// the apertures *and* that lands all of it inside one memory bank
+		return self.get_hw_start_addr(apertures)
+			.ok()
+			.and_then(|hw_start_addr| {
+				return Some(in_one_bank(banks, hw_start_addr,
+							 self.hw_end(hw_start_addr)?))
+			})
+			.unwrap_or(false)
+	}
+
+	pub fn hosting_apertures(&self, apertures: &[MemoryAperture]) -> Vec<String>
+	{
+		return apertures.iter()
+			.filter(|aperture| return aperture.check_region_in_aperture(self.address,
+										    self.size))
+			.map(|aperture| return aperture.reg_name.clone())
+			.collect()
+	}
+
+	pub fn is_ambiguous(&self, apertures: &[MemoryAperture]) -> bool
+	{
+		// several apertures could host the region & none of them has been
+		// picked, so the first match wins
+		let hosts = self.hosting_apertures(apertures);
+		if hosts.len() < 2 {
+			return false
+		}
+
+		return !matches!(&self.aperture, Some(picked) if hosts.contains(picked))
+	}
 }
 
 pub trait NoGoodNameYet {
-	fn to_strings(&self, board: &mut MPFS) -> Vec<String>;
+	fn to_strings(&self, board: &MPFS, radix: Radix) -> Vec<Vec<String>>;
 
-	fn get_hw_start_addr
-	(&self, apertures: &mut Vec<MemoryAperture>) -> Result<u64, SegError>;
+	fn get_hw_start_addrs
+	(&self, apertures: &[MemoryAperture]) -> Vec<Result<u64, SegError>>;
 }
 
 impl NoGoodNameYet for MemoryNode {
-	fn to_strings(&self, board: &mut MPFS) -> Vec<String>
+	fn to_strings(&self, board: &MPFS, radix: Radix) -> Vec<Vec<String>>
 	{
-		let mut strings = Vec::new();
-		let hw_address = self.get_hw_start_addr(&mut board.memory_apertures);
+		// each reg tuple gets a row of its own, if there's more than one
+		// of them the index gets tacked onto the name to tell them apart
+		let mut rows = Vec::new();
+		for (index, region) in self.regions.iter().enumerate() {
+			let mut strings = Vec::new();
+			let hw_address = region.get_hw_start_addr(board.apertures())
+				.ok()
+				.and_then(|hw_address| {
+					return Some((hw_address, region.hw_end(hw_address)?))
+				});
 
-		strings.push(self.label.clone());
-		strings.push(format!("{:#012x}", self.address));
-		strings.push(format!("{:#012x}", self.size));
+			if self.regions.len() > 1 {
+				strings.push(format!("{} [{}]", self.label, index));
+			} else {
+				strings.push(self.label.clone());
+			}
+			strings.push(units::format_number(region.address, radix, 12));
+			strings.push(units::format_number(region.size, radix, 12));
+
+			if let Some((hw_address, hw_end)) = hw_address {
+				strings.push(units::format_number(hw_address, radix, 12));
+				strings.push(units::format_number(hw_end - 1, radix, 12));
+			} else {
+				strings.push(units::format_number(0, radix, 12));
+				strings.push(units::format_number(0, radix, 12));
+			}
 
-		if let Ok(hw_address) = hw_address {
-			strings.push(format!("{:#012x}", hw_address));
-			strings.push(format!("{:#012x}", hw_address + self.size - 1));
-		} else {
-			strings.push(format!("{:#012x}", 0));
-			strings.push(format!("{:#012x}", 0));
+			rows.push(strings);
 		}
 
-		return strings.clone()
+		return rows
 	}
 
-	fn get_hw_start_addr
-	(&self, apertures: &mut Vec<MemoryAperture>) -> Result<u64, SegError>
+	fn get_hw_start_addrs
+	(&self, apertures: &[MemoryAperture]) -> Vec<Result<u64, SegError>>
 	{
-		for aperture in apertures.iter_mut() {
-			let hw_start_addr = aperture.get_region_hw_start_addr(self.address,
-									      self.size);
-			if hw_start_addr.is_none() {
-				continue
-			}
+		return self.regions
+			.iter()
+			.map(|region| return region.get_hw_start_addr(apertures))
+			.collect()
+	}
+
+}
 
-			return Ok(hw_start_addr.unwrap())
+pub fn pin_node_apertures(nodes: &mut [MemoryNode], node_apertures: &[(String, String)])
+{
+	for node in nodes.iter_mut() {
+		let picked = node_apertures.iter()
+			.find(|(label, _)| return *label == node.label)
+			.map(|(_, reg_name)| return reg_name.clone());
+		for region in node.regions.iter_mut() {
+			region.aperture = picked.clone();
 		}
+	}
+}
 
-		dbg!("no overlapping region found for {:?} {:?}", apertures, self);
+pub fn ambiguous_nodes(nodes: &[MemoryNode], apertures: &[MemoryAperture]) -> Vec<String>
+{
+	let mut ambiguous: Vec<String> = Vec::new();
+	for node in nodes.iter().filter(|node| return node.enabled) {
+		let hosts: Vec<String> = node.regions.iter()
+			.filter(|region| return region.is_ambiguous(apertures))
+			.flat_map(|region| return region.hosting_apertures(apertures))
+			.collect();
+		if !hosts.is_empty() {
+			ambiguous.push(format!("{} ({})", node.label, hosts.join(" or ")));
+		}
+	}
 
-		return Err(SegError {})
+	return ambiguous
+}
+
+pub fn uncached_nodes(nodes: &[MemoryNode], apertures: &[MemoryAperture]) -> Vec<String>
+{
+	// linux expects its memory to be cached, running it out of a non-cached
+	// or write-combining window is almost certainly not what was meant
+	let mut uncached: Vec<String> = Vec::new();
+	for node in nodes.iter().filter(|node| return node.enabled) {
+		let hosts: Vec<String> = node.regions.iter()
+			.filter(|region| return region.is_uncached(apertures))
+			.filter_map(|region| return region.host_aperture(apertures))
+			.map(|aperture| return format!("{}, {}", aperture.reg_name,
+						       aperture.performance_class.name()))
+			.collect();
+		if !hosts.is_empty() {
+			uncached.push(format!("{} ({})", node.label, hosts.join(" & ")));
+		}
 	}
 
+	return uncached
 }
 
-pub fn memory_nodes_to_strings(board: &mut MPFS, nodes: Vec<MemoryNode>) -> Vec<Vec<String>>
+pub fn check_node_aperture
+(nodes: &[MemoryNode], label: &str, reg_name: &str, apertures: &[MemoryAperture])
+-> Result<(), String>
 {
-	//I'm sure this should be a closure or w/e
-	let mut strings = Vec::new();
-	for node in nodes {
-		strings.push(node.to_strings(board));
+	let node = nodes.iter()
+		.find(|node| return node.label == label)
+		.ok_or(format!("Unknown dt node {}.", label))?;
+
+	let can_host = node.regions.iter()
+		.any(|region| return region.hosting_apertures(apertures).iter()
+			.any(|host| return host == reg_name));
+	if !can_host {
+		return Err(format!("{} cannot reach {}.", reg_name, label))
+	}
+
+	return Ok(())
+}
+
+pub fn set_memory_node
+(nodes: &mut Vec<MemoryNode>, label: &str, address: u64, size: u64) -> Result<String, String>
+{
+	// an existing node is replaced by a single region, a new one is added
+	// enabled. two nodes can't both claim the same bus addresses
+	if size == 0 {
+		return Err(format!("{} needs a size.", label))
+	}
+	let end = address.checked_add(size)
+		.ok_or(format!("{:#x} at {:#x} runs past the end of the address space.", size,
+			       address))?;
+
+	// a region read from a dtb that runs off the top of the address space
+	// still clashes with everything above its start
+	let clash = nodes.iter()
+		.filter(|node| return node.enabled && node.label != label)
+		.find(|node| return node.regions.iter()
+			.any(|region| return region.address < end &&
+					     address < region.address.saturating_add(region.size)));
+	if let Some(clash) = clash {
+		return Err(format!("{:#x} to {:#x} overlaps {}.", address, end, clash.label))
+	}
+
+	let region = MemoryRegion {
+		address,
+		size,
+		aperture: None,
+	};
+
+	let node = nodes.iter_mut().find(|node| return node.label == label);
+	if let Some(node) = node {
+		node.regions = vec![region];
+		return Ok(format!("{} is now {:#x} to {:#x}.", label, address, end))
+	}
+
+	nodes.push(MemoryNode {
+		regions: vec![region],
+		label: label.to_string(),
+		enabled: true,
+	});
+
+	return Ok(format!("Added {} at {:#x} to {:#x}.", label, address, end))
+}
+
+pub fn remove_memory_node(nodes: &mut Vec<MemoryNode>, label: &str) -> Result<String, String>
+{
+	let count = nodes.len();
+	nodes.retain(|node| return node.label != label);
+	if nodes.len() == count {
+		return Err(format!("Unknown dt node {}.", label))
+	}
+
+	return Ok(format!("Removed {}.", label))
+}
+
+pub fn unreachable_nodes(nodes: &[MemoryNode], apertures: &[MemoryAperture]) -> Vec<String>
+{
+	return nodes.iter()
+		.filter(|node| return node.enabled)
+		.filter(|node| return node.regions.iter()
+			.any(|region| return region.host_aperture(apertures).is_none()))
+		.map(|node| return node.label.clone())
+		.collect()
+}
+
+pub fn unreachable_regions(nodes: &[MemoryNode], apertures: &[MemoryAperture]) -> Vec<String>
+{
+	// the regions themselves rather than their nodes, as a node can have
+	// some of its regions reached & not others
+	return nodes.iter()
+		.filter(|node| return node.enabled)
+		.flat_map(|node| return node.regions.iter()
+			.filter(|region| return region.host_aperture(apertures).is_none())
+			.map(|region| return format!("{} at {:#x} (size {:#x})", node.label,
+						     region.address, region.size)))
+		.collect()
+}
+
+pub fn memory_nodes_get_total_memory
+(nodes: &[MemoryNode], apertures: &[MemoryAperture]) -> Option<u64>
+{
+	// the memory nodes are bus addresses, and there can be several views of
+	// the same ddr, so adding up the sizes would double count. instead take
+	// the top of the highest region once it has gone through the apertures
+	let mut top: Option<u64> = None;
+	for node in nodes.iter().filter(|node| return node.enabled) {
+		for region in node.regions.iter() {
+			let region_top = region.get_hw_start_addr(apertures)
+				.ok()
+				.and_then(|hw_start_addr| return region.hw_end(hw_start_addr));
+			if let Some(region_top) = region_top {
+				top = Some(top.map_or(region_top, |top| return top.max(region_top)));
+			}
+		}
+	}
+
+	return top
+}
+
+fn node_is_enabled(node: &device_tree::Node) -> bool
+{
+	// no status property at all means the node is enabled, as does either
+	// of the two spellings of "okay" permitted by the spec
+	let status = node.prop_str("status");
+	if status.is_err() {
+		return true
+	}
+
+	let status = status.unwrap();
+	return status == "okay" || status == "ok"
+}
+
+// how many cells an address & a size take up in the reg of a node's
+// children, from its "#address-cells" & "#size-cells"
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Cells {
+	pub address: usize,
+	pub size: usize,
+}
+
+// the two parents an overlay writes into, the root for memory nodes &
+// /reserved-memory for reserved ones. without a dtb to go on it's two &
+// two, as on the boards this is for
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TreeCells {
+	pub root: Cells,
+	pub reserved_memory: Cells,
+}
+
+impl Default for TreeCells {
+	fn default() -> TreeCells
+	{
+		let cells = Cells { address: 2, size: 2 };
+		return TreeCells { root: cells, reserved_memory: cells }
+	}
+}
+
+pub struct DtbNodes {
+	pub memory_nodes: Vec<MemoryNode>,
+	pub reserved_nodes: Vec<ReservedMemoryNode>,
+	pub cells: TreeCells,
+}
+
+fn get_cells(parent: &device_tree::Node, name: &str, default: u32) -> Result<usize, String>
+{
+	// a parent without them gets the defaults from the spec. more than two
+	// cells wouldn't fit in a u64, & an address or size of none is useless
+	// for memory
+	let cells = parent.prop_u32(name).unwrap_or(default);
+	if !(1..=2).contains(&cells) {
+		return Err(format!("{}: {} of {} isn't supported, only 1 or 2", parent.name, name,
+				   cells))
+	}
+
+	return Ok(cells as usize)
+}
+
+fn node_cells(parent: &device_tree::Node) -> Result<Cells, String>
+{
+	return Ok(Cells {
+		address: get_cells(parent, "#address-cells", 2)?,
+		size: get_cells(parent, "#size-cells", 1)?,
+	})
+}
+
+fn reg_to_regions(node: &device_tree::Node, reg: &[u8], cells: Cells)
+-> Result<Vec<MemoryRegion>, String>
+{
+	let address_cells = cells.address;
+	let size_cells = cells.size;
+	let tuple_len = 4 * (address_cells + size_cells);
+	if !reg.len().is_multiple_of(tuple_len) {
+		return Err(format!("{}: reg is {} bytes, which isn't a whole number of {} byte \
+				    (address, size) pairs", node.name, reg.len(), tuple_len))
+	}
+
+	let from_cells = |cells: &[u8]| {
+		return cells.chunks_exact(4)
+			.map(|cell| return u32::from_be_bytes(cell.try_into().unwrap()) as u64)
+			.fold(0u64, |value, cell| return value << 32 | cell)
+	};
+	let mut regions: Vec<MemoryRegion> = Vec::new();
+	for tuple in reg.chunks_exact(tuple_len) {
+		let (addr_vec, size_vec) = tuple.split_at(4 * address_cells);
+		let address = from_cells(addr_vec);
+		let size = from_cells(size_vec);
+		// kept, so that an overlay doesn't lose it, but it covers nothing
+		if size == 0 {
+			messages::warn(format!("{}: the reg entry at {:#x} is empty", node.name,
+					       address));
+		}
+		regions.push(MemoryRegion {
+			address,
+			size,
+			aperture: None,
+		});
 	}
-	return strings.clone()
+
+	return Ok(regions)
 }
 
-fn get_memory_nodes(root_node: device_tree::Node)
+fn get_memory_nodes(root_node: device_tree::Node, cells: Cells)
 -> Result<Vec<MemoryNode>, Box<dyn std::error::Error>>
 {
-	//TODO: parse size/address cells
-	//TODO: consider disabled nodes
-	let size_cells = 2;
-	let address_cells = 2;
 	let mut memory_nodes: Vec<MemoryNode> = Vec::new();
 	let children = root_node.children.iter();
 	for child in children {
@@ -97,30 +486,270 @@ fn get_memory_nodes(root_node: device_tree::Node)
 			if reg.is_none() {
 				continue;
 			}
-			let (addr_vec, size_vec) = reg.unwrap().split_at(8);
-			let addr = u64::from_be_bytes(addr_vec.try_into().unwrap());
-			let size = u64::from_be_bytes(size_vec.try_into().unwrap());
 			let node = MemoryNode {
 				label: child.name.clone(),
-				address: addr,
-				size,
+				regions: reg_to_regions(child, reg.unwrap(), cells)?,
+				enabled: node_is_enabled(child),
 			};
 			memory_nodes.push(node);
 		}
 	}
-	println!("{:?}", memory_nodes);
+	for node in &memory_nodes {
+		messages::push(format!("Found memory node {} with {} region(s)",
+				       node.label, node.regions.len()));
+	}
 	return Ok(memory_nodes.clone())
 }
 
-pub fn dtb_get_memory_nodes(dtb_file: String)
--> Result<Option<Vec<MemoryNode>>, Box<dyn std::error::Error>>
+fn get_reserved_memory_nodes(root_node: &device_tree::Node)
+-> Result<Vec<ReservedMemoryNode>, Box<dyn std::error::Error>>
 {
-	let mut dtb_handle = fs::File::open(dtb_file)?;
-	let mut dtb = Vec::new();
-	dtb_handle.read_to_end(&mut dtb)?;
+	let mut reserved_nodes: Vec<ReservedMemoryNode> = Vec::new();
+
+	let reserved_memory = root_node.find("reserved-memory");
+	if reserved_memory.is_none() {
+		return Ok(reserved_nodes)
+	}
+
+	let reserved_memory = reserved_memory.unwrap();
+	let cells = node_cells(reserved_memory)?;
+	for child in reserved_memory.children.iter() {
+		// dynamically allocated regions only have a size & no reg, so
+		// there's no way to know where they will end up
+		let reg = child.prop_raw("reg");
+		if reg.is_none() {
+			continue;
+		}
+
+		let node = ReservedMemoryNode {
+			label: child.name.clone(),
+			regions: reg_to_regions(child, reg.unwrap(), cells)?,
+			no_map: child.has_prop("no-map"),
+			reusable: child.has_prop("reusable"),
+		};
+		reserved_nodes.push(node);
+	}
+
+	return Ok(reserved_nodes)
+}
+
+fn load_dtb(dtb_file: &Path)
+-> Result<device_tree::DeviceTree, Box<dyn std::error::Error>>
+{
+	if dts::is_dts(dtb_file) {
+		return Ok(dts::load_dts(dtb_file)?)
+	}
+
+	let dtb = stdio::read(dtb_file)?;
+	// there's no extension to go on for stdin, but a dtb starts with its
+	// magic number & a dts never will
+	if stdio::is_stdio(dtb_file) && !dtb.starts_with(&DTB_MAGIC) {
+		let dts = String::from_utf8(dtb).or(Err("stdin is neither a dtb nor a dts"))?;
+		return Ok(dts::load_dts_from_str(&dts, "<stdin>")?)
+	}
+
 	let dt = device_tree::DeviceTree::load(dtb.as_slice())
 			.or(Err("bad dtb"))?;
-	let root_node = dt.root;
-	return Ok(Some(get_memory_nodes(root_node)?));
+	return Ok(dt)
 }
 
+// both kinds of node from the one read, stdin can only be read once & a
+// dts would warn about everything in it twice
+pub fn dtb_get_nodes(dtb_file: impl AsRef<Path>)
+-> Result<DtbNodes, Box<dyn std::error::Error>>
+{
+	let dt = load_dtb(dtb_file.as_ref())?;
+	let root_cells = node_cells(&dt.root)?;
+	// with no /reserved-memory, there's nothing for its cells to apply to
+	let reserved_cells = match dt.root.find("reserved-memory") {
+		Some(reserved_memory) => node_cells(reserved_memory)?,
+		None => root_cells,
+	};
+	let reserved_nodes = get_reserved_memory_nodes(&dt.root)?;
+	let memory_nodes = get_memory_nodes(dt.root, root_cells)?;
+
+	return Ok(DtbNodes {
+		memory_nodes,
+		reserved_nodes,
+		cells: TreeCells { root: root_cells, reserved_memory: reserved_cells },
+	})
+}
+
+fn to_cells(value: u64, cells: usize) -> Option<Vec<u8>>
+{
+	// big endian, in however many cells there are, if it fits in them
+	let bytes = value.to_be_bytes();
+	let (high, low) = bytes.split_at(8 - 4 * cells);
+	if high.iter().any(|byte| return *byte != 0) {
+		return None
+	}
+
+	return Some(low.to_vec())
+}
+
+fn regions_to_reg(regions: &[MemoryRegion], cells: Cells) -> Result<Vec<u8>, String>
+{
+	// the reverse of reg_to_regions(), with as many cells as the parent has
+	// in the tree that the overlay gets applied to
+	let mut reg: Vec<u8> = Vec::new();
+	for region in regions {
+		reg.extend(to_cells(region.address, cells.address)
+			   .ok_or(format!("{:#x} doesn't fit in {} address cell(s)",
+					  region.address, cells.address))?);
+		reg.extend(to_cells(region.size, cells.size)
+			   .ok_or(format!("a size of {:#x} doesn't fit in {} size cell(s)",
+					  region.size, cells.size))?);
+	}
+
+	return Ok(reg)
+}
+
+fn same_regions(regions: &[MemoryRegion], others: &[MemoryRegion]) -> bool
+{
+	return regions.len() == others.len() &&
+	       regions.iter().zip(others)
+			.all(|(region, other)| return region.address == other.address &&
+						       region.size == other.size)
+}
+
+fn string_prop(name: &str, value: &str) -> (String, Vec<u8>)
+{
+	let mut bytes = value.as_bytes().to_vec();
+	bytes.push(0);
+
+	return (name.to_string(), bytes)
+}
+
+fn status_prop(enabled: bool) -> (String, Vec<u8>)
+{
+	if enabled {
+		return string_prop("status", "okay")
+	}
+
+	return string_prop("status", "disabled")
+}
+
+fn overlay_fragment(index: usize, target_path: &str, children: Vec<device_tree::Node>)
+-> device_tree::Node
+{
+	return device_tree::Node {
+		name: format!("fragment@{}", index),
+		props: vec![string_prop("target-path", target_path)],
+		children: vec![device_tree::Node {
+			name: "__overlay__".to_string(),
+			props: Vec::new(),
+			children,
+		}],
+	}
+}
+
+pub fn memory_overlay
+(original_nodes: &[MemoryNode], nodes: &[MemoryNode],
+ original_reserved: &[ReservedMemoryNode], reserved: &[ReservedMemoryNode], cells: TreeCells)
+-> Result<Option<device_tree::DeviceTree>, String>
+{
+	// only what differs from the dtb goes in, so that the overlay can be
+	// applied on top of it at boot. an overlay can't delete a node, so
+	// anything that has gone is disabled instead
+	let mut memory: Vec<device_tree::Node> = Vec::new();
+	for node in nodes {
+		let original = original_nodes.iter().find(|original| return original.label == node.label);
+		let unchanged = original
+			.map(|original| return same_regions(&original.regions, &node.regions) &&
+					      original.enabled == node.enabled)
+			.unwrap_or(false);
+		if unchanged {
+			continue;
+		}
+
+		memory.push(device_tree::Node {
+			name: node.label.clone(),
+			props: vec![
+				string_prop("device_type", "memory"),
+				("reg".to_string(), regions_to_reg(&node.regions, cells.root)
+				 .map_err(|error| return format!("{}: {}", node.label, error))?),
+				status_prop(node.enabled),
+			],
+			children: Vec::new(),
+		});
+	}
+
+	let mut reserved_memory: Vec<device_tree::Node> = Vec::new();
+	for node in reserved {
+		let original = original_reserved.iter().find(|original| return original.label == node.label);
+		let unchanged = original
+			.map(|original| return same_regions(&original.regions, &node.regions) &&
+					      original.no_map == node.no_map &&
+					      original.reusable == node.reusable)
+			.unwrap_or(false);
+		if unchanged {
+			continue;
+		}
+
+		let reg = regions_to_reg(&node.regions, cells.reserved_memory)
+			.map_err(|error| return format!("{}: {}", node.label, error))?;
+		let mut props = vec![("reg".to_string(), reg)];
+		if node.no_map {
+			props.push(("no-map".to_string(), Vec::new()));
+		}
+		if node.reusable {
+			props.push(("reusable".to_string(), Vec::new()));
+		}
+		reserved_memory.push(device_tree::Node {
+			name: node.label.clone(),
+			props,
+			children: Vec::new(),
+		});
+	}
+
+	let removed = |label: &String| return device_tree::Node {
+		name: label.clone(),
+		props: vec![status_prop(false)],
+		children: Vec::new(),
+	};
+	memory.extend(original_nodes.iter()
+		.map(|original| return &original.label)
+		.filter(|label| return !nodes.iter().any(|node| return node.label == **label))
+		.map(removed));
+	reserved_memory.extend(original_reserved.iter()
+		.map(|original| return &original.label)
+		.filter(|label| return !reserved.iter().any(|node| return node.label == **label))
+		.map(removed));
+
+	let mut fragments: Vec<device_tree::Node> = Vec::new();
+	if !memory.is_empty() {
+		fragments.push(overlay_fragment(fragments.len(), "/", memory));
+	}
+	if !reserved_memory.is_empty() {
+		fragments.push(overlay_fragment(fragments.len(), "/reserved-memory",
+						reserved_memory));
+	}
+	if fragments.is_empty() {
+		return Ok(None)
+	}
+
+	return Ok(Some(device_tree::DeviceTree {
+		version: 17,
+		boot_cpuid_phys: 0,
+		reserved: Vec::new(),
+		root: device_tree::Node {
+			name: String::new(),
+			props: Vec::new(),
+			children: fragments,
+		},
+	}))
+}
+
+pub fn write_overlay(overlay: &device_tree::DeviceTree, overlay_file: &Path)
+-> Result<(), Box<dyn std::error::Error>>
+{
+	// a ".dtbo" is compiled, anything else gets the source for dtc
+	if overlay_file.extension().map(|extension| return extension == "dtbo").unwrap_or(false) {
+		let blob = overlay.store().or(Err("failed to build the overlay"))?;
+		fs::write(overlay_file, blob)?;
+		return Ok(())
+	}
+
+	fs::write(overlay_file, dts::overlay_source(overlay))?;
+	return Ok(())
+}
@@ -0,0 +1,834 @@
+// SPDX-License-Identifier: MIT or GPL-2.0
+
+#![allow(unused_variables)]
+#![allow(dead_code)]
+#![deny(clippy::implicit_return)]
+#![allow(clippy::needless_return)]
+
+use std::fs;
+use std::path::Path;
+
+use crate::messages;
+
+// enough of the devicetree source format to get at the memory & reserved
+// memory nodes during bring-up, when there's a source tree but no dtb yet.
+// it builds the same tree that loading a dtb does, so everything after that is
+// shared. the c preprocessor is only mimicked: includes are pulled in,
+// object-like #defines are substituted and every other directive is ignored
+
+const MAX_INCLUDE_DEPTH: usize = 16;
+const MAX_MACRO_DEPTH: usize = 16;
+
+const DIRECTIVES: [&str; 12] = [
+	"include", "define", "undef", "if", "ifdef", "ifndef", "elif", "else", "endif",
+	"error", "pragma", "line",
+];
+
+// longest first, so that e.g. "<<" isn't taken for "<"
+const OPERATORS: [(&str, usize); 18] = [
+	("||", 1), ("&&", 2), ("==", 6), ("!=", 6), ("<=", 7), (">=", 7), ("<<", 8),
+	(">>", 8), ("|", 3), ("^", 4), ("&", 5), ("<", 7), (">", 7), ("+", 9),
+	("-", 9), ("*", 10), ("/", 10), ("%", 10),
+];
+
+pub fn is_dts(file: &Path) -> bool
+{
+	return file.extension()
+		.map(|extension| return extension == "dts" || extension == "dtsi")
+		.unwrap_or(false)
+}
+
+pub fn load_dts(dts_file: &Path) -> Result<device_tree::DeviceTree, String>
+{
+	let text = expand_includes(dts_file, 0)?;
+	return parse(&text)
+}
+
+pub fn load_dts_from_str(contents: &str, name: &str) -> Result<device_tree::DeviceTree, String>
+{
+	// e.g. from stdin, with includes relative to where the tool is run
+	let text = expand_contents(contents, Path::new(name), 0)?;
+	return parse(&text)
+}
+
+fn parse(text: &str) -> Result<device_tree::DeviceTree, String>
+{
+	let mut parser = Parser {
+		text: text.as_bytes(),
+		pos: 0,
+		defines: Vec::new(),
+		labels: Vec::new(),
+		macro_depth: 0,
+	};
+
+	return parser.parse_document()
+}
+
+fn include_name(line: &str) -> Option<&str>
+{
+	// the name between the quotes or angle brackets, ignoring anything that
+	// comes after it
+	let trimmed = line.trim_start();
+	let rest = trimmed.strip_prefix("#include")
+		.or_else(|| return trimmed.strip_prefix("/include/"))?
+		.trim_start();
+	let close = match rest.chars().next()? {
+		'"' => '"',
+		'<' => '>',
+		_ => return None,
+	};
+
+	return rest[1..].split(close).next()
+}
+
+fn expand_includes(path: &Path, depth: usize) -> Result<String, String>
+{
+	// "#line" markers keep track of which file each line came from, so that
+	// errors can point at the right one
+	let contents = fs::read_to_string(path)
+		.map_err(|error| return format!("{}: {}", path.display(), error))?;
+	return expand_contents(&contents, path, depth)
+}
+
+fn expand_contents(contents: &str, path: &Path, depth: usize) -> Result<String, String>
+{
+	let directory = path.parent().unwrap_or_else(|| return Path::new("."));
+
+	let mut expanded = format!("#line 1 \"{}\"\n", path.display());
+	for (line_number, line) in contents.lines().enumerate() {
+		let name = include_name(line);
+		if name.is_none() {
+			expanded += line;
+			expanded += "\n";
+			continue;
+		}
+
+		let include_path = directory.join(name.unwrap());
+		if !include_path.exists() {
+			// mostly dt-bindings headers, which only matter for things
+			// like interrupts, not memory
+			messages::warn(format!("{}:{}: {} not found, skipping it.",
+					       path.display(), line_number + 1, name.unwrap()));
+			expanded += "\n";
+			continue;
+		}
+
+		if depth >= MAX_INCLUDE_DEPTH {
+			return Err(format!("{}:{}: includes nested too deeply.", path.display(),
+					   line_number + 1))
+		}
+
+		expanded += &expand_includes(&include_path, depth + 1)?;
+		expanded += &format!("#line {} \"{}\"\n", line_number + 2, path.display());
+	}
+
+	return Ok(expanded)
+}
+
+fn new_node(name: &str) -> device_tree::Node
+{
+	return device_tree::Node {
+		name: name.to_string(),
+		props: Vec::new(),
+		children: Vec::new(),
+	}
+}
+
+fn node_at<'a>(root: &'a mut device_tree::Node, path: &[String])
+-> Option<&'a mut device_tree::Node>
+{
+	let mut node = root;
+	for name in path {
+		node = node.children.iter_mut().find(|child| return child.name == *name)?;
+	}
+
+	return Some(node)
+}
+
+fn is_name_char(c: u8) -> bool
+{
+	return c.is_ascii_alphanumeric() || b",._+*#?@-".contains(&c)
+}
+
+fn is_label_char(c: u8) -> bool
+{
+	return c.is_ascii_alphanumeric() || c == b'_'
+}
+
+struct Parser<'a> {
+	text: &'a [u8],
+	pos: usize,
+	defines: Vec<(String, String)>,
+	labels: Vec<(String, Vec<String>)>,
+	macro_depth: usize,
+}
+
+impl Parser<'_> {
+	fn error(&self, message: &str) -> String
+	{
+		// a macro's body has no file or line of its own
+		if self.macro_depth > 0 {
+			return message.to_string()
+		}
+
+		let before = String::from_utf8_lossy(&self.text[..self.pos]);
+		let mut lines: Vec<&str> = before.split('\n').collect();
+		lines.pop();
+
+		let mut file = String::new();
+		let mut line_number = 1;
+		for line in lines {
+			let marker = line.strip_prefix("#line ");
+			if marker.is_none() {
+				line_number += 1;
+				continue;
+			}
+
+			let (number, name) = marker.unwrap().split_once(' ').unwrap_or(("1", ""));
+			line_number = number.parse().unwrap_or(1);
+			file = name.trim_matches('"').to_string();
+		}
+
+		return format!("{}:{}: {}", file, line_number, message)
+	}
+
+	fn peek(&self) -> Option<u8>
+	{
+		return self.text.get(self.pos).copied()
+	}
+
+	fn looking_at(&self, expected: &str) -> bool
+	{
+		return self.text[self.pos..].starts_with(expected.as_bytes())
+	}
+
+	fn eat(&mut self, expected: &str) -> bool
+	{
+		self.skip_space();
+		if !self.looking_at(expected) {
+			return false
+		}
+
+		self.pos += expected.len();
+		return true
+	}
+
+	fn expect(&mut self, expected: &str) -> Result<(), String>
+	{
+		if !self.eat(expected) {
+			return Err(self.error(&format!("expected \"{}\"", expected)))
+		}
+
+		return Ok(())
+	}
+
+	fn take_while(&mut self, accept: fn(u8) -> bool) -> String
+	{
+		let start = self.pos;
+		while self.peek().map(accept).unwrap_or(false) {
+			self.pos += 1;
+		}
+
+		return String::from_utf8_lossy(&self.text[start..self.pos]).to_string()
+	}
+
+	fn rest_of_line(&mut self) -> String
+	{
+		// backslash-newline continues a directive onto the next line
+		let mut line = String::new();
+		while let Some(c) = self.peek() {
+			self.pos += 1;
+			if c == b'\n' {
+				if line.ends_with('\\') {
+					line.pop();
+					continue;
+				}
+				break;
+			}
+			line.push(c as char);
+		}
+
+		return line
+	}
+
+	fn at_line_start(&self) -> bool
+	{
+		return self.text[..self.pos].iter()
+			.rev()
+			.take_while(|c| return **c != b'\n')
+			.all(|c| return c.is_ascii_whitespace())
+	}
+
+	fn skip_directive(&mut self) -> bool
+	{
+		// "#address-cells" & co. look a lot like directives, so only the
+		// ones the preprocessor knows about count
+		let directive = DIRECTIVES.iter()
+			.find(|directive| {
+				let word = format!("#{}", directive);
+				let after = self.text.get(self.pos + word.len()).copied();
+				return self.looking_at(&word) &&
+				       !after.map(is_name_char).unwrap_or(false)
+			});
+		if directive.is_none() || !self.at_line_start() {
+			return false
+		}
+
+		let directive = directive.unwrap();
+		self.pos += directive.len() + 1;
+		let line = self.rest_of_line();
+		let line = line.trim();
+		if *directive == "undef" {
+			self.defines.retain(|(name, _)| return name != line);
+		}
+		if *directive != "define" {
+			return true
+		}
+
+		let name_len = line.find(|c: char| return !c.is_ascii_alphanumeric() && c != '_')
+			.unwrap_or(line.len());
+		let (name, body) = line.split_at(name_len);
+		// function-like macros aren't supported, see parse_primary()
+		if !body.starts_with('(') {
+			self.defines.push((name.to_string(), body.trim().to_string()));
+		}
+
+		return true
+	}
+
+	fn skip_space(&mut self)
+	{
+		loop {
+			while self.peek().map(|c| return c.is_ascii_whitespace()).unwrap_or(false) {
+				self.pos += 1;
+			}
+
+			if self.looking_at("//") {
+				self.rest_of_line();
+				continue;
+			}
+
+			if self.looking_at("/*") {
+				let end = self.text[self.pos + 2..]
+					.windows(2)
+					.position(|end| return end == b"*/")
+					.map(|end| return self.pos + 2 + end + 2)
+					.unwrap_or(self.text.len());
+				self.pos = end;
+				continue;
+			}
+
+			if self.peek() == Some(b'#') && self.skip_directive() {
+				continue;
+			}
+
+			return
+		}
+	}
+
+	fn parse_document(&mut self) -> Result<device_tree::DeviceTree, String>
+	{
+		let mut root = new_node("");
+		let mut reserved: Vec<(u64, u64)> = Vec::new();
+
+		loop {
+			self.skip_space();
+			if self.peek().is_none() {
+				break;
+			}
+
+			if self.eat("/dts-v1/") || self.eat("/plugin/") {
+				self.expect(";")?;
+				continue;
+			}
+
+			if self.eat("/memreserve/") {
+				let address = self.parse_primary()?;
+				let size = self.parse_primary()?;
+				self.expect(";")?;
+				reserved.push((address, size));
+				continue;
+			}
+
+			if self.eat("/delete-node/") {
+				let path = self.parse_reference()?;
+				if let Some((name, parent)) = path.split_last() {
+					if let Some(parent) = node_at(&mut root, parent) {
+						parent.children.retain(|child| return child.name != *name);
+					}
+				}
+				self.expect(";")?;
+				continue;
+			}
+
+			// a label on the root, or on a reference, doesn't mean anything
+			self.parse_labels();
+			let path = if self.eat("/") {
+				Vec::new()
+			} else if self.peek() == Some(b'&') {
+				self.parse_reference()?
+			} else {
+				return Err(self.error("expected \"/\" or a reference to a node"))
+			};
+
+			self.expect("{")?;
+			if node_at(&mut root, &path).is_some() {
+				self.parse_body(&mut root, &path)?;
+			} else {
+				// whatever the label belonged to must have been in a
+				// file that couldn't be found, so there's nothing to add to
+				messages::warn(self.error(&format!("{} not found, skipping it.",
+								   path.join("/"))));
+				let mut discarded = new_node("");
+				self.parse_body(&mut discarded, &[])?;
+			}
+			self.expect("}")?;
+			self.expect(";")?;
+		}
+
+		return Ok(device_tree::DeviceTree {
+			version: 17,
+			boot_cpuid_phys: 0,
+			reserved,
+			root,
+		})
+	}
+
+	fn parse_labels(&mut self) -> Vec<String>
+	{
+		let mut labels: Vec<String> = Vec::new();
+		loop {
+			let start = self.pos;
+			self.skip_space();
+			let label = self.take_while(is_label_char);
+			if label.is_empty() || !self.looking_at(":") {
+				self.pos = start;
+				return labels
+			}
+
+			self.pos += 1;
+			labels.push(label);
+		}
+	}
+
+	fn parse_reference(&mut self) -> Result<Vec<String>, String>
+	{
+		// either &label or &{/path/to/node}. a label that hasn't been seen
+		// comes back as a path that doesn't exist, which only matters when
+		// adding to a node: phandles are never resolved, so they can refer
+		// forwards to anything
+		self.expect("&")?;
+		if self.eat("{") {
+			let start = self.pos;
+			while self.peek().map(|c| return c != b'}').unwrap_or(false) {
+				self.pos += 1;
+			}
+			let path = String::from_utf8_lossy(&self.text[start..self.pos]).to_string();
+			self.expect("}")?;
+
+			return Ok(path.split('/')
+				  .filter(|name| return !name.is_empty())
+				  .map(|name| return name.to_string())
+				  .collect())
+		}
+
+		let label = self.take_while(is_label_char);
+		if label.is_empty() {
+			return Err(self.error("expected a label after \"&\""))
+		}
+
+		let path = self.labels.iter()
+			.rev()
+			.find(|(name, _)| return *name == label)
+			.map(|(_, path)| return path.clone());
+		if path.is_none() {
+			return Ok(vec![format!("&{}", label)])
+		}
+
+		return Ok(path.unwrap())
+	}
+
+	fn parse_name(&mut self) -> Result<String, String>
+	{
+		self.skip_space();
+		let name = self.take_while(is_name_char);
+		if name.is_empty() {
+			return Err(self.error("expected a node or property name"))
+		}
+
+		return Ok(name)
+	}
+
+	fn parse_body(&mut self, root: &mut device_tree::Node, path: &[String])
+	-> Result<(), String>
+	{
+		loop {
+			self.skip_space();
+			if self.peek() == Some(b'}') || self.peek().is_none() {
+				return Ok(())
+			}
+
+			if self.eat("/delete-node/") {
+				let name = self.parse_name()?;
+				self.expect(";")?;
+				if let Some(node) = node_at(root, path) {
+					node.children.retain(|child| return child.name != name);
+				}
+				continue;
+			}
+
+			if self.eat("/delete-property/") {
+				let name = self.parse_name()?;
+				self.expect(";")?;
+				if let Some(node) = node_at(root, path) {
+					node.props.retain(|(prop, _)| return *prop != name);
+				}
+				continue;
+			}
+
+			let labels = self.parse_labels();
+			let name = self.parse_name()?;
+
+			if self.eat("{") {
+				let node = node_at(root, path)
+					.ok_or_else(|| return self.error("node has been deleted"))?;
+				if !node.children.iter().any(|child| return child.name == name) {
+					node.children.push(new_node(&name));
+				}
+
+				let mut child_path = path.to_vec();
+				child_path.push(name);
+				for label in labels {
+					self.labels.push((label, child_path.clone()));
+				}
+
+				self.parse_body(root, &child_path)?;
+				self.expect("}")?;
+				self.expect(";")?;
+				continue;
+			}
+
+			let mut value: Vec<u8> = Vec::new();
+			if self.eat("=") {
+				value = self.parse_value()?;
+			}
+			self.expect(";")?;
+
+			let node = node_at(root, path)
+				.ok_or_else(|| return self.error("node has been deleted"))?;
+			node.props.retain(|(prop, _)| return *prop != name);
+			node.props.push((name, value));
+		}
+	}
+
+	fn parse_value(&mut self) -> Result<Vec<u8>, String>
+	{
+		let mut value: Vec<u8> = Vec::new();
+		loop {
+			self.skip_space();
+			self.parse_labels();
+			self.skip_space();
+
+			if self.peek() == Some(b'"') {
+				value.extend(self.parse_string()?);
+				value.push(0);
+			} else if self.eat("/bits/") {
+				let bits = self.parse_primary()?;
+				self.expect("<")?;
+				value.extend(self.parse_cells(bits)?);
+			} else if self.eat("<") {
+				value.extend(self.parse_cells(32)?);
+			} else if self.eat("[") {
+				value.extend(self.parse_bytes()?);
+			} else if self.peek() == Some(b'&') {
+				let path = self.parse_reference()?;
+				value.extend(format!("/{}", path.join("/")).bytes());
+				value.push(0);
+			} else {
+				return Err(self.error("expected a property value"))
+			}
+
+			if !self.eat(",") {
+				return Ok(value)
+			}
+		}
+	}
+
+	fn parse_string(&mut self) -> Result<Vec<u8>, String>
+	{
+		self.expect("\"")?;
+		let mut string: Vec<u8> = Vec::new();
+		loop {
+			let c = self.peek().ok_or_else(|| return self.error("unterminated string"))?;
+			self.pos += 1;
+			match c {
+				b'"' => return Ok(string),
+				b'\\' => {
+					let escaped = self.peek()
+						.ok_or_else(|| return self.error("unterminated string"))?;
+					self.pos += 1;
+					string.push(match escaped {
+						b'n' => b'\n',
+						b't' => b'\t',
+						b'r' => b'\r',
+						b'0' => 0,
+						other => other,
+					});
+				}
+				other => string.push(other),
+			}
+		}
+	}
+
+	fn parse_cells(&mut self, bits: u64) -> Result<Vec<u8>, String>
+	{
+		let mut cells: Vec<u8> = Vec::new();
+		loop {
+			if self.eat(">") {
+				return Ok(cells)
+			}
+
+			self.parse_labels();
+			self.skip_space();
+			// phandles are only known once the dtb is built, and nothing
+			// here needs them
+			let mut cell = 0;
+			if self.peek() == Some(b'&') {
+				self.parse_reference()?;
+			} else {
+				cell = self.parse_primary()?;
+			}
+
+			match bits {
+				8 => cells.push(cell as u8),
+				16 => cells.extend((cell as u16).to_be_bytes()),
+				32 => cells.extend((cell as u32).to_be_bytes()),
+				64 => cells.extend(cell.to_be_bytes()),
+				_ => return Err(self.error(&format!("/bits/ {} isn't 8, 16, 32 or 64",
+								    bits))),
+			}
+		}
+	}
+
+	fn parse_bytes(&mut self) -> Result<Vec<u8>, String>
+	{
+		let mut bytes: Vec<u8> = Vec::new();
+		loop {
+			if self.eat("]") {
+				return Ok(bytes)
+			}
+
+			self.skip_space();
+			let digits = self.text.get(self.pos..self.pos + 2)
+				.map(|digits| return String::from_utf8_lossy(digits).to_string())
+				.unwrap_or_default();
+			let byte = u8::from_str_radix(&digits, 16)
+				.map_err(|_| return self.error("expected a pair of hex digits"))?;
+			self.pos += 2;
+			bytes.push(byte);
+		}
+	}
+
+	fn parse_number(&mut self) -> Result<u64, String>
+	{
+		let literal = self.take_while(|c| return c.is_ascii_alphanumeric());
+		let digits = literal.trim_end_matches(['u', 'U', 'l', 'L']);
+		let number = if let Some(hex) = digits.strip_prefix("0x")
+			.or_else(|| return digits.strip_prefix("0X")) {
+			u64::from_str_radix(hex, 16)
+		} else if digits.len() > 1 && digits.starts_with('0') {
+			u64::from_str_radix(&digits[1..], 8)
+		} else {
+			digits.parse::<u64>()
+		};
+
+		return number.map_err(|_| return self.error(&format!("{} isn't a number", literal)))
+	}
+
+	fn parse_primary(&mut self) -> Result<u64, String>
+	{
+		self.skip_space();
+		let c = self.peek().ok_or_else(|| return self.error("expected a number"))?;
+
+		if c == b'(' {
+			self.pos += 1;
+			let value = self.parse_binary(0)?;
+			self.expect(")")?;
+			return Ok(value)
+		}
+
+		if c.is_ascii_digit() {
+			return self.parse_number()
+		}
+
+		if c == b'\'' {
+			self.pos += 1;
+			let value = self.peek().ok_or_else(|| return self.error("expected a char"))?;
+			self.pos += 1;
+			self.expect("'")?;
+			return Ok(value as u64)
+		}
+
+		let name = self.take_while(is_label_char);
+		if name.is_empty() {
+			return Err(self.error("expected a number"))
+		}
+
+		// anything from a header that couldn't be found, or a function-like
+		// macro, is taken as 0. it's almost certainly an interrupt or a gpio
+		// rather than anything to do with memory
+		if self.looking_at("(") {
+			let mut nesting = 0;
+			while let Some(c) = self.peek() {
+				self.pos += 1;
+				nesting += (c == b'(') as i32 - (c == b')') as i32;
+				if nesting == 0 {
+					break;
+				}
+			}
+			messages::warn(self.error(&format!("{}() isn't supported, using 0.", name)));
+			return Ok(0)
+		}
+
+		return self.expand_macro(&name)
+	}
+
+	fn expand_macro(&mut self, name: &str) -> Result<u64, String>
+	{
+		let body = self.defines.iter()
+			.rev()
+			.find(|(define, _)| return define == name)
+			.map(|(_, body)| return body.clone());
+		if body.is_none() {
+			messages::warn(self.error(&format!("{} isn't defined, using 0.", name)));
+			return Ok(0)
+		}
+
+		if self.macro_depth >= MAX_MACRO_DEPTH {
+			return Err(self.error(&format!("{} expands into itself", name)))
+		}
+
+		let body = body.unwrap();
+		let mut parser = Parser {
+			text: body.as_bytes(),
+			pos: 0,
+			defines: self.defines.clone(),
+			labels: Vec::new(),
+			macro_depth: self.macro_depth + 1,
+		};
+		let value = parser.parse_binary(0)
+			.map_err(|error| return self.error(&format!("in {}: {}", name, error)))?;
+
+		return Ok(value)
+	}
+
+	fn parse_unary(&mut self) -> Result<u64, String>
+	{
+		if self.eat("-") {
+			return Ok(self.parse_unary()?.wrapping_neg())
+		}
+		if self.eat("~") {
+			return Ok(!self.parse_unary()?)
+		}
+		if self.eat("!") {
+			return Ok((self.parse_unary()? == 0) as u64)
+		}
+
+		return self.parse_primary()
+	}
+
+	fn parse_binary(&mut self, min_precedence: usize) -> Result<u64, String>
+	{
+		// precedence climbing, over the c operators that turn up in dts
+		let mut left = self.parse_unary()?;
+		loop {
+			self.skip_space();
+			let operator = OPERATORS.iter()
+				.find(|(operator, _)| return self.looking_at(operator))
+				.filter(|(_, precedence)| return *precedence >= min_precedence);
+			if operator.is_none() {
+				return Ok(left)
+			}
+
+			let (operator, precedence) = operator.unwrap();
+			self.pos += operator.len();
+			let right = self.parse_binary(precedence + 1)?;
+			left = match *operator {
+				"||" => (left != 0 || right != 0) as u64,
+				"&&" => (left != 0 && right != 0) as u64,
+				"==" => (left == right) as u64,
+				"!=" => (left != right) as u64,
+				"<=" => (left <= right) as u64,
+				">=" => (left >= right) as u64,
+				"<" => (left < right) as u64,
+				">" => (left > right) as u64,
+				"<<" => left.wrapping_shl(right as u32),
+				">>" => left.wrapping_shr(right as u32),
+				"|" => left | right,
+				"^" => left ^ right,
+				"&" => left & right,
+				"+" => left.wrapping_add(right),
+				"-" => left.wrapping_sub(right),
+				"*" => left.wrapping_mul(right),
+				"/" => left.checked_div(right)
+					.ok_or_else(|| return self.error("division by zero"))?,
+				_ => left.checked_rem(right)
+					.ok_or_else(|| return self.error("division by zero"))?,
+			};
+		}
+	}
+}
+
+fn prop_source(value: &[u8]) -> String
+{
+	// the same guesses dtc makes when decompiling: printable & nul
+	// terminated is a string, otherwise whole cells if it can be
+	let printable = value.split_last()
+		.map(|(last, string)| return *last == 0 && !string.is_empty() &&
+					      string.iter().all(|c| return c.is_ascii_graphic() ||
+									   *c == b' '))
+		.unwrap_or(false);
+	if printable {
+		return format!("\"{}\"", String::from_utf8_lossy(&value[..value.len() - 1]))
+	}
+
+	if value.chunks_exact(4).remainder().is_empty() {
+		let cells: Vec<String> = value.chunks_exact(4)
+			.map(|cell| return format!("{:#x}", u32::from_be_bytes(cell.try_into().unwrap())))
+			.collect();
+		return format!("<{}>", cells.join(" "))
+	}
+
+	let bytes: Vec<String> = value.iter().map(|byte| return format!("{:02x}", byte)).collect();
+	return format!("[{}]", bytes.join(" "))
+}
+
+fn node_source(node: &device_tree::Node, depth: usize) -> String
+{
+	// the root node has no name of its own
+	let mut name = node.name.as_str();
+	if name.is_empty() {
+		name = "/";
+	}
+
+	let indent = "\t".repeat(depth);
+	let mut source = format!("{}{} {{\n", indent, name);
+	for (name, value) in &node.props {
+		if value.is_empty() {
+			source += &format!("{}\t{};\n", indent, name);
+		} else {
+			source += &format!("{}\t{} = {};\n", indent, name, prop_source(value));
+		}
+	}
+	for child in &node.children {
+		source += &node_source(child, depth + 1);
+	}
+	source += &format!("{}}};\n", indent);
+
+	return source
+}
+
+pub fn overlay_source(overlay: &device_tree::DeviceTree) -> String
+{
+	return format!("/dts-v1/;\n/plugin/;\n\n{}", node_source(&overlay.root, 0))
+}
@@ -0,0 +1,986 @@
+// SPDX-License-Identifier: MIT or GPL-2.0
+
+#![allow(unused_variables)]
+#![allow(dead_code)]
+#![deny(clippy::implicit_return)]
+#![allow(clippy::needless_return)]
+
+use crate::contexts;
+use crate::dt::{MemoryNode, MemoryRegion, ReservedMemoryNode};
+use crate::pmp;
+use crate::soc;
+use crate::soc::{Aperture, MemoryAperture, PerformanceClass};
+use crate::soc::SoC;
+use crate::theme;
+use crate::units;
+use tui::style::Color;
+
+// things other than the config that need to agree with the seg registers,
+// generated from the board so that they can't drift out of step with it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+	Uboot,
+	Cmdline,
+	Openocd,
+	Gdb,
+	Contexts,
+	Pmp,
+	PmpC,
+	Report(Markup),
+	Svg,
+	Renode,
+	Qemu,
+}
+
+// the memory map report reads much the same in either, only the markup of
+// the headings, tables & the map itself changes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Markup {
+	Markdown,
+	Asciidoc,
+}
+
+pub const EXPORT_FORMATS: [(&str, ExportFormat); 12] = [
+	("uboot", ExportFormat::Uboot),
+	("cmdline", ExportFormat::Cmdline),
+	("openocd", ExportFormat::Openocd),
+	("gdb", ExportFormat::Gdb),
+	("contexts", ExportFormat::Contexts),
+	("pmp", ExportFormat::Pmp),
+	("pmp-c", ExportFormat::PmpC),
+	("report", ExportFormat::Report(Markup::Markdown)),
+	("report-adoc", ExportFormat::Report(Markup::Asciidoc)),
+	("svg", ExportFormat::Svg),
+	("renode", ExportFormat::Renode),
+	("qemu", ExportFormat::Qemu),
+];
+
+pub fn parse_format(name: &str) -> Option<ExportFormat>
+{
+	return EXPORT_FORMATS.iter()
+		.find(|(format_name, _)| return format_name.eq_ignore_ascii_case(name))
+		.map(|(_, format)| return *format)
+}
+
+pub fn format_names() -> String
+{
+	let names: Vec<&str> = EXPORT_FORMATS.iter()
+		.map(|(name, _)| return *name)
+		.collect();
+
+	return names.join("|")
+}
+
+pub fn export(board: &soc::MPFS, nodes: &[MemoryNode], reserved_nodes: &[ReservedMemoryNode],
+	      format: ExportFormat)
+-> String
+{
+	match format {
+		ExportFormat::Report(markup) => return report(board, nodes, reserved_nodes, markup),
+		ExportFormat::Svg => return svg(board, nodes, reserved_nodes),
+		ExportFormat::Renode => return renode(board),
+		ExportFormat::Qemu => return qemu(board),
+		ExportFormat::Uboot => return uboot(board),
+		ExportFormat::Cmdline => return cmdline(board, reserved_nodes),
+		ExportFormat::Contexts => return contexts(board),
+		ExportFormat::Pmp => return pmp_yaml(board),
+		ExportFormat::PmpC => return pmp_c(board),
+		ExportFormat::Openocd => return register_writes(board, |addr, seg| {
+			return format!("mww {:#x} {:#x}", addr, seg)
+		}),
+		ExportFormat::Gdb => return register_writes(board, |addr, seg| {
+			return format!("set {{unsigned int}}{:#x} = {:#x}", addr, seg)
+		}),
+	}
+}
+
+fn kernel_size(bytes: u64) -> String
+{
+	// memparse() takes a K/M/G suffix, which is a lot easier to read than
+	// the raw number when it fits
+	if bytes != 0 && bytes & ((1 << 20) - 1) == 0 {
+		return format!("{}M", bytes >> 20)
+	}
+
+	return bytes.to_string()
+}
+
+fn uboot(board: &soc::MPFS) -> String
+{
+	// linux only runs out of the cached apertures, so they're the ones that
+	// make up its memory, at the bus addresses it sees them at
+	let banks = board.bus_banks(PerformanceClass::CachedAxi);
+	let total: u64 = banks.iter().map(|(_, size)| return size).sum();
+	if banks.is_empty() {
+		return "# none of the cached apertures reach ddr\n".to_string()
+	}
+
+	let mut output = String::new();
+	output += "# generated from the seg-reg-config, using the cached apertures\n";
+	output += "# kernel command line\n";
+
+	let memmap: Vec<String> = banks.iter()
+		.map(|(bus_addr, size)| return format!("memmap={}@{:#x}",
+						       kernel_size(*size), bus_addr))
+		.collect();
+	output += &format!("mem={} {}\n", kernel_size(total), memmap.join(" "));
+
+	output += "# u-boot\n";
+	output += "fdt addr ${fdt_addr_r}\n";
+	// "fdt memory" only takes a single bank, so any others have to be
+	// described by the devicetree itself
+	for (index, (bus_addr, size)) in banks.iter().enumerate() {
+		let mut line = format!("fdt memory {:#x} {:#x}\n", bus_addr, size);
+		if index > 0 {
+			line = format!("# {}", line);
+		}
+		output += &line;
+	}
+	output += &format!("setenv bootargs \"${{bootargs}} mem={} {}\"\n", kernel_size(total),
+			   memmap.join(" "));
+
+	return output
+}
+
+fn memmap_holes(board: &soc::MPFS) -> Vec<(u64, u64, String)>
+{
+	// the (bus address, size) of anything in the cached banks that linux
+	// shouldn't treat as its own memory: ddr that an earlier cached aperture
+	// already gives it, or that something else reaches uncached
+	let mut holes: Vec<(u64, u64, String)> = Vec::new();
+	// a hole between memory banks is left out of the banks altogether
+	let pieces = board.hw_pieces();
+	let pieces = pieces.iter().filter(|(start, end, _)| return !board.is_hole(*start, *end));
+	for (start, end, hosts) in pieces {
+		let cached: Vec<&&MemoryAperture> = hosts.iter()
+			.filter(|aperture| return aperture.performance_class.is_cached())
+			.collect();
+		let uncached = hosts.iter()
+			.find(|aperture| return !aperture.performance_class.is_cached());
+
+		for (index, aperture) in cached.iter().enumerate() {
+			let reason = match (index, uncached) {
+				(_, Some(other)) => format!("{} also reaches it through {}",
+							    other.performance_class.name(),
+							    other.reg_name),
+				(0, None) => continue,
+				(_, None) => format!("the same ddr as {}", cached[0].reg_name),
+			};
+			let bus_addr = aperture.bus_addr + (start - aperture.hardware_addr);
+			holes.push((bus_addr, end - start,
+				    format!("via {}, {}", aperture.reg_name, reason)));
+		}
+	}
+
+	return holes
+}
+
+fn cmdline(board: &soc::MPFS, reserved_nodes: &[ReservedMemoryNode]) -> String
+{
+	// the memory linux gets through the cached apertures, with everything
+	// in it that it shouldn't use carved back out with memmap=nn$ss. for
+	// trying a layout out before the devicetree catches up with it
+	let banks = board.bus_banks(PerformanceClass::CachedAxi);
+	let total: u64 = banks.iter().map(|(_, size)| return size).sum();
+	if banks.is_empty() {
+		return "# none of the cached apertures reach ddr\n".to_string()
+	}
+
+	let mut reserved = memmap_holes(board);
+	for node in reserved_nodes {
+		for region in &node.regions {
+			// only what's in linux's memory needs keeping out of it
+			let in_banks = banks.iter().any(|(bus_addr, size)| {
+				return region.address < bus_addr.saturating_add(*size) &&
+					*bus_addr < region.address.saturating_add(region.size)
+			});
+			if in_banks && region.size != 0 {
+				reserved.push((region.address, region.size,
+					       format!("reserved-memory {}", node.label)));
+			}
+		}
+	}
+	reserved.sort_by_key(|(bus_addr, _, _)| return *bus_addr);
+
+	let mut output = String::new();
+	output += "# generated from the seg-reg-config, using the cached apertures\n";
+	for (bus_addr, size, reason) in &reserved {
+		output += &format!("# {:#x}-{:#x} {}\n", bus_addr,
+				   bus_addr.saturating_add(size - 1), reason);
+	}
+	output += "# in u-boot's bootargs each $ has to be escaped as \\$\n";
+
+	let mut options = vec![format!("mem={}", kernel_size(total))];
+	options.extend(banks.iter().map(|(bus_addr, size)| {
+		return format!("memmap={}@{:#x}", kernel_size(*size), bus_addr)
+	}));
+	options.extend(reserved.iter().map(|(bus_addr, size, _)| {
+		return format!("memmap={}${:#x}", kernel_size(*size), bus_addr)
+	}));
+	output += &options.join(" ");
+	output += "\n";
+
+	return output
+}
+
+fn window_comment(aperture: &MemoryAperture, start: u64, end: u64) -> String
+{
+	return format!("{} ({}) {:#x}-{:#x}, ddr {:#x}-{:#x}", aperture.reg_name,
+		       aperture.performance_class.name(), aperture.bus_addr,
+		       aperture.bus_addr + (end - start) - 1, start, end - 1)
+}
+
+fn renode(board: &soc::MPFS) -> String
+{
+	// a memory block for each window onto ddr, at its bus address & cut
+	// down to the part that lands in ddr. renode can't map one block at
+	// more than one offset, so windows onto the same ddr don't share it
+	let ranges = board.hw_ranges();
+	if ranges.is_empty() {
+		return "// none of the apertures reach ddr\n".to_string()
+	}
+
+	let mut output = String::new();
+	output += "// generated from the seg-reg-config, one block per aperture\n";
+	output += "// aliased windows are separate memory here, unlike on the board\n";
+	for (aperture, start, end) in ranges {
+		output += &format!("\n// {}\n", window_comment(aperture, start, end));
+		output += &format!("ddr_{}: Memory.MappedMemory @ sysbus {:#x}\n", aperture.reg_name,
+				   aperture.bus_addr);
+		output += &format!("    size: {:#x}\n", end - start);
+	}
+
+	return output
+}
+
+fn qemu(board: &soc::MPFS) -> String
+{
+	// qemu's machine model decides where ddr shows up on the bus, so all
+	// that can be given is how much of it there is. the windows are listed
+	// to check the model against
+	let total = board.total_memory();
+	let mut output = "# generated from the seg-reg-config\n".to_string();
+	output += "# the machine model places ddr itself, the board's windows onto it are:\n";
+	for (aperture, start, end) in board.hw_ranges() {
+		output += &format!("#   {}\n", window_comment(aperture, start, end));
+	}
+	output += &format!("-m {} -object memory-backend-ram,id=ddr,size={} \
+			    -machine memory-backend=ddr\n", kernel_size(total), kernel_size(total));
+
+	return output
+}
+
+fn register_writes(board: &soc::MPFS, write: fn(u64, u64) -> String) -> String
+{
+	// pokes the seg registers of a live target directly, so that a layout
+	// can be tried out without reprogramming the envm. has to be done from
+	// m-mode, before anything has started using ddr through the apertures
+	// both openocd & gdb scripts take # for comments
+	let mut output = "# generated from the seg-reg-config\n".to_string();
+	for aperture in board.apertures() {
+		let seg = aperture.seg();
+		let addr = soc::seg_reg_addr(&aperture.reg_name);
+		if addr.is_none() {
+			output += &format!("# {}: no known register address\n",
+					   aperture.reg_name);
+			continue;
+		}
+
+		if let Err(error) = seg {
+			output += &format!("# {}: {}\n", aperture.reg_name, error);
+			continue;
+		}
+		let seg = seg.unwrap();
+
+		output += &format!("# {}\n{}\n", aperture.reg_name, write(addr.unwrap(), seg));
+	}
+
+	return output
+}
+
+fn contexts(board: &soc::MPFS) -> String
+{
+	// a memory node for each context, made up of its memory as seen through
+	// the cached apertures, & the seg registers that it relies on
+	if board.contexts.is_empty() {
+		return "# no contexts are defined in the config\n".to_string()
+	}
+
+	let mut output = String::new();
+	for problem in contexts::check_contexts(&board.contexts) {
+		output += &format!("# warning: {}\n", problem);
+	}
+
+	for context in &board.contexts {
+		let (banks, unreachable) = contexts::context_banks(board, context);
+		output += &format!("\n# {} (harts: {})\n", context.name, context.harts.join(", "));
+
+		let mut reg_names: Vec<&str> = banks.iter()
+			.map(|bank| return bank.reg_name.as_str())
+			.collect();
+		reg_names.dedup();
+		for reg_name in reg_names {
+			let aperture = board.aperture_by_name(reg_name);
+			if let Some(aperture) = aperture {
+				output += &match aperture.seg() {
+					Ok(seg) => format!("# {} = {:#x}\n", reg_name, seg),
+					Err(error) => format!("# {}: {}\n", reg_name, error),
+				};
+			}
+		}
+
+		for (start, end) in unreachable {
+			output += &format!("# warning: {:#x}-{:#x} is not reachable through a \
+					    cached aperture\n", start, end - 1);
+		}
+
+		if banks.is_empty() {
+			continue;
+		}
+
+		let reg: Vec<String> = banks.iter()
+			.map(|bank| return format!("{:#x} {:#x} {:#x} {:#x}", bank.bus_addr >> 32,
+						   bank.bus_addr & 0xffff_ffff, bank.size >> 32,
+						   bank.size & 0xffff_ffff))
+			.collect();
+		output += &format!("memory@{:x} {{\n", banks[0].bus_addr);
+		output += "\tdevice_type = \"memory\";\n";
+		output += &format!("\treg = <{}>;\n", reg.join(" "));
+		output += "};\n";
+	}
+
+	return output
+}
+
+fn pmp_warnings(board: &soc::MPFS, context: &contexts::Context, entries: &[pmp::PmpEntry])
+-> Vec<String>
+{
+	let mut warnings: Vec<String> = Vec::new();
+	let (_, unreachable) = contexts::context_banks(board, context);
+	if !unreachable.is_empty() {
+		warnings.push(format!("not all of {} is reachable, so not all of it is covered",
+				      context.name));
+	}
+	if entries.len() > pmp::PMP_ENTRIES_PER_HART {
+		warnings.push(format!("{} needs {} pmp entries, but there are only {}",
+				      context.name, entries.len(), pmp::PMP_ENTRIES_PER_HART));
+	}
+
+	return warnings
+}
+
+fn pmp_yaml(board: &soc::MPFS) -> String
+{
+	// candidate entries for each context's harts, giving them access to
+	// their own memory. these still need merging with whatever else the
+	// harts need, e.g. peripherals, so they're a starting point only
+	if board.contexts.is_empty() {
+		return "# no contexts are defined in the config\n".to_string()
+	}
+
+	let mut output = "pmp:\n".to_string();
+	for context in &board.contexts {
+		let entries = pmp::context_entries(board, context);
+		output += &format!("  {}:\n", context.name);
+		for warning in pmp_warnings(board, context, &entries) {
+			output += &format!("    # warning: {}\n", warning);
+		}
+		output += &format!("    harts: [{}]\n", context.harts.join(", "));
+		output += "    entries:\n";
+		for entry in &entries {
+			output += &format!("      - {{mode: {}, cfg: '{:#04x}', access: {}, \
+					    addr: '{:#x}', base: '{:#x}', size: '{:#x}'}}\n",
+					   entry.mode.name(), entry.cfg, entry.access(), entry.addr,
+					   entry.base, entry.size);
+		}
+	}
+
+	return output
+}
+
+fn pmp_c(board: &soc::MPFS) -> String
+{
+	if board.contexts.is_empty() {
+		return "/* no contexts are defined in the config */\n".to_string()
+	}
+
+	let mut output = "/* generated from the seg-reg-config & contexts */\n".to_string();
+	for context in &board.contexts {
+		let entries = pmp::context_entries(board, context);
+		let prefix = format!("PMP_{}", context.name.to_uppercase().replace(['-', ' ', '.'], "_"));
+
+		output += &format!("\n/* {} (harts: {}) */\n", context.name, context.harts.join(", "));
+		for warning in pmp_warnings(board, context, &entries) {
+			output += &format!("/* warning: {} */\n", warning);
+		}
+		output += &format!("#define {}_COUNT {}\n", prefix, entries.len());
+		for (index, entry) in entries.iter().enumerate() {
+			output += &format!("#define {}_{}_CFG {:#04x} /* {}, {} */\n", prefix, index,
+					   entry.cfg, entry.mode.name(), entry.access());
+			output += &format!("#define {}_{}_ADDR {:#x}UL /* {:#x}, {:#x} bytes */\n",
+					   prefix, index, entry.addr, entry.base, entry.size);
+		}
+	}
+
+	return output
+}
+
+fn heading(markup: Markup, level: usize, title: &str) -> String
+{
+	let marker = match markup {
+		Markup::Markdown => "#",
+		Markup::Asciidoc => "=",
+	};
+
+	return format!("{} {}\n\n", marker.repeat(level), title)
+}
+
+fn table(markup: Markup, headers: &[&str], rows: &[Vec<String>]) -> String
+{
+	let mut output = String::new();
+	match markup {
+		Markup::Markdown => {
+			output += &format!("| {} |\n", headers.join(" | "));
+			output += &format!("|{}\n", "---|".repeat(headers.len()));
+			for row in rows {
+				output += &format!("| {} |\n", row.join(" | "));
+			}
+		}
+		Markup::Asciidoc => {
+			output += &format!("[options=\"header\"]\n|===\n| {}\n", headers.join(" | "));
+			for row in rows {
+				output += &format!("\n| {}\n", row.join(" | "));
+			}
+			output += "|===\n";
+		}
+	}
+
+	return output + "\n"
+}
+
+fn literal(markup: Markup, text: &str) -> String
+{
+	match markup {
+		Markup::Markdown => return format!("```text\n{}```\n\n", text),
+		Markup::Asciidoc => return format!("....\n{}....\n\n", text),
+	}
+}
+
+fn region_hw_start(region: &MemoryRegion, apertures: &[MemoryAperture]) -> Option<u64>
+{
+	// like MemoryRegion::get_hw_start_addr, but without complaining about
+	// the ones that don't land anywhere
+	return region.host_aperture(apertures)
+		.and_then(|aperture| return aperture.get_region_hw_start_addr(region.address,
+									      region.size))
+}
+
+fn region_row(label: &str, region: &MemoryRegion, flags: &str, apertures: &[MemoryAperture])
+-> Vec<String>
+{
+	let host = region.host_aperture(apertures)
+		.map(|aperture| return aperture.reg_name.clone())
+		.unwrap_or("-".to_string());
+	let hw_start = region_hw_start(region, apertures)
+		.map(|start| return format!("{:#x}", start))
+		.unwrap_or("not mapped".to_string());
+
+	let mut flags = flags.to_string();
+	if flags.is_empty() {
+		flags = "-".to_string();
+	}
+
+	return vec![
+		label.to_string(),
+		format!("{:#x}", region.address),
+		units::format_size(region.size),
+		flags,
+		host,
+		hw_start,
+	]
+}
+
+fn ascii_map(board: &soc::MPFS, labels: &[(String, u64)]) -> String
+{
+	// one box per piece of ddr, rather than to scale, so that small pieces
+	// can still be read. the nodes are noted against the piece they start in
+	let pieces = board.hw_pieces();
+	let contents: Vec<String> = pieces.iter()
+		.map(|(start, end, hosts)| {
+			if board.is_hole(*start, *end) {
+				return "(no memory)".to_string()
+			}
+			if hosts.is_empty() {
+				return "(not reachable)".to_string()
+			}
+			let names: Vec<&str> = hosts.iter()
+				.map(|aperture| return aperture.reg_name.as_str())
+				.collect();
+			return names.join(" ")
+		})
+		.collect();
+	let width = contents.iter().map(|content| return content.len()).max().unwrap_or(0).max(20);
+	let border = format!("+{}+", "-".repeat(width + 2));
+	let indent = " ".repeat(13);
+
+	let mut output = String::new();
+	for ((start, end, _), content) in pieces.iter().zip(&contents) {
+		output += &format!("{:#012x} {}\n", start, border);
+		let notes: Vec<&str> = labels.iter()
+			.filter(|(_, hw_start)| return start <= hw_start && hw_start < end)
+			.map(|(label, _)| return label.as_str())
+			.collect();
+		let mut line = format!("{}| {:<width$} |", indent, content, width = width);
+		if !notes.is_empty() {
+			line += &format!(" <- {}", notes.join(", "));
+		}
+		output += &format!("{}\n", line);
+	}
+	output += &format!("{:#012x} {}\n", board.total_memory(), border);
+
+	return output
+}
+
+fn describe_memory(board: &soc::MPFS) -> String
+{
+	// e.g. "2 GiB (0x80000000) of DDR", with where each bank is if there's
+	// more than the one
+	let total = board.total_memory();
+	if board.memory_banks.is_empty() {
+		return format!("{} ({:#x}) of DDR", units::format_size(total), total)
+	}
+
+	let banks: Vec<String> = board.memory_banks.iter()
+		.map(|bank| return format!("{} at {:#x} - {:#x}", bank.name, bank.base,
+					   bank.end()))
+		.collect();
+	return format!("{} of DDR in {} banks, {}", units::format_size(board.installed_memory()),
+		       banks.len(), banks.join(", "))
+}
+
+fn report(board: &soc::MPFS, nodes: &[MemoryNode], reserved_nodes: &[ReservedMemoryNode],
+	  markup: Markup)
+-> String
+{
+	// for bring-up docs, so it says what everything is rather than being
+	// something to feed back into a tool
+	let apertures = board.apertures();
+	let banks = board.banks();
+	let mut output = heading(markup, 1, "Memory map");
+	output += &format!("Generated by {} {} from the seg-reg-config. {}.\n\n",
+			   env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"),
+			   describe_memory(board));
+
+	output += &heading(markup, 2, "Apertures");
+	let rows: Vec<Vec<String>> = apertures.iter()
+		.map(|aperture| {
+			let in_ddr = aperture.get_hw_start_addr(&banks)
+				.and_then(|start| return aperture.get_hw_end_addr(&banks)
+					.map(|end| return (start, end)))
+				.ok()
+				.filter(|(start, end)| return start < end)
+				.map(|(start, end)| return format!("{:#x} - {:#x}", start, end))
+				.unwrap_or("not in DDR".to_string());
+			return vec![
+				aperture.reg_name.clone(),
+				aperture.description(),
+				aperture.seg().map(|seg| return format!("{:#x}", seg))
+					.unwrap_or("invalid".to_string()),
+				format!("{:#x} - {:#x}", aperture.bus_addr,
+					aperture.bus_addr + aperture.aperture_size),
+				units::format_size(aperture.aperture_size),
+				in_ddr,
+				aperture.access.to_string(),
+			]
+		})
+		.collect();
+	output += &table(markup, &["Register", "Aperture", "Seg", "Bus range", "Size", "DDR range",
+				   "Access"], &rows);
+
+	// each pair once, from the side of the one that comes first
+	let aliases: Vec<Vec<String>> = (0..apertures.len())
+		.flat_map(|id| {
+			return board.alias_views(id).into_iter()
+				.filter(move |view| return view.id > id)
+				.map(move |view| return (id, view))
+		})
+		.map(|(id, view)| {
+			let (aperture, alias) = (&apertures[id], &apertures[view.id]);
+			let bus_start = aperture.bus_addr + (view.start - aperture.hardware_addr);
+			let alias_start = alias.bus_addr + (view.start - alias.hardware_addr);
+			let offset = match view.bus_offset < 0 {
+				true => format!("-{:#x}", view.bus_offset.unsigned_abs()),
+				false => format!("+{:#x}", view.bus_offset),
+			};
+			return vec![
+				aperture.reg_name.clone(),
+				alias.reg_name.clone(),
+				alias.description(),
+				format!("{:#x} - {:#x}", view.start, view.end),
+				format!("{:#x}", bus_start),
+				format!("{:#x}", alias_start),
+				offset,
+			]
+		})
+		.collect();
+	if !aliases.is_empty() {
+		output += &heading(markup, 2, "Aliases");
+		output += "DDR that is seen through more than one aperture, with different \
+			   attributes, and how far apart the bus addresses for it are.\n\n";
+		output += &table(markup, &["Register", "Alias", "Alias aperture", "DDR range",
+					   "Bus address", "Alias bus address", "Offset"], &aliases);
+	}
+
+	let headers = ["Node", "Address", "Size", "Flags", "Via", "DDR address"];
+	let mut labels: Vec<(String, u64)> = Vec::new();
+	if !nodes.is_empty() {
+		output += &heading(markup, 2, "Memory nodes");
+		let mut rows: Vec<Vec<String>> = Vec::new();
+		for node in nodes {
+			let flags = match node.enabled {
+				true => "",
+				false => "disabled",
+			};
+			for region in &node.regions {
+				rows.push(region_row(&node.label, region, flags, apertures));
+				if let Some(start) = region_hw_start(region, apertures) {
+					labels.push((node.label.clone(), start));
+				}
+			}
+		}
+		output += &table(markup, &headers, &rows);
+	}
+
+	if !reserved_nodes.is_empty() {
+		output += &heading(markup, 2, "Reserved memory");
+		let mut rows: Vec<Vec<String>> = Vec::new();
+		for node in reserved_nodes {
+			for region in &node.regions {
+				rows.push(region_row(&node.label, region, &node.flags(), apertures));
+				if let Some(start) = region_hw_start(region, apertures) {
+					labels.push((node.label.clone(), start));
+				}
+			}
+		}
+		output += &table(markup, &headers, &rows);
+	}
+
+	output += &heading(markup, 2, "DDR");
+	output += "Each piece of DDR between aperture boundaries, lowest address first, with the \
+		   apertures that reach it.\n\n";
+	output += &literal(markup, &ascii_map(board, &labels));
+
+	return output
+}
+
+// wide enough for a reg name & a space
+const TEXT_MAP_COLUMN_WIDTH: usize = 7;
+
+// the layout of the svg, in its own units. the same shape as the canvas in
+// the tui: bus windows on the left, a column per aperture in ddr, then the
+// memory nodes & reserved memory, with a legend for the nodes to the right
+const SVG_WIDTH: f64 = 1040.0;
+const SVG_HEIGHT: f64 = 640.0;
+const SVG_TOP: f64 = 50.0;
+const SVG_MAP_HEIGHT: f64 = 540.0;
+const SVG_BUS_X: f64 = 110.0;
+const SVG_BUS_WIDTH: f64 = 110.0;
+const SVG_DDR_X: f64 = 350.0;
+const SVG_COLUMN_WIDTH: f64 = 50.0;
+const SVG_COLUMN_GAP: f64 = 8.0;
+const SVG_LEGEND_X: f64 = 830.0;
+
+fn escape_xml(text: &str) -> String
+{
+	return text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+		.replace('"', "&quot;")
+}
+
+fn svg_rect(x: f64, y: f64, width: f64, height: f64, fill: &str, title: &str) -> String
+{
+	// the title shows up as a tooltip when the svg is viewed on its own
+	return format!("<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" \
+			fill=\"{}\" fill-opacity=\"0.6\" stroke=\"{}\"><title>{}</title></rect>\n",
+		       x, y, width, height.max(1.0), fill, fill, escape_xml(title))
+}
+
+fn svg_text(x: f64, y: f64, anchor: &str, text: &str) -> String
+{
+	return format!("<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"{}\">{}</text>\n", x, y,
+		       anchor, escape_xml(text))
+}
+
+fn svg_column_x(column: usize) -> f64
+{
+	return SVG_DDR_X + SVG_COLUMN_GAP + column as f64 * (SVG_COLUMN_WIDTH + SVG_COLUMN_GAP)
+}
+
+fn svg(board: &soc::MPFS, nodes: &[MemoryNode], reserved_nodes: &[ReservedMemoryNode]) -> String
+{
+	let apertures = board.apertures();
+	let total = board.total_memory().max(1);
+	let columns = apertures.len() + 2;
+	let ddr_width = columns as f64 * (SVG_COLUMN_WIDTH + SVG_COLUMN_GAP) + SVG_COLUMN_GAP;
+	let ddr_bottom = SVG_TOP + SVG_MAP_HEIGHT;
+
+	// address 0 at the bottom, as in the tui
+	let ddr_to_y = |addr: u64| {
+		return ddr_bottom - SVG_MAP_HEIGHT * addr.min(total) as f64 / total as f64
+	};
+
+	// log scale, so that the 32-bit windows don't vanish next to the
+	// 64-bit ones
+	let bus_bottom = (apertures.iter()
+		.map(|aperture| return aperture.bus_addr)
+		.min()
+		.unwrap_or(1)
+		.max(1) as f64).log2();
+	let bus_top = (apertures.iter()
+		.map(|aperture| return aperture.bus_addr + aperture.aperture_size)
+		.max()
+		.unwrap_or(2) as f64).log2();
+	let bus_to_y = |bus_addr: u64| {
+		return ddr_bottom - SVG_MAP_HEIGHT * ((bus_addr.max(1) as f64).log2() - bus_bottom)
+			/ (bus_top - bus_bottom).max(f64::EPSILON)
+	};
+
+	let mut output = format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" \
+				 height=\"{}\" viewBox=\"0 0 {} {}\" font-family=\"monospace\" \
+				 font-size=\"11\">\n", SVG_WIDTH, SVG_HEIGHT, SVG_WIDTH,
+				 SVG_HEIGHT);
+	output += "<defs><pattern id=\"unreachable\" width=\"6\" height=\"6\" \
+		   patternUnits=\"userSpaceOnUse\" patternTransform=\"rotate(45)\">\
+		   <line x1=\"0\" y1=\"0\" x2=\"0\" y2=\"6\" stroke=\"gray\"/>\
+		   </pattern></defs>\n";
+	output += "<rect width=\"100%\" height=\"100%\" fill=\"white\"/>\n";
+	output += &svg_text(SVG_WIDTH / 2.0, 20.0, "middle",
+			    &format!("{}, generated by {} {}", describe_memory(board),
+				     env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")));
+	output += &svg_text(SVG_BUS_X + SVG_BUS_WIDTH / 2.0, SVG_TOP - 10.0, "middle",
+			    "bus (log scale)");
+	output += &svg_text(SVG_DDR_X + ddr_width / 2.0, SVG_TOP - 24.0, "middle", "DDR");
+
+	// ddr that nothing reaches is hatched out across the whole column
+	for (start, end) in board.unmapped_regions() {
+		output += &format!("<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" \
+				    height=\"{:.1}\" fill=\"url(#unreachable)\"><title>{:#x} - {:#x} \
+				    isn't reachable</title></rect>\n",
+				   SVG_DDR_X, ddr_to_y(end), ddr_width, ddr_to_y(start) - ddr_to_y(end),
+				   start, end);
+	}
+	// & a hole between banks, with no memory in it, is greyed out
+	for (start, end) in board.holes() {
+		output += &format!("<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" \
+				    height=\"{:.1}\" fill=\"lightgray\"><title>{:#x} - {:#x} \
+				    has no memory</title></rect>\n",
+				   SVG_DDR_X, ddr_to_y(end), ddr_width,
+				   ddr_to_y(start) - ddr_to_y(end), start, end);
+	}
+	output += &format!("<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" \
+			    fill=\"none\" stroke=\"black\"/>\n",
+			   SVG_DDR_X, SVG_TOP, ddr_width, SVG_MAP_HEIGHT);
+	output += &svg_text(SVG_DDR_X - 6.0, ddr_bottom, "end", "0x0");
+	output += &svg_text(SVG_DDR_X - 6.0, SVG_TOP + 4.0, "end", &format!("{:#x}", total));
+
+	// aliases are stacked up next to the aperture whose ddr they also see
+	for (column, index) in board.alias_stack().into_iter().enumerate() {
+		let aperture = &apertures[index];
+		let colour = theme::css_colour(theme::palette_colour(index));
+		let column_x = svg_column_x(column);
+		let bus_y = bus_to_y(aperture.bus_addr + aperture.aperture_size);
+		let bus_height = bus_to_y(aperture.bus_addr) - bus_y;
+		let bus_end = aperture.bus_addr + aperture.aperture_size;
+		output += &svg_rect(SVG_BUS_X, bus_y, SVG_BUS_WIDTH, bus_height, &colour,
+				    &format!("{}: {:#x} - {:#x} on the bus", aperture.reg_name,
+					     aperture.bus_addr, bus_end));
+		output += &svg_text(SVG_BUS_X + SVG_BUS_WIDTH / 2.0, bus_y + bus_height / 2.0 + 4.0,
+				    "middle", &aperture.reg_name);
+		output += &svg_text(SVG_BUS_X - 6.0, bus_to_y(aperture.bus_addr) + 4.0, "end",
+				    &format!("{:#x}", aperture.bus_addr));
+		output += &svg_text(column_x + SVG_COLUMN_WIDTH / 2.0, SVG_TOP - 6.0, "middle",
+				    &aperture.reg_name);
+
+		let start = aperture.get_hw_start_addr(&board.banks());
+		let end = aperture.get_hw_end_addr(&board.banks());
+		let (start, end) = match (start, end) {
+			(Ok(start), Ok(end)) if start < end => (start, end),
+			_ => continue,
+		};
+
+		// from the bottom of the window on the bus to where it lands
+		output += &format!("<line x1=\"{:.1}\" y1=\"{:.1}\" x2=\"{:.1}\" y2=\"{:.1}\" \
+				    stroke=\"{}\"/>\n", SVG_BUS_X + SVG_BUS_WIDTH,
+				   bus_to_y(aperture.bus_addr), column_x, ddr_to_y(start), colour);
+		output += &svg_rect(column_x, ddr_to_y(end), SVG_COLUMN_WIDTH,
+				    ddr_to_y(start) - ddr_to_y(end), &colour,
+				    &format!("{}: {:#x} - {:#x} in DDR, seg {}", aperture.reg_name,
+					     start, end, aperture.seg()
+						.map(|seg| return format!("{:#x}", seg))
+						.unwrap_or("invalid".to_string())));
+	}
+
+	// the nodes are lettered as in the tui, with what the letters mean
+	// listed down the side
+	let mut legend: Vec<String> = Vec::new();
+	let node_column_x = svg_column_x(apertures.len());
+	output += &svg_text(node_column_x + SVG_COLUMN_WIDTH / 2.0, SVG_TOP - 6.0, "middle",
+			    "nodes");
+	for (index, node) in nodes.iter().enumerate() {
+		let letter = char::from_u32('a' as u32 + index as u32).unwrap_or('?');
+		let mut colour = theme::css_colour(theme::palette_colour(index));
+		if !node.enabled {
+			colour = theme::css_colour(theme::dim());
+		}
+		legend.push(format!("{}: {}", letter, node.label));
+
+		for region in &node.regions {
+			let (start, end) = match region_hw_start(region, apertures)
+				.and_then(|start| return Some((start, region.hw_end(start)?))) {
+				Some(range) => range,
+				None => continue,
+			};
+			output += &svg_rect(node_column_x, ddr_to_y(end), SVG_COLUMN_WIDTH,
+					    ddr_to_y(start) - ddr_to_y(end), &colour,
+					    &format!("{}: {:#x} - {:#x} in DDR", node.label, start, end));
+			output += &svg_text(node_column_x + SVG_COLUMN_WIDTH / 2.0,
+					    (ddr_to_y(start) + ddr_to_y(end)) / 2.0 + 4.0, "middle",
+					    &letter.to_string());
+		}
+	}
+
+	let reserved_column_x = svg_column_x(apertures.len() + 1);
+	output += &svg_text(reserved_column_x + SVG_COLUMN_WIDTH / 2.0, SVG_TOP - 6.0, "middle",
+			    "reserved");
+	for node in reserved_nodes {
+		let mut colour = theme::css_colour(Color::Gray);
+		if node.no_map {
+			colour = theme::css_colour(theme::dim());
+		}
+		legend.push(format!("r: {}", node.label));
+
+		for region in &node.regions {
+			let (start, end) = match region_hw_start(region, apertures)
+				.and_then(|start| return Some((start, region.hw_end(start)?))) {
+				Some(range) => range,
+				None => continue,
+			};
+			output += &svg_rect(reserved_column_x, ddr_to_y(end), SVG_COLUMN_WIDTH,
+					    ddr_to_y(start) - ddr_to_y(end), &colour,
+					    &format!("{}: {:#x} - {:#x} in DDR", node.label, start, end));
+			output += &svg_text(reserved_column_x + SVG_COLUMN_WIDTH / 2.0,
+					    (ddr_to_y(start) + ddr_to_y(end)) / 2.0 + 4.0, "middle", "r");
+		}
+	}
+
+	for (index, entry) in legend.iter().enumerate() {
+		output += &svg_text(SVG_LEGEND_X, SVG_TOP + 14.0 * index as f64, "start", entry);
+	}
+
+	return output + "</svg>\n"
+}
+
+fn text_cell(covered: bool, fill: char) -> String
+{
+	if covered {
+		return fill.to_string().repeat(TEXT_MAP_COLUMN_WIDTH - 1) + " "
+	}
+
+	return " ".repeat(TEXT_MAP_COLUMN_WIDTH)
+}
+
+pub fn text_map(board: &soc::MPFS, nodes: &[MemoryNode], reserved_nodes: &[ReservedMemoryNode],
+		rows: usize)
+-> String
+{
+	// the canvas drawn with characters, for logs & serial consoles: ddr is
+	// cut into rows, highest address at the top, & each column is filled in
+	// for the rows that its aperture or nodes reach
+	let apertures = board.apertures();
+	let banks = board.banks();
+	let total = board.total_memory();
+	let rows = rows.max(1) as u64;
+	let row_size = total.div_ceil(rows).max(1);
+	let overlaps = |start: u64, end: u64, row: u64| {
+		return start < (row + 1) * row_size && end > row * row_size
+	};
+
+	let ranges: Vec<Option<(u64, u64)>> = apertures.iter()
+		.map(|aperture| {
+			let start = aperture.get_hw_start_addr(&banks).ok()?;
+			let end = aperture.get_hw_end_addr(&banks).ok()?;
+			return Some((start, end)).filter(|_| return start < end)
+		})
+		.collect();
+	let node_ranges: Vec<(char, u64, u64)> = nodes.iter()
+		.enumerate()
+		.flat_map(|(index, node)| {
+			let letter = char::from_u32('a' as u32 + index as u32).unwrap_or('?');
+			return node.regions.iter()
+				.filter_map(move |region| {
+					let start = region_hw_start(region, apertures)?;
+					return Some((letter, start, region.hw_end(start)?))
+				})
+				.collect::<Vec<(char, u64, u64)>>()
+		})
+		.collect();
+	let reserved_ranges: Vec<(u64, u64)> = reserved_nodes.iter()
+		.flat_map(|node| return node.regions.iter())
+		.filter_map(|region| {
+			let start = region_hw_start(region, apertures)?;
+			return Some((start, region.hw_end(start)?))
+		})
+		.collect();
+
+	let mut output = format!("{:>12} ", "ddr");
+	for aperture in apertures {
+		output += &format!("{:<width$}", aperture.reg_name, width = TEXT_MAP_COLUMN_WIDTH);
+	}
+	output += &format!("{:<width$}reserved\n", "nodes", width = TEXT_MAP_COLUMN_WIDTH);
+	output += &format!("{:#012x} (top of DDR)\n", total);
+
+	for row in (0..rows).rev() {
+		let mut line = format!("{:#012x} ", row * row_size);
+		for (index, range) in ranges.iter().enumerate() {
+			let covered = range.map(|(start, end)| return overlaps(start, end, row))
+				.unwrap_or(false);
+			line += &text_cell(covered, apertures[index].reg_name.chars().last().unwrap_or('#'));
+		}
+
+		// several nodes can share a row, the first one is shown
+		let node = node_ranges.iter()
+			.find(|(_, start, end)| return overlaps(*start, *end, row));
+		line += &text_cell(node.is_some(), node.map(|(letter, _, _)| return *letter)
+						       .unwrap_or(' '));
+		let reserved = reserved_ranges.iter()
+			.any(|(start, end)| return overlaps(*start, *end, row));
+		line += &text_cell(reserved, 'r');
+
+		let unreachable = board.unmapped_regions()
+			.iter()
+			.any(|(start, end)| return overlaps(*start, *end, row));
+		if board.is_hole(row * row_size, (row + 1) * row_size) {
+			line += " <- no memory";
+		} else if unreachable {
+			line += " <- not reachable";
+		}
+		output += &format!("{}\n", line.trim_end());
+	}
+
+	output += &format!("\n{}, {} per row, each labelled with where it starts\n\n",
+			   describe_memory(board), units::format_size(row_size));
+	for (aperture, range) in apertures.iter().zip(&ranges) {
+		let in_ddr = range.map(|(start, end)| return format!("{:#x} - {:#x}", start, end))
+			.unwrap_or("not in DDR".to_string());
+		output += &format!("{:<8}{:<20}bus {:#x}, {}\n", aperture.reg_name,
+				   aperture.description(), aperture.bus_addr, in_ddr);
+	}
+	for (index, node) in nodes.iter().enumerate() {
+		let letter = char::from_u32('a' as u32 + index as u32).unwrap_or('?');
+		output += &format!("{}       {}\n", letter, node.label);
+	}
+	for node in reserved_nodes {
+		output += &format!("r       {}\n", node.label);
+	}
+
+	return output
+}
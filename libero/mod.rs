@@ -0,0 +1,112 @@
+// SPDX-License-Identifier: MIT or GPL-2.0
+
+#![allow(unused_variables)]
+#![allow(dead_code)]
+#![deny(clippy::implicit_return)]
+#![allow(clippy::needless_return)]
+
+// the seg register & ddr settings from a libero design, as written out by the
+// mss configurator. both its xml & the LIBERO_SETTING_ header generated from it
+// are understood, without needing to know much about either's structure
+#[derive(Debug, Clone, Default)]
+pub struct MssConfig {
+	pub segs: Vec<(String, u64)>,
+	pub total_memory: Option<u64>,
+}
+
+// the seg value is the ADDRESS_OFFSET field, the LOCKED bit above it is left
+// to the bootloader
+const ADDRESS_OFFSET_MASK: u64 = 0x7fff;
+
+fn first_hex(text: &str) -> Option<u64>
+{
+	let start = text.find("0x").or_else(|| return text.find("0X"))?;
+	let digits: String = text[start + 2..].chars()
+		.take_while(|c| return c.is_ascii_hexdigit())
+		.collect();
+
+	return u64::from_str_radix(&digits, 16).ok()
+}
+
+fn seg_name(word: &str) -> Option<String>
+{
+	// SEG0_0 & friends, wherever they turn up in a longer name
+	let upper = word.to_uppercase();
+	let start = upper.find("SEG")?;
+	let name: String = upper[start..].chars()
+		.take_while(|c| return c.is_ascii_alphanumeric() || *c == '_')
+		.collect();
+	let (block, index) = name.strip_prefix("SEG")?.split_once('_')?;
+	if block.len() != 1 || index.len() != 1
+	   || !block.chars().all(|c| return c.is_ascii_digit())
+	   || !index.chars().all(|c| return c.is_ascii_digit()) {
+		return None
+	}
+
+	return Some(name.to_lowercase())
+}
+
+fn parse_header(contents: &str) -> MssConfig
+{
+	// #define LIBERO_SETTING_SEG0_0    0x80007F80UL
+	let mut config = MssConfig::default();
+	for line in contents.lines() {
+		let words: Vec<&str> = line.split_whitespace().collect();
+		if words.len() < 3 || words[0] != "#define" {
+			continue;
+		}
+
+		let value = first_hex(words[2]);
+		if value.is_none() {
+			continue;
+		}
+
+		if let Some(reg_name) = seg_name(words[1]) {
+			config.segs.push((reg_name, value.unwrap() & ADDRESS_OFFSET_MASK));
+		} else if words[1].ends_with("DDR_SIZE") {
+			config.total_memory = value;
+		}
+	}
+
+	return config
+}
+
+fn parse_xml(contents: &str) -> MssConfig
+{
+	// <register name="SEG0_0" ...> ... <field name="ADDRESS_OFFSET" ...>0x7F80
+	// with the value either as an attribute or as the text of the field
+	let mut config = MssConfig::default();
+	for register in contents.split("<register").skip(1) {
+		let register = register.split("</register>").next().unwrap_or_default();
+		let name = register.split('"').nth(1).and_then(seg_name);
+		if name.is_none() {
+			continue;
+		}
+
+		let field = register.find("ADDRESS_OFFSET")
+			.and_then(|start| return first_hex(&register[start..]));
+		if let Some(value) = field {
+			config.segs.push((name.unwrap(), value & ADDRESS_OFFSET_MASK));
+		}
+	}
+
+	if let Some(start) = contents.find("DDR_SIZE") {
+		config.total_memory = first_hex(&contents[start..]);
+	}
+
+	return config
+}
+
+pub fn parse_mss_config(contents: &str) -> Result<MssConfig, String>
+{
+	let mut config = parse_header(contents);
+	if config.segs.is_empty() {
+		config = parse_xml(contents);
+	}
+
+	if config.segs.is_empty() {
+		return Err("no seg register settings found".to_string())
+	}
+
+	return Ok(config)
+}
@@ -0,0 +1,141 @@
+// SPDX-License-Identifier: MIT or GPL-2.0
+
+#![allow(unused_variables)]
+#![allow(dead_code)]
+#![deny(clippy::implicit_return)]
+#![allow(clippy::needless_return)]
+
+// enough to get back to anything typed this session, without growing forever
+const MAX_HISTORY: usize = 100;
+
+// the command prompt's line, with a cursor that can be moved around in it &
+// the commands that have already been entered, so that a long hex address can
+// be fixed up or a command run again without typing it all out
+#[derive(Default)]
+pub struct LineEditor {
+	pub line: String,
+	// in chars, rather than bytes, from the start of the line
+	cursor: usize,
+	history: Vec<String>,
+	// which history entry is being shown, if any, & what had been typed
+	// before going back through them
+	recalled: Option<usize>,
+	draft: String,
+}
+
+impl LineEditor {
+	fn byte_offset(&self) -> usize
+	{
+		return self.line.char_indices()
+			.nth(self.cursor)
+			.map(|(offset, _)| return offset)
+			.unwrap_or(self.line.len())
+	}
+
+	pub fn cursor(&self) -> usize
+	{
+		return self.cursor
+	}
+
+	pub fn insert(&mut self, c: char)
+	{
+		let offset = self.byte_offset();
+		self.line.insert(offset, c);
+		self.cursor += 1;
+	}
+
+	pub fn backspace(&mut self)
+	{
+		if self.cursor == 0 {
+			return
+		}
+
+		self.cursor -= 1;
+		let offset = self.byte_offset();
+		self.line.remove(offset);
+	}
+
+	pub fn delete(&mut self)
+	{
+		if self.cursor < self.line.chars().count() {
+			let offset = self.byte_offset();
+			self.line.remove(offset);
+		}
+	}
+
+	pub fn left(&mut self)
+	{
+		self.cursor = self.cursor.saturating_sub(1);
+	}
+
+	pub fn right(&mut self)
+	{
+		self.cursor = (self.cursor + 1).min(self.line.chars().count());
+	}
+
+	pub fn clear(&mut self)
+	{
+		self.set(String::new());
+	}
+
+	pub fn set(&mut self, line: String)
+	{
+		self.cursor = line.chars().count();
+		self.line = line;
+	}
+
+	pub fn older(&mut self)
+	{
+		if self.recalled.is_none() && self.history.is_empty() {
+			return
+		}
+
+		if self.recalled.is_none() {
+			self.draft = self.line.clone();
+		}
+		let index = self.recalled
+			.map(|index| return index.saturating_sub(1))
+			.unwrap_or(self.history.len() - 1);
+
+		self.recalled = Some(index);
+		self.set(self.history[index].clone());
+	}
+
+	pub fn newer(&mut self)
+	{
+		if self.recalled.is_none() {
+			return
+		}
+
+		let index = self.recalled.unwrap() + 1;
+		if index >= self.history.len() {
+			// past the newest entry is whatever was being typed before
+			self.recalled = None;
+			let draft = std::mem::take(&mut self.draft);
+			self.set(draft);
+			return
+		}
+
+		self.recalled = Some(index);
+		self.set(self.history[index].clone());
+	}
+
+	pub fn take(&mut self) -> String
+	{
+		// hand the line over to be run, remembering it unless it's a
+		// repeat of the last one
+		let line = std::mem::take(&mut self.line);
+		self.cursor = 0;
+		self.recalled = None;
+		self.draft.clear();
+
+		if !line.trim().is_empty() && self.history.last() != Some(&line) {
+			self.history.push(line.clone());
+			if self.history.len() > MAX_HISTORY {
+				self.history.remove(0);
+			}
+		}
+
+		return line
+	}
+}
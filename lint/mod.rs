@@ -0,0 +1,257 @@
+// SPDX-License-Identifier: MIT or GPL-2.0
+
+#![allow(unused_variables)]
+#![allow(dead_code)]
+#![deny(clippy::implicit_return)]
+#![allow(clippy::needless_return)]
+
+use std::fmt;
+use yaml_rust::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust::scanner::Marker;
+
+use crate::config;
+use crate::config::ConfigFormat;
+use crate::soc;
+use crate::units;
+
+// how much a finding matters, & so whether it fails the lint
+#[derive(clap::ArgEnum, Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub enum Severity {
+	Info,
+	Warning,
+	Error,
+}
+
+impl fmt::Display for Severity {
+fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+	match self {
+		Severity::Info => return write!(f, "info"),
+		Severity::Warning => return write!(f, "warning"),
+		Severity::Error => return write!(f, "error"),
+	}
+}
+}
+
+// something in a config that probably isn't what was meant, whether or not
+// the config would load
+#[derive(Clone, Debug)]
+pub struct Finding {
+	pub severity: Severity,
+	pub line: Option<usize>,
+	pub message: String,
+}
+
+// a mapping or sequence being walked, & for a mapping the keys seen so far
+struct Container {
+	path: String,
+	mapping: bool,
+	keys: Vec<String>,
+	expecting_key: bool,
+}
+
+// once parsed, only the last of a key that's given twice is left, so the
+// duplicates have to be spotted from the parser's events instead
+#[derive(Default)]
+struct DuplicateKeys {
+	stack: Vec<Container>,
+	// path, key & line of each key that had already been seen
+	duplicates: Vec<(String, String, usize)>,
+}
+
+impl DuplicateKeys {
+	fn child_path(&self) -> String
+	{
+		let parent = match self.stack.last() {
+			Some(parent) => parent,
+			None => return String::new(),
+		};
+
+		let key = match parent.keys.last() {
+			Some(key) if parent.mapping => key,
+			_ => return parent.path.clone(),
+		};
+		if parent.path.is_empty() {
+			return key.clone()
+		}
+
+		return format!("{}.{}", parent.path, key)
+	}
+
+	fn next_item(&mut self)
+	{
+		// keys & values take turns, whatever they are
+		let container = self.stack.last_mut();
+		if let Some(container) = container.filter(|container| return container.mapping) {
+			container.expecting_key = !container.expecting_key;
+		}
+	}
+}
+
+impl MarkedEventReceiver for DuplicateKeys {
+	fn on_event(&mut self, event: Event, mark: Marker)
+	{
+		match event {
+			Event::MappingStart(_) | Event::SequenceStart(_) => {
+				let container = Container {
+					path: self.child_path(),
+					mapping: matches!(event, Event::MappingStart(_)),
+					keys: Vec::new(),
+					expecting_key: true,
+				};
+				self.stack.push(container);
+			}
+			Event::MappingEnd | Event::SequenceEnd => {
+				self.stack.pop();
+				self.next_item();
+			}
+			Event::Scalar(value, ..) => {
+				let container = self.stack.last_mut().filter(|container| {
+					return container.mapping && container.expecting_key
+				});
+				if let Some(container) = container {
+					if container.keys.contains(&value) {
+						let path = container.path.clone();
+						self.duplicates.push((path, value.clone(), mark.line()));
+					}
+					container.keys.push(value);
+				}
+				self.next_item();
+			}
+			Event::Alias(_) => self.next_item(),
+			_ => {}
+		}
+	}
+}
+
+fn duplicate_keys(contents: &str) -> Vec<Finding>
+{
+	// json is near enough yaml for this. anything that doesn't parse has
+	// bigger problems, which loading it reports
+	let mut receiver = DuplicateKeys::default();
+	if Parser::new(contents.chars()).load(&mut receiver, false).is_err() {
+		return Vec::new()
+	}
+
+	// the hss payload generator's payloads give secondary-hart once per hart
+	return receiver.duplicates.into_iter()
+		.filter(|(path, _, _)| return !path.starts_with("payloads"))
+		.map(|(path, key, line)| {
+			let mut name = key;
+			if !path.is_empty() {
+				name = format!("{} in {}", name, path);
+			}
+			return Finding {
+				severity: Severity::Error,
+				line: Some(line),
+				message: format!("{} is given more than once, only the last one is \
+						  used", name),
+			}
+		})
+		.collect()
+}
+
+fn check_seg(aperture: &soc::MemoryAperture, seg: u64, total_memory: Option<u64>)
+-> Option<(Severity, String)>
+{
+	// what the bootloader will really do with the value, which loading the
+	// config either refuses or just gets on with
+	let reg_name = &aperture.reg_name;
+	if seg == 0 {
+		// left where it is on the bus, which is how a window that isn't
+		// wanted gets put out of the way
+		return None
+	}
+
+	if soc::seg_is_treated_as_zero(seg) {
+		return Some((Severity::Warning,
+			     format!("{} is {:#x}, which doesn't have bit 14 set so is treated as \
+				      0x0", reg_name, seg)))
+	}
+
+	let start = match soc::seg_to_hw_start_addr(seg, aperture.bus_addr) {
+		Ok(start) => start,
+		Err(error) => return Some((Severity::Error, format!("{}: {}", reg_name, error))),
+	};
+	let total_memory = total_memory?;
+	if start >= total_memory {
+		return Some((Severity::Error,
+			     format!("{} starts its window at {:#x}, past the end of memory at \
+				      {:#x}, so it reaches nothing", reg_name, start, total_memory)))
+	}
+
+	// the 64-bit windows are bigger than most ddr, so this is normal enough
+	let end = start.saturating_add(aperture.aperture_size);
+	if end > total_memory {
+		return Some((Severity::Info,
+			     format!("{} runs past the end of memory, so the last {} of its \
+				      window is clamped off", reg_name,
+				     units::format_size(end - total_memory))))
+	}
+
+	return None
+}
+
+pub fn lint(contents: &str, format: ConfigFormat, board: &soc::MPFS, total_memory: Option<u64>)
+-> Result<Vec<Finding>, String>
+{
+	// unlike --strict, none of this stops a config loading, it's for finding
+	// what a config that does load gets wrong
+	let mut config = config::parse_config(contents, format)
+		.map_err(|error| return error.to_string())?;
+	let mut findings = duplicate_keys(contents);
+
+	let migrations = config::migrate_config(&mut config)?;
+	for migration in migrations {
+		findings.push(Finding {
+			severity: Severity::Info,
+			line: None,
+			message: format!("older config, {} when saved", migration),
+		});
+	}
+
+	for (key, _) in config.as_mapping().into_iter().flatten() {
+		let name = key.as_str().unwrap_or_default();
+		if !config::KNOWN_KEYS.contains(&name) {
+			findings.push(Finding {
+				severity: Severity::Warning,
+				line: config::key_line(contents, name, 1),
+				message: format!("unknown key \"{}\", which nothing reads", name),
+			});
+		}
+	}
+
+	let seg_config_line = config::key_line(contents, "seg-reg-config", 1).unwrap_or(1);
+	for (reg_name, seg) in config["seg-reg-config"].as_mapping().into_iter().flatten() {
+		let reg_name = reg_name.as_str().unwrap_or_default();
+		let line = config::key_line(contents, reg_name, seg_config_line);
+		let aperture = board.memory_apertures.iter()
+			.find(|aperture| return aperture.reg_name == reg_name);
+		let finding = match (aperture, config::parse_seg_value(seg)) {
+			(None, _) => Some((Severity::Error,
+					   format!("seg value for unknown register \"{}\", which \
+						    is ignored", reg_name))),
+			(_, Err(error)) => Some((Severity::Error,
+						 format!("{}: {}", reg_name, error))),
+			(_, Ok(None)) => Some((Severity::Warning,
+					       format!("{} has no value, so the design's is used",
+						       reg_name))),
+			(Some(aperture), Ok(Some(seg))) => check_seg(aperture, seg, total_memory),
+		};
+
+		if let Some((severity, message)) = finding {
+			findings.push(Finding { severity, line, message });
+		}
+	}
+
+	if total_memory.is_none() {
+		findings.push(Finding {
+			severity: Severity::Info,
+			line: None,
+			message: "the total memory isn't known, so where the windows end up in it \
+				  wasn't checked".to_string(),
+		});
+	}
+
+	findings.sort_by_key(|finding| return finding.line.unwrap_or(0));
+	return Ok(findings)
+}
@@ -5,52 +5,550 @@
 #![deny(clippy::implicit_return)]
 #![allow(clippy::needless_return)]
 
-use clap::Parser;
+use clap::{CommandFactory, Parser};
 use crossterm::{
-	event::{self, Event, KeyCode},
-	terminal::{disable_raw_mode, enable_raw_mode},
+	cursor::MoveTo,
+	event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyModifiers},
+	event::{MouseButton, MouseEvent, MouseEventKind},
+	execute,
+	terminal::{disable_raw_mode, enable_raw_mode, Clear, ClearType},
 };
+use notify::Watcher;
 use serde_yaml::Value;
 use std::io;
-use std::time::Duration;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
 use std::fs;
+use tracing::{debug, info};
+use tracing_subscriber::filter::LevelFilter;
 use tui::{
-	backend::CrosstermBackend,
+	backend::{CrosstermBackend, TestBackend},
 	Frame,
 	layout::{Constraint, Direction, Layout, Rect},
 	style::{Color, Modifier, Style},
-	text::Span, Terminal,
-	widgets::{Block, Borders, Paragraph, Cell, Row, Table},
-	widgets::canvas::{Canvas, Rectangle},
+	text::{Span, Spans}, Terminal,
+	widgets::{Block, Borders, Paragraph, Cell, Row, Table, TableState},
+	widgets::canvas::{Canvas, Line, Rectangle},
 };
 
+mod autosave;
+mod clipboard;
+mod commands;
+use crate::commands::{FixChoice, ParsedCommand};
+mod config;
+mod contexts;
+use crate::config::ConfigFormat;
+mod diff;
 mod dt;
+mod dts;
+mod export;
+mod libero;
+mod lineedit;
+mod lint;
+mod messages;
 use crate::dt::MemoryNode;
 use crate::dt::NoGoodNameYet;
+use crate::dt::ReservedMemoryNode;
 mod soc;
 use crate::soc::Aperture;
+use crate::soc::SoC;
+mod plain;
+mod plan;
+mod pmp;
+mod regdump;
+mod regions;
+use crate::regions::NamedRegion;
 mod states;
+mod stdio;
+mod theme;
+mod units;
+use crate::units::Radix;
+mod validate;
 
-fn hex_to_mib(hex: u64) -> u64
+// no point zooming in past the granularity of the seg registers
+const MIN_MAP_RANGE: u64 = 1 << 24;
+
+// how many seg granules + & - move an aperture with Shift or Alt held
+const NUDGE_BIG_STEP: i64 = 16;
+
+// which pane PgUp, PgDn & Home act on, Ctrl+T moves between them
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ScrollFocus {
+	Map,
+	Segs,
+	Nodes,
+}
+
+impl ScrollFocus {
+	fn next(self) -> ScrollFocus
+	{
+		match self {
+			ScrollFocus::Map => return ScrollFocus::Segs,
+			ScrollFocus::Segs => return ScrollFocus::Nodes,
+			ScrollFocus::Nodes => return ScrollFocus::Map,
+		}
+	}
+}
+
+// how the user has asked for things to be shown, none of which ends up in
+// the config
+#[derive(Clone, Debug)]
+struct ViewOptions {
+	show_disabled_nodes: bool,
+	radix: Radix,
+	// the part of ddr shown in the memory map, all of it if None
+	map_range: Option<(u64, u64)>,
+	focus: ScrollFocus,
+	scroll: TableScroll,
+	map: MapGeometry,
+	// what was typed after a "/", only the rows of the aperture, dt node &
+	// reserved memory tables with it in are shown
+	search: Option<String>,
+}
+
+impl Default for ViewOptions {
+	fn default() -> ViewOptions {
+		return ViewOptions {
+			show_disabled_nodes: true,
+			radix: Radix::default(),
+			map_range: None,
+			focus: ScrollFocus::Map,
+			scroll: TableScroll::default(),
+			map: MapGeometry::default(),
+			search: None,
+		}
+	}
+}
+
+// a table with more rows than fit in its rect, scrolled by moving its
+// selection a page at a time. the TableState has to outlive each frame for
+// the offset to stick, & the page & row counts are from the last frame drawn
+#[derive(Clone, Debug, Default)]
+struct ScrollableTable {
+	state: TableState,
+	// tui doesn't give the offset back out, so it's followed here too
+	offset: usize,
+	page: usize,
+	rows: usize,
+	// where it was drawn, so that a click can be matched up with a row
+	rect: Rect,
+	// what each row shown stands for, as a search can leave some out
+	ids: Vec<usize>,
+}
+
+impl ScrollableTable {
+	fn scroll(&mut self, down: bool)
+	{
+		let selected = self.state.selected().unwrap_or(0);
+		let page = self.page.max(1);
+		if down {
+			self.state.select(Some((selected + page).min(self.rows.saturating_sub(1))));
+		} else {
+			self.state.select(Some(selected.saturating_sub(page)));
+		}
+	}
+
+	fn home(&mut self)
+	{
+		self.state.select(Some(0));
+	}
+
+	fn update(&mut self, ids: Vec<usize>, display_rect: Rect)
+	{
+		// called while drawing, as that's the only time the number of rows
+		// & the space for them is known
+		let page = table_page(display_rect);
+		let rows = ids.len();
+		self.ids = ids;
+		self.rows = rows;
+		self.page = page;
+		self.rect = display_rect;
+
+		let selected = self.state.selected().unwrap_or(0).min(rows.saturating_sub(1));
+		self.state.select(Some(selected));
+		if selected >= self.offset + page {
+			self.offset = selected + 1 - page;
+		}
+		if selected < self.offset {
+			self.offset = selected;
+		}
+	}
+
+	fn row_at(&self, column: u16, row: u16) -> Option<usize>
+	{
+		// inside the borders & under the header, skipping the margins
+		let first = self.rect.y + 3;
+		if column <= self.rect.x || column >= self.rect.right().saturating_sub(1) ||
+		   row < first || row >= self.rect.bottom().saturating_sub(1) ||
+		   (row - first) & 1 == 1 {
+			return None
+		}
+
+		let index = self.offset + (row - first) as usize / 2;
+		if index >= self.rows {
+			return None
+		}
+
+		return Some(index)
+	}
+
+	fn id_at(&self, index: usize) -> Option<usize>
+	{
+		return self.ids.get(index).copied()
+	}
+
+	fn selected_id(&self) -> Option<usize>
+	{
+		return self.id_at(self.state.selected()?)
+	}
+}
+
+#[derive(Clone, Debug, Default)]
+struct TableScroll {
+	segs: ScrollableTable,
+	nodes: ScrollableTable,
+}
+
+fn table_page(display_rect: Rect) -> usize
 {
-	return hex / (2_u64.pow(10).pow(2))
+	// rows are a line of text & a line of margin, inside the borders & under
+	// a header that's the same, but the last row doesn't need its margin
+	let space = display_rect.height.saturating_sub(4) as usize;
+	return space.div_ceil(2)
 }
 
-const READABLE_COLOURS: [Color; 6] =
-[
-	Color::LightRed,
-	Color::LightGreen,
-	Color::LightMagenta,
-	Color::LightYellow,
-	Color::LightCyan,
-	Color::LightBlue
-];
+fn render_scrollbar<B: tui::backend::Backend>
+(scroll: &ScrollableTable, frame:&mut Frame<B>, display_rect: Rect)
+{
+	// a thumb down the right hand border, under the header, & only when
+	// there's more than fits
+	if scroll.rows <= scroll.page || display_rect.height < 5 {
+		return
+	}
+
+	let track = display_rect.height as usize - 4;
+	let thumb = (track * scroll.page / scroll.rows).max(1);
+	let thumb_start = (track * scroll.offset / scroll.rows).min(track - thumb);
+	let lines: Vec<&str> = (0..track)
+		.map(|line| {
+			if line >= thumb_start && line < thumb_start + thumb {
+				return "█"
+			}
+			return "│"
+		})
+		.collect();
+
+	let area = Rect {
+		x: display_rect.right() - 1,
+		y: display_rect.y + 3,
+		width: 1,
+		height: track as u16,
+	};
+	frame.render_widget(Paragraph::new(lines.join("\n")), area);
+}
+
+fn scroll_title(focused: bool) -> &'static str
+{
+	if focused {
+		return "PgUp/PgDn to scroll, Ctrl+T for the next pane"
+	}
+
+	return ""
+}
+
+fn table_title(focused: bool, search: Option<&str>) -> String
+{
+	let title = scroll_title(focused);
+	return match search {
+		Some(search) if title.is_empty() => format!("Matching \"{}\", Esc to clear", search),
+		Some(search) => format!("Matching \"{}\", Esc to clear. {}", search, title),
+		None => title.to_string(),
+	}
+}
+
+fn search_text(text: &str) -> String
+{
+	// the zeros that pad out an address are left out, so that searching for
+	// 0x80000000 finds 0x0080000000
+	let text = text.trim().to_ascii_lowercase();
+	return match text.strip_prefix("0x").map(|digits| return digits.trim_start_matches('0')) {
+		Some(digits) if !digits.is_empty() => digits.to_string(),
+		_ => text,
+	}
+}
+
+fn cell_matches(cell: &str, search: &str) -> bool
+{
+	return cell.to_ascii_lowercase().contains(&search.to_ascii_lowercase()) ||
+		search_text(cell).contains(&search_text(search))
+}
+
+fn row_matches(row: &[String], search: Option<&str>) -> bool
+{
+	return search.is_none_or(|search| {
+		return row.iter().any(|cell| return cell_matches(cell, search))
+	})
+}
+
+fn search_cell(text: &str, search: Option<&str>) -> Cell<'static>
+{
+	// underlined rather than coloured, as the rows already have colours of
+	// their own
+	let cell = Cell::from(text.to_string());
+	if search.is_some_and(|search| return cell_matches(text, search)) {
+		return cell.style(Style::default().add_modifier(Modifier::UNDERLINED))
+	}
+
+	return cell
+}
+
+impl ViewOptions {
+	fn map_range(&self, total_system_memory: u64) -> (u64, u64)
+	{
+		// the memory may have shrunk since the range was picked
+		let (bottom, top) = self.map_range.unwrap_or((0, total_system_memory));
+		let top = top.min(total_system_memory);
+		if bottom >= top {
+			return (0, total_system_memory)
+		}
+
+		return (bottom, top)
+	}
+
+	fn zoom(&mut self, total_system_memory: u64, zoom_in: bool)
+	{
+		// zooming keeps the middle of the map where it is
+		let (bottom, top) = self.map_range(total_system_memory);
+		let middle = bottom + (top - bottom) / 2;
+		let mut size = top - bottom;
+		if zoom_in {
+			size = (size / 2).max(MIN_MAP_RANGE);
+		} else {
+			size = size.saturating_mul(2);
+		}
+
+		if size >= total_system_memory {
+			self.map_range = None;
+			return
+		}
+
+		let bottom = middle.saturating_sub(size / 2).min(total_system_memory - size);
+		self.map_range = Some((bottom, bottom + size));
+	}
+
+	fn pan(&mut self, total_system_memory: u64, up: bool)
+	{
+		// half a screen at a time, so there's something to keep track of
+		let (bottom, top) = self.map_range(total_system_memory);
+		let size = top - bottom;
+		let mut bottom = bottom.saturating_sub(size / 2);
+		if up {
+			bottom = (top - size / 2).min(total_system_memory - size);
+		}
+
+		if size < total_system_memory {
+			self.map_range = Some((bottom, bottom + size));
+		}
+	}
+}
+
+// the columns in the memory map: one per aperture, one for the dt memory
+// nodes & one for the reserved-memory nodes
+const MAP_COLUMNS: usize = 8;
+
+// where everything in the memory map was last drawn, in the canvas's own
+// coordinates, so that the mouse can be matched up with what's under it
+#[derive(Clone, Copy, Debug, Default)]
+struct MapGeometry {
+	rect: Rect,
+	bus_map_x: f64,
+	bus_map_width: f64,
+	mem_map_x: f64,
+	mem_map_y: f64,
+	mem_map_width: f64,
+	mem_map_height: f64,
+	aperture_width: f64,
+	view_bottom: u64,
+	view_top: u64,
+}
+
+impl MapGeometry {
+	fn new(display_rect: Rect, view_range: (u64, u64)) -> MapGeometry
+	{
+		let border: f64 = 0.5;
+		let width = display_rect.width as f64;
+		let bus_map_x = 1.0;
+		let bus_map_width = 0.15 * width;
+		let mem_map_width = 0.52 * width - 2.0 * border;
+		return MapGeometry {
+			rect: display_rect,
+			bus_map_x,
+			bus_map_width,
+			// leave a gap between the bus & ddr columns for the
+			// connecting lines
+			mem_map_x: bus_map_x + bus_map_width + 0.1 * width,
+			mem_map_y: 0.5,
+			mem_map_width,
+			mem_map_height: display_rect.height as f64 - 2.0 * border,
+			aperture_width: mem_map_width / (MAP_COLUMNS + 1) as f64,
+			view_bottom: view_range.0,
+			view_top: view_range.1,
+		}
+	}
+
+	fn px_per_byte(&self) -> f64
+	{
+		return self.mem_map_height / (self.view_top - self.view_bottom) as f64
+	}
+
+	fn column_x(&self, column: usize) -> f64
+	{
+		// each column is followed by a gap of an eighth of its width
+		let step = self.aperture_width + self.aperture_width / MAP_COLUMNS as f64;
+		return self.mem_map_x + self.aperture_width / MAP_COLUMNS as f64
+			+ column as f64 * step
+	}
+
+	fn canvas_point(&self, column: u16, row: u16) -> Option<(f64, f64)>
+	{
+		// the canvas is drawn inside the borders, with y going up the screen
+		let inner_width = self.rect.width.saturating_sub(2);
+		let inner_height = self.rect.height.saturating_sub(2);
+		if column <= self.rect.x || column > self.rect.x + inner_width ||
+		   row <= self.rect.y || row > self.rect.y + inner_height {
+			return None
+		}
+
+		let x = (column - self.rect.x - 1) as f64 + 0.5;
+		let y = (row - self.rect.y - 1) as f64 + 0.5;
+		return Some((x * self.rect.width as f64 / inner_width as f64,
+			     self.rect.height as f64 * (1.0 - y / inner_height as f64)))
+	}
+
+	fn y_to_ddr(&self, y: f64) -> u64
+	{
+		let offset = ((y - self.mem_map_y) / self.px_per_byte()).max(0.0) as u64;
+		return (self.view_bottom + offset).min(self.view_top)
+	}
+
+	fn column_at(&self, x: f64) -> Option<usize>
+	{
+		return (0..MAP_COLUMNS).find(|column| {
+			let column_x = self.column_x(*column);
+			return x >= column_x && x < column_x + self.aperture_width
+		})
+	}
+}
+
+// an aperture being dragged around the memory map, & how far into it it was
+// picked up, so that it doesn't jump to put its start under the mouse
+#[derive(Clone, Copy, Debug)]
+struct MapDrag {
+	id: usize,
+	grab_offset: u64,
+}
+
+fn start_drag(board: &soc::MPFS, map: &MapGeometry, column: u16, row: u16) -> Option<MapDrag>
+{
+	let (x, y) = map.canvas_point(column, row)?;
+	let id = *board.alias_stack().get(map.column_at(x)?)?;
+	let aperture = &board.apertures()[id];
+	let start = aperture.get_hw_start_addr(&board.banks()).ok()?;
+	let end = aperture.get_hw_end_addrs(&board.banks()).ok()?.clamped;
+	let addr = map.y_to_ddr(y);
+	if addr < start || addr >= end {
+		return None
+	}
+
+	return Some(MapDrag { id, grab_offset: addr - start })
+}
+
+fn drag_to(board: &mut soc::MPFS, map: &MapGeometry, drag: MapDrag, row: u16)
+{
+	// only up & down matter, so the mouse can wander off to the side
+	let column = map.rect.x + 1;
+	let row = row.clamp(map.rect.y + 1, map.rect.bottom().saturating_sub(2));
+	let (_, y) = match map.canvas_point(column, row) {
+		Some(point) => point,
+		None => return,
+	};
+
+	let addr = soc::snap_to_seg_granularity(map.y_to_ddr(y).saturating_sub(drag.grab_offset));
+	// anywhere the seg register can't reach is just not moved to
+	let _ = board.set_hw_start_addr_by_id(addr, drag.id);
+}
+
+// the aperture that + & - move: the highlighted row while the aperture table
+// has focus, otherwise whichever was last picked to edit
+fn nudge_target(board: &soc::MPFS, view: &ViewOptions) -> Option<usize>
+{
+	if view.focus == ScrollFocus::Segs {
+		return view.scroll.segs.selected_id()
+			.filter(|id| return *id < board.apertures().len())
+	}
+
+	return board.current_aperture_id
+}
+
+// the canvas and its legend both need to agree on who gets what colour
+fn aperture_colour(index: usize) -> Color
+{
+	return theme::palette_colour(index)
+}
+
+fn node_colour(index: usize, node: &MemoryNode) -> Color
+{
+	if !node.enabled {
+		return theme::dim()
+	}
+
+	return theme::palette_colour(index)
+}
+
+fn reserved_node_colour(node: &ReservedMemoryNode) -> Color
+{
+	if node.no_map {
+		return theme::dim()
+	}
+
+	return Color::Gray
+}
+
+const NODE_TABLE_HEADERS: [&str; 6] = ["ID", "Node Name", "Address", "Size", "HW Start", "HW End"];
+
+// a row for each region of each node, lettered as on the map, & whether the
+// node is enabled
+fn node_rows(board: &soc::MPFS, nodes: &[MemoryNode], radix: Radix) -> Vec<(Vec<String>, bool)>
+{
+	let mut rows: Vec<(Vec<String>, bool)> = Vec::new();
+	let mut label: Option<char> = Some('a');
+	for node in nodes {
+		for (mut row, region) in node.to_strings(board, radix).into_iter().zip(node.regions.iter()) {
+			let mut labeled_node: Vec<String> = Vec::new();
+			labeled_node.push(label.unwrap_or('?').to_string());
+			if !node.enabled {
+				row[0] += " (disabled)";
+			} else if region.is_ambiguous(board.apertures()) {
+				row[0] += " (ambiguous)";
+			} else if region.is_uncached(board.apertures()) {
+				row[0] += " (non-cached)";
+			}
+			labeled_node.append(&mut row);
+			rows.push((labeled_node, node.enabled));
+		}
+
+		label = label.and_then(|label| return char::from_u32(label as u32 + 1));
+	}
+
+	return rows
+}
 
 fn render_dt_node_table<B: tui::backend::Backend>
-(board: &mut soc::MPFS, nodes: Option<Vec<MemoryNode>>, frame:&mut Frame<B>, display_rect: Rect)
+(node_rows: Option<Vec<(Vec<String>, bool)>>, frame:&mut Frame<B>, display_rect: Rect,
+ scroll: &mut ScrollableTable, focused: bool, search: Option<&str>)
 {
-	let selected_style = Style::default().add_modifier(Modifier::REVERSED);
-	let header_cells = ["ID", "Node Name", "Address", "Size", "HW Start", "HW End",]
+	let selected_style = theme::highlight();
+	let header_cells = NODE_TABLE_HEADERS
 		.iter()
 		.map(|h|
 			return
@@ -60,42 +558,42 @@ fn render_dt_node_table<B: tui::backend::Backend>
 
 	let header = Row::new(header_cells).height(1).bottom_margin(1);
 
-	if nodes.is_none() {
-		return
-	}
-
-	let mut data = dt::memory_nodes_to_strings(board, nodes.unwrap());
-
-	let mut labeled_data: Vec<Vec<String>> = Vec::new();
-	let mut label: Option<char> = Some('a');
-	for node in data.iter_mut() {
-		let mut labeled_node: Vec<String> = Vec::new();
-		labeled_node.push(label.unwrap().to_string());
-		labeled_node.append(node);
-		labeled_data.push(labeled_node);
-
-		label = char::from_u32(label.unwrap() as u32 + 1);
-	}
+	let node_rows = match node_rows {
+		Some(node_rows) => node_rows,
+		None => return,
+	};
 
+	let labeled_data: Vec<(usize, Vec<String>, Style)> = node_rows.into_iter()
+		.enumerate()
+		.filter(|(_, (row, _))| return row_matches(row, search))
+		.map(|(index, (row, enabled))| {
+			let mut style = Style::default();
+			if !enabled {
+				style = style.fg(theme::dim());
+			}
+			return (index, row, style)
+		})
+		.collect();
 
-	let rows = labeled_data.iter().map(|item| {
+	let rows = labeled_data.iter().map(|(_, item, style)| {
 		let cells = item.iter().map(|c|
-			return Cell::from(c.clone())
+			return search_cell(c, search)
 		);
-		return Row::new(cells).height(1).bottom_margin(1)
+		return Row::new(cells).height(1).bottom_margin(1).style(*style)
 	});
+	scroll.update(labeled_data.iter().map(|(index, _, _)| return *index).collect(),
+		      display_rect);
 
-	let table =
+	let mut table =
 		Table::new(rows)
 		.header(header)
 		.block(
 			Block::default()
 			.borders(Borders::ALL)
-
+			.border_style(theme::border())
+			.title(table_title(focused, search))
 		)
 		.style(Style::default())
-		.highlight_style(selected_style)
-		.highlight_symbol(">> ")
 		.widths(&[
 			Constraint::Percentage(5),
 			Constraint::Percentage(19),
@@ -104,20 +602,62 @@ fn render_dt_node_table<B: tui::backend::Backend>
 			Constraint::Percentage(19),
 			Constraint::Percentage(19),
 		]);
+	if focused {
+		table = table.highlight_style(selected_style).highlight_symbol(">> ");
+	}
 
-	frame.render_widget(table, display_rect);
+	frame.render_stateful_widget(table, display_rect, &mut scroll.state);
+	render_scrollbar(scroll, frame, display_rect);
 }
 
-fn render_seg_table<B: tui::backend::Backend>
-(data: Vec<Vec<String>>, frame:&mut Frame<B>, display_rect: Rect)
+const RESERVED_TABLE_HEADERS: [&str; 6] = [
+	"Reserved Node", "Address", "Size", "Flags", "HW Start", "HW End",
+];
+
+// a row for each region of each node, & whether the region is mapped
+fn reserved_rows(board: &soc::MPFS, reserved_nodes: &[ReservedMemoryNode], radix: Radix)
+-> Vec<(Vec<String>, bool)>
 {
-	let selected_style = Style::default().add_modifier(Modifier::REVERSED);
-	let header_cells =
-		[
-			"ID", "Register Name", "Description", "Bus Address",
-			"Register Value", "Aperture HW Start", "Aperture HW End",
-			"Aperature Size",
-		 ]
+	let mut rows: Vec<(Vec<String>, bool)> = Vec::new();
+	for node in reserved_nodes {
+		for region in node.regions.iter() {
+			let mut cells: Vec<String> = vec![
+				node.label.clone(),
+				units::format_number(region.address, radix, 12),
+				units::format_number(region.size, radix, 12),
+				node.flags(),
+			];
+
+			let hw_range = region.get_hw_start_addr(board.apertures())
+				.ok()
+				.filter(|_| return region.is_mapped(board.apertures(),
+								    &board.banks()))
+				.and_then(|hw_address| {
+					return Some((hw_address, region.hw_end(hw_address)?))
+				});
+			match hw_range {
+				Some((hw_address, hw_end)) => {
+					cells.push(units::format_number(hw_address, radix, 12));
+					cells.push(units::format_number(hw_end - 1, radix, 12));
+					rows.push((cells, true));
+				}
+				_ => {
+					cells.push("unmapped".to_string());
+					cells.push("unmapped".to_string());
+					rows.push((cells, false));
+				}
+			}
+		}
+	}
+
+	return rows
+}
+
+fn render_reserved_table<B: tui::backend::Backend>
+(board: &soc::MPFS, reserved_nodes: &[ReservedMemoryNode], radix: Radix,
+ frame:&mut Frame<B>, display_rect: Rect, search: Option<&str>)
+{
+	let header_cells = RESERVED_TABLE_HEADERS
 		.iter()
 		.map(|h|
 			return
@@ -126,12 +666,21 @@ fn render_seg_table<B: tui::backend::Backend>
 		);
 
 	let header = Row::new(header_cells).height(1).bottom_margin(1);
-	let rows = data.iter().map(|item| {
-		let cells = item.iter().map(|c|
-			return Cell::from(c.clone())
-		);
-		return Row::new(cells).height(1).bottom_margin(1)
-	});
+
+	let mut rows: Vec<Row> = Vec::new();
+	for (cells, mapped) in reserved_rows(board, reserved_nodes, radix) {
+		if !row_matches(&cells, search) {
+			continue;
+		}
+
+		let mut style = Style::default();
+		if !mapped {
+			style = style.fg(theme::warning());
+		}
+
+		let cells = cells.iter().map(|cell| return search_cell(cell, search));
+		rows.push(Row::new(cells).height(1).style(style));
+	}
 
 	let table =
 		Table::new(rows)
@@ -139,198 +688,674 @@ fn render_seg_table<B: tui::backend::Backend>
 		.block(
 			Block::default()
 			.borders(Borders::ALL)
-
+			.border_style(theme::border())
 		)
 		.style(Style::default())
-		.highlight_style(selected_style)
-		.highlight_symbol(">> ")
 		.widths(&[
-			Constraint::Percentage(5),
-			Constraint::Percentage(10),
-			Constraint::Percentage(15),
-			Constraint::Percentage(12),
-			Constraint::Percentage(8),
-			Constraint::Percentage(12),
-			Constraint::Percentage(12),
-			Constraint::Percentage(12),
+			Constraint::Percentage(24),
+			Constraint::Percentage(16),
+			Constraint::Percentage(14),
+			Constraint::Percentage(14),
+			Constraint::Percentage(16),
+			Constraint::Percentage(16),
 		]);
 
 	frame.render_widget(table, display_rect);
 }
 
-#[derive(Clone)]
-struct ApertureVis {
-	rectangle: Option<Rectangle>,
-	label: Option<char>,
-	label_x: f64,
-	label_y: f64
-}
-
-impl Default for ApertureVis {
-	fn default() -> ApertureVis {
-		return ApertureVis {
-			rectangle: None,
-			label: None,
-			label_x: 0.0,
-			label_y: 0.0
+fn unmapped_reserved_regions(board: &soc::MPFS, reserved_nodes: &[ReservedMemoryNode])
+-> Vec<String>
+{
+	let mut unmapped: Vec<String> = Vec::new();
+	for node in reserved_nodes {
+		for region in node.regions.iter() {
+			if !region.is_mapped(board.apertures(), &board.banks()) {
+				unmapped.push(node.label.clone());
+			}
 		}
 	}
+
+	return unmapped
 }
 
-fn render_visualisation<B: tui::backend::Backend>
-(board: &mut soc::MPFS, nodes: Option<Vec<MemoryNode>>, frame:&mut Frame<B>, display_rect: Rect)
+fn render_region_table<B: tui::backend::Backend>
+(regions: &[NamedRegion], radix: Radix, frame:&mut Frame<B>, display_rect: Rect)
 {
-	let border: f64 = 0.5;
-	let mem_map_height: f64 = (display_rect.height) as f64 - 2.0 * border;
-	let mem_map_width = 0.67 * (display_rect.width) as f64 - 2.0 * border;
-	let mem_map_x = 1.0;
-	let mem_map_y = 0.5;
-	let px_per_byte: f64 = mem_map_height / board.total_system_memory as f64;
-
-	let mut aperature_colours = READABLE_COLOURS.iter();
-
-	let memory_map = Rectangle {
-		x: mem_map_x,
-		y: mem_map_y,
-		width: mem_map_width,
-		height: mem_map_height,
-		color: Color::White,
-	};
-
-	let memory_apertures = board.memory_apertures.iter();
-	let mut apertures: Vec<ApertureVis> = Vec::new();
-	let num_apertures = 6.0; // this is a fixed property of the SoC
-	let num_apertures = 7.0; // inc. by one for the dt node rendering
-	let aperature_width = mem_map_width / (num_apertures + 1.0);
-	let mut display_offset = aperature_width / num_apertures;
-
-	for aperature in memory_apertures {
-		let aperature_start = aperature.get_hw_start_addr(board.total_system_memory);
-		let aperature_end = aperature.get_hw_end_addr(board.total_system_memory);
-		let colour = *aperature_colours.next().unwrap(); // yeah, yeah this could crash
-		let mut aperture_vis: ApertureVis = ApertureVis {
-			label: aperature.reg_name.chars().last(),
-			..Default::default()
-		};
+	let header_cells = ["Region Name", "Size", "Attributes", "HW Start", "HW End",]
+		.iter()
+		.map(|h|
+			return
+			Cell::from(*h)
+			.style(Style::default())
+		);
 
-		let rectangle_x = mem_map_x + display_offset;
+	let header = Row::new(header_cells).height(1).bottom_margin(1);
 
-		aperture_vis.label_x = rectangle_x + 0.5 * aperature_width;
-		aperture_vis.label_y = mem_map_y - 0.5;
+	let rows = regions.iter().map(|region| {
+		let mut cells: Vec<String> = vec![
+			region.name.clone(),
+			units::format_number(region.size, radix, 12),
+			region.attributes.join(" "),
+		];
 
-		if aperature_start.is_ok() && aperature_end.is_ok() {
-			let aperture_y: f64 = px_per_byte * aperature_start.unwrap() as f64;
-			let aperture_height: f64 = px_per_byte * aperature_end.unwrap() as f64
-						   - aperture_y;
-			let rectangle = Rectangle {
-				x: rectangle_x,
-				y: mem_map_y + aperture_y,
-				width: aperature_width,
-				height: aperture_height,
-				color: colour,
-			};
-			aperture_vis.rectangle = Some(rectangle);
+		if let Some(hardware_addr) = region.hardware_addr {
+			cells.push(units::format_number(hardware_addr, radix, 12));
+			cells.push(units::format_number(hardware_addr + region.size - 1, radix, 12));
+		} else {
+			cells.push("unplaced".to_string());
+			cells.push("unplaced".to_string());
 		}
-		apertures.push(aperture_vis.clone());
-		display_offset += aperature_width + aperature_width / num_apertures;
-	}
-
-	if let Some(nodes) = nodes {
-		let mut node_colours = READABLE_COLOURS.iter();
-		let mut label: Option<char> = Some('a');
-		for node in nodes.iter() {
-			let start_addr = node.get_hw_start_addr(&mut board.memory_apertures.clone());
-			if start_addr.is_err() {
-				break;
-			}
 
-			let colour = *node_colours.next().unwrap(); // yeah, yeah this could crash
-
-			let start_addr = start_addr.unwrap();
+		return Row::new(cells).height(1)
+	});
 
-			let mut node_vis = ApertureVis {
-				label,
-				..Default::default()
+	let table =
+		Table::new(rows)
+		.header(header)
+		.block(
+			Block::default()
+			.borders(Borders::ALL)
+			.border_style(theme::border())
+		)
+		.style(Style::default())
+		.widths(&[
+			Constraint::Percentage(20),
+			Constraint::Percentage(20),
+			Constraint::Percentage(20),
+			Constraint::Percentage(20),
+			Constraint::Percentage(20),
+		]);
+
+	frame.render_widget(table, display_rect);
+}
+
+fn render_free_table<B: tui::backend::Backend>
+(unmapped: &[(u64, u64)], radix: Radix, frame:&mut Frame<B>, display_rect: Rect)
+{
+	let header_cells = ["Free Start", "Free End", "Size",]
+		.iter()
+		.map(|h|
+			return
+			Cell::from(*h)
+			.style(Style::default())
+		);
+
+	let header = Row::new(header_cells).height(1).bottom_margin(1);
+
+	let rows = unmapped.iter().map(|(start, end)| {
+		let cells = vec![
+			units::format_number(*start, radix, 12),
+			units::format_number(end - 1, radix, 12),
+			units::format_size(end - start),
+		];
+
+		return Row::new(cells).height(1)
+	});
+
+	let table =
+		Table::new(rows)
+		.header(header)
+		.block(
+			Block::default()
+			.borders(Borders::ALL)
+			.border_style(theme::border())
+			.title("Not reachable through any aperture")
+		)
+		.style(Style::default())
+		.widths(&[
+			Constraint::Percentage(34),
+			Constraint::Percentage(33),
+			Constraint::Percentage(33),
+		]);
+
+	frame.render_widget(table, display_rect);
+}
+
+fn render_stats_table<B: tui::backend::Backend>
+(stats: &soc::CoverageStats, radix: Radix, frame:&mut Frame<B>, display_rect: Rect)
+{
+	// laid out sideways, a row of names above a row of values, so that it
+	// still fits when it only gets a handful of lines. hex isn't padded and
+	// the percentages are whole, a row of seven has to fit in half a screen
+	let amount = |bytes: u64| {
+		let formatted = units::format_number(bytes, radix, 0);
+		if stats.total_memory == 0 {
+			return formatted
+		}
+		return format!("{} ({:.0}%)", formatted,
+			       100.0 * bytes as f64 / stats.total_memory as f64)
+	};
+
+	let mut summary_names = vec!["Total DDR".to_string()];
+	let mut summary_values = vec![units::format_number(stats.total_memory, radix, 0)];
+	for (class, bytes) in &stats.covered_by_class {
+		summary_names.push(class.name().to_string());
+		summary_values.push(amount(*bytes));
+	}
+	summary_names.push("overlapping".to_string());
+	summary_values.push(amount(stats.overlap));
+	summary_names.push("aliased".to_string());
+	summary_values.push(amount(stats.aliased));
+	summary_names.push("unreachable".to_string());
+	summary_values.push(amount(stats.unreachable));
+
+	let aperture_names: Vec<String> = stats.reachable.iter()
+		.map(|(reg_name, _)| return format!("via {}", reg_name))
+		.collect();
+	let aperture_values: Vec<String> = stats.reachable.iter()
+		.map(|(_, bytes)| return amount(*bytes))
+		.collect();
+
+	let mut cells = vec![summary_names, summary_values, aperture_names, aperture_values];
+	if !stats.reachable_by_masters.is_empty() {
+		cells.push(stats.reachable_by_masters.iter()
+			   .map(|(name, _)| return format!("by {}", name))
+			   .collect());
+		cells.push(stats.reachable_by_masters.iter()
+			   .map(|(_, bytes)| return amount(*bytes))
+			   .collect());
+	}
+
+	// each column is as wide as the widest thing in it, so nothing gets cut
+	// off while there's room for it
+	let num_columns = cells.iter().map(|row| return row.len()).max().unwrap_or(0);
+	let widths: Vec<Constraint> = (0..num_columns)
+		.map(|column| {
+			let width = cells.iter()
+				.filter_map(|row| return row.get(column))
+				.map(|cell| return cell.chars().count())
+				.max()
+				.unwrap_or(0);
+			return Constraint::Length(width as u16)
+		})
+		.collect();
+
+	let name_style = Style::default().add_modifier(Modifier::BOLD);
+	let rows: Vec<Row> = cells.into_iter()
+		.enumerate()
+		.map(|(index, row)| {
+			match index % 2 {
+				0 => return Row::new(row).style(name_style),
+				_ => return Row::new(row),
+			}
+		})
+		.collect();
+
+	let table =
+		Table::new(rows)
+		.block(
+			Block::default()
+			.borders(Borders::ALL)
+			.border_style(theme::border())
+			.title("Statistics")
+		)
+		.style(Style::default())
+		.widths(&widths);
+
+	frame.render_widget(table, display_rect);
+}
+
+fn hatch(x: f64, y: f64, width: f64, height: f64, colour: Color) -> Vec<Line>
+{
+	// diagonal lines across a rectangle, each clipped to fit inside it
+	let spacing = 2.0;
+	let mut lines: Vec<Line> = Vec::new();
+	let mut offset = -height + spacing;
+	while offset < width {
+		let x1 = x + offset.max(0.0);
+		let y1 = y + (-offset).max(0.0);
+		let x2 = x + (offset + height).min(width);
+		let y2 = y + (width - offset).min(height);
+		lines.push(Line { x1, y1, x2, y2, color: colour });
+		offset += spacing;
+	}
+
+	return lines
+}
+
+fn dashed_rectangle(rectangle: &Rectangle) -> Vec<Line>
+{
+	// the four sides of the rectangle, broken up into dashes
+	let dash = 1.0;
+	let corners = [
+		(rectangle.x, rectangle.y),
+		(rectangle.x + rectangle.width, rectangle.y),
+		(rectangle.x + rectangle.width, rectangle.y + rectangle.height),
+		(rectangle.x, rectangle.y + rectangle.height),
+	];
+	let mut lines: Vec<Line> = Vec::new();
+	for (index, (x1, y1)) in corners.iter().enumerate() {
+		let (x2, y2) = corners[(index + 1) % corners.len()];
+		let length = (x2 - x1).abs().max((y2 - y1).abs());
+		let mut offset = 0.0;
+		while offset < length {
+			let from = offset / length;
+			let to = ((offset + dash) / length).min(1.0);
+			lines.push(Line {
+				x1: x1 + (x2 - x1) * from,
+				y1: y1 + (y2 - y1) * from,
+				x2: x1 + (x2 - x1) * to,
+				y2: y1 + (y2 - y1) * to,
+				color: rectangle.color,
+			});
+			offset += 2.0 * dash;
+		}
+	}
+
+	return lines
+}
+
+const SEG_TABLE_HEADERS: [&str; 10] = [
+	"ID", "Register Name", "Description", "Bus Address", "Register Value",
+	"Aperture HW Start", "Aperture HW End", "Aperature Size", "Perf. Class", "Alias Of",
+];
+
+fn render_seg_table<B: tui::backend::Backend>
+(data: Vec<Vec<String>>, frame:&mut Frame<B>, display_rect: Rect,
+ scroll: &mut ScrollableTable, focused: bool, search: Option<&str>)
+{
+	let selected_style = theme::highlight();
+	let header_cells = SEG_TABLE_HEADERS
+		.iter()
+		.map(|h|
+			return
+			Cell::from(*h)
+			.style(Style::default())
+		);
+
+	let header = Row::new(header_cells).height(1).bottom_margin(1);
+	// the ids are the apertures', so that a click still picks the right one
+	let shown: Vec<(usize, &Vec<String>)> = data.iter()
+		.enumerate()
+		.filter(|(_, item)| return row_matches(item, search))
+		.collect();
+	let rows = shown.iter().map(|(_, item)| {
+		let cells = item.iter().map(|c|
+			return search_cell(c, search)
+		);
+		return Row::new(cells).height(1).bottom_margin(1)
+	});
+	scroll.update(shown.iter().map(|(id, _)| return *id).collect(), display_rect);
+
+	let mut table =
+		Table::new(rows)
+		.header(header)
+		.block(
+			Block::default()
+			.borders(Borders::ALL)
+			.border_style(theme::border())
+			.title(table_title(focused, search))
+		)
+		.style(Style::default())
+		.widths(&[
+			Constraint::Percentage(3),
+			Constraint::Percentage(7),
+			Constraint::Percentage(12),
+			Constraint::Percentage(11),
+			Constraint::Percentage(8),
+			Constraint::Percentage(11),
+			Constraint::Percentage(17),
+			Constraint::Percentage(7),
+			Constraint::Percentage(12),
+			Constraint::Percentage(9),
+		]);
+	if focused {
+		table = table.highlight_style(selected_style).highlight_symbol(">> ");
+	}
+
+	frame.render_stateful_widget(table, display_rect, &mut scroll.state);
+	render_scrollbar(scroll, frame, display_rect);
+}
+
+#[derive(Clone)]
+struct ApertureVis {
+	rectangle: Option<Rectangle>,
+	// drawn with a dashed border, for an aperture cut short by the end of
+	// memory
+	clamped: bool,
+	label: Option<char>,
+	label_x: f64,
+	label_y: f64
+}
+
+impl Default for ApertureVis {
+	fn default() -> ApertureVis {
+		return ApertureVis {
+			rectangle: None,
+			clamped: false,
+			label: None,
+			label_x: 0.0,
+			label_y: 0.0
+		}
+	}
+}
+
+fn render_visualisation<B: tui::backend::Backend>
+(board: &soc::MPFS, nodes: Option<Vec<MemoryNode>>, reserved_nodes: &[ReservedMemoryNode],
+ view: &ViewOptions, frame:&mut Frame<B>, display_rect: Rect)
+{
+	let radix = view.radix;
+	let map = view.map;
+	let mem_map_height = map.mem_map_height;
+	let bus_map_x = map.bus_map_x;
+	let bus_map_width = map.bus_map_width;
+	let mem_map_x = map.mem_map_x;
+	let mem_map_width = map.mem_map_width;
+	let mem_map_y = map.mem_map_y;
+	let (view_bottom, view_top) = (map.view_bottom, map.view_top);
+	let px_per_byte: f64 = map.px_per_byte();
+
+	// where a range of ddr lands on the canvas, clipped to what's in view
+	let ddr_to_y = |start: u64, end: u64| {
+		if end <= view_bottom || start >= view_top {
+			return None
+		}
+
+		let start = start.max(view_bottom) - view_bottom;
+		let end = end.min(view_top) - view_bottom;
+		return Some((mem_map_y + px_per_byte * start as f64,
+			     px_per_byte * (end - start) as f64))
+	};
+
+	// the windows are scattered across a 38-bit bus address space, a linear
+	// scale would make the 32-bit ones invisible so it's a log scale instead
+	let bus_bottom = board.apertures().iter()
+		.map(|aperture| return aperture.bus_addr)
+		.min()
+		.unwrap_or(1)
+		.max(1) as f64;
+	let bus_top = board.apertures().iter()
+		.map(|aperture| return aperture.bus_addr + aperture.aperture_size)
+		.max()
+		.unwrap_or(2) as f64;
+	let bus_to_y = |bus_addr: u64| return mem_map_y + mem_map_height
+		* ((bus_addr.max(1) as f64).log2() - bus_bottom.log2())
+		/ (bus_top.log2() - bus_bottom.log2()).max(f64::EPSILON);
+	let mut connections: Vec<Line> = Vec::new();
+
+	// ddr that nothing can reach gets hatched out across the whole map
+	let mut holes: Vec<Line> = Vec::new();
+	for (start, end) in board.unmapped_regions() {
+		if let Some((hole_y, hole_height)) = ddr_to_y(start, end) {
+			holes.append(&mut hatch(mem_map_x, hole_y, mem_map_width, hole_height,
+						theme::dim()));
+		}
+	}
+
+	// & the gaps between banks, where there's no memory at all, are left
+	// empty with a dashed outline
+	let mut gaps: Vec<(f64, f64)> = Vec::new();
+	for (start, end) in board.holes() {
+		if let Some((gap_y, gap_height)) = ddr_to_y(start, end) {
+			let gap = Rectangle {
+				x: mem_map_x,
+				y: gap_y,
+				width: mem_map_width,
+				height: gap_height,
+				color: theme::dim(),
+			};
+			holes.append(&mut dashed_rectangle(&gap));
+			gaps.push((gap_y, gap_height));
+		}
+	}
+
+	let memory_map = Rectangle {
+		x: mem_map_x,
+		y: mem_map_y,
+		width: mem_map_width,
+		height: mem_map_height,
+		color: Color::White,
+	};
+
+	let mut apertures: Vec<ApertureVis> = Vec::new();
+	let aperature_width = map.aperture_width;
+	let mut column = 0;
+
+	// aliases are stacked up next to the aperture whose ddr they also see
+	for index in board.alias_stack() {
+		let aperature = &board.apertures()[index];
+		let aperature_start = aperature.get_hw_start_addr(&board.banks());
+		let aperature_end = aperature.get_hw_end_addrs(&board.banks());
+		let colour = aperture_colour(index);
+		let mut aperture_vis: ApertureVis = ApertureVis {
+			clamped: aperature_end.as_ref()
+				.map(|end| return end.is_clamped())
+				.unwrap_or(false),
+			label: aperature.reg_name.chars().last(),
+			..Default::default()
+		};
+
+		let rectangle_x = map.column_x(column);
+
+		aperture_vis.label_x = rectangle_x + 0.5 * aperature_width;
+		aperture_vis.label_y = mem_map_y - 0.5;
+
+		let bus_y = bus_to_y(aperature.bus_addr);
+		let bus_height = bus_to_y(aperature.bus_addr + aperature.aperture_size) - bus_y;
+		apertures.push(ApertureVis {
+			rectangle: Some(Rectangle {
+				x: bus_map_x,
+				y: bus_y,
+				width: bus_map_width,
+				height: bus_height,
+				color: colour,
+			}),
+			clamped: false,
+			label: aperture_vis.label,
+			label_x: bus_map_x + 0.5 * bus_map_width,
+			label_y: bus_y + bus_height / 2.0 - 0.5,
+		});
+
+		let in_view = match (aperature_start, aperature_end) {
+			(Ok(aperature_start), Ok(aperature_end)) => ddr_to_y(aperature_start,
+									     aperature_end.clamped),
+			_ => None,
+		};
+
+		if let Some((aperture_y, aperture_height)) = in_view {
+			// from the bottom of the window on the bus to where it
+			// lands in ddr
+			connections.push(Line {
+				x1: bus_map_x + bus_map_width,
+				y1: bus_y,
+				x2: rectangle_x,
+				y2: aperture_y,
+				color: colour,
+			});
+			let rectangle = Rectangle {
+				x: rectangle_x,
+				y: aperture_y,
+				width: aperature_width,
+				height: aperture_height,
+				color: colour,
+			};
+			aperture_vis.rectangle = Some(rectangle);
+		}
+		apertures.push(aperture_vis.clone());
+		column += 1;
+	}
+
+	if let Some(nodes) = nodes {
+		let mut label: Option<char> = Some('a');
+		for (index, node) in nodes.iter().enumerate() {
+			let colour = node_colour(index, node);
+
+			// every reg tuple is drawn on its own, but they share the
+			// colour and label of the node they came from
+			for region in node.regions.iter() {
+				let start_addr = match region.get_hw_start_addr(board.apertures()) {
+					Ok(start_addr) => start_addr,
+					Err(_) => continue,
+				};
+				let end_addr = match region.hw_end(start_addr) {
+					Some(end_addr) => end_addr,
+					None => continue,
+				};
+
+				let (rectangle_y, node_height) =
+					match ddr_to_y(start_addr, end_addr) {
+						Some(in_view) => in_view,
+						None => continue,
+					};
+
+				let mut node_vis = ApertureVis {
+					label,
+					..Default::default()
+				};
+
+				let rectangle_x = map.column_x(column);
+
+				node_vis.label_x = rectangle_x + 0.5 * aperature_width;
+				node_vis.label_y = rectangle_y + node_height / 2.0 - 0.5;
+				let rectangle = Rectangle {
+					x: rectangle_x,
+					y: rectangle_y,
+					width: aperature_width,
+					height: node_height,
+					color: colour,
+				};
+
+				node_vis.rectangle = Some(rectangle);
+				apertures.push(node_vis.clone());
+			}
+			label = label.and_then(|label| return char::from_u32(label as u32 + 1));
+		}
+	}
+
+	// reserved memory gets a column of its own, to the right of the memory
+	// nodes, so that it can be seen whether they overlap
+	column += 1;
+	for node in reserved_nodes {
+		let colour = reserved_node_colour(node);
+
+		for region in node.regions.iter() {
+			let start_addr = match region.get_hw_start_addr(board.apertures()) {
+				Ok(start_addr) => start_addr,
+				Err(_) => continue,
+			};
+			let end_addr = match region.hw_end(start_addr) {
+				Some(end_addr) => end_addr,
+				None => continue,
 			};
-			label = char::from_u32(label.unwrap() as u32 + 1);
 
-			let rectangle_x = mem_map_x + display_offset;
-			let node_y: f64 = px_per_byte * start_addr as f64;
-			let node_height: f64 = px_per_byte * (node.size as f64 - 1.0);
-			let rectangle_y = mem_map_y + node_y;
+			let (node_y, node_height) =
+				match ddr_to_y(start_addr, end_addr) {
+					Some(in_view) => in_view,
+					None => continue,
+				};
 
-			node_vis.label_x = rectangle_x + 0.5 * aperature_width;
-			node_vis.label_y = rectangle_y + node_height / 2.0 - 0.5;
+			let rectangle_x = map.column_x(column);
 			let rectangle = Rectangle {
 				x: rectangle_x,
-				y: rectangle_y,
+				y: node_y,
 				width: aperature_width,
 				height: node_height,
 				color: colour,
 			};
 
-			node_vis.rectangle = Some(rectangle);
-			apertures.push(node_vis.clone());
+			apertures.push(ApertureVis {
+				rectangle: Some(rectangle),
+				clamped: false,
+				label: Some('r'),
+				label_x: rectangle_x + 0.5 * aperature_width,
+				label_y: node_y + node_height / 2.0 - 0.5,
+			});
 		}
 	}
 
+	// with units as well, unless that's what the number is already shown in
+	let total_memory = board.total_memory();
+	let mut memory_size = units::format_number(total_memory, radix, 10);
+	if radix != Radix::Units {
+		memory_size = format!("{} ({})", memory_size, units::format_size(total_memory));
+	}
+	if !board.memory_banks.is_empty() {
+		memory_size = format!("{} in {} banks up to {}",
+				      units::format_size(board.installed_memory()),
+				      board.memory_banks.len(), memory_size);
+	}
+
 	let canvas =
 		Canvas::default()
 		.block(
 			Block::default()
 			.borders(Borders::ALL)
-			.title(format!(
-				"System memory available: {:#010x?} ({} MiB)",
-				board.total_system_memory,
-				hex_to_mib(board.total_system_memory)
-				)
-			)
+			.border_style(theme::border())
+			.title(format!("System memory available: {}", memory_size))
 		)
 		.paint(|ctx| {
 				ctx.draw(&memory_map);
 
+				for hole in &holes {
+					ctx.draw(hole);
+				}
+
+				for connection in &connections {
+					ctx.draw(connection);
+				}
+
+				for (gap_y, gap_height) in &gaps {
+					ctx.print(
+						mem_map_x + 0.5,
+						gap_y + gap_height / 2.0,
+						Span::styled("no memory",
+							     Style::default().fg(theme::dim())),
+					);
+				}
+
+				ctx.print(
+					bus_map_x,
+					mem_map_y + mem_map_height,
+					Span::styled("bus (log)", Style::default()),
+				);
+				ctx.print(
+					mem_map_x,
+					mem_map_y + mem_map_height,
+					Span::styled("ddr", Style::default()),
+				);
+
 				for aperture in &apertures {
 
-					if aperture.label.is_some() {
+					if let Some(label) = aperture.label {
 						ctx.print(
 							aperture.label_x,
 							aperture.label_y,
 							Span::styled(
-								format!("{}",
-									aperture.label
-										.as_ref()
-										.unwrap()
-								),
+								format!("{}", label),
 								Style::default()
 							)
 						);
 					}
 
-					if aperture.rectangle.is_none() {
-						continue;
+					match &aperture.rectangle {
+						Some(rectangle) if aperture.clamped => {
+							for dash in dashed_rectangle(rectangle) {
+								ctx.draw(&dash);
+							}
+						}
+						Some(rectangle) => ctx.draw(rectangle),
+						None => {}
 					}
-
-					ctx.draw(aperture.rectangle.as_ref().unwrap());
 				}
 
 				ctx.print(
 					mem_map_x + mem_map_width + 1.25,
 					mem_map_y - 0.5,
-					Span::styled(format!("{:#010x?}", 0_u64),
+					Span::styled(units::format_number(view_bottom, radix, 10),
 					Style::default()),
 				);
 				ctx.print(
 					mem_map_x + mem_map_width + 1.25,
 					mem_map_y + mem_map_height / 2.0,
-					Span::styled(format!("{:#010x?}",
-							     board.total_system_memory / 2),
+					Span::styled(units::format_number(view_bottom
+									  + (view_top - view_bottom) / 2,
+									  radix, 10),
 					Style::default()),
 				);
 				ctx.print(
 					mem_map_x + mem_map_width + 1.25,
 					mem_map_y + mem_map_height,
-					Span::styled(format!("{:#010x?}",
-							     board.total_system_memory),
+					Span::styled(units::format_number(view_top, radix, 10),
 					Style::default()),
 				);
 			}
@@ -341,47 +1366,146 @@ fn render_visualisation<B: tui::backend::Backend>
 	frame.render_widget(canvas, display_rect);
 }
 
-fn format_table_data(board: &mut soc::MPFS) -> (Vec<Vec<String>>, Result<(), ()>)
+fn legend_entry(colour: Color, label: String, name: String) -> Spans<'static>
+{
+	return Spans::from(vec![
+		Span::styled(format!("\u{2588} {} ", label), Style::default().fg(colour)),
+		Span::raw(name),
+	])
+}
+
+fn legend_height(board: &soc::MPFS, nodes: &Option<Vec<MemoryNode>>,
+		 reserved_nodes: &[ReservedMemoryNode]) -> u16
+{
+	let mut node_lines = nodes.as_ref().map_or(0, |nodes| return nodes.len());
+	if !reserved_nodes.is_empty() {
+		node_lines += 1;
+	}
+
+	// a line per entry in whichever column is longer, plus the borders
+	return (board.aperture_count().max(node_lines) + 2) as u16
+}
+
+fn render_legend<B: tui::backend::Backend>
+(board: &soc::MPFS, nodes: &Option<Vec<MemoryNode>>, reserved_nodes: &[ReservedMemoryNode],
+ frame:&mut Frame<B>, display_rect: Rect)
+{
+	let columns =
+		Layout::default()
+		.direction(Direction::Horizontal)
+		.constraints(
+		[
+			Constraint::Percentage(50),
+			Constraint::Percentage(50),
+		]
+		.as_ref(),
+		)
+		.split(display_rect);
+
+	let aperture_lines: Vec<Spans> = board.apertures()
+		.iter()
+		.enumerate()
+		.map(|(index, aperture)| return legend_entry(
+			aperture_colour(index),
+			aperture.reg_name.chars().last().unwrap_or('?').to_string(),
+			aperture.reg_name.clone()
+		))
+		.collect();
+
+	let mut node_lines: Vec<Spans> = Vec::new();
+	let mut label: Option<char> = Some('a');
+	for (index, node) in nodes.iter().flatten().enumerate() {
+		node_lines.push(legend_entry(node_colour(index, node),
+					     label.unwrap_or('?').to_string(),
+					     node.label.clone()));
+		label = label.and_then(|label| return char::from_u32(label as u32 + 1));
+	}
+
+	// reserved memory all shares the one label, so it gets the one line
+	if !reserved_nodes.is_empty() {
+		node_lines.push(legend_entry(Color::Gray, "r".to_string(),
+					     "reserved-memory (darker if no-map)".to_string()));
+	}
+
+	let aperture_legend =
+		Paragraph::new(aperture_lines)
+		.block(Block::default().borders(Borders::ALL).border_style(theme::border()).title("Apertures"));
+	let node_legend =
+		Paragraph::new(node_lines)
+		.block(Block::default().borders(Borders::ALL).border_style(theme::border()).title("DT Nodes"));
+
+	frame.render_widget(aperture_legend, columns[0]);
+	frame.render_widget(node_legend, columns[1]);
+}
+
+fn format_table_data(board: &soc::MPFS, radix: Radix)
+-> (Vec<Vec<String>>, Result<(), ()>)
 {
 	let mut config_is_valid: Vec<bool> = Vec::new();
 	let mut data: Vec<Vec<String>> = Vec::new();
 
-	for memory_aperture in &board.memory_apertures {
-		let aperature_start = memory_aperture.get_hw_start_addr(board.total_system_memory);
-		let aperature_end = memory_aperture.get_hw_end_addr(board.total_system_memory);
+	for (id, memory_aperture) in board.apertures().iter().enumerate() {
+		let aperature_start = memory_aperture.get_hw_start_addr(&board.banks());
+		let aperature_end = memory_aperture.get_hw_end_addrs(&board.banks());
 
 		let mut row_cells: Vec<String> = Vec::new();
 		row_cells.push(data.len().to_string());
 		row_cells.push(memory_aperture.reg_name.clone());
-		row_cells.push(memory_aperture.description.clone());
-		row_cells.push(format!("{:#012x?}", memory_aperture.bus_addr));
+		row_cells.push(memory_aperture.description());
+		row_cells.push(units::format_number(memory_aperture.bus_addr, radix, 12));
 		row_cells.push(
-			format!("{:#08x?}",
-				soc::hw_start_addr_to_seg(
-					memory_aperture.get_hw_start_addr(u64::MAX).unwrap(),
-					memory_aperture.bus_addr)
-				)
+			memory_aperture.seg()
+			.map(|seg| return format!("{:#08x?}", seg))
+			.unwrap_or_else(|_| return "invalid".to_string())
 			);
 
-		if aperature_start.is_err() || aperature_end.is_err() {
+		if let (Ok(start), Ok(end)) = (&aperature_start, &aperature_end) {
+			let size = end.clamped - start;
+
+			row_cells.push(units::format_number(*start, radix, 12));
+			// the aperture carries on past the end of memory
+			if end.is_clamped() {
+				row_cells.push(format!("{} (clamped)",
+						       units::format_number(end.clamped, radix, 12)));
+			} else {
+				row_cells.push(units::format_number(end.clamped, radix, 12));
+			}
+			// a size is easier to take in with units than as a long hex
+			// number, so that's what hex gets
+			if radix == Radix::Hex {
+				row_cells.push(units::format_size(size));
+			} else {
+				row_cells.push(units::format_number(size, radix, 12));
+			}
+		} else if memory_aperture.is_unused(&board.banks()) {
+			row_cells.push("unused".to_string());
+			row_cells.push("unused".to_string());
+			row_cells.push("n/a".to_string());
+			// maps nothing, so counts towards there being no memory mapped
+			config_is_valid.push(false);
+		} else {
 			row_cells.push("invalid".to_string());
 			row_cells.push("invalid".to_string());
-			row_cells.push("n/a MiB".to_string());
+			row_cells.push("n/a".to_string());
 			config_is_valid.push(false);
-		} else {
-			let start = aperature_start.as_ref().unwrap();
-			let end = aperature_end.as_ref().unwrap();
-			let size = end - start;
+		}
+
+		row_cells.push(memory_aperture.performance_class.to_string());
 
-			row_cells.push(format!("{:#012x?}", start));
-			row_cells.push(format!("{:#012x?}", end));
-			row_cells.push(format!("{} MiB", hex_to_mib(size)));
+		// an alias is fine, unless they've been forbidden
+		let mut alias = String::new();
+		if let Some(first) = board.alias_of(id) {
+			alias = board.apertures()[first].reg_name.clone();
+			if board.forbid_aliases {
+				alias += "!";
+			}
 		}
+		row_cells.push(alias);
 
 		data.push(row_cells.clone());
 	}
 
-	if config_is_valid.len() != board.memory_apertures.len() {
+	if config_is_valid.len() != board.aperture_count() {
 		return (data, Ok(()))
 	}
 	else {
@@ -389,42 +1513,103 @@ fn format_table_data(board: &mut soc::MPFS) -> (Vec<Vec<String>>, Result<(), ()>
 	}
 }
 
-fn render_seg_regs<T, G, B: tui::backend::Backend>
-(board: &mut soc::MPFS, config_is_valid: Result<T,G>, frame:&mut Frame<B>, display_rect: Rect)
+fn format_seg_regs(board: &soc::MPFS, width: usize) -> String
 {
-	let mut output;
+	// break lines between entries, never inside one, and indent the
+	// continuation lines so that the result is still valid yaml if it gets
+	// copied out of the terminal wholesale
+	let mut entries: Vec<String> = Vec::new();
+	let mut invalid: Vec<String> = Vec::new();
+	for memory_aperture in board.apertures() {
+		let seg = memory_aperture.seg();
+		entries.push(format!(
+			"{}: '{:#x}',",
+			memory_aperture.reg_name,
+			seg.unwrap_or_default()
+		));
 
-	if config_is_valid.is_ok() {
-		output = "seg-reg-config: { ".to_string();
-		for memory_aperture in &board.memory_apertures {
-			output += &format!(
-				"{}: {:#x?}, ",
-				memory_aperture.reg_name,
-				soc::hw_start_addr_to_seg(memory_aperture.hardware_addr,
-							  memory_aperture.bus_addr)
-			).to_string();
-		}
-		output += "}\n";
-	} else {
-		output = "Cannot calculate seg registers, configuration is invalid as \
-			no memory is mapped.".to_string();
+		let banks = board.banks();
+		if seg.is_err() || (memory_aperture.get_hw_start_addr(&banks).is_err() &&
+				    !memory_aperture.is_unused(&banks)) {
+			invalid.push(memory_aperture.reg_name.clone());
+		}
 	}
+	entries.push("}".to_string());
 
-	let segs =
-		Paragraph::new(output)
-		.block(
+	let mut output = "seg-reg-config: {".to_string();
+	let mut line_len = output.len();
+	for entry in entries {
+		if line_len + 1 + entry.len() > width {
+			output += "\n ";
+			line_len = 1;
+		}
+		output += " ";
+		output += &entry;
+		line_len += 1 + entry.len();
+	}
+	output += "\n";
+
+	if !invalid.is_empty() {
+		output += &format!("# invalid, start beyond the end of memory: {}\n",
+				   invalid.join(", "));
+	}
+
+	return output
+}
+
+fn copy_seg_regs(board: &soc::MPFS) -> String
+{
+	// all on the one line, as it's going to be pasted rather than read
+	let output = format_seg_regs(board, usize::MAX);
+	let mut lines = output.lines();
+	let line = lines.next().unwrap_or_default();
+	if let Some(invalid) = lines.next() {
+		messages::warn(invalid.trim_start_matches("# ").to_string());
+	}
+
+	return match clipboard::copy(line) {
+		Ok(via) => format!("Copied the seg-reg-config line to the clipboard, via {}.", via),
+		Err(error) => format!("Failed to copy to the clipboard: {}", error),
+	}
+}
+
+fn render_seg_regs<T, G, B: tui::backend::Backend>
+(board: &soc::MPFS, config_is_valid: Result<T,G>, frame:&mut Frame<B>, display_rect: Rect)
+{
+	let mut output = "Cannot calculate seg registers, configuration is invalid as \
+		no memory is mapped.".to_string();
+
+	if config_is_valid.is_ok() {
+		// minus two for the borders
+		let width = display_rect.width.saturating_sub(2) as usize;
+		output = format_seg_regs(board, width);
+	}
+
+	let segs =
+		Paragraph::new(output)
+		.block(
 			Block::default()
 			.title("For insertion into config.yaml:")
-			.borders(Borders::ALL))
+			.borders(Borders::ALL)
+			.border_style(theme::border()))
 		.style(Style::default());
 
 	frame.render_widget(segs, display_rect);
 }
 
 fn render_display<B: tui::backend::Backend>
-(board: &mut soc::MPFS, memory_nodes: Option<Vec<MemoryNode>>,
- frame: &mut Frame<B>, display_rect: Rect)
+(board: &soc::MPFS, memory_nodes: Option<Vec<MemoryNode>>,
+ reserved_nodes: &[ReservedMemoryNode], named_regions: &[NamedRegion],
+ view: &mut ViewOptions, frame: &mut Frame<B>, display_rect: Rect)
 {
+	let radix = view.radix;
+	let mut memory_nodes = memory_nodes;
+	if !view.show_disabled_nodes {
+		memory_nodes = memory_nodes.map(|nodes|
+			return nodes.into_iter().filter(|node| return node.enabled).collect()
+		);
+	}
+
 	let chunks =
 		Layout::default()
 		.direction(Direction::Vertical)
@@ -449,9 +1634,349 @@ fn render_display<B: tui::backend::Backend>
 		)
 		.split(chunks[0]);
 
+	// the seg table always gets the lion's share, whatever is left over is
+	// split evenly between the dt node & statistics tables and any of the
+	// optional tables
+	let mut num_extra_tables: u16 = 2;
+	if !reserved_nodes.is_empty() {
+		num_extra_tables += 1;
+	}
+	if !named_regions.is_empty() {
+		num_extra_tables += 1;
+	}
+	let unmapped = board.unmapped_regions();
+	if !unmapped.is_empty() {
+		num_extra_tables += 1;
+	}
+
+	let mut seg_table_percentage: u16 = 60;
+	if num_extra_tables > 1 {
+		seg_table_percentage = 50;
+	}
+
+	let extra_table_percentage = (100 - seg_table_percentage) / num_extra_tables;
+	let mut table_constraints = vec![Constraint::Percentage(seg_table_percentage)];
+	for _ in 0..num_extra_tables {
+		table_constraints.push(Constraint::Percentage(extra_table_percentage));
+	}
+
 	let table_area =
 		Layout::default()
 		.direction(Direction::Vertical)
+		.constraints(table_constraints)
+		.split(display_area[1]);
+
+	let (data, config_is_valid) = format_table_data(board, radix);
+
+	render_seg_regs(board, config_is_valid, frame, chunks[1]);
+
+	let search = view.search.as_deref();
+	render_seg_table(data, frame, table_area[0], &mut view.scroll.segs,
+			 view.focus == ScrollFocus::Segs, search);
+	let node_rows = memory_nodes.as_ref().map(|nodes| return node_rows(board, nodes, radix));
+	render_dt_node_table(node_rows, frame, table_area[1], &mut view.scroll.nodes,
+			     view.focus == ScrollFocus::Nodes, search);
+	render_stats_table(&board.coverage_stats(), radix, frame, table_area[2]);
+
+	let mut next_table = 3;
+	if !reserved_nodes.is_empty() {
+		render_reserved_table(board, reserved_nodes, radix, frame,
+				      table_area[next_table], search);
+		next_table += 1;
+	}
+
+	if !named_regions.is_empty() {
+		render_region_table(named_regions, radix, frame, table_area[next_table]);
+		next_table += 1;
+	}
+
+	if !unmapped.is_empty() {
+		render_free_table(&unmapped, radix, frame, table_area[next_table]);
+	}
+
+	let visualisation_area =
+		Layout::default()
+		.direction(Direction::Vertical)
+		.constraints(
+		[
+			Constraint::Min(0),
+			Constraint::Length(legend_height(board, &memory_nodes, reserved_nodes)),
+		]
+		.as_ref(),
+		)
+		.split(display_area[0]);
+
+	render_legend(board, &memory_nodes, reserved_nodes, frame, visualisation_area[1]);
+	view.map = MapGeometry::new(visualisation_area[0], view.map_range(board.total_memory()));
+	render_visualisation(board, memory_nodes, reserved_nodes, view, frame,
+			     visualisation_area[0]);
+}
+
+// one changed seg register, as shown before saving
+struct SegChange {
+	reg_name: String,
+	old_seg: u64,
+	new_seg: u64,
+	old_hw_start_addr: u64,
+	new_hw_start_addr: u64,
+}
+
+fn seg_changes(board: &soc::MPFS, old_hw_start_addrs: &[u64]) -> Vec<SegChange>
+{
+	return board.apertures()
+		.iter()
+		.zip(old_hw_start_addrs)
+		.filter(|(aperture, old)| return aperture.hardware_addr != **old)
+		.map(|(aperture, old)| {
+			// both were checked when they were set, so can be encoded
+			return SegChange {
+				reg_name: aperture.reg_name.clone(),
+				old_seg: soc::hw_start_addr_to_seg(*old, aperture.bus_addr)
+					.unwrap_or_default(),
+				new_seg: aperture.seg().unwrap_or_default(),
+				old_hw_start_addr: *old,
+				new_hw_start_addr: aperture.hardware_addr,
+			}
+		})
+		.collect()
+}
+
+fn format_seg_change(change: &SegChange, radix: Radix) -> Vec<String>
+{
+	return vec![
+		change.reg_name.clone(),
+		format!("{:#x}", change.old_seg),
+		format!("{:#x}", change.new_seg),
+		units::format_number(change.old_hw_start_addr, radix, 12),
+		units::format_number(change.new_hw_start_addr, radix, 12),
+	]
+}
+
+fn seg_change_table(changes: &[SegChange]) -> String
+{
+	let rows: Vec<Vec<String>> = changes.iter()
+		.map(|change| return format_seg_change(change, Radix::Hex))
+		.collect();
+
+	return plain::table(&["register", "old seg", "new seg", "old hw start", "new hw start"],
+			    &rows)
+}
+
+fn render_save_diff<B: tui::backend::Backend>
+(changes: &[SegChange], radix: Radix, frame:&mut Frame<B>, display_rect: Rect)
+{
+	let header_cells =
+		[
+			"Register Name", "Old Seg", "New Seg", "Old HW Start", "New HW Start",
+		]
+		.iter()
+		.map(|h|
+			return
+			Cell::from(*h)
+			.style(Style::default())
+		);
+
+	let header = Row::new(header_cells).height(1).bottom_margin(1);
+	let rows = changes.iter().map(|change| {
+		return Row::new(format_seg_change(change, radix)).height(1)
+	});
+
+	// drawn over the middle of the display, it only sticks around until
+	// the save is answered
+	let height = (changes.len() as u16 + 4).min(display_rect.height);
+	let width = (display_rect.width / 2).max(70).min(display_rect.width);
+	let area = Rect {
+		x: display_rect.x + (display_rect.width - width) / 2,
+		y: display_rect.y + (display_rect.height - height) / 2,
+		width,
+		height,
+	};
+
+	let table =
+		Table::new(rows)
+		.header(header)
+		.block(
+			Block::default()
+			.borders(Borders::ALL)
+			.border_style(theme::border())
+			.title("Changes to be saved")
+		)
+		.style(Style::default())
+		.widths(&[
+			Constraint::Percentage(20),
+			Constraint::Percentage(16),
+			Constraint::Percentage(16),
+			Constraint::Percentage(24),
+			Constraint::Percentage(24),
+		]);
+
+	frame.render_widget(tui::widgets::Clear, area);
+	frame.render_widget(table, area);
+}
+
+// how far PgUp/PgDn move the dry run's popup
+const DRY_RUN_PAGE: u16 = 20;
+
+fn render_dry_run<B: tui::backend::Backend>
+(text: &str, scroll: u16, frame:&mut Frame<B>, display_rect: Rect)
+{
+	// a config is usually longer than the display, so this one scrolls
+	let width = (display_rect.width * 3 / 4).max(100).min(display_rect.width);
+	let area = Rect {
+		x: display_rect.x + (display_rect.width - width) / 2,
+		y: display_rect.y,
+		width,
+		height: display_rect.height,
+	};
+
+	let dry_run =
+		Paragraph::new(text.to_string())
+		.block(
+			Block::default()
+			.borders(Borders::ALL)
+			.border_style(theme::border())
+			.title("Dry run, nothing was saved (Up/Down & PgUp/PgDn to scroll, \
+				Esc to close)")
+		)
+		.style(Style::default())
+		.scroll((scroll, 0));
+
+	frame.render_widget(tui::widgets::Clear, area);
+	frame.render_widget(dry_run, area);
+}
+
+// below this the tables get cut off & the prompt loses the line that the
+// input goes on, so nothing useful can be shown
+const MIN_WIDTH: u16 = 160;
+const MIN_HEIGHT: u16 = 40;
+
+fn render_too_small<B: tui::backend::Backend>
+(frame:&mut Frame<B>, command_text: &str, input: &str)
+{
+	// the prompt is still shown, so that a question like whether to save
+	// before quitting doesn't get answered blind
+	let size = frame.size();
+	let text = format!("The terminal is {}x{}, please resize it to at least {}x{}.\n\
+			    Esc quits.\n{}\n{}", size.width, size.height, MIN_WIDTH, MIN_HEIGHT,
+			   command_text, input);
+
+	let message =
+		Paragraph::new(text)
+		.wrap(tui::widgets::Wrap { trim: true })
+		.style(Style::default());
+
+	frame.render_widget(message, size);
+}
+
+const HELP_KEYS: [(&str, &str); 22] = [
+	("?", "show or hide this help, when nothing has been typed"),
+	("Esc", "quit, asking first if there are unsaved changes"),
+	("Ctrl+S", "save"),
+	("Ctrl+R", "reload the config from disk"),
+	("Ctrl+Y", "copy the seg-reg-config line to the clipboard"),
+	("Ctrl+D", "show or hide disabled dt nodes"),
+	("Ctrl+B", "switch between hex, decimal & units"),
+	("Ctrl+A", "allow or forbid aliases"),
+	("Ctrl+E", "list everything wrong with the configuration"),
+	("Ctrl+G", "show how the selected aperture's seg value gives its hardware address"),
+	("Ctrl+Up/Down", "zoom the memory map in & out"),
+	("PgUp/PgDn", "pan the memory map, or scroll a table"),
+	("Home", "show the whole memory map, or the top of a table, again"),
+	("Ctrl+T", "switch PgUp/PgDn/Home between the map & the tables"),
+	("Up/Down", "go back through the commands already entered"),
+	("Left/Right", "move the cursor along the prompt"),
+	("Ctrl+U", "clear the prompt"),
+	("/", "only show the table rows with what's typed after it in, Esc to show them all"),
+	("Tab", "complete a command name"),
+	("+/-", "move the selected aperture up or down 16 MiB, 256 MiB with Shift or Alt"),
+	("Click", "pick an aperture from the table to edit"),
+	("Drag", "move an aperture around the memory map"),
+];
+
+const HELP_COLUMNS: [(&str, &str); 8] = [
+	("Bus Address", "where the aperture sits in the address map that the harts & \
+			 bus masters see"),
+	("Register Value", "the seg register, which moves the aperture's window onto \
+			    DDR down from its bus address"),
+	("Aperture HW Start", "the DDR (hardware) address that the aperture's bus address \
+			       ends up at, with 0x0 being the start of DDR"),
+	("Aperture HW End", "where the aperture stops in DDR, \"(clamped)\" when it would \
+			     run past the end of memory"),
+	("Aperature Size", "how much of the bus address map the aperture covers"),
+	("Perf. Class", "cached, non-cached or write-combining, from which aperture it is"),
+	("Alias Of", "another aperture that reaches the same DDR"),
+	("HW Start/End", "in the other tables, where a node or region ends up in DDR"),
+];
+
+// everything the main screen is drawn from, so that drawing it depends on
+// nothing else & can go to a TestBackend as easily as to the terminal
+struct Screen<'a> {
+	board: &'a soc::MPFS,
+	memory_nodes: &'a Option<Vec<MemoryNode>>,
+	reserved_nodes: &'a [ReservedMemoryNode],
+	named_regions: &'a [NamedRegion],
+	problems: &'a [validate::Problem],
+	command_text: &'a str,
+	input: &'a lineedit::LineEditor,
+	modified: bool,
+}
+
+fn render_screen<B: tui::backend::Backend>
+(screen: &Screen, view: &mut ViewOptions, frame: &mut Frame<B>) -> Option<Rect>
+{
+	// the area that popups go over, or None if the terminal is too small for
+	// anything but saying so
+	if frame.size().width < MIN_WIDTH || frame.size().height < MIN_HEIGHT {
+		render_too_small(frame, screen.command_text, &screen.input.line);
+		return None
+	}
+
+	let entire_window =
+		Layout::default()
+		.direction(Direction::Vertical)
+		.constraints(
+		[
+			Constraint::Min(0),
+			Constraint::Length(1),
+			Constraint::Percentage(10),
+		]
+		.as_ref(),
+		)
+		.split(frame.size());
+
+	render_display(screen.board, screen.memory_nodes.clone(), screen.reserved_nodes,
+		       screen.named_regions, view, frame, entire_window[0]);
+	render_status(screen.problems, frame, entire_window[1]);
+
+	let prompt = commands::help_for(&screen.input.line)
+		.unwrap_or(screen.command_text.to_string());
+	let input_row = prompt.lines().count().max(1) as u16;
+	let txt = format!("{}\n{}", prompt, screen.input.line);
+
+	let mut title = "Press ? for help, Esc to quit, Ctrl+S or \"save\" to save, \
+			 Ctrl+R to reload the config, \
+			 Ctrl+D to show/hide disabled nodes, \
+			 Ctrl+B to switch between hex, decimal & units, \
+			 Ctrl+A to allow/forbid aliases, \
+			 Ctrl+Up/Down & PgUp/PgDn to zoom & pan the map, \
+			 \"<command>?\" for help on a command."
+			 .to_string();
+	if screen.modified {
+		title = format!("[modified] {}", title);
+	}
+
+	let graph =
+		Paragraph::new(txt)
+		.block(
+			Block::default()
+			.title(title)
+			.borders(Borders::ALL)
+			.border_style(theme::border()))
+		.style(Style::default());
+
+	let bottom =
+		Layout::default()
+		.direction(Direction::Horizontal)
 		.constraints(
 		[
 			Constraint::Percentage(60),
@@ -459,193 +1984,3183 @@ fn render_display<B: tui::backend::Backend>
 		]
 		.as_ref(),
 		)
-		.split(display_area[1]);
+		.split(entire_window[2]);
 
-	let (data, config_is_valid) = format_table_data(board);
+	frame.render_widget(graph, bottom[0]);
 
-	render_seg_regs(board, config_is_valid, frame, chunks[1]);
+	// inside the border, on the line after the prompt
+	let cursor_x = bottom[0].x + 1 + screen.input.cursor() as u16;
+	let cursor_y = bottom[0].y + 1 + input_row;
+	if cursor_x < bottom[0].right() - 1 && cursor_y < bottom[0].bottom() - 1 {
+		frame.set_cursor(cursor_x, cursor_y);
+	}
+
+	// newest at the bottom, as many as fit inside the borders
+	let visible = bottom[1].height.saturating_sub(2) as usize;
+	let log =
+		Paragraph::new(messages::recent(visible).join("\n"))
+		.block(
+			Block::default()
+			.title("Messages")
+			.borders(Borders::ALL)
+			.border_style(theme::border()))
+		.style(Style::default());
 
-	render_seg_table(data, frame, table_area[0]);
-	render_dt_node_table(board, memory_nodes.clone(), frame, table_area[1]);
+	frame.render_widget(log, bottom[1]);
 
-	render_visualisation(board, memory_nodes, frame, display_area[0]);
+	return Some(entire_window[0])
 }
 
-fn setup_segs_from_config(board: &mut soc::MPFS, input_file: String)
--> Result<(), Box<dyn std::error::Error>>
+fn render_help<B: tui::backend::Backend>
+(frame:&mut Frame<B>, display_rect: Rect)
 {
-	let contents = fs::read_to_string(input_file);
-	if let Err(error) = &contents {
-		return Ok(())
+	let mut lines: Vec<String> = vec!["Keys".to_string()];
+	for (key, description) in HELP_KEYS {
+		lines.push(format!("  {:<14} {}", key, description));
 	}
 
-	let d: Value = serde_yaml::from_str(&contents.unwrap())?;
-	let seg_config = d["seg-reg-config"].clone();
+	lines.push(String::new());
+	lines.push("Commands, \"<command>?\" shows an example".to_string());
+	for command in commands::COMMANDS.iter() {
+		lines.push(format!("  {:<40} {}", command.syntax, command.description));
+	}
 
-	let apertures = board.memory_apertures.iter_mut();
-	for aperture in apertures {
-		let seg_name = aperture.reg_name.as_str();
-		let seg_string = seg_config[seg_name].clone();
-		if seg_string.as_str().is_some() {
-			let seg_string_raw = seg_string.as_str().unwrap();
-			let seg_string_trimmed = seg_string_raw.trim_start_matches("0x");
-			let seg = u64::from_str_radix(seg_string_trimmed, 16)?;
-			aperture.set_hw_start_addr_from_seg(
-				board.total_system_memory,
-				seg
-			)?;
-		}
+	lines.push(String::new());
+	lines.push("Aperture table columns".to_string());
+	for (column, description) in HELP_COLUMNS {
+		lines.push(format!("  {:<18} {}", column, description));
 	}
-	return Ok(());
 
+	// drawn over the middle of the display like the save diff, until it's
+	// dismissed
+	let height = (lines.len() as u16 + 2).min(display_rect.height);
+	let width = (display_rect.width * 3 / 4).max(100).min(display_rect.width);
+	let area = Rect {
+		x: display_rect.x + (display_rect.width - width) / 2,
+		y: display_rect.y + (display_rect.height - height) / 2,
+		width,
+		height,
+	};
+
+	let help =
+		Paragraph::new(lines.join("\n"))
+		.block(
+			Block::default()
+			.borders(Borders::ALL)
+			.border_style(theme::border())
+			.title("Help (? or Esc to close)")
+		)
+		.style(Style::default());
+
+	frame.render_widget(tui::widgets::Clear, area);
+	frame.render_widget(help, area);
 }
 
-use std::io::Write;
-fn save_segs_to_config(board: &mut soc::MPFS, input_file: String, output_file: String)
--> Result<(), Box<dyn std::error::Error>>
+fn render_status<B: tui::backend::Backend>
+(problems: &[validate::Problem], frame:&mut Frame<B>, display_rect: Rect)
 {
-	let contents = fs::read_to_string(input_file);
-	if let Err(error) = contents {
-		return Err(Box::new(error))
+	// always there, so that a change that breaks something is noticed
+	// straight away
+	let mut style = Style::default();
+	let mut status = "Checks: no problems".to_string();
+	if !problems.is_empty() {
+		style = style.fg(theme::warning());
+		status = format!("Checks: {} (Ctrl+E for details)", validate::summary(problems));
 	}
 
-	let mut d: Value = serde_yaml::from_str(&contents.unwrap())?;
+	frame.render_widget(Paragraph::new(Span::styled(status, style)), display_rect);
+}
 
-	for memory_aperture in &board.memory_apertures {
-		let seg_value =
-			format!("{:#x?}",
-				 soc::hw_start_addr_to_seg(memory_aperture.hardware_addr,
-							   memory_aperture.bus_addr)
-				);
-		let seg_as_yaml = Value::String(seg_value);
-		d["seg-reg-config"][&memory_aperture.reg_name[..]] = seg_as_yaml;
+fn render_problems<B: tui::backend::Backend>
+(board: &soc::MPFS, problems: &[validate::Problem], frame:&mut Frame<B>, display_rect: Rect)
+{
+	let mut lines: Vec<String> = problems.iter()
+		.map(|problem| return format!("  {:<24} {:<16} {}", problem.kind.name(),
+					      problem.subject, problem.detail))
+		.collect();
+	if lines.is_empty() {
+		lines.push("  Nothing wrong with the configuration.".to_string());
 	}
 
-	let output = serde_yaml::to_string(&d);
-	let mut file = fs::File::create(output_file)?;
-	file.write_all(output.unwrap()[..].as_bytes())?;
+	let fixes = validate::fixes(problems);
+	if !fixes.is_empty() {
+		lines.push(String::new());
+		lines.push("  Suggested fixes, apply with \"fix <n>\" or \"fix all\":".to_string());
+	}
+	for (number, fix) in fixes.iter().enumerate() {
+		lines.push(format!("  {:>3}: {}", number + 1, fix.describe(board)));
+	}
 
-	return Ok(())
+	// drawn over the middle of the display like the help
+	let height = (lines.len() as u16 + 2).min(display_rect.height);
+	let width = (display_rect.width * 3 / 4).max(100).min(display_rect.width);
+	let area = Rect {
+		x: display_rect.x + (display_rect.width - width) / 2,
+		y: display_rect.y + (display_rect.height - height) / 2,
+		width,
+		height,
+	};
+
+	let list =
+		Paragraph::new(lines.join("\n"))
+		.block(
+			Block::default()
+			.borders(Borders::ALL)
+			.border_style(theme::border())
+			.title(format!("Problems: {} (Ctrl+E or Esc to close)",
+				       validate::summary(problems)))
+		)
+		.style(Style::default());
+
+	frame.render_widget(tui::widgets::Clear, area);
+	frame.render_widget(list, area);
 }
 
-fn handle_messages(messages: &mut Vec<String>) -> Option<String>
+fn seg_detail_lines(aperture: &soc::MemoryAperture) -> Vec<String>
 {
-	if messages.is_empty(){
-		return None;
+	// the seg value taken apart, & the sums the bootloader does with it, for
+	// anyone trying to match it up with a value from somewhere else
+	let bus_addr = aperture.bus_addr;
+	let seg = match aperture.seg() {
+		Ok(seg) => seg,
+		Err(error) => return vec![format!("  {} has no seg value: {}", aperture.reg_name,
+						  error)],
+	};
+	let enabled = seg & 0x4000 != 0;
+	let offset = seg & 0x3FFF;
+
+	let mut lines = vec![
+		format!("  {:<20} {:#x}", "Bus address", bus_addr),
+		format!("  {:<20} {:#x} (0b{:b}_{:014b})", "Seg value", seg, seg >> 14, offset),
+		format!("    {:<18} {:<8} {}", "bit 14", seg >> 14, match enabled {
+			true => "set, so the offset field is used",
+			false => "clear, so the aperture isn't moved",
+		}),
+		format!("    {:<18} {:<8} offset field", "bits 13:0", format!("{:#x}", offset)),
+		format!("  {:<20} {:#x}", "Hardware address", aperture.hardware_addr),
+		format!("  {:<20} {}", "Access", aperture.access),
+		String::new(),
+		"  Bus to hardware address".to_string(),
+	];
+
+	if enabled {
+		let steps = 0x4000 - offset;
+		lines.push(format!("    0x4000 - {:#x} = {:#x} steps of 16 MiB = {:#x}", offset,
+				   steps, steps << 24));
+		lines.push(format!("    {:#x} - {:#x} = {:#x}", bus_addr, steps << 24,
+				   aperture.hardware_addr));
+	} else {
+		lines.push(format!("    the hardware address is the bus address, {:#x}", bus_addr));
 	}
 
-	let message = messages.pop();
-	messages.clear();
-	message.as_ref()?;
+	// 64 bit, as anything past 0x7f steps overflows an int once shifted
+	lines.push(String::new());
+	lines.push("  In C".to_string());
+	lines.push("    hw = (seg & 0x4000) ? bus - ((0x4000ULL - (seg & 0x3fff)) << 24) : bus;"
+		   .to_string());
+	lines.push(format!("    (({:#x} & 0x4000) ? {:#x} - ((0x4000ULL - ({:#x} & 0x3fff)) << 24) \
+			    : {:#x}) == {:#x}", seg, bus_addr, seg, bus_addr, aperture.hardware_addr));
 
-	let input = message.as_ref().unwrap();
-	return Some(input.to_string());
+	return lines
 }
 
-/// PolarFire SoC memory aperture configurator
-#[derive(Parser, Debug)]
-#[clap(author, version, about, long_about = None)]
-struct Args {
-	/// input yaml config file
-	#[clap(short, long, default_value = "config.yaml")]
-	config: String,
+fn render_seg_detail<B: tui::backend::Backend>
+(aperture: &soc::MemoryAperture, frame:&mut Frame<B>, display_rect: Rect)
+{
+	let lines = seg_detail_lines(aperture);
 
-	/// input dtb
-	#[clap(short, long)]
-	dtb: Option<String>,
+	// drawn over the middle of the display like the help
+	let height = (lines.len() as u16 + 2).min(display_rect.height);
+	let width = (display_rect.width * 3 / 4).max(100).min(display_rect.width);
+	let area = Rect {
+		x: display_rect.x + (display_rect.width - width) / 2,
+		y: display_rect.y + (display_rect.height - height) / 2,
+		width,
+		height,
+	};
 
-	/// edit the config in place rather tha use the default output of "generated.yaml"
-	#[clap(short, long)]
-	in_place: bool,
+	let detail =
+		Paragraph::new(lines.join("\n"))
+		.block(
+			Block::default()
+			.borders(Borders::ALL)
+			.border_style(theme::border())
+			.title(format!("{}: {} (Ctrl+G or Esc to close)", aperture.reg_name,
+				       aperture.description()))
+		)
+		.style(Style::default());
+
+	frame.render_widget(tui::widgets::Clear, area);
+	frame.render_widget(detail, area);
 }
-fn main() -> Result<(),Box<dyn std::error::Error>> {
-	let args = Args::parse();
-	let mut next_state = states::State::default();
-	let mut board = soc::MPFS::default();
-	let stdout = io::stdout();
-	let backend = CrosstermBackend::new(stdout);
-	let mut terminal = Terminal::new(backend)?;
-	let mut input: String = String::new();
-	let mut messages: Vec<String> = Vec::new();
-	let input_file = args.config;
-	let mut output_file = "generated.yaml".to_string();
-	let mut memory_nodes: Option<Vec<MemoryNode>> = None;
-	if args.in_place {
-		output_file = input_file.clone();
+
+fn apply_seg_values(board: &mut soc::MPFS, segs: &[(String, u64)])
+-> Result<Vec<String>, Box<dyn std::error::Error>>
+{
+	let mut warnings: Vec<String> = Vec::new();
+
+	let banks = board.banks();
+	for (seg_name, seg) in segs {
+		let aperture = board.memory_apertures
+			.iter_mut()
+			.find(|aperture| return aperture.reg_name == *seg_name);
+		if aperture.is_none() {
+			continue;
+		}
+
+		let aperture = aperture.unwrap();
+		if soc::seg_is_treated_as_zero(*seg) {
+			warnings.push(format!(
+				"{} is {:#x}, which doesn't have bit 14 set so the \
+				 bootloader treats it as 0x0 (hardware address {:#x}).",
+				seg_name, seg, aperture.bus_addr
+			));
+		}
+		aperture.set_hw_start_addr_from_seg(
+			&banks,
+			*seg
+		).map_err(|error| return format!("{}: {}", seg_name, error))?;
+		debug!("{} = {:#x} -> hardware address {:#x}", seg_name, seg,
+		       aperture.hardware_addr);
 	}
 
-	if let Some(dtb_file) = args.dtb {
-		memory_nodes = dt::dtb_get_memory_nodes(dtb_file)?;
+	return Ok(warnings)
+}
+
+fn setup_segs_from_mss_config(board: &mut soc::MPFS, mss_config_file: &Path)
+-> Result<Vec<String>, Box<dyn std::error::Error>>
+{
+	info!("reading mss config {}", mss_config_file.display());
+	let contents = fs::read_to_string(mss_config_file)?;
+	let mss_config = libero::parse_mss_config(&contents)
+		.map_err(|error| return format!("{}: {}", mss_config_file.display(), error))?;
+
+	if let Some(total_memory) = mss_config.total_memory {
+		board.total_system_memory = total_memory;
 	}
 
-	setup_segs_from_config(&mut board, input_file.clone())?;
+	return apply_seg_values(board, &mss_config.segs)
+}
 
-	terminal.clear()?;
-	enable_raw_mode()?;
-	terminal.clear()?;
+fn setup_segs_from_dump(board: &mut soc::MPFS, dump_file: &Path)
+-> Result<Vec<String>, Box<dyn std::error::Error>>
+{
+	info!("reading register dump {}", dump_file.display());
+	let contents = fs::read_to_string(dump_file)?;
+	let reg_names: Vec<String> = board.apertures()
+		.iter()
+		.map(|aperture| return aperture.reg_name.clone())
+		.collect();
+	let segs = regdump::parse_register_dump(&contents, &reg_names)
+		.map_err(|error| return format!("{}: {}", dump_file.display(), error))?;
 
-	loop {
-		let command_text = next_state.command_text.clone();
-		terminal.draw(|frame| {
-			let entire_window =
-				Layout::default()
-				.direction(Direction::Vertical)
-				.constraints(
-				[
-					Constraint::Percentage(90),
-					Constraint::Percentage(10),
-				]
-				.as_ref(),
-				)
-				.split(frame.size());
-
-			render_display(&mut board, memory_nodes.clone(), frame, entire_window[0]);
-
-			let txt = format!("{}\n{}", command_text, input);
-
-			let graph =
-				Paragraph::new(txt)
-				.block(
-					Block::default()
-					.title("Press Esc to quit, enter \"save\" to save.")
-					.borders(Borders::ALL))
-				.style(Style::default());
-
-			frame.render_widget(graph, entire_window[1]);
-		})?;
-
-		if event::poll(Duration::from_millis(30))? {
-			if let Event::Key(key) = event::read()? {
-				match key.code {
-					KeyCode::Char(c) => {
-						input.push(c);
-					}
-					KeyCode::Backspace => {
-						input.pop();
-					}
-					KeyCode::Esc => {
-						terminal.clear()?;
-						if disable_raw_mode().is_err() {
-							panic!("Failed to clean up terminal");
-						}
-						return Ok(());
-					}
-					KeyCode::Enter => {
-						messages.push(input.drain(..).collect());
-					}
-					_ => {}
-				}
-			}
-		}
+	return apply_seg_values(board, &segs)
+}
 
-		let input = handle_messages(&mut messages);
-		if let Some(command) = input.clone() {
-			if command.contains("save") {
-				save_segs_to_config(&mut board, input_file.clone(), output_file.clone())?;
+fn setup_segs_from_str(board: &mut soc::MPFS, contents: &str, input_format: ConfigFormat)
+-> Result<Vec<String>, Box<dyn std::error::Error>>
+{
+	// returns warnings about anything that loaded, but not as written
+	let mut d: Value = config::parse_config(contents, input_format)?;
+	let migrations = config::migrate_config(&mut d)?;
+	theme::apply_config(&d["theme"])?;
+	let seg_config = d["seg-reg-config"].clone();
+	let mut segs: Vec<(String, u64)> = Vec::new();
+
+	for aperture in board.apertures() {
+		let seg_name = aperture.reg_name.as_str();
+		let seg = config::parse_seg_value(&seg_config[seg_name])
+			.map_err(|error| return format!("{}: {}", seg_name, error))?;
+		if let Some(seg) = seg {
+			segs.push((seg_name.to_string(), seg));
+		}
+	}
+
+	let mut warnings: Vec<String> = migrations.iter()
+		.map(|change| return format!("older config, {}", change))
+		.collect();
+
+	// the banks say where memory is, which the seg values are checked
+	// against, so a seg that puts an aperture's start in a hole is refused
+	board.memory_banks = config::parse_memory_banks(&d["memory-banks"])?;
+	if let Some(top) = board.memory_banks.iter().map(|bank| return bank.end()).max() {
+		board.total_system_memory = top;
+	}
+	warnings.extend(apply_seg_values(board, &segs)?);
+
+	board.contexts = contexts::parse_contexts(&d["contexts"])?;
+	warnings.extend(contexts::check_contexts(&board.contexts));
+	board.bus_masters = config::parse_bus_masters(&d["bus-masters"])?;
+	warnings.extend(board.check_bus_masters());
+
+	let reg_names: Vec<String> = board.apertures().iter()
+		.map(|aperture| return aperture.reg_name.clone())
+		.collect();
+	let reg_names: Vec<&str> = reg_names.iter()
+		.map(|reg_name| return reg_name.as_str())
+		.collect();
+	let accesses = config::parse_aperture_access(&d["aperture-access"], &reg_names)?;
+	for aperture in board.memory_apertures.iter_mut() {
+		aperture.access = accesses.iter()
+			.find(|(reg_name, _)| return *reg_name == aperture.reg_name)
+			.map(|(_, access)| return *access)
+			.unwrap_or(soc::Access::default_for(aperture.performance_class));
+	}
+
+	if let Some(node_apertures) = d["dt-node-apertures"].as_mapping() {
+		board.node_apertures = node_apertures.iter()
+			.filter_map(|(label, reg_name)|
+				return Some((label.as_str()?.to_string(),
+					     reg_name.as_str()?.to_string())))
+			.collect();
+	}
+	return Ok(warnings);
+
+}
+
+fn check_config_strict(board: &soc::MPFS, input_file: &Path, contents: &str,
+		       input_format: ConfigFormat)
+-> Result<(), String>
+{
+	let reg_names: Vec<&str> = board.apertures().iter()
+		.map(|aperture| return aperture.reg_name.as_str())
+		.collect();
+	return config::check_strict(&input_file.display().to_string(), contents, input_format,
+				    &reg_names)
+}
+
+fn load_dtb(board: &mut soc::MPFS, dtb_file: &Path, args: &Args)
+-> Result<dt::DtbNodes, String>
+{
+	// "dtb" reads the nodes the same way as -d does at startup, with the
+	// size of memory following them unless it came from somewhere that wins
+	let failed = |error: Box<dyn std::error::Error>| {
+		return format!("{}: {}", dtb_file.display(), error)
+	};
+	let mut loaded = dt::dtb_get_nodes(dtb_file).map_err(failed)?;
+	let nodes = &mut loaded.memory_nodes;
+
+	dt::pin_node_apertures(nodes, &board.node_apertures);
+	let total_memory = dt::memory_nodes_get_total_memory(nodes, board.apertures())
+		.filter(|_| return board.memory_banks.is_empty() && args.memory.is_none());
+	if let Some(total_memory) = total_memory {
+		board.total_system_memory = total_memory;
+	}
+	for node in dt::uncached_nodes(nodes, board.apertures()) {
+		messages::warn(format!("memory {} is not cached.", node));
+	}
+	for region in dt::unreachable_regions(nodes, board.apertures()) {
+		messages::warn(format!("no aperture reaches {}.", region));
+	}
+
+	return Ok(loaded)
+}
+
+fn load_board(board: &soc::MPFS, config_file: &Path, args: &Args)
+-> Result<(soc::MPFS, Vec<String>), String>
+{
+	// "load" & Ctrl+R start again from the defaults, keeping only what
+	// wasn't in the config to begin with
+	let mut loaded_board = soc::MPFS {
+		total_system_memory: board.total_memory(),
+		forbid_aliases: board.forbid_aliases,
+		any_memory_size: board.any_memory_size,
+		..Default::default()
+	};
+	if !config_file.exists() {
+		return Err(format!("{} does not exist.", config_file.display()))
+	}
+
+	let warnings = setup_segs_from_config(&mut loaded_board, config_file, args.format,
+					      args.strict)
+		.map_err(|error| return format!("Failed to load {}: {}", config_file.display(),
+						error))?;
+
+	return Ok((loaded_board, warnings))
+}
+
+fn setup_segs_from_config(board: &mut soc::MPFS, input_file: impl AsRef<Path>,
+			  format: Option<ConfigFormat>, strict: bool)
+-> Result<Vec<String>, Box<dyn std::error::Error>>
+{
+	let input_file = input_file.as_ref();
+	let input_format = config::detect_format(input_file, format);
+	info!("reading config {} as {:?}", input_file.display(), input_format);
+	let contents = stdio::read_to_string(input_file);
+	if let Err(error) = &contents {
+		if strict {
+			return Err(format!("{}: {}", input_file.display(), error).into())
+		}
+		info!("not using {}: {}", input_file.display(), error);
+		return Ok(Vec::new())
+	}
+
+	let contents = contents.unwrap();
+	if strict {
+		check_config_strict(board, input_file, &contents, input_format)?;
+	}
+
+	return setup_segs_from_str(board, &contents, input_format)
+}
+
+fn generate_config_from_str(board: &mut soc::MPFS, contents: &str, input_format: ConfigFormat,
+			    output_format: ConfigFormat)
+-> Result<String, Box<dyn std::error::Error>>
+{
+	let mut d: Value = config::parse_config(contents, input_format)?;
+	let migrated = !config::migrate_config(&mut d)?.is_empty();
+	d["schema-version"] = Value::from(config::SCHEMA_VERSION);
+	let mut segs: Vec<(String, String)> = Vec::new();
+
+	for memory_aperture in board.apertures() {
+		let seg = memory_aperture.seg()
+			.map_err(|error| return format!("{}: {}", memory_aperture.reg_name, error))?;
+		let seg_value = format!("{:#x?}", seg);
+		let reg_name = &memory_aperture.reg_name[..];
+
+		// values that haven't changed are left exactly as they were written,
+		// however odd the formatting, so a load & save is a no-op
+		let existing = config::parse_seg_value(&d["seg-reg-config"][reg_name]);
+		if let Ok(Some(existing)) = existing {
+			if existing == seg {
+				continue;
+			}
+		}
+
+		debug!("{}: hardware address {:#x} on bus address {:#x} -> seg {}",
+		       reg_name, memory_aperture.hardware_addr, memory_aperture.bus_addr,
+		       seg_value);
+		segs.push((memory_aperture.reg_name.clone(), seg_value.clone()));
+		let seg_as_yaml = Value::String(seg_value);
+		d["seg-reg-config"][reg_name] = seg_as_yaml;
+	}
+
+	let mut node_apertures: Vec<(String, String)> = Vec::new();
+	for (label, reg_name) in &board.node_apertures {
+		let node_aperture = &mut d["dt-node-apertures"][label.as_str()];
+		if node_aperture.as_str() == Some(reg_name.as_str()) {
+			continue;
+		}
+
+		node_apertures.push((label.clone(), reg_name.clone()));
+		*node_aperture = Value::String(reg_name.clone());
+	}
+
+	// going yaml to yaml the original text is edited directly so as not to
+	// lose any comments, otherwise the whole document is re-serialised. an
+	// older config has had its layout changed, so that has to be redone too
+	let mut output = None;
+	if input_format == ConfigFormat::Yaml && output_format == ConfigFormat::Yaml && !migrated {
+		output = config::update_seg_values(contents, &segs)
+			.and_then(|updated| return config::update_mapping_values(&updated,
+										  "dt-node-apertures",
+										  &node_apertures))
+			.and_then(|updated| return config::update_schema_version(&updated));
+	}
+	if output.is_none() {
+		output = Some(config::serialize_config(&d, output_format)?);
+	}
+
+	return Ok(output.unwrap())
+}
+
+fn generate_config(board: &mut soc::MPFS, input_file: impl AsRef<Path>,
+		   output_file: impl AsRef<Path>, format: Option<ConfigFormat>,
+		   output_format: Option<ConfigFormat>)
+-> Result<String, Box<dyn std::error::Error>>
+{
+	// "save --format" only changes what's written, not how the input is read
+	let input_format = config::detect_format(input_file.as_ref(), format);
+	let output_format = config::detect_format(output_file.as_ref(), output_format);
+	let contents = stdio::read_to_string(input_file);
+	if let Err(error) = contents {
+		return Err(Box::new(error))
+	}
+
+	return generate_config_from_str(board, &contents.unwrap(), input_format, output_format)
+}
+
+fn save_format(file: &Option<PathBuf>, format: Option<ConfigFormat>,
+	       last_format: Option<ConfigFormat>, args: &Args)
+-> Option<ConfigFormat>
+{
+	// a format given to "save" sticks, until a file is given without one,
+	// which goes back to working it out from the file's name
+	if format.is_some() {
+		return format
+	}
+	if file.is_some() {
+		return args.format
+	}
+
+	return last_format
+}
+
+fn autosave_session(board: &mut soc::MPFS, input_file: &Path, format: Option<ConfigFormat>)
+-> Result<(), Box<dyn std::error::Error>>
+{
+	// the same config as a save would write, so that restoring it is only
+	// a matter of loading it over the top of the one it came from
+	let input_format = config::detect_format(input_file, format);
+	let contents = stdio::read_to_string(input_file).unwrap_or_else(|_| return "{}".to_string());
+	let contents = generate_config_from_str(board, &contents, input_format, ConfigFormat::Yaml)?;
+	let session = autosave::Autosave {
+		config: input_file.display().to_string(),
+		total_system_memory: board.total_system_memory,
+		contents,
+	};
+
+	return Ok(autosave::write(input_file, &session)?)
+}
+
+fn restore_session(board: &mut soc::MPFS, input_file: &Path)
+-> Result<bool, Box<dyn std::error::Error>>
+{
+	// asked before the tui takes over the terminal, as a plain question
+	let session = match autosave::read(input_file)? {
+		Some(session) => session,
+		None => return Ok(false),
+	};
+
+	let question = format!("{} has unsaved changes from a session that didn't finish, \
+				restore them? (y/n)", session.config);
+	let answer = ask(&question)?;
+	if !answer.is_some_and(|answer| return answer.eq_ignore_ascii_case("y")) {
+		autosave::remove(input_file);
+		return Ok(false)
+	}
+
+	setup_segs_from_str(board, &session.contents, ConfigFormat::Yaml)?;
+	board.total_system_memory = session.total_system_memory;
+	info!("restored the session for {}", input_file.display());
+
+	return Ok(true)
+}
+
+// nobody is looking at the screen in batch mode, so the changes go to stderr
+// and the answer comes from stdin. running out of input counts as a no
+fn confirm_save(changes: &[SegChange], yes: bool) -> Result<bool, io::Error>
+{
+	if changes.is_empty() {
+		return Ok(true)
+	}
+
+	eprint!("{}", seg_change_table(changes));
+
+	if yes {
+		return Ok(true)
+	}
+
+	eprint!("Save these changes? (y/n) ");
+	let mut answer = String::new();
+	io::stdin().read_line(&mut answer)?;
+
+	return Ok(answer.trim().eq_ignore_ascii_case("y"))
+}
+
+fn apply_fixes(board: &mut soc::MPFS, nodes: &[MemoryNode], reserved_nodes: &[ReservedMemoryNode],
+	       choice: FixChoice)
+-> Result<String, String>
+{
+	// "fix <n>" & "fix all", picking from a list is left to the tui
+	let problems = validate::check(board, nodes, reserved_nodes);
+	match choice {
+		FixChoice::One(number) => {
+			let fixes = validate::fixes(&problems);
+			let fix = fixes.get(number - 1)
+				.ok_or(format!("There are only {} suggested fixes.", fixes.len()))?;
+			let description = fix.describe(board);
+			fix.apply(board).map_err(|error| return format!("Failed to {}: {}",
+									description, error))?;
+			return Ok(format!("Applied: {}.", description))
+		}
+		_ => {
+			let applied = validate::fix_all(board, nodes, reserved_nodes);
+			let remaining = validate::check(board, nodes, reserved_nodes);
+			if applied.is_empty() {
+				return Ok(format!("Nothing could be fixed, {}.",
+						  validate::summary(&remaining)))
+			}
+			let applied: Vec<String> = applied.iter()
+				.map(|fix| return fix.describe(board))
+				.collect();
+			return Ok(format!("Applied: {} ({} left).", applied.join(", "),
+					  validate::summary(&remaining)))
+		}
+	}
+}
+
+fn run_script(board: &mut soc::MPFS, script_file: &Path, input_file: PathBuf,
+	      output_file: PathBuf, named_regions: Vec<NamedRegion>, cells: dt::TreeCells,
+	      args: &Args)
+-> Result<(), Box<dyn std::error::Error>>
+{
+	let format = args.format;
+	// runs the same one line commands as the tui, one per line, stopping at
+	// the first one that fails. whatever the end result is gets saved
+	let script = stdio::read_to_string(script_file)?;
+
+	let mut input_file = input_file;
+	let mut output_file = output_file;
+	let mut output_format = format;
+	let mut saved = false;
+	let mut saved_segs = seg_snapshot(board).0;
+	let mut named_regions = named_regions;
+	// only ever the ones that placing the named regions adds
+	let mut reserved_nodes: Vec<ReservedMemoryNode> = Vec::new();
+
+	for (line_number, line) in script.lines().enumerate() {
+		let line = line.trim();
+		if line.is_empty() || line.starts_with('#') {
+			continue;
+		}
+
+		let context = format!("{}:{}", script_file.display(), line_number + 1);
+		debug!("{}: {}", context, line);
+		let command = commands::parse(line)
+			.ok_or(format!("{}: unknown command \"{}\"", context, line))?
+			.map_err(|usage| return format!("{}: {}", context, usage))?;
+
+		saved = false;
+		match command {
+			ParsedCommand::Save { dry_run, file, format: save_as } => {
+				output_format = save_format(&file, save_as, output_format, args);
+				output_file = file.unwrap_or(output_file);
+				if let Some(error) = board.alias_errors().first() {
+					return Err(format!("{}: {}", context, error).into())
+				}
+				if dry_run || args.dry_run {
+					print_dry_run(board, &input_file, &output_file, format,
+						      output_format)?;
+					// nothing was written, but nor should the end of the
+					// script go & write it
+					saved = true;
+					continue;
+				}
+				let output = generate_config(board, &input_file, &output_file,
+							     format, output_format)?;
+				if let Some(warning) = check_overwrite(&input_file, &output_file, &output) {
+					if !args.force {
+						return Err(format!("{}: {} Use --force to overwrite it.",
+								   context, warning).into())
+					}
+				}
+				if !confirm_save(&seg_changes(board, &saved_segs), args.yes)? {
+					return Err(format!("{}: not saved, use --yes to save without \
+							   asking.", context).into())
+				}
+				save_segs_to_config(board, &input_file, &output_file, format,
+						    output_format, !args.no_backup)?;
+				saved_segs = seg_snapshot(board).0;
+				saved = true;
+			}
+			ParsedCommand::Export { format, file: Some(file) } => {
+				fs::write(&file, export::export(board, &[], &[], format))
+					.map_err(|error| return format!("{}: {}: {}", context,
+									 file.display(), error))?;
+				info!("exported {:?} to {}", format, file.display());
+			}
+			ParsedCommand::Export { format, file: None } => {
+				print!("{}", export::export(board, &[], &[], format));
+			}
+			ParsedCommand::Node { .. } | ParsedCommand::RemoveNode(_) => {
+				// the nodes are only ever used to draw the tui, a script
+				// has nothing to put the edits into
+				return Err(format!("{}: dt nodes can only be edited in the tui.",
+						   context).into())
+			}
+			ParsedCommand::Dtb(_) => {
+				return Err(format!("{}: a dtb can only be loaded in the tui.",
+						   context).into())
+			}
+			// stdout may well be the config being written, so there's no
+			// room for the terminal's clipboard sequence
+			ParsedCommand::Copy => {
+				return Err(format!("{}: copy only works in the tui, or with \
+						   --no-tui.", context).into())
+			}
+			ParsedCommand::Place => {
+				let message = regions::place_regions(board, &mut named_regions,
+								     &mut reserved_nodes)
+					.map_err(|error| return format!("{}: {}", context, error))?;
+				eprintln!("{}", message);
+			}
+			ParsedCommand::Overlay(file) => {
+				let overlay = dt::memory_overlay(&[], &[], &[], &reserved_nodes,
+								 cells)
+					.map_err(|error| return format!("{}: {}", context, error))?
+					.ok_or(format!("{}: nothing to overlay, place the named \
+							regions first.", context))?;
+				dt::write_overlay(&overlay, &file)
+					.map_err(|error| return format!("{}: {}: {}", context,
+									 file.display(), error))?;
+				info!("wrote the overlay to {}", file.display());
+			}
+			ParsedCommand::Fix(FixChoice::Pick) => {
+				return Err(format!("{}: nobody is around to pick a fix, use fix <n> or \
+						   fix all.", context).into())
+			}
+			ParsedCommand::Fix(choice) => {
+				// without any dt nodes, so only the apertures get fixed
+				let message = apply_fixes(board, &[], &[], choice)
+					.map_err(|error| return format!("{}: {}", context, error))?;
+				eprintln!("{}", message);
+			}
+			ParsedCommand::Load(config_file) => {
+				*board = soc::MPFS {
+					total_system_memory: board.total_memory(),
+					forbid_aliases: board.forbid_aliases,
+				any_memory_size: board.any_memory_size,
+					..Default::default()
+				};
+				info!("reading config {}", config_file.display());
+				let contents = fs::read_to_string(&config_file)
+					.map_err(|error| return format!("{}: {}: {}", context,
+									 config_file.display(), error))?;
+				let config_format = config::detect_format(&config_file, format);
+				if args.strict {
+					check_config_strict(board, &config_file, &contents,
+							    config_format)
+						.map_err(|error| return format!("{}: {}", context,
+										 error))?;
+				}
+				let warnings = setup_segs_from_str(board, &contents, config_format)?;
+				for warning in warnings {
+					eprintln!("{}: warning: {}", context, warning);
+				}
+				if args.in_place {
+					output_file = config_file.clone();
+				}
+				input_file = config_file;
+				saved_segs = seg_snapshot(board).0;
+			}
+			command => {
+				// nobody is around to take up the offer of an aligned
+				// address either, so the error is all there is
+				let message = states::apply_command(board, command)
+					.map_err(|error| return format!("{}: {}", context, error))?;
+				eprintln!("{}", message);
+
+				// nobody is around to confirm a reflow, so leave it be
+				if let Some(reflow) = board.pending_reflow.take() {
+					eprintln!("{}: resizing caused {} overlap(s), not reflowing",
+						  context, reflow.len());
+				}
+			}
+		}
+	}
+
+	if !saved {
+		if let Some(error) = board.alias_errors().first() {
+			return Err(error.clone().into())
+		}
+		if args.dry_run {
+			return print_dry_run(board, &input_file, &output_file, format, output_format)
+		}
+		let output = generate_config(board, &input_file, &output_file, format,
+					     output_format)?;
+		if let Some(warning) = check_overwrite(&input_file, &output_file, &output) {
+			if !args.force {
+				return Err(format!("{} Use --force to overwrite it.", warning).into())
+			}
+		}
+		if !confirm_save(&seg_changes(board, &saved_segs), args.yes)? {
+			return Err("Not saved, use --yes to save without asking.".into())
+		}
+		save_segs_to_config(board, &input_file, &output_file, format, output_format,
+				    !args.no_backup)?;
+	}
+
+	return Ok(())
+}
+
+fn check_config(board: &mut soc::MPFS, nodes: &[MemoryNode], reserved_nodes: &[ReservedMemoryNode],
+		named_regions: &[NamedRegion], input_file: &Path, output_file: &Path, args: &Args)
+-> Result<(), Box<dyn std::error::Error>>
+{
+	let (fix, output_format) = match args.action {
+		Some(Action::Check { fix, output_format, .. }) => (fix, output_format),
+		_ => (false, validate::ReportFormat::Text),
+	};
+
+	// the same checks as the tui's status line, for ci & the like, failing
+	// if anything is left wrong. a json report is all that goes to stdout,
+	// so that it can be piped straight into something else
+	let text = output_format == validate::ReportFormat::Text;
+	if fix && args.dry_run && !text {
+		return Err("--dry-run would mix the config in with the json report, use \
+			    --output-format text".into())
+	}
+	let saved_segs = seg_snapshot(board).0;
+	let mut problems = validate::check(board, nodes, reserved_nodes);
+	problems.extend(validate::check_regions(board, named_regions));
+	for problem in problems.iter().filter(|_| return text) {
+		println!("{}: {}: {}", problem.kind.name(), problem.subject, problem.detail);
+		if let Some(suggested) = &problem.fix {
+			println!("    suggested fix: {}", suggested.describe(board));
+		}
+	}
+
+	let mut fixed: Vec<validate::Fix> = Vec::new();
+	if fix && !problems.is_empty() {
+		fixed = validate::fix_all(board, nodes, reserved_nodes);
+		for applied in &fixed {
+			if text {
+				println!("fixed: {}", applied.describe(board));
+			}
+			// the config only has the seg values, so a size has to be
+			// changed in the design as well to stick
+			if let validate::Fix::Resize { .. } = applied {
+				eprintln!("warning: sizes aren't saved in the config, change it in the \
+					   design too");
+			}
+		}
+
+		if args.dry_run {
+			print_dry_run(board, input_file, output_file, args.format, args.format)?;
+		} else {
+			let output = generate_config(board, input_file, output_file, args.format,
+						     args.format)?;
+			if let Some(warning) = check_overwrite(input_file, output_file, &output) {
+				if !args.force {
+					return Err(format!("{} Use --force to overwrite it.",
+							   warning).into())
+				}
+			}
+			if !confirm_save(&seg_changes(board, &saved_segs), args.yes)? {
+				return Err("Not saved, use --yes to save without asking.".into())
+			}
+			save_segs_to_config(board, input_file, output_file, args.format, args.format,
+					    !args.no_backup)?;
+		}
+	}
+
+	let mut remaining = validate::check(board, nodes, reserved_nodes);
+	remaining.extend(validate::check_regions(board, named_regions));
+	match output_format {
+		validate::ReportFormat::Text => println!("{}", validate::summary(&remaining)),
+		validate::ReportFormat::Json => {
+			let report = validate::report(board, &remaining, &fixed);
+			println!("{}", serde_json::to_string_pretty(&report)?);
+		}
+	}
+	if !remaining.is_empty() {
+		return Err(format!("problems found: {}", validate::summary(&remaining)).into())
+	}
+
+	return Ok(())
+}
+
+fn roundtrip_check(config_file: &Path, format: Option<ConfigFormat>, strict: bool)
+-> Result<(), Box<dyn std::error::Error>>
+{
+	// load -> save -> load should give the same board, and saving that
+	// again should give exactly the same text
+	let config_format = config::detect_format(config_file, format);
+	let contents = stdio::read_to_string(config_file)?;
+
+	let mut first_board = soc::MPFS::default();
+	if strict {
+		check_config_strict(&first_board, config_file, &contents, config_format)?;
+	}
+	let warnings = setup_segs_from_str(&mut first_board, &contents, config_format)?;
+	for warning in &warnings {
+		eprintln!("{}: warning: {}", config_file.display(), warning);
+	}
+	let first_save = generate_config_from_str(&mut first_board, &contents,
+						  config_format, config_format)?;
+
+	let mut second_board = soc::MPFS::default();
+	setup_segs_from_str(&mut second_board, &first_save, config_format)?;
+	let second_save = generate_config_from_str(&mut second_board, &first_save,
+						   config_format, config_format)?;
+
+	let mut failures: Vec<String> = Vec::new();
+	for (first, second) in first_board.apertures().iter()
+				.zip(second_board.apertures().iter()) {
+		if first.hardware_addr != second.hardware_addr {
+			failures.push(format!("{}: {:#x} became {:#x}", first.reg_name,
+					      first.hardware_addr, second.hardware_addr));
+		}
+	}
+	if first_save != second_save {
+		failures.push("saving a second time changed the output".to_string());
+	}
+
+	if !failures.is_empty() {
+		return Err(format!("{} does not round trip:\n{}", config_file.display(),
+				   failures.join("\n")).into())
+	}
+
+	println!("{}: round trip ok", config_file.display());
+	return Ok(())
+}
+
+fn lint_config(board: &soc::MPFS, input_file: &Path, format: Option<ConfigFormat>,
+	       total_memory: Option<u64>, fail_on: lint::Severity)
+-> Result<(), Box<dyn std::error::Error>>
+{
+	let name = input_file.display().to_string();
+	let contents = stdio::read_to_string(input_file)
+		.map_err(|error| return format!("{}: {}", name, error))?;
+	let findings = lint::lint(&contents, config::detect_format(input_file, format), board,
+				  total_memory)
+		.map_err(|error| return format!("{}: {}", name, error))?;
+
+	for finding in &findings {
+		match finding.line {
+			Some(line) => println!("{}:{}: {}: {}", name, line, finding.severity,
+					       finding.message),
+			None => println!("{}: {}: {}", name, finding.severity, finding.message),
+		}
+	}
+
+	let failing = findings.iter().filter(|finding| return finding.severity >= fail_on).count();
+	if failing != 0 {
+		return Err(format!("{} of {} findings are {} or worse", failing, findings.len(),
+				   fail_on).into())
+	}
+
+	return Ok(())
+}
+
+fn screen_text(screen: &Screen, width: u16, height: u16) -> io::Result<String>
+{
+	// colours & the cursor are left out, it's what ends up where that tends
+	// to get broken by a layout change
+	let mut terminal = Terminal::new(TestBackend::new(width, height))?;
+	terminal.draw(|frame| {
+		render_screen(screen, &mut ViewOptions::default(), frame);
+	})?;
+
+	let buffer = terminal.backend().buffer();
+	let mut text = String::new();
+	for y in 0..height {
+		let row: String = (0..width)
+			.map(|x| return buffer.get(x, y).symbol.as_str())
+			.collect();
+		text += row.trim_end();
+		text.push('\n');
+	}
+
+	return Ok(text)
+}
+
+fn snapshot(screen: &Screen, width: u16, height: u16, expected_file: Option<&Path>)
+-> Result<(), Box<dyn std::error::Error>>
+{
+	let text = screen_text(screen, width, height)?;
+	let expected_file = match expected_file {
+		Some(expected_file) => expected_file,
+		None => {
+			print!("{}", text);
+			return Ok(())
+		}
+	};
+
+	let expected = stdio::read_to_string(expected_file)
+		.map_err(|error| return format!("{}: {}", expected_file.display(), error))?;
+	let name = expected_file.display().to_string();
+	let changes = diff::unified(&expected, &text, &name, "snapshot");
+	if !changes.is_empty() {
+		print!("{}", changes);
+		return Err(format!("the screen has changed since {}", name).into())
+	}
+
+	println!("the screen matches {}", name);
+	return Ok(())
+}
+
+fn layout_seg_mismatches(board: &mut soc::MPFS, named_regions: &mut [NamedRegion],
+			 expected: &Value)
+-> Result<Vec<String>, String>
+{
+	// the named regions are placed the same way as "place" does, on top of
+	// wherever the config or plan left the apertures, & the seg values are
+	// whatever that makes them. a seg value that's treated as 0x0 is
+	// expected to come out as 0x0
+	if !named_regions.is_empty() {
+		regions::place_regions(board, named_regions, &mut Vec::new())?;
+	}
+
+	let mut mismatches: Vec<String> = Vec::new();
+	for aperture in board.apertures() {
+		let reg_name = &aperture.reg_name;
+		let seg = aperture.seg()
+			.map_err(|error| return format!("{}: {}", reg_name, error))?;
+		let expected_seg = config::parse_seg_value(&expected["seg-reg-config"][reg_name])
+			.map_err(|error| return format!("{}: {}", reg_name, error))?;
+		let expected_seg = match expected_seg {
+			Some(expected_seg) if soc::seg_is_treated_as_zero(expected_seg) => 0,
+			Some(expected_seg) => expected_seg,
+			None => {
+				mismatches.push(format!("{}: {:#x}, but there's no expected value",
+							reg_name, seg));
+				continue;
+			}
+		};
+
+		if seg != expected_seg {
+			mismatches.push(format!("{}: {:#x}, expected {:#x}", reg_name, seg,
+						expected_seg));
+		}
+	}
+
+	return Ok(mismatches)
+}
+
+fn verify_segs(board: &mut soc::MPFS, named_regions: &mut [NamedRegion], expected_file: &Path,
+	       format: Option<ConfigFormat>)
+-> Result<(), Box<dyn std::error::Error>>
+{
+	// for board repos to pin their memory map, so that a change to the tool,
+	// the design or the layout that moves an aperture gets noticed
+	let contents = stdio::read_to_string(expected_file)
+		.map_err(|error| return format!("{}: {}", expected_file.display(), error))?;
+	let expected = config::parse_config(&contents, config::detect_format(expected_file, format))
+		.map_err(|error| return format!("{}: {}", expected_file.display(), error))?;
+
+	let mismatches = layout_seg_mismatches(board, named_regions, &expected)
+		.map_err(|error| return format!("{}: {}", expected_file.display(), error))?;
+	for mismatch in &mismatches {
+		println!("{}", mismatch);
+	}
+	if !mismatches.is_empty() {
+		return Err(format!("{} seg values differ from {}", mismatches.len(),
+				   expected_file.display()).into())
+	}
+
+	println!("all {} seg values match {}", board.apertures().len(), expected_file.display());
+	return Ok(())
+}
+
+fn selftest() -> Result<(), Box<dyn std::error::Error>>
+{
+	// there are only 0x8000 seg values, so rather than sample them every one
+	// is tried against every aperture's bus address, & a few awkward ones
+	let mut bus_addrs: Vec<u64> = soc::MPFS::default().apertures().iter()
+		.map(|aperture| return aperture.bus_addr)
+		.collect();
+	bus_addrs.extend([0, soc::SEG_GRANULARITY, 0x4000 * soc::SEG_GRANULARITY,
+			  !(soc::SEG_GRANULARITY - 1)]);
+
+	let mut failures: Vec<String> = Vec::new();
+	let mut checked = 0;
+	for bus_addr in &bus_addrs {
+		for seg in 0..=soc::SEG_MAX {
+			if let Err(failure) = soc::verify_roundtrip(seg, *bus_addr) {
+				failures.push(failure);
+			}
+			checked += 1;
+		}
+
+		// an address in between granules mustn't be quietly rounded to
+		// one that a seg register can encode
+		for granule in 1..0x4000 {
+			let hw_start_addr = bus_addr.wrapping_sub(granule * soc::SEG_GRANULARITY)
+				.wrapping_add(granule % (soc::SEG_GRANULARITY - 1) + 1);
+			if let Ok(seg) = soc::hw_start_addr_to_seg(hw_start_addr, *bus_addr) {
+				failures.push(format!("{:#x} at bus address {:#x} isn't aligned, \
+						       but encodes as {:#x}", hw_start_addr,
+						       bus_addr, seg));
+			}
+			checked += 1;
+		}
+	}
+
+	for failure in failures.iter().take(10) {
+		eprintln!("{}", failure);
+	}
+	if !failures.is_empty() {
+		return Err(format!("{} of {} conversions failed", failures.len(), checked).into())
+	}
+
+	println!("{} conversions checked, all ok", checked);
+	return Ok(())
+}
+
+fn merge_configs(base_file: &Path, overlay_file: &Path, output_file: &Path,
+		 format: Option<ConfigFormat>)
+-> Result<(), Box<dyn std::error::Error>>
+{
+	stdio::check_one_stdin(&[("the base", Some(base_file)),
+				 ("the overlay", Some(overlay_file))])?;
+	let base = stdio::read_to_string(base_file)
+		.map_err(|error| return format!("{}: {}", base_file.display(), error))?;
+	let overlay = stdio::read_to_string(overlay_file)
+		.map_err(|error| return format!("{}: {}", overlay_file.display(), error))?;
+
+	let board = soc::MPFS::default();
+	let reg_names: Vec<&str> = board.apertures().iter()
+		.map(|aperture| return aperture.reg_name.as_str())
+		.collect();
+	let (merged, ignored) = config::merge(&base, config::detect_format(base_file, format),
+					      &overlay, config::detect_format(overlay_file, format),
+					      config::detect_format(output_file, format),
+					      &reg_names)?;
+	for key in ignored {
+		eprintln!("{}: warning: only seg values are merged, not \"{}\"",
+			  overlay_file.display(), key);
+	}
+
+	info!("merging {} into {} as {}", overlay_file.display(), base_file.display(),
+	      output_file.display());
+	stdio::write(output_file, merged.as_bytes())?;
+	return Ok(())
+}
+
+fn run_wizard(output_file: &Path, args: &Args) -> Result<(), Box<dyn std::error::Error>>
+{
+	// plain questions & answers on the terminal rather than the tui, which
+	// only makes sense once the apertures do
+	if !args.force && output_file.exists() {
+		return Err(format!("{} already exists, use --force to overwrite it",
+				   output_file.display()).into())
+	}
+
+	let mut board = soc::MPFS {
+		any_memory_size: args.any_memory_size,
+		..Default::default()
+	};
+	let mut state = states::State::setup();
+	while state.in_setup() {
+		let answer = ask(&state.command_text)?
+			.ok_or("the wizard wasn't finished, nothing was written")?;
+		state = state.handle(&mut board, states::Event::Input(answer));
+	}
+
+	let mut problems: Vec<String> = validate::check(&board, &[], &[]).iter()
+		.map(|problem| return format!("{}: {}: {}", problem.kind.name(), problem.subject,
+					      problem.detail))
+		.collect();
+	problems.extend(contexts::check_contexts(&board.contexts));
+	problems.extend(board.check_bus_masters());
+	if !problems.is_empty() {
+		for problem in &problems {
+			eprintln!("{}", problem);
+		}
+		return Err("the layout has problems, nothing was written".into())
+	}
+
+	let config = config::scaffold(&board, "the answers given to --wizard");
+	stdio::write(output_file, config.as_bytes())?;
+	println!("wrote {}", output_file.display());
+	for context in &board.contexts {
+		for (start, end) in context.ranges() {
+			println!("  {} on {} gets ddr {:#x}-{:#x}", context.name,
+				 context.harts.join(", "), start, end - 1);
+		}
+	}
+	for master in &board.bus_masters {
+		for (start, size) in &master.windows {
+			println!("  the {} buffers are the first {} of ddr, at bus address {:#x}",
+				 master.name, units::format_size(*size), start);
+			println!("  linux's devicetree needs a no-map reserved-memory node over \
+				  them");
+		}
+	}
+
+	return Ok(())
+}
+
+fn ask(question: &str) -> io::Result<Option<String>>
+{
+	// None once stdin runs out, as there's nobody left to answer
+	println!("{}", question);
+	let mut answer = String::new();
+	if io::stdin().read_line(&mut answer)? == 0 {
+		return Ok(None)
+	}
+
+	return Ok(Some(answer.trim().to_string()))
+}
+
+// what can be typed without the tui on top of the commands, standing in for
+// the keys & panes that it has
+const NO_TUI_WORDS: [(&str, &str); 6] = [
+	("show [<filter>...]", "list the apertures, dt memory nodes & reserved memory, only the \
+				apertures that are e.g. \"cached\" or \"64-bit\" if asked"),
+	("map", "draw the memory map with characters, as the map action does"),
+	("problems", "list everything wrong with the configuration & how to fix it"),
+	("<command>?", "explain a command, e.g. \"size?\""),
+	("help", "list the commands"),
+	("quit", "stop, asking first if there are unsaved changes"),
+];
+
+// the same as the map action's default
+const NO_TUI_MAP_ROWS: usize = 32;
+
+// everything that gets worked on without the tui, which is too much to be
+// passing around one at a time
+struct Session {
+	board: soc::MPFS,
+	memory_nodes: Option<Vec<MemoryNode>>,
+	reserved_nodes: Vec<ReservedMemoryNode>,
+	named_regions: Vec<NamedRegion>,
+	// of the dtb the nodes came from, for writing overlays of them
+	cells: dt::TreeCells,
+	input_file: PathBuf,
+	output_file: PathBuf,
+	// what the last "save --format" asked for, if anything
+	output_format: Option<ConfigFormat>,
+}
+
+impl Session {
+	fn nodes(&self) -> &[MemoryNode]
+	{
+		return self.memory_nodes.as_deref().unwrap_or_default()
+	}
+
+	fn problems(&mut self) -> Vec<validate::Problem>
+	{
+		if let Some(nodes) = self.memory_nodes.as_mut() {
+			dt::pin_node_apertures(nodes, &self.board.node_apertures);
+		}
+		regions::allocate_regions(&self.board, &mut self.named_regions);
+		let mut problems = validate::check(&self.board, self.nodes(), &self.reserved_nodes);
+		problems.extend(validate::check_regions(&self.board, &self.named_regions));
+
+		return problems
+	}
+}
+
+fn plain_overview(session: &mut Session, filters: &[&str]) -> Result<String, String>
+{
+	// the tables from the tui, in the same order
+	let unknown = filters.iter().find(|filter| {
+		return !soc::APERTURE_FILTERS.contains(&filter.to_ascii_lowercase().as_str())
+	});
+	if let Some(filter) = unknown {
+		return Err(format!("unknown filter {}, expected one of {}", filter,
+				   soc::APERTURE_FILTERS.join(", ")))
+	}
+
+	let problems = session.problems();
+	let board = &session.board;
+	let mut memory_size = units::format_size(board.total_memory());
+	if !board.memory_banks.is_empty() {
+		memory_size = format!("{} in {} banks up to {}",
+				      units::format_size(board.installed_memory()),
+				      board.memory_banks.len(), memory_size);
+	}
+	let mut output = format!("Total memory: {}\n\n", memory_size);
+	let apertures: Vec<Vec<String>> = format_table_data(board, Radix::Hex).0.into_iter()
+		.zip(board.apertures())
+		.filter(|(_, aperture)| {
+			return filters.iter().all(|filter| return aperture.matches(filter))
+		})
+		.map(|(row, _)| return row)
+		.collect();
+	output += &plain::table(&SEG_TABLE_HEADERS, &apertures);
+
+	let nodes: Vec<Vec<String>> = node_rows(board, session.nodes(), Radix::Hex).into_iter()
+		.map(|(row, _)| return row)
+		.collect();
+	if !nodes.is_empty() {
+		output += &format!("\n{}", plain::table(&NODE_TABLE_HEADERS, &nodes));
+	}
+
+	let reserved: Vec<Vec<String>> = reserved_rows(board, &session.reserved_nodes, Radix::Hex)
+		.into_iter()
+		.map(|(row, _)| return row)
+		.collect();
+	if !reserved.is_empty() {
+		output += &format!("\n{}", plain::table(&RESERVED_TABLE_HEADERS, &reserved));
+	}
+
+	return Ok(output + &format!("\n{}\n", validate::summary(&problems)))
+}
+
+fn plain_problems(session: &mut Session) -> String
+{
+	let problems = session.problems();
+	let mut output = String::new();
+	for problem in &problems {
+		output += &format!("{}: {}: {}\n", problem.kind.name(), problem.subject,
+				   problem.detail);
+		if let Some(suggested) = &problem.fix {
+			output += &format!("    suggested fix: {}\n",
+					   suggested.describe(&session.board));
+		}
+	}
+
+	return output + &format!("{}\n", validate::summary(&problems))
+}
+
+fn plain_help() -> String
+{
+	let commands: Vec<Vec<String>> = commands::COMMANDS.iter()
+		.map(|command| return vec![command.syntax.to_string(),
+					   command.description.to_string()])
+		.chain(NO_TUI_WORDS.iter()
+		       .map(|(word, description)| return vec![word.to_string(),
+							       description.to_string()]))
+		.collect();
+
+	return plain::table(&["command", "what it does"], &commands)
+}
+
+fn save_without_tui(session: &mut Session, dry_run: bool,
+		    saved_segs: &mut (Vec<u64>, Vec<(String, String)>), args: &Args)
+-> Result<String, Box<dyn std::error::Error>>
+{
+	// the same as the tui's save, with its questions asked on stdout
+	let board = &mut session.board;
+	if let Some(error) = board.alias_errors().first() {
+		return Ok(format!("Not saved, {}", error))
+	}
+
+	if dry_run || args.dry_run {
+		let (_, changes) = dry_run_save(board, &session.input_file, &session.output_file,
+						args.format, session.output_format)?;
+		print!("{}", changes);
+		return Ok("Dry run, nothing was saved.".to_string())
+	}
+
+	let changes = seg_changes(board, &saved_segs.0);
+	if !changes.is_empty() && !args.yes {
+		print!("{}", seg_change_table(&changes));
+		let answer = ask("Save these changes? (y/n)")?;
+		if answer.filter(|answer| return answer == "y").is_none() {
+			return Ok("Not saved.".to_string())
+		}
+	}
+
+	let output = generate_config(board, &session.input_file, &session.output_file,
+				     args.format, session.output_format)?;
+	let warning = check_overwrite(&session.input_file, &session.output_file, &output);
+	if let Some(warning) = warning.filter(|_| return !args.force) {
+		let answer = ask(&format!("{} Overwrite it? (y/n)", warning))?;
+		if answer.filter(|answer| return answer == "y").is_none() {
+			return Ok("Not saved.".to_string())
+		}
+	}
+
+	save_segs_to_config(board, &session.input_file, &session.output_file, args.format,
+			    session.output_format, !args.no_backup)?;
+	*saved_segs = seg_snapshot(board);
+
+	return Ok(format!("Saved to {}.", session.output_file.display()))
+}
+
+fn command_without_tui(session: &mut Session, state: states::State, command: ParsedCommand,
+		       saved_segs: &mut (Vec<u64>, Vec<(String, String)>), args: &Args)
+-> Result<states::State, Box<dyn std::error::Error>>
+{
+	// whatever the tui does with a command, other than drawing it
+	let message = match command {
+		ParsedCommand::Save { dry_run, file, format } => {
+			session.output_format = save_format(&file, format, session.output_format,
+							    args);
+			session.output_file = file.unwrap_or(session.output_file.clone());
+			save_without_tui(session, dry_run, saved_segs, args)?
+		}
+		ParsedCommand::Load(config_file) => {
+			match load_board(&session.board, &config_file, args) {
+				Ok((board, warnings)) => {
+					session.board = board;
+					*saved_segs = seg_snapshot(&session.board);
+					if args.in_place {
+						session.output_file = config_file.clone();
+					}
+					let name = config_file.display();
+					for warning in warnings {
+						messages::warn(format!("{}: {}", name, warning));
+					}
+					session.input_file = config_file;
+					format!("Loaded {}.", session.input_file.display())
+				}
+				Err(error) => error,
+			}
+		}
+		ParsedCommand::Dtb(dtb_file) => {
+			match load_dtb(&mut session.board, &dtb_file, args) {
+				Ok(loaded) => {
+					session.memory_nodes = Some(loaded.memory_nodes);
+					session.reserved_nodes = loaded.reserved_nodes;
+					session.cells = loaded.cells;
+					format!("Loaded {}.", dtb_file.display())
+				}
+				Err(error) => error,
+			}
+		}
+		ParsedCommand::Copy => copy_seg_regs(&session.board),
+		ParsedCommand::Export { format, file } => {
+			let exported = export::export(&session.board, session.nodes(),
+						      &session.reserved_nodes, format);
+			match file {
+				Some(file) => match fs::write(&file, exported) {
+					Ok(_) => format!("Exported to {}.", file.display()),
+					Err(error) => format!("Failed to write {}: {}",
+							      file.display(), error),
+				},
+				None => {
+					print!("{}", exported);
+					String::new()
+				}
+			}
+		}
+		ParsedCommand::Node { label, address, size } => {
+			let nodes = session.memory_nodes.get_or_insert_with(Vec::new);
+			let edited = dt::set_memory_node(nodes, &label, address, size);
+			if edited.is_ok() {
+				dt::pin_node_apertures(nodes, &session.board.node_apertures);
+				warn_about_node(nodes, &label, session.board.apertures());
+			}
+			edited.unwrap_or_else(|error| return error)
+		}
+		ParsedCommand::RemoveNode(label) => {
+			let removed = session.memory_nodes.as_mut()
+				.ok_or(format!("Unknown dt node {}.", label))
+				.and_then(|nodes| return dt::remove_memory_node(nodes, &label));
+			if removed.is_ok() {
+				session.board.node_apertures
+					.retain(|(node, _)| return *node != label);
+			}
+			removed.unwrap_or_else(|error| return error)
+		}
+		ParsedCommand::Overlay(file) => {
+			// without the dtb as it started out, only placed regions
+			// are new
+			let overlay = dt::memory_overlay(&[], &[], &[], &session.reserved_nodes,
+							 session.cells);
+			match overlay.map(|overlay| return overlay.map(|overlay| {
+				return dt::write_overlay(&overlay, &file)
+			})) {
+				Err(error) => error,
+				Ok(None) => "Nothing to overlay, place the named regions first."
+					.to_string(),
+				Ok(Some(Ok(_))) => format!("Wrote the overlay to {}.",
+							   file.display()),
+				Ok(Some(Err(error))) => format!("Failed to write {}: {}",
+								file.display(), error),
+			}
+		}
+		ParsedCommand::Place => {
+			regions::place_regions(&mut session.board, &mut session.named_regions,
+					       &mut session.reserved_nodes)
+				.unwrap_or_else(|error| return error)
+		}
+		ParsedCommand::Fix(FixChoice::Pick) => {
+			let fixes = validate::fixes(&validate::check(&session.board, session.nodes(),
+								    &session.reserved_nodes));
+			if fixes.is_empty() {
+				return Ok(states::after_command("Nothing to fix.".to_string()))
+			}
+			return Ok(states::State::pick_fix(&session.board, fixes))
+		}
+		ParsedCommand::Fix(choice) => {
+			let nodes = session.memory_nodes.clone().unwrap_or_default();
+			apply_fixes(&mut session.board, &nodes, &session.reserved_nodes, choice)
+				.unwrap_or_else(|error| return error)
+		}
+		ParsedCommand::Via { label, reg_name } => {
+			let checked = dt::check_node_aperture(session.nodes(), &label, &reg_name,
+							      session.board.apertures());
+			if let Err(error) = checked {
+				return Ok(states::after_command(error))
+			}
+			let command = ParsedCommand::Via { label, reg_name };
+			return Ok(state.handle(&mut session.board, states::Event::Command(command)))
+		}
+		command => {
+			return Ok(state.handle(&mut session.board, states::Event::Command(command)))
+		}
+	};
+
+	return Ok(states::after_command(message))
+}
+
+fn run_without_tui(session: Session, state: states::State, args: &Args)
+-> Result<(), Box<dyn std::error::Error>>
+{
+	// the same commands & questions as the tui, a line at a time, for
+	// screen readers & terminals that can't draw it. the answer to each
+	// comes back as the next prompt, the same as on the tui's status line
+	let mut session = session;
+	let mut state = state;
+	let mut saved_segs = seg_snapshot(&session.board);
+	if !args.plain {
+		println!("{}", export::text_map(&session.board, session.nodes(),
+						&session.reserved_nodes, NO_TUI_MAP_ROWS));
+	}
+	print!("{}", plain_overview(&mut session, &[])?);
+	println!("Type help for the commands.");
+
+	let mut summary = validate::summary(&session.problems());
+	loop {
+		for message in messages::drain() {
+			println!("{}", message);
+		}
+
+		let modified = seg_snapshot(&session.board) != saved_segs;
+		let line = match ask(&state.command_text)? {
+			Some(line) => line,
+			None if modified => {
+				return Err("stdin ran out, the changes weren't saved".into())
+			}
+			None => return Ok(()),
+		};
+
+		if let Some(help) = commands::help_for(&line) {
+			println!("{}", help);
+			continue;
+		}
+		match line.as_str() {
+			"help" | "?" => {
+				print!("{}", plain_help());
+				continue;
+			}
+			_ if line.split_whitespace().next() == Some("show") => {
+				let filters: Vec<&str> = line.split_whitespace().skip(1).collect();
+				match plain_overview(&mut session, &filters) {
+					Ok(overview) => print!("{}", overview),
+					Err(error) => println!("{}", error),
+				}
+				continue;
+			}
+			"map" => {
+				let map = export::text_map(&session.board, session.nodes(),
+							   &session.reserved_nodes, NO_TUI_MAP_ROWS);
+				print!("{}", map);
+				continue;
+			}
+			"problems" => {
+				print!("{}", plain_problems(&mut session));
+				continue;
+			}
+			"quit" if modified => {
+				match ask("Save changes? (y/n/cancel)")?.as_deref() {
+					None | Some("n") => return Ok(()),
+					Some("y") => {
+						let message = save_without_tui(&mut session, false,
+									       &mut saved_segs,
+									       args)?;
+						println!("{}", message);
+						if seg_snapshot(&session.board) == saved_segs {
+							return Ok(());
+						}
+					}
+					Some(_) => {}
+				}
+				continue;
+			}
+			"quit" => return Ok(()),
+			_ => {}
+		}
+
+		state = match commands::parse(&line) {
+			Some(Ok(command)) => command_without_tui(&mut session, state, command,
+								 &mut saved_segs, args)?,
+			Some(Err(usage)) => states::after_command(usage),
+			None => state.handle(&mut session.board, states::Event::Input(line)),
+		};
+
+		// nobody can see the status line change, so say when it does
+		let now = validate::summary(&session.problems());
+		if now != summary {
+			println!("Problems now: {}.", now);
+			summary = now;
+		}
+	}
+}
+
+fn init_config(board_name: Option<String>, output_file: &Path, force: bool)
+-> Result<(), Box<dyn std::error::Error>>
+{
+	let mut board = soc::MPFS::default();
+	if let Some(board_name) = &board_name {
+		let preset = soc::find_board_preset(board_name)
+			.ok_or(format!("unknown board {}, expected one of {}", board_name,
+				       soc::board_preset_names()))?;
+		board = soc::MPFS::from_preset(preset)?;
+	}
+
+	if !force && output_file.exists() {
+		return Err(format!("{} already exists, use --force to overwrite it",
+				   output_file.display()).into())
+	}
+
+	info!("writing a new config to {}", output_file.display());
+	let source = match &board_name {
+		Some(name) => format!("the {} reference design", name),
+		None => "every aperture starting at hardware address 0x0".to_string(),
+	};
+	fs::write(output_file, config::scaffold(&board, &source))?;
+	println!("wrote {}", output_file.display());
+	return Ok(())
+}
+
+fn check_overwrite(input_file: &Path, output_file: &Path, output: &str) -> Option<String>
+{
+	// the default output name is shared by everyone, so if somebody has
+	// touched it since the input config was last changed it has probably
+	// been hand edited & shouldn't be silently stomped on
+	if input_file == output_file {
+		return None
+	}
+
+	let existing = fs::read_to_string(output_file);
+	if existing.is_err() || existing.unwrap() == output {
+		return None
+	}
+
+	let output_modified = fs::metadata(output_file).and_then(|m| return m.modified());
+	let input_modified = fs::metadata(input_file).and_then(|m| return m.modified());
+	if output_modified.is_err() || input_modified.is_err() {
+		return None
+	}
+
+	if output_modified.unwrap() <= input_modified.unwrap() {
+		return None
+	}
+
+	return Some(format!("{} has been modified more recently than {}.",
+			    output_file.display(), input_file.display()))
+}
+
+// editing in place would otherwise leave no copy of the config as it was,
+// so keep one alongside it, named for when it was replaced
+fn backup_config(config_file: &Path) -> Result<Option<PathBuf>, io::Error>
+{
+	if !config_file.exists() {
+		return Ok(None)
+	}
+
+	let timestamp = std::time::SystemTime::now()
+		.duration_since(std::time::UNIX_EPOCH)
+		.map(|since_epoch| return since_epoch.as_secs())
+		.unwrap_or(0);
+	// appended to the os string, so a name that isn't utf-8 survives
+	let mut backup_file = config_file.as_os_str().to_owned();
+	backup_file.push(format!(".bak-{}", timestamp));
+	let backup_file = PathBuf::from(backup_file);
+	fs::copy(config_file, &backup_file)?;
+	info!("backed up {} to {}", config_file.display(), backup_file.display());
+
+	return Ok(Some(backup_file))
+}
+
+fn save_segs_to_config(board: &mut soc::MPFS, input_file: impl AsRef<Path>,
+		       output_file: impl AsRef<Path>, format: Option<ConfigFormat>,
+		       output_format: Option<ConfigFormat>, backup: bool)
+-> Result<(), Box<dyn std::error::Error>>
+{
+	let output_file = output_file.as_ref();
+	let in_place = input_file.as_ref() == output_file;
+	let output = generate_config(board, input_file, output_file, format, output_format)?;
+
+	if backup && in_place {
+		backup_config(output_file)?;
+	}
+
+	stdio::write(output_file, output.as_bytes())?;
+	info!("saved {}", output_file.display());
+
+	return Ok(())
+}
+
+fn dry_run_save(board: &mut soc::MPFS, input_file: &Path, output_file: &Path,
+		format: Option<ConfigFormat>, output_format: Option<ConfigFormat>)
+-> Result<(String, String), Box<dyn std::error::Error>>
+{
+	// what a save would write & a diff against what's there now, with
+	// nothing touched. stdout has nothing to compare against
+	let output = generate_config(board, input_file, output_file, format, output_format)?;
+	let mut existing = None;
+	if !stdio::is_stdio(output_file) {
+		existing = fs::read_to_string(output_file).ok();
+	}
+
+	let name = output_file.display().to_string();
+	let changes = match &existing {
+		Some(existing) => diff::unified(existing, &output, &name, &name),
+		None => diff::unified("", &output, "/dev/null", &name),
+	};
+	if changes.is_empty() {
+		return Ok((output, format!("{} would be unchanged.\n", name)))
+	}
+
+	return Ok((output, changes))
+}
+
+fn print_dry_run(board: &mut soc::MPFS, input_file: &Path, output_file: &Path,
+		 format: Option<ConfigFormat>, output_format: Option<ConfigFormat>)
+-> Result<(), Box<dyn std::error::Error>>
+{
+	// exactly what would be written goes to stdout, so it can be redirected
+	// somewhere to look at, & the diff to stderr
+	let (output, changes) = dry_run_save(board, input_file, output_file, format,
+					     output_format)?;
+	if let Some(warning) = check_overwrite(input_file, output_file, &output) {
+		eprintln!("warning: {}", warning);
+	}
+	print!("{}", output);
+	eprint!("{}", changes);
+
+	return Ok(())
+}
+
+// only the seg values and the apertures picked for dt nodes make it into the
+// config, so they are all that needs comparing to tell whether there is
+// anything left to save
+fn seg_snapshot(board: &soc::MPFS) -> (Vec<u64>, Vec<(String, String)>)
+{
+	let segs = board.apertures()
+		.iter()
+		.map(|aperture| return aperture.hardware_addr)
+		.collect();
+
+	return (segs, board.node_apertures.clone())
+}
+
+// the same checks as are made when loading a dtb, for a node that has just
+// been added or changed by hand
+fn warn_about_node(nodes: &[MemoryNode], label: &str, apertures: &[soc::MemoryAperture])
+{
+	let node = nodes.iter()
+		.filter(|node| return node.label == label)
+		.cloned()
+		.collect::<Vec<MemoryNode>>();
+
+	if !dt::unreachable_nodes(&node, apertures).is_empty() {
+		messages::warn(format!("none of the apertures reach all of {}.", label));
+	}
+	for ambiguous in dt::ambiguous_nodes(&node, apertures) {
+		messages::warn(format!("{} could go through more than one aperture, pick one \
+					with \"via <dt node> <register>\".", ambiguous));
+	}
+	for uncached in dt::uncached_nodes(&node, apertures) {
+		messages::warn(format!("memory {} is not cached.", uncached));
+	}
+}
+
+fn handle_messages(messages: &mut Vec<String>) -> Option<String>
+{
+	if messages.is_empty(){
+		return None;
+	}
+
+	let message = messages.pop();
+	messages.clear();
+	message.as_ref()?;
+
+	let input = message.as_ref().unwrap();
+	return Some(input.to_string());
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum Action {
+	/// check that loading & saving a config doesn't change it
+	RoundtripCheck {
+		/// config file to check
+		#[clap(parse(from_os_str))]
+		config: PathBuf,
+
+		/// skip the strict checks, & load the config the same way as
+		/// when --strict isn't given
+		#[clap(long)]
+		lenient: bool,
+	},
+	/// list anything wrong with the config, & the dtb if given, without
+	/// starting the tui
+	Check {
+		/// apply every suggested fix & save the result
+		#[clap(long)]
+		fix: bool,
+
+		/// skip the strict checks, & load the config the same way as
+		/// when --strict isn't given
+		#[clap(long)]
+		lenient: bool,
+
+		/// print the problems as text, or the memory map, apertures &
+		/// problems as a json report
+		#[clap(long, arg_enum, default_value = "text")]
+		output_format: validate::ReportFormat,
+	},
+	/// print the apertures & nodes as a plain text map, without starting
+	/// the tui
+	Map {
+		/// how many rows to split ddr into
+		#[clap(long, default_value = "32")]
+		rows: usize,
+	},
+	/// work out the seg values from the config, board, design & plan given,
+	/// & fail if they aren't the ones in a config that's known to be good
+	Verify {
+		/// config with the seg values that should come out
+		#[clap(long, parse(from_os_str))]
+		expected: PathBuf,
+	},
+	/// draw the tui's first screen as plain text, to compare against one
+	/// that's known to be good after changing how it's laid out
+	Snapshot {
+		/// width of the screen to draw, in columns
+		#[clap(long, default_value = "200")]
+		width: u16,
+
+		/// height of the screen to draw, in rows
+		#[clap(long, default_value = "50")]
+		height: u16,
+
+		/// snapshot to compare against, failing with a diff if it's different
+		#[clap(long, parse(from_os_str))]
+		expected: Option<PathBuf>,
+	},
+	/// apply the seg values from a small config on top of a bigger one,
+	/// keeping everything else from the bigger one
+	Merge {
+		/// config to start from ("-" for stdin)
+		#[clap(parse(from_os_str))]
+		base: PathBuf,
+
+		/// config with the seg values to change ("-" for stdin)
+		#[clap(parse(from_os_str))]
+		overlay: PathBuf,
+
+		/// file to write the merged config to ("-" for stdout)
+		#[clap(short, long, parse(from_os_str), default_value = "-")]
+		output: PathBuf,
+	},
+	/// point out anything in the config that probably isn't what was meant,
+	/// like unknown registers, keys given twice & windows past the end of
+	/// memory, without the config having to load
+	Lint {
+		/// the least severe finding that fails the lint, one of info,
+		/// warning or error
+		#[clap(long, arg_enum, default_value = "error")]
+		fail_on: lint::Severity,
+	},
+	/// check that every seg value round trips through the conversions
+	#[clap(hide = true)]
+	Selftest,
+	/// print a script that completes the options & subcommands in a shell,
+	/// for packages or a shell's startup files to install
+	Completions {
+		/// shell to complete in, one of bash, elvish, fish, powershell or zsh
+		#[clap(arg_enum)]
+		shell: clap_complete::Shell,
+	},
+	/// print a man page for the options & subcommands, in roff
+	Manpage,
+	/// write a commented config to start from
+	Init {
+		/// take the seg values from a known board, icicle-kit
+		#[clap(long, env = "MAC_BOARD")]
+		board: Option<String>,
+
+		/// file to write the config to
+		#[clap(short, long, parse(from_os_str), default_value = "config.yaml")]
+		output: PathBuf,
+
+		/// overwrite the file if it already exists
+		#[clap(short, long)]
+		force: bool,
+	},
+}
+
+/// PolarFire SoC memory aperture configurator
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+	#[clap(subcommand)]
+	action: Option<Action>,
+
+	/// input yaml config file ("-" for stdin)
+	#[clap(short, long, global = true, env = "MAC_CONFIG", parse(from_os_str),
+	       default_value = "config.yaml")]
+	config: PathBuf,
+
+	/// start from a known board's memory size, seg values & reserved memory,
+	/// icicle-kit
+	#[clap(long, env = "MAC_BOARD")]
+	board: Option<String>,
+
+	/// total system memory, e.g. 0x80000000 or 2G, instead of entering it
+	/// or working it out from the board, design or dtb
+	#[clap(long, env = "MAC_MEMORY", parse(try_from_str = units::parse_size))]
+	memory: Option<u64>,
+
+	/// input dtb, or dts ("-" for stdin)
+	#[clap(short, long, parse(from_os_str))]
+	dtb: Option<PathBuf>,
+
+	/// mss configurator xml, or the header generated from it, to take the
+	/// default seg values & ddr size from
+	#[clap(long, parse(from_os_str))]
+	mss_config: Option<PathBuf>,
+
+	/// dump of the seg registers of a running board, to use instead of the
+	/// config's seg values
+	#[clap(long, parse(from_os_str))]
+	register_dump: Option<PathBuf>,
+
+	/// csv of named regions (name, size, attributes) to place in memory
+	#[clap(long, parse(from_os_str))]
+	regions: Option<PathBuf>,
+
+	/// config file format, detected from the file extension if not set
+	#[clap(long, arg_enum)]
+	format: Option<ConfigFormat>,
+
+	/// edit the config in place rather tha use the default output of "generated.yaml"
+	#[clap(short, long)]
+	in_place: bool,
+
+	/// file to save the config to rather than "generated.yaml" ("-" for
+	/// stdout, only when running a script or check --fix)
+	#[clap(short, long, parse(from_os_str), conflicts_with = "in-place")]
+	output: Option<PathBuf>,
+
+	/// don't keep a timestamped backup of the config when editing it in place
+	#[clap(long)]
+	no_backup: bool,
+
+	/// print what saving would write, & how it differs from what's there,
+	/// rather than writing it
+	#[clap(long)]
+	dry_run: bool,
+
+	/// treat apertures that map the same memory as conflicts, rather than
+	/// deliberate aliases
+	#[clap(long)]
+	forbid_aliases: bool,
+
+	/// don't warn about a total memory size that isn't a power of two
+	/// multiple of 512 MiB, or is more than 16 GiB, e.g. for an emulator
+	#[clap(long)]
+	any_memory_size: bool,
+
+	/// overwrite the output file even if it looks to have been hand edited
+	#[clap(short, long)]
+	force: bool,
+
+	/// colours to use instead of any theme in the config, one of default,
+	/// colour-blind or monochrome
+	#[clap(long)]
+	theme: Option<String>,
+
+	/// leave the mouse to the terminal, e.g. for selecting text, rather than
+	/// using it to pick & drag apertures
+	#[clap(long)]
+	no_mouse: bool,
+
+	/// ask for commands & answers a line at a time on stdin, printing what
+	/// they do to stdout, rather than drawing the tui
+	#[clap(long)]
+	no_tui: bool,
+
+	/// with --no-tui, print only words & aligned tables, leaving out the
+	/// memory map drawn with characters, e.g. for a screen reader
+	#[clap(long, requires = "no-tui")]
+	plain: bool,
+
+	/// reload the config & dtb whenever they change on disk, e.g. while
+	/// iterating on whatever generates them
+	#[clap(long)]
+	watch: bool,
+
+	/// make a missing config, unknown keys & bad seg values errors, rather
+	/// than skipping over them
+	#[clap(long)]
+	strict: bool,
+
+	/// run commands from this file ("-" for stdin), save the result & exit
+	#[clap(long, parse(from_os_str))]
+	script: Option<PathBuf>,
+
+	/// save without showing the changes & asking first
+	#[clap(short, long)]
+	yes: bool,
+
+	/// write the complete board memory plan, as json, to this file & exit
+	#[clap(long, parse(from_os_str))]
+	export_plan: Option<PathBuf>,
+
+	/// load the board memory plan from this file instead of the config & dtb
+	#[clap(long, parse(from_os_str))]
+	import_plan: Option<PathBuf>,
+
+	/// print the json schema for board memory plans & exit
+	#[clap(long)]
+	plan_schema: bool,
+
+	/// answer a few questions about the board & what runs on it, & write a
+	/// config for it to --output, or "config.yaml"
+	#[clap(long)]
+	wizard: bool,
+
+	/// how much to log: off, error, warn, info, debug or trace
+	#[clap(long, default_value = "off")]
+	log_level: LevelFilter,
+
+	/// file to log to, a new one is started each day with the date appended
+	#[clap(long, parse(from_os_str), default_value = "seg-configurator.log")]
+	log_file: PathBuf,
+}
+
+// the tui owns the terminal, so logs can only go to a file. that's kept to a
+// file per day, rather than one that grows forever
+fn setup_logging(level: LevelFilter, log_file: &Path)
+{
+	if level == LevelFilter::OFF {
+		return
+	}
+
+	let directory = log_file.parent()
+		.filter(|parent| return !parent.as_os_str().is_empty())
+		.unwrap_or_else(|| return Path::new("."));
+	let file_name = log_file.file_name()
+		.unwrap_or_else(|| return std::ffi::OsStr::new("seg-configurator.log"));
+	let appender = tracing_appender::rolling::daily(directory, file_name);
+
+	tracing_subscriber::fmt()
+		.with_max_level(level)
+		.with_writer(appender)
+		.with_ansi(false)
+		.init();
+}
+// whatever woke the tui up
+enum Wakeup {
+	Input(io::Result<Event>),
+	// a file being watched has been written to, or replaced
+	Changed(PathBuf),
+}
+
+// editors & generators tend to write a file in several goes, so changes are
+// only passed on once it has been left alone for this long
+const WATCH_SETTLE_TIME: std::time::Duration = std::time::Duration::from_millis(200);
+
+fn spawn_input_thread(sender: mpsc::Sender<Wakeup>)
+{
+	// reading the terminal blocks, so it's done on a thread of its own that
+	// hands each event over, waking the tui up only when there is something
+	// to do
+	std::thread::spawn(move || {
+		loop {
+			let event = event::read();
+			let failed = event.is_err();
+			if sender.send(Wakeup::Input(event)).is_err() || failed {
+				return
+			}
+		}
+	});
+}
+
+fn spawn_file_watcher(files: &[PathBuf], sender: mpsc::Sender<Wakeup>)
+-> notify::Result<notify::RecommendedWatcher>
+{
+	// it's the directories that get watched, as a file that is replaced
+	// rather than written to would otherwise stop being watched
+	let files: Vec<PathBuf> = files.iter()
+		.map(|file| return fs::canonicalize(file).unwrap_or(file.clone()))
+		.collect();
+	let (changes, changed) = mpsc::channel::<PathBuf>();
+	let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+		let event = match event {
+			Ok(event) if event.kind.is_create() || event.kind.is_modify() => event,
+			_ => return,
+		};
+		for path in event.paths {
+			let _ = changes.send(path);
+		}
+	})?;
+
+	for directory in files.iter().filter_map(|file| return file.parent()) {
+		watcher.watch(directory, notify::RecursiveMode::NonRecursive)?;
+	}
+
+	std::thread::spawn(move || {
+		while let Ok(path) = changed.recv() {
+			let mut paths = vec![path];
+			while let Ok(path) = changed.recv_timeout(WATCH_SETTLE_TIME) {
+				paths.push(path);
+			}
+
+			for file in files.iter().filter(|file| return paths.contains(file)) {
+				if sender.send(Wakeup::Changed(file.clone())).is_err() {
+					return
+				}
+			}
+		}
+	});
+
+	return Ok(watcher)
+}
+
+// undo whatever the tui did to the terminal, so that a crash or an error does
+// not leave it in raw mode
+fn restore_terminal()
+{
+	let _ = disable_raw_mode();
+	let _ = execute!(io::stdout(), DisableMouseCapture, Clear(ClearType::All), MoveTo(0, 0));
+}
+
+// restores the terminal on every way out of the tui, including errors that get
+// propagated out of main with ?
+struct TerminalGuard;
+
+impl Drop for TerminalGuard {
+	fn drop(&mut self)
+	{
+		restore_terminal();
+	}
+}
+
+fn main() -> Result<(),Box<dyn std::error::Error>> {
+	let args = Args::parse();
+	setup_logging(args.log_level, &args.log_file);
+	if let Some(theme_name) = &args.theme {
+		theme::force_preset(theme_name)?;
+	}
+	info!("starting with {:?}", std::env::args().collect::<Vec<String>>());
+	if args.plan_schema {
+		print!("{}", plan::PLAN_SCHEMA);
+		return Ok(());
+	}
+
+	if let Some(Action::RoundtripCheck { config, lenient }) = args.action {
+		return roundtrip_check(&config, args.format, !lenient);
+	}
+
+	if let Some(Action::Selftest) = args.action {
+		return selftest();
+	}
+
+	if let Some(Action::Completions { shell }) = args.action {
+		let mut command = Args::command();
+		let name = command.get_name().to_string();
+		clap_complete::generate(shell, &mut command, name, &mut io::stdout());
+		return Ok(());
+	}
+
+	if let Some(Action::Manpage) = args.action {
+		clap_mangen::Man::new(Args::command()).render(&mut io::stdout())?;
+		return Ok(());
+	}
+
+	if let Some(Action::Merge { base, overlay, output }) = &args.action {
+		return merge_configs(base, overlay, output, args.format);
+	}
+
+	if let Some(Action::Init { board, output, force }) = args.action {
+		return init_config(board, &output, force);
+	}
+
+	if args.wizard {
+		let output = args.output.clone().unwrap_or(PathBuf::from("config.yaml"));
+		return run_wizard(&output, &args);
+	}
+
+	let mut next_state = states::State::default();
+	let mut board = soc::MPFS::default();
+	let mut board_preset = None;
+	if let Some(board_name) = &args.board {
+		board_preset = Some(soc::find_board_preset(board_name)
+			.ok_or(format!("unknown board {}, expected one of {}", board_name,
+				       soc::board_preset_names()))?);
+		board = soc::MPFS::from_preset(board_preset.unwrap())?;
+		next_state = states::State::skip_init();
+	}
+	board.forbid_aliases = args.forbid_aliases;
+	board.any_memory_size = args.any_memory_size;
+	let mut input = lineedit::LineEditor::default();
+	let mut messages: Vec<String> = Vec::new();
+	let mut input_file = args.config.clone();
+	let mut output_file = PathBuf::from("generated.yaml");
+	let mut output_format = args.format;
+	let mut memory_nodes: Option<Vec<MemoryNode>> = None;
+	let mut view = ViewOptions::default();
+	let mut named_regions: Vec<NamedRegion> = Vec::new();
+	let mut reserved_nodes: Vec<ReservedMemoryNode> = Vec::new();
+	let mut dtb_cells = dt::TreeCells::default();
+	let mut show_help = false;
+	let mut show_problems = false;
+	let mut show_seg_detail = false;
+	let mut dry_run: Option<String> = None;
+	let mut dry_run_scroll: u16 = 0;
+	let mut drag: Option<MapDrag> = None;
+	if args.in_place {
+		output_file = input_file.clone();
+	}
+	if let Some(output) = &args.output {
+		output_file = output.clone();
+	}
+	stdio::check_one_stdin(&[("--config", Some(&input_file)), ("--dtb", args.dtb.as_deref()),
+				 ("--script", args.script.as_deref())])?;
+
+	if let Some(preset) = board_preset {
+		reserved_nodes = preset.reserved.iter()
+			.map(|(label, address, size)| return ReservedMemoryNode {
+				label: label.to_string(),
+				regions: vec![dt::MemoryRegion {
+					address: *address,
+					size: *size,
+					aperture: None,
+				}],
+				no_map: true,
+				reusable: false,
+			})
+			.collect();
+	}
+
+	if let Some(dtb_file) = &args.dtb {
+		let loaded = dt::dtb_get_nodes(dtb_file)?;
+		memory_nodes = Some(loaded.memory_nodes);
+		reserved_nodes = loaded.reserved_nodes;
+		dtb_cells = loaded.cells;
+	}
+
+	if let Some(regions_file) = &args.regions {
+		named_regions = regions::csv_get_regions(regions_file)?;
+	}
+
+	// the design's settings are only defaults, anything in the config wins
+	let mut load_warnings: Vec<String> = Vec::new();
+	if let Some(mss_config_file) = &args.mss_config {
+		load_warnings = setup_segs_from_mss_config(&mut board, mss_config_file)?;
+	}
+	if let Some(Action::Lint { fail_on }) = args.action {
+		// a size from the design or a known board is as good as one given,
+		// but the dtb's can only be worked out once the config has loaded
+		let mut total_memory = args.memory;
+		if board_preset.is_some() || args.mss_config.is_some() {
+			total_memory = total_memory.or(Some(board.total_system_memory));
+		}
+		return lint_config(&board, &input_file, args.format, total_memory, fail_on);
+	}
+
+	// like roundtrip-check, check has no business passing a config that's
+	// missing or that it only read half of
+	let strict = args.strict ||
+		     matches!(args.action, Some(Action::Check { lenient: false, .. }));
+	load_warnings.extend(setup_segs_from_config(&mut board, &input_file, args.format, strict)?);
+	if !board.memory_banks.is_empty() {
+		next_state = states::State::skip_init();
+	}
+	let mut saved_segs = seg_snapshot(&board);
+
+	// whatever the board is really running with shows up as changes to the
+	// config, so it can be saved over what the config claims
+	if let Some(dump_file) = &args.register_dump {
+		load_warnings.extend(setup_segs_from_dump(&mut board, dump_file)?);
+	}
+
+	if let Some(nodes) = memory_nodes.as_mut() {
+		dt::pin_node_apertures(nodes, &board.node_apertures);
+		let total_memory = dt::memory_nodes_get_total_memory(nodes,
+								     board.apertures());
+		// the banks in the config say where the memory is, the dtb's nodes
+		// only where it's been given to linux
+		let total_memory = total_memory.filter(|_| return board.memory_banks.is_empty());
+		if let Some(total_memory) = total_memory {
+			board.total_system_memory = total_memory;
+			next_state = states::State::skip_init();
+		}
+	}
+
+	if let Some(plan_file) = &args.import_plan {
+		info!("reading plan {}", plan_file.display());
+		let contents = fs::read_to_string(plan_file)?;
+		let plan_json: serde_json::Value = serde_json::from_str(&contents)?;
+		let board_plan = plan::import_plan(&plan_json, &mut board)
+			.map_err(|error| return format!("{}: {}", plan_file.display(), error))?;
+
+		memory_nodes = board_plan.memory_nodes;
+		reserved_nodes = board_plan.reserved_nodes;
+		named_regions = board_plan.named_regions;
+	}
+
+	// given explicitly, so it wins over wherever else the size came from
+	if let Some(total_memory) = args.memory {
+		if total_memory == 0 {
+			return Err("--memory can't be 0".into());
+		}
+		if !board.memory_banks.is_empty() {
+			return Err("--memory can't be used with memory-banks in the config, \
+				    the banks say how much memory there is".into());
+		}
+		board.total_system_memory = total_memory;
+		next_state = states::State::skip_init();
+	}
+
+	if let Some(nodes) = &memory_nodes {
+		for node in dt::uncached_nodes(nodes, board.apertures()) {
+			load_warnings.push(format!("memory {} is not cached.", node));
+		}
+		for region in dt::unreachable_regions(nodes, board.apertures()) {
+			load_warnings.push(format!("no aperture reaches {}.", region));
+		}
+	}
+
+	// what the dtb started out with, for working out what an overlay needs
+	let mut original_nodes = memory_nodes.clone().unwrap_or_default();
+	let mut original_reserved = reserved_nodes.clone();
+
+	let checking = matches!(args.action, Some(Action::Check { .. }));
+	let mapping = matches!(args.action, Some(Action::Map { .. }));
+	let verifying = matches!(args.action, Some(Action::Verify { .. }));
+	let snapshotting = matches!(args.action, Some(Action::Snapshot { .. }));
+	// the tui is drawn on stdout, so there's no room for a config there
+	if stdio::is_stdio(&output_file) && args.script.is_none() && !checking {
+		return Err("saving to stdout only works with --script or check --fix".into());
+	}
+	if args.script.is_some() || args.export_plan.is_some() || checking || mapping || verifying ||
+	   snapshotting || args.no_tui {
+		for warning in &load_warnings {
+			eprintln!("{}: warning: {}", input_file.display(), warning);
+		}
+		for message in messages::drain() {
+			eprintln!("{}", message);
+		}
+	}
+
+	if let Some(script_file) = &args.script {
+		let result = run_script(&mut board, script_file, input_file, output_file,
+					named_regions, dtb_cells, &args);
+		for message in messages::drain() {
+			eprintln!("{}", message);
+		}
+		return result;
+	}
+
+	if let Some(Action::Map { rows }) = args.action {
+		print!("{}", export::text_map(&board, memory_nodes.as_deref().unwrap_or_default(),
+					      &reserved_nodes, rows));
+		return Ok(());
+	}
+
+	if let Some(Action::Snapshot { width, height, expected }) = &args.action {
+		regions::allocate_regions(&board, &mut named_regions);
+		let nodes = memory_nodes.as_deref().unwrap_or_default();
+		let mut problems = validate::check(&board, nodes, &reserved_nodes);
+		problems.extend(validate::check_regions(&board, &named_regions));
+		let screen = Screen {
+			board: &board,
+			memory_nodes: &memory_nodes,
+			reserved_nodes: &reserved_nodes,
+			named_regions: &named_regions,
+			problems: &problems,
+			command_text: &next_state.command_text,
+			input: &input,
+			modified: false,
+		};
+		return snapshot(&screen, *width, *height, expected.as_deref());
+	}
+
+	if let Some(Action::Verify { expected }) = &args.action {
+		if named_regions.is_empty() && args.import_plan.is_none() {
+			return Err("verify works the seg values out from a layout, give it one with \
+				    --regions or --import-plan".into());
+		}
+		return verify_segs(&mut board, &mut named_regions, expected, args.format);
+	}
+
+	if checking {
+		let nodes = memory_nodes.as_deref().unwrap_or_default();
+		regions::allocate_regions(&board, &mut named_regions);
+		return check_config(&mut board, nodes, &reserved_nodes, &named_regions, &input_file,
+				    &output_file, &args);
+	}
+
+	if let Some(plan_file) = &args.export_plan {
+		regions::allocate_regions(&board, &mut named_regions);
+		let provenance = plan::Provenance {
+			config: Some(input_file.display().to_string()),
+			dtb: args.dtb.as_ref().map(|dtb_file| return dtb_file.display().to_string()),
+		};
+		let board_plan = plan::export_plan(&board, &memory_nodes, &reserved_nodes,
+						   &named_regions, provenance);
+		fs::write(plan_file, serde_json::to_string_pretty(&board_plan)? + "\n")?;
+		info!("exported plan to {}", plan_file.display());
+		return Ok(());
+	}
+
+	// the default hook prints the panic message, so the terminal needs to
+	// be usable again before it runs
+	let default_hook = std::panic::take_hook();
+	std::panic::set_hook(Box::new(move |info| {
+		restore_terminal();
+		default_hook(info);
+	}));
+
+	if args.no_tui {
+		let session = Session {
+			board,
+			memory_nodes,
+			reserved_nodes,
+			named_regions,
+			cells: dtb_cells,
+			input_file,
+			output_file,
+			output_format: args.format,
+		};
+		return run_without_tui(session, next_state, &args);
+	}
+
+	for warning in load_warnings {
+		messages::warn(format!("{}: {}", input_file.display(), warning));
+	}
+
+	// compared against the config rather than the session, so that what was
+	// restored still shows up as unsaved
+	if restore_session(&mut board, &input_file)? {
+		next_state = states::State::skip_init();
+		messages::push(format!("Restored the unsaved changes to {}.", input_file.display()));
+	}
+	let mut autosaved_segs = seg_snapshot(&board);
+	let mut last_autosave = std::time::Instant::now();
+
+	let stdout = io::stdout();
+	let backend = CrosstermBackend::new(stdout);
+	let mut terminal = Terminal::new(backend)?;
+	terminal.clear()?;
+	enable_raw_mode()?;
+	let _terminal_guard = TerminalGuard;
+	if !args.no_mouse {
+		execute!(io::stdout(), EnableMouseCapture)?;
+	}
+	terminal.clear()?;
+
+	let (wakeup_sender, wakeups) = mpsc::channel();
+	spawn_input_thread(wakeup_sender.clone());
+	// kept around for as long as the tui is, as dropping it stops the watching
+	// & the dtb can be swapped for another, which is then watched instead
+	let mut dtb_file = args.dtb.clone();
+	let mut _watcher = None;
+	if args.watch {
+		let watched: Vec<PathBuf> = std::iter::once(input_file.clone())
+			.chain(dtb_file.clone())
+			.collect();
+		_watcher = Some(spawn_file_watcher(&watched, wakeup_sender.clone())?);
+	}
+	let mut redraw = true;
+	loop {
+		if let Some(nodes) = memory_nodes.as_mut() {
+			dt::pin_node_apertures(nodes, &board.node_apertures);
+		}
+		regions::allocate_regions(&board, &mut named_regions);
+
+		// the tables narrow down as the search is typed, not only once it's
+		// entered
+		if let Some(search) = input.line.strip_prefix('/') {
+			view.search = Some(search.trim().to_string())
+				.filter(|search| return !search.is_empty());
+		}
+
+		let command_text = next_state.command_text.clone();
+		let modified = seg_snapshot(&board) != saved_segs;
+		let nodes = memory_nodes.as_deref().unwrap_or_default();
+		let mut problems = validate::check(&board, nodes, &reserved_nodes);
+		problems.extend(validate::check_regions(&board, &named_regions));
+		if redraw {
+			terminal.draw(|frame| {
+				let screen = Screen {
+					board: &board,
+					memory_nodes: &memory_nodes,
+					reserved_nodes: &reserved_nodes,
+					named_regions: &named_regions,
+					problems: &problems,
+					command_text: &command_text,
+					input: &input,
+					modified,
+				};
+				let area = match render_screen(&screen, &mut view, frame) {
+					Some(area) => area,
+					None => return,
+				};
+
+				if next_state.is_confirming_save() {
+					render_save_diff(&seg_changes(&board, &saved_segs.0), view.radix,
+							 frame, area);
+				}
+
+				if show_problems {
+					render_problems(&board, &problems, frame, area);
+				}
+
+				let detail_id = nudge_target(&board, &view)
+					.filter(|_| return show_seg_detail);
+				if let Some(id) = detail_id {
+					render_seg_detail(&board.apertures()[id], frame,
+							  area);
+				}
+
+				if let Some(text) = &dry_run {
+					render_dry_run(text, dry_run_scroll, frame, area);
+				}
+
+				if show_help {
+					render_help(frame, area);
+				}
+			})?;
+		}
+
+		let prompt_pending = next_state.is_confirming();
+		let mut key_command = None;
+		let nudge_id = nudge_target(&board, &view);
+
+		// kept somewhere that outlives the terminal, in case it goes away
+		// before the changes are saved
+		let snapshot = seg_snapshot(&board);
+		let autosave_due = last_autosave.elapsed() >= autosave::AUTOSAVE_INTERVAL;
+		if snapshot != autosaved_segs && autosave_due {
+			if snapshot == saved_segs {
+				autosave::remove(&input_file);
+			} else if let Err(error) = autosave_session(&mut board, &input_file,
+								    args.format) {
+				messages::warn(format!("failed to autosave: {}", error));
+			}
+			autosaved_segs = snapshot.clone();
+			last_autosave = std::time::Instant::now();
+		}
+
+		// nothing moves on by itself, so sleep until there is some input
+		// rather than redrawing the same screen, other than to autosave
+		let stopped = || return io::Error::new(io::ErrorKind::BrokenPipe,
+						       "stopped reading the terminal");
+		let wakeup = match snapshot != autosaved_segs {
+			true => {
+				let wait = autosave::AUTOSAVE_INTERVAL
+					.saturating_sub(last_autosave.elapsed());
+				match wakeups.recv_timeout(wait) {
+					Ok(wakeup) => Some(wakeup),
+					Err(mpsc::RecvTimeoutError::Timeout) => None,
+					Err(mpsc::RecvTimeoutError::Disconnected) => {
+						return Err(stopped().into())
+					}
+				}
+			}
+			false => Some(wakeups.recv().map_err(|_| return stopped())?),
+		};
+		let wakeup = match wakeup {
+			Some(wakeup) => wakeup,
+			None => {
+				redraw = false;
+				continue;
+			}
+		};
+		let event = match wakeup {
+			Wakeup::Input(event) => Some(event?),
+			Wakeup::Changed(file) => {
+				let dtb = dtb_file.as_ref()
+					.filter(|dtb| return fs::canonicalize(dtb).ok() == Some(file.clone()));
+				if let Some(dtb_file) = dtb {
+					// the nodes are only ever read, so there's nothing
+					// of the user's to lose by reading them again
+					match dt::dtb_get_nodes(dtb_file) {
+						Ok(loaded) => {
+							messages::push(format!("{} changed, reloaded it.",
+									       dtb_file.display()));
+							let unreachable = dt::unreachable_regions(
+								&loaded.memory_nodes,
+								board.apertures());
+							for region in unreachable {
+								messages::warn(format!("no aperture \
+											reaches {}.",
+										       region));
+							}
+							memory_nodes = Some(loaded.memory_nodes);
+							reserved_nodes = loaded.reserved_nodes;
+							dtb_cells = loaded.cells;
+						}
+						Err(error) => messages::warn(format!("{} changed, but failed \
+										     to reload it: {}",
+										     dtb_file.display(), error)),
+					}
+				} else if modified || prompt_pending {
+					messages::warn(format!("{} changed, Ctrl+R to reload it.",
+							       input_file.display()));
+				} else {
+					key_command = Some(ParsedCommand::Load(input_file.clone()));
+				}
+				None
+			}
+		};
+		// with the mouse captured, every movement of it comes through
+		redraw = !matches!(event, Some(Event::Mouse(MouseEvent {
+			kind: MouseEventKind::Moved, ..
+		})));
+		if let Some(Event::Mouse(mouse)) = event {
+			match mouse.kind {
+				MouseEventKind::Down(MouseButton::Left) => {
+					drag = start_drag(&board, &view.map, mouse.column, mouse.row);
+					let clicked = view.scroll.segs.row_at(mouse.column, mouse.row);
+					let clicked_id = clicked
+						.and_then(|row| return view.scroll.segs.id_at(row));
+					if let Some(id) = clicked_id {
+						view.focus = ScrollFocus::Segs;
+						view.scroll.segs.state.select(clicked);
+						// as if the id had been typed in
+						if next_state.wants_aperture_id() {
+							messages.push(format!("{:x}", id));
+						}
+					}
+				}
+				MouseEventKind::Drag(MouseButton::Left) => {
+					if let Some(drag) = drag {
+						drag_to(&mut board, &view.map, drag, mouse.row);
+					}
+				}
+				MouseEventKind::Up(MouseButton::Left) => {
+					drag = None;
+				}
+				_ => {}
+			}
+		}
+		if let Some(Event::Key(key)) = event {
+			match key.code {
+				KeyCode::Esc if show_help => {
+					show_help = false;
+				}
+				KeyCode::Esc if show_problems => {
+					show_problems = false;
+				}
+				KeyCode::Esc if show_seg_detail => {
+					show_seg_detail = false;
+				}
+				KeyCode::Char('g') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+					show_seg_detail = !show_seg_detail;
+					if show_seg_detail && nudge_id.is_none() {
+						show_seg_detail = false;
+						messages::push("Pick an aperture first.".to_string());
+					}
+				}
+				KeyCode::Esc if dry_run.is_some() => {
+					dry_run = None;
+				}
+				KeyCode::Up if dry_run.is_some() => {
+					dry_run_scroll = dry_run_scroll.saturating_sub(1);
+				}
+				KeyCode::Down if dry_run.is_some() => {
+					dry_run_scroll = dry_run_scroll.saturating_add(1);
+				}
+				KeyCode::PageUp if dry_run.is_some() => {
+					dry_run_scroll = dry_run_scroll.saturating_sub(DRY_RUN_PAGE);
+				}
+				KeyCode::PageDown if dry_run.is_some() => {
+					dry_run_scroll = dry_run_scroll.saturating_add(DRY_RUN_PAGE);
+				}
+				KeyCode::Char('e') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+					show_problems = !show_problems;
+				}
+				// "<command>?" is how help on a command is asked for, so
+				// this is only the overlay on an empty line
+				KeyCode::Char('?') if input.line.is_empty() && !prompt_pending => {
+					show_help = !show_help;
+				}
+				// these only make sense when nothing is waiting on an answer
+				KeyCode::Char('s' | 'r') if key.modifiers.contains(KeyModifiers::CONTROL)
+							    && prompt_pending => {}
+				KeyCode::Char('s') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+					key_command = Some(ParsedCommand::Save {
+						dry_run: false,
+						file: None,
+						format: None,
+					});
+				}
+				KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL)
+						      && modified => {
+					next_state = next_state.confirm_reload(input_file.clone());
+				}
+				KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+					key_command = Some(ParsedCommand::Load(input_file.clone()));
+				}
+				KeyCode::Char('y') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+					key_command = Some(ParsedCommand::Copy);
+				}
+				KeyCode::Char('d') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+					view.show_disabled_nodes = !view.show_disabled_nodes;
+				}
+				KeyCode::Char('b') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+					view.radix = view.radix.next();
+				}
+				KeyCode::Char('a') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+					board.forbid_aliases = !board.forbid_aliases;
+				}
+				KeyCode::Up if key.modifiers.contains(KeyModifiers::CONTROL) => {
+					view.zoom(board.total_memory(), true);
+				}
+				KeyCode::Down if key.modifiers.contains(KeyModifiers::CONTROL) => {
+					view.zoom(board.total_memory(), false);
+				}
+				KeyCode::Char('t') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+					view.focus = view.focus.next();
+				}
+				KeyCode::PageUp | KeyCode::PageDown => {
+					let up = key.code == KeyCode::PageUp;
+					match view.focus {
+						ScrollFocus::Map => view.pan(board.total_memory(), up),
+						ScrollFocus::Segs => view.scroll.segs.scroll(!up),
+						ScrollFocus::Nodes => view.scroll.nodes.scroll(!up),
+					}
+				}
+				KeyCode::Home => {
+					match view.focus {
+						ScrollFocus::Map => view.map_range = None,
+						ScrollFocus::Segs => view.scroll.segs.home(),
+						ScrollFocus::Nodes => view.scroll.nodes.home(),
+					}
+				}
+				KeyCode::Char('u') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+					input.clear();
+				}
+				// only at the start of a line, so that they can still be
+				// typed as part of a command. Shift+- comes through as _
+				KeyCode::Char('+' | '-' | '_') if input.line.is_empty() && !prompt_pending
+								  && nudge_id.is_some() => {
+					let id = nudge_id.unwrap();
+					let mut granules = 1;
+					if key.code == KeyCode::Char('_') || key.modifiers
+						.intersects(KeyModifiers::SHIFT | KeyModifiers::ALT) {
+						granules = NUDGE_BIG_STEP;
+					}
+					if key.code != KeyCode::Char('+') {
+						granules = -granules;
+					}
+
+					if let Err(error) = board.nudge(id, granules) {
+						messages::warn(format!("{}: {}",
+								       board.apertures()[id].reg_name,
+								       error));
+					}
+				}
+				KeyCode::Up => {
+					input.older();
+				}
+				KeyCode::Down => {
+					input.newer();
+				}
+				KeyCode::Left => {
+					input.left();
+				}
+				KeyCode::Right => {
+					input.right();
+				}
+				KeyCode::Char(c) => {
+					input.insert(c);
+				}
+				KeyCode::Backspace => {
+					input.backspace();
+				}
+				KeyCode::Delete => {
+					input.delete();
+				}
+				// including a second Esc backing out of the quit prompt
+				KeyCode::Esc if prompt_pending => {
+					next_state = next_state.cancel();
+				}
+				KeyCode::Esc if view.search.is_some() && input.line.is_empty() => {
+					view.search = None;
+				}
+				KeyCode::Esc if modified => {
+					next_state = next_state.confirm_quit();
+				}
+				KeyCode::Esc => {
+					autosave::remove(&input_file);
+					return Ok(());
+				}
+				KeyCode::Tab => {
+					if let Some(completion) = commands::complete(&input.line) {
+						input.set(completion);
+					}
+				}
+				// the search has already been made while typing it, so
+				// there's nothing to run
+				KeyCode::Enter if input.line.starts_with('/') && !prompt_pending => {
+					input.take();
+				}
+				// help is only ever shown, never run
+				KeyCode::Enter if commands::help_for(&input.line).is_none() => {
+					messages.push(input.take());
+				}
+				_ => {}
+			}
+		}
+
+		let input = handle_messages(&mut messages);
+		let mut parsed_command = None;
+		// only for the save that this time round the loop makes, if any
+		let mut quit_after_save = false;
+		let mut save_confirmed = false;
+		if let Some(command) = input.clone() {
+			if !next_state.is_confirming() {
+				parsed_command = commands::parse(&command);
+			} else {
+				let answer = states::Event::Input(command);
+				next_state = next_state.handle(&mut board, answer);
+				match next_state.take_action() {
+					None => continue,
+					Some(states::Action::Quit) => {
+						autosave::remove(&input_file);
+						return Ok(());
+					}
+					Some(states::Action::Overwrite { quit }) => {
+						save_segs_to_config(&mut board, &input_file,
+								    &output_file, args.format,
+								    output_format, !args.no_backup)?;
+						saved_segs = seg_snapshot(&board);
+						if quit {
+							autosave::remove(&input_file);
+							return Ok(());
+						}
+						continue;
+					}
+					Some(states::Action::Save { quit, confirmed }) => {
+						quit_after_save = quit;
+						save_confirmed = confirmed;
+						parsed_command = Some(Ok(ParsedCommand::Save {
+							dry_run: false,
+							file: None,
+							format: None,
+						}));
+					}
+					Some(states::Action::Load(file)) => {
+						parsed_command = Some(Ok(ParsedCommand::Load(file)));
+					}
+				}
+			}
+		}
+
+		if parsed_command.is_none() {
+			parsed_command = key_command.map(Ok);
+		}
+
+		if let Some(Ok(ParsedCommand::Save { dry_run: dry_run_only, file, format })) =
+		   parsed_command {
+			// remembered even if it doesn't get saved this time, so that
+			// the next save goes there too
+			output_format = save_format(&file, format, output_format, &args);
+			output_file = file.unwrap_or(output_file);
+			if let Some(error) = board.alias_errors().first() {
+				next_state = states::after_command(format!("Not saved, {}", error));
+				continue;
+			}
+
+			if dry_run_only || args.dry_run {
+				let (output, changes) = dry_run_save(&mut board, &input_file,
+								     &output_file, args.format,
+								     output_format)?;
+				dry_run = Some(format!("{}\n{}", changes, output));
+				dry_run_scroll = 0;
+				next_state = states::after_command("Dry run, nothing was saved.".to_string());
+				continue;
+			}
+
+			let changes = seg_changes(&board, &saved_segs.0);
+			if !changes.is_empty() && !args.yes && !save_confirmed {
+				next_state = next_state.confirm_save(quit_after_save);
+				continue;
+			}
+
+			let output = generate_config(&mut board, &input_file, &output_file,
+						     args.format, output_format)?;
+			let warning = check_overwrite(&input_file, &output_file, &output);
+			if let Some(warning) = warning.filter(|_| return !args.force) {
+				next_state = next_state.confirm_overwrite(warning, quit_after_save);
+				continue;
+			}
+
+			save_segs_to_config(&mut board, &input_file, &output_file, args.format,
+					    output_format, !args.no_backup)?;
+			saved_segs = seg_snapshot(&board);
+			if quit_after_save {
+				autosave::remove(&input_file);
+				return Ok(());
+			}
+			next_state = states::after_command(format!("Saved to {}.", output_file.display()));
+			continue;
+		}
+
+		if let Some(Ok(ParsedCommand::Load(config_file))) = parsed_command {
+			let loaded = load_board(&board, &config_file, &args);
+			let (loaded_board, warnings) = match loaded {
+				Ok(loaded) => loaded,
+				Err(error) => {
+					next_state = states::after_command(error);
+					continue;
+				}
+			};
+
+			board = loaded_board;
+			saved_segs = seg_snapshot(&board);
+			// whatever wasn't saved was thrown away by loading over it
+			autosave::remove(&input_file);
+			autosaved_segs = saved_segs.clone();
+			if args.in_place {
+				output_file = config_file.clone();
+			}
+			input_file = config_file;
+			for warning in warnings {
+				messages::warn(format!("{}: {}", input_file.display(), warning));
+			}
+			next_state = states::after_command(format!("Loaded {}.", input_file.display()));
+			continue;
+		}
+
+		if let Some(Ok(ParsedCommand::Copy)) = parsed_command {
+			next_state = states::after_command(copy_seg_regs(&board));
+			continue;
+		}
+
+		if let Some(Ok(ParsedCommand::Dtb(file))) = &parsed_command {
+			let loaded = match load_dtb(&mut board, file, &args) {
+				Ok(loaded) => loaded,
+				Err(error) => {
+					next_state = states::after_command(error);
+					continue;
+				}
+			};
+
+			// overlays are of what's changed from this dtb now
+			original_nodes = loaded.memory_nodes.clone();
+			original_reserved = loaded.reserved_nodes.clone();
+			memory_nodes = Some(loaded.memory_nodes);
+			reserved_nodes = loaded.reserved_nodes;
+			dtb_cells = loaded.cells;
+			dtb_file = Some(file.clone());
+			if args.watch {
+				let watched = [input_file.clone(), file.clone()];
+				let sender = wakeup_sender.clone();
+				_watcher = Some(spawn_file_watcher(&watched, sender)?);
+			}
+			next_state = states::after_command(format!("Loaded {}.", file.display()));
+			continue;
+		}
+
+		if let Some(Ok(ParsedCommand::Export { format, file })) = &parsed_command {
+			let exported = export::export(&board,
+						      memory_nodes.as_deref().unwrap_or_default(),
+						      &reserved_nodes, *format);
+			let mut message = exported.clone();
+			if let Some(file) = file {
+				message = match fs::write(file, exported) {
+					Ok(_) => format!("Exported to {}.", file.display()),
+					Err(error) => format!("Failed to write {}: {}", file.display(),
+							      error),
+				};
+				info!("{:?} export: {}", format, message);
+			}
+			next_state = states::after_command(message);
+			continue;
+		}
+
+		if let Some(Ok(ParsedCommand::Node { label, address, size })) = &parsed_command {
+			let nodes = memory_nodes.get_or_insert_with(Vec::new);
+			let edited = dt::set_memory_node(nodes, label, *address, *size);
+			if edited.is_ok() {
+				dt::pin_node_apertures(nodes, &board.node_apertures);
+				warn_about_node(nodes, label, board.apertures());
+			}
+			next_state = states::after_command(edited.unwrap_or_else(|error| return error));
+			continue;
+		}
+
+		if let Some(Ok(ParsedCommand::Place)) = &parsed_command {
+			let placed = regions::place_regions(&mut board, &mut named_regions,
+							    &mut reserved_nodes);
+			info!("place: {:?}", placed);
+			next_state = states::after_command(placed.unwrap_or_else(|error| {
+				return error
+			}));
+			continue;
+		}
+
+		if let Some(Ok(ParsedCommand::Fix(choice))) = &parsed_command {
+			let nodes = memory_nodes.as_deref().unwrap_or_default();
+			if *choice == FixChoice::Pick {
+				let fixes = validate::fixes(&validate::check(&board, nodes,
+									    &reserved_nodes));
+				next_state = match fixes.is_empty() {
+					true => states::after_command("Nothing to fix.".to_string()),
+					false => states::State::pick_fix(&board, fixes),
+				};
+				continue;
+			}
+
+			let fixed = apply_fixes(&mut board, nodes, &reserved_nodes, *choice);
+			info!("fix: {:?}", fixed);
+			next_state = states::after_command(fixed.unwrap_or_else(|error| return error));
+			continue;
+		}
+
+		if let Some(Ok(ParsedCommand::RemoveNode(label))) = &parsed_command {
+			let removed = memory_nodes.as_mut()
+				.ok_or(format!("Unknown dt node {}.", label))
+				.and_then(|nodes| return dt::remove_memory_node(nodes, label));
+			if removed.is_ok() {
+				board.node_apertures.retain(|(node, _)| return node != label);
+			}
+			next_state = states::after_command(removed.unwrap_or_else(|error| return error));
+			continue;
+		}
+
+		if let Some(Ok(ParsedCommand::Overlay(file))) = &parsed_command {
+			let overlay = dt::memory_overlay(&original_nodes,
+							 memory_nodes.as_deref().unwrap_or_default(),
+							 &original_reserved, &reserved_nodes,
+							 dtb_cells);
+			let message = match overlay.map(|overlay| return overlay.map(|overlay| {
+				return dt::write_overlay(&overlay, file)
+			})) {
+				Err(error) => error,
+				Ok(None) => "No memory nodes have changed, there's nothing to \
+					     overlay.".to_string(),
+				Ok(Some(Ok(_))) => format!("Wrote the overlay to {}.",
+							   file.display()),
+				Ok(Some(Err(error))) => format!("Failed to write {}: {}",
+								file.display(), error),
+			};
+			info!("overlay: {}", message);
+			next_state = states::after_command(message);
+			continue;
+		}
+
+		if let (Some(Ok(ParsedCommand::Via { label, reg_name })), Some(nodes)) =
+			(&parsed_command, &memory_nodes) {
+			let checked = dt::check_node_aperture(nodes, label, reg_name,
+							      board.apertures());
+			if let Err(error) = checked {
+				next_state = states::after_command(error);
 				continue;
 			}
 		}
-		next_state = states::get_next_state(next_state, &mut board, input);
 
+		let previously_unmapped = unmapped_reserved_regions(&board, &reserved_nodes);
+		let previously_ambiguous = memory_nodes.as_ref()
+			.map(|nodes| return dt::ambiguous_nodes(nodes, board.apertures()))
+			.unwrap_or_default();
+		let previously_uncached = memory_nodes.as_ref()
+			.map(|nodes| return dt::uncached_nodes(nodes, board.apertures()))
+			.unwrap_or_default();
+		let previously_unreachable = memory_nodes.as_ref()
+			.map(|nodes| return dt::unreachable_regions(nodes, board.apertures()))
+			.unwrap_or_default();
+		match parsed_command {
+			Some(Ok(command)) => {
+				next_state = next_state.handle(&mut board, states::Event::Command(command));
+			}
+			Some(Err(usage)) => {
+				next_state = states::after_command(usage);
+			}
+			None => {
+				if let Some(input) = input {
+					next_state = next_state.handle(&mut board, states::Event::Input(input));
+				}
+			}
+		}
+
+		let newly_unmapped: Vec<String> =
+			unmapped_reserved_regions(&board, &reserved_nodes)
+			.into_iter()
+			.filter(|label| return !previously_unmapped.contains(label))
+			.collect();
+		if !newly_unmapped.is_empty() {
+			messages::warn(format!("reserved memory {} is no longer mapped!",
+					       newly_unmapped.join(", ")));
+		}
+
+		if let Some(nodes) = memory_nodes.as_mut() {
+			dt::pin_node_apertures(nodes, &board.node_apertures);
+			let newly_ambiguous: Vec<String> =
+				dt::ambiguous_nodes(nodes, board.apertures())
+				.into_iter()
+				.filter(|label| return !previously_ambiguous.contains(label))
+				.collect();
+			if !newly_ambiguous.is_empty() {
+				messages::warn(format!(
+					"{} could go through more than one aperture, \
+					 pick one with \"via <dt node> <register>\".",
+					newly_ambiguous.join(", ")
+				));
+			}
+
+			let newly_uncached: Vec<String> =
+				dt::uncached_nodes(nodes, board.apertures())
+				.into_iter()
+				.filter(|label| return !previously_uncached.contains(label))
+				.collect();
+			if !newly_uncached.is_empty() {
+				messages::warn(format!("memory {} is not cached.",
+						       newly_uncached.join(", ")));
+			}
+
+			let newly_unreachable: Vec<String> =
+				dt::unreachable_regions(nodes, board.apertures())
+				.into_iter()
+				.filter(|region| return !previously_unreachable.contains(region))
+				.collect();
+			for region in newly_unreachable {
+				messages::warn(format!("no aperture reaches {}.", region));
+			}
+		}
+
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn screen_matches_snapshot()
+	{
+		// the same screen as:
+		// seg-configurator -c config.yaml --memory 2G snapshot > testdata/screen.txt
+		// which is how to update it after changing the layout on purpose
+		let mut board = soc::MPFS::default();
+		setup_segs_from_str(&mut board, include_str!("config.yaml"), ConfigFormat::Yaml)
+			.unwrap();
+		board.total_system_memory = 0x8000_0000;
+		let problems = validate::check(&board, &[], &[]);
+		let screen = Screen {
+			board: &board,
+			memory_nodes: &None,
+			reserved_nodes: &[],
+			named_regions: &[],
+			problems: &problems,
+			command_text: &states::State::skip_init().command_text,
+			input: &lineedit::LineEditor::default(),
+			modified: false,
+		};
+
+		let text = screen_text(&screen, 200, 50).unwrap();
+		let expected = include_str!("testdata/screen.txt");
+		assert!(text == expected, "{}", diff::unified(expected, &text, "testdata/screen.txt",
+								"snapshot"));
+	}
+
+	fn laid_out_seg_mismatches(expected: &str) -> Vec<String>
+	{
+		// config.yaml's apertures with linux & a buffer for the fabric
+		// placed over them, which only needs seg1_2 to move
+		let mut board = soc::MPFS::default();
+		setup_segs_from_str(&mut board, include_str!("config.yaml"), ConfigFormat::Yaml)
+			.unwrap();
+		board.total_system_memory = 0x8000_0000;
+		let csv = "linux,0x40000000,cached\nfabric,0x1000000,non-cached;dma32\n";
+		let mut named_regions = regions::regions_from_csv(csv).unwrap();
+		let expected = config::parse_config(expected, ConfigFormat::Yaml).unwrap();
+
+		return layout_seg_mismatches(&mut board, &mut named_regions, &expected).unwrap()
+	}
+
+	#[test]
+	fn verify_passes_when_the_layout_gives_the_expected_segs()
+	{
+		let mismatches = laid_out_seg_mismatches("seg-reg-config: {seg0_0: '0x7FC0', \
+							  seg0_1: '0x7000', seg1_2: '0x7F80', \
+							  seg1_3: '0x0', seg1_4: '0x7FA0', \
+							  seg1_5: '0x0'}");
+		assert!(mismatches.is_empty(), "{:?}", mismatches);
+	}
+
+	#[test]
+	fn verify_fails_when_the_layout_moves_an_aperture()
+	{
+		// the config on its own, before the fabric buffer moved seg1_2
+		let mismatches = laid_out_seg_mismatches(include_str!("config.yaml"));
+		assert_eq!(mismatches, vec!["seg1_2: 0x7f80, expected 0x7fb0".to_string()]);
 	}
 }
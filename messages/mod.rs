@@ -0,0 +1,52 @@
+// SPDX-License-Identifier: MIT or GPL-2.0
+
+#![allow(unused_variables)]
+#![allow(dead_code)]
+#![deny(clippy::implicit_return)]
+#![allow(clippy::needless_return)]
+
+use std::cell::RefCell;
+
+// diagnostics from anywhere in the program end up here rather than being
+// printed, which would scribble all over the tui. the tui shows them in a
+// pane of their own & batch mode prints them to stderr
+thread_local! {
+	static MESSAGES: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+// the pane only shows the last few, the rest are kept for batch mode to
+// print, without growing forever
+const MAX_MESSAGES: usize = 100;
+
+pub fn push(message: String)
+{
+	MESSAGES.with(|messages| {
+		let mut messages = messages.borrow_mut();
+		messages.push(message);
+		if messages.len() > MAX_MESSAGES {
+			messages.remove(0);
+		}
+	});
+}
+
+pub fn warn(message: String)
+{
+	push(format!("Warning: {}", message));
+}
+
+pub fn recent(count: usize) -> Vec<String>
+{
+	return MESSAGES.with(|messages| {
+		let messages = messages.borrow();
+		let skip = messages.len().saturating_sub(count);
+
+		return messages[skip..].to_vec()
+	})
+}
+
+pub fn drain() -> Vec<String>
+{
+	return MESSAGES.with(|messages| {
+		return messages.borrow_mut().drain(..).collect()
+	})
+}
@@ -0,0 +1,42 @@
+// SPDX-License-Identifier: MIT or GPL-2.0
+
+#![allow(unused_variables)]
+#![allow(dead_code)]
+#![deny(clippy::implicit_return)]
+#![allow(clippy::needless_return)]
+
+// spaces between one column & the next
+const COLUMN_GAP: usize = 2;
+
+pub fn table(headers: &[&str], rows: &[Vec<String>]) -> String
+{
+	// every column as wide as the widest thing in it & nothing else, no
+	// borders, colours or markers, so that a screen reader or a dumb
+	// terminal gets only the words & numbers
+	let mut widths: Vec<usize> = headers.iter()
+		.map(|header| return header.chars().count())
+		.collect();
+	for row in rows {
+		for (column, cell) in row.iter().enumerate() {
+			let width = cell.trim().chars().count();
+			match widths.get_mut(column) {
+				Some(widest) => *widest = width.max(*widest),
+				None => widths.push(width),
+			}
+		}
+	}
+
+	let headers: Vec<String> = headers.iter().map(|header| return header.to_string()).collect();
+	let mut output = String::new();
+	for row in std::iter::once(&headers).chain(rows) {
+		let line: String = row.iter()
+			.zip(&widths)
+			.map(|(cell, width)| return format!("{:<width$}", cell.trim(),
+							      width = width + COLUMN_GAP))
+			.collect();
+		output += line.trim_end();
+		output.push('\n');
+	}
+
+	return output
+}
@@ -0,0 +1,410 @@
+// SPDX-License-Identifier: MIT or GPL-2.0
+
+#![allow(unused_variables)]
+#![allow(dead_code)]
+#![deny(clippy::implicit_return)]
+#![allow(clippy::needless_return)]
+
+use serde_json::{json, Value};
+
+use crate::dt::MemoryNode;
+use crate::dt::MemoryRegion;
+use crate::dt::ReservedMemoryNode;
+use crate::regions;
+use crate::regions::NamedRegion;
+use crate::soc::Access;
+use crate::soc::Aperture;
+use crate::soc::MPFS;
+use crate::soc::PerformanceClass;
+
+pub const PLAN_VERSION: u64 = 1;
+pub const PLAN_SCHEMA: &str = include_str!("schema.json");
+
+pub struct Provenance {
+	pub config: Option<String>,
+	pub dtb: Option<String>,
+}
+
+pub struct BoardPlan {
+	pub memory_nodes: Option<Vec<MemoryNode>>,
+	pub reserved_nodes: Vec<ReservedMemoryNode>,
+	pub named_regions: Vec<NamedRegion>,
+}
+
+pub fn hex(value: u64) -> Value
+{
+	return Value::String(format!("{:#x}", value))
+}
+
+fn regions_to_json(regions: &[MemoryRegion]) -> Value
+{
+	return regions.iter()
+		.map(|region| return json!({
+			"address": hex(region.address),
+			"size": hex(region.size),
+		}))
+		.collect()
+}
+
+fn constraints_to_json(named_regions: &[NamedRegion]) -> Value
+{
+	// what each region asks of where it goes, as typed fields rather than
+	// the attribute strings they came from. a region whose attributes don't
+	// make sense is left to its attributes
+	return named_regions.iter()
+		.filter_map(|region| {
+			let constraints = regions::constraints(region).ok()?;
+			let tags: Vec<&String> = region.attributes.iter()
+				.filter(|attribute| return !regions::is_constraint(attribute))
+				.collect();
+			return Some(json!({
+				"region": region.name,
+				"class": constraints.class.name(),
+				"dma32": constraints.dma32,
+				"align": hex(constraints.align),
+				"at": constraints.fixed.map(hex),
+				"access": constraints.access.map(|access| return access.to_string()),
+				"attributes": tags,
+			}))
+		})
+		.collect()
+}
+
+pub fn export_plan
+(board: &MPFS, memory_nodes: &Option<Vec<MemoryNode>>, reserved_nodes: &[ReservedMemoryNode],
+ named_regions: &[NamedRegion], provenance: Provenance) -> Value
+{
+	let apertures: Value = board.memory_apertures.iter()
+		.map(|aperture| return json!({
+			"reg-name": aperture.reg_name,
+			"description": aperture.description(),
+			"bus-width": aperture.bus_width.bits(),
+			"bus-addr": hex(aperture.bus_addr),
+			"hardware-addr": hex(aperture.hardware_addr),
+			"aperture-size": hex(aperture.aperture_size),
+			"seg": aperture.seg().ok().map(hex),
+			"performance-class": aperture.performance_class.to_string(),
+			"aliases-with": aperture.aliases_with,
+		}))
+		.collect();
+
+	let regions: Value = named_regions.iter()
+		.map(|region| return json!({
+			"name": region.name,
+			"size": hex(region.size),
+			"attributes": region.attributes,
+			"hardware-addr": region.hardware_addr.map(hex),
+		}))
+		.collect();
+
+	let mut nodes = Value::Array(Vec::new());
+	if let Some(memory_nodes) = memory_nodes {
+		nodes = memory_nodes.iter()
+			.map(|node| return json!({
+				"label": node.label,
+				"enabled": node.enabled,
+				"regions": regions_to_json(&node.regions),
+			}))
+			.collect();
+	}
+
+	let reserved: Value = reserved_nodes.iter()
+		.map(|node| return json!({
+			"label": node.label,
+			"no-map": node.no_map,
+			"reusable": node.reusable,
+			"regions": regions_to_json(&node.regions),
+		}))
+		.collect();
+
+	return json!({
+		"plan-version": PLAN_VERSION,
+		"provenance": {
+			"tool": env!("CARGO_PKG_NAME"),
+			"tool-version": env!("CARGO_PKG_VERSION"),
+			"config": provenance.config,
+			"dtb": provenance.dtb,
+		},
+		"total-system-memory": hex(board.total_system_memory),
+		"apertures": apertures,
+		"regions": regions,
+		"memory-nodes": nodes,
+		"reserved-memory": reserved,
+		"constraints": constraints_to_json(named_regions),
+	})
+}
+
+fn parse_hex(value: &Value, path: &str) -> Result<u64, String>
+{
+	let hex_string = value.as_str()
+		.ok_or(format!("{}: expected a hex string", path))?;
+	let hex_trimmed = hex_string.strip_prefix("0x")
+		.ok_or(format!("{}: \"{}\" is missing the 0x prefix", path, hex_string))?;
+
+	return u64::from_str_radix(hex_trimmed, 16)
+		.map_err(|error| return format!("{}: {}", path, error))
+}
+
+fn parse_string(value: &Value, path: &str) -> Result<String, String>
+{
+	return value.as_str()
+		.map(|string| return string.to_string())
+		.ok_or(format!("{}: expected a string", path))
+}
+
+static EMPTY: Vec<Value> = Vec::new();
+
+fn parse_array<'a>(value: &'a Value, path: &str) -> Result<&'a Vec<Value>, String>
+{
+	// optional arrays are treated as empty if missing
+	if value.is_null() {
+		return Ok(&EMPTY)
+	}
+
+	return value.as_array().ok_or(format!("{}: expected an array", path))
+}
+
+fn parse_regions(value: &Value, path: &str) -> Result<Vec<MemoryRegion>, String>
+{
+	let mut regions: Vec<MemoryRegion> = Vec::new();
+	for (index, region) in parse_array(value, path)?.iter().enumerate() {
+		let region_path = format!("{}[{}]", path, index);
+		regions.push(MemoryRegion {
+			address: parse_hex(&region["address"], &format!("{}.address", region_path))?,
+			size: parse_hex(&region["size"], &format!("{}.size", region_path))?,
+			aperture: None,
+		});
+	}
+
+	return Ok(regions)
+}
+
+fn parse_strings(value: &Value, path: &str) -> Result<Vec<String>, String>
+{
+	let mut strings: Vec<String> = Vec::new();
+	for (index, string) in parse_array(value, path)?.iter().enumerate() {
+		strings.push(parse_string(string, &format!("{}[{}]", path, index))?);
+	}
+
+	return Ok(strings)
+}
+
+fn apply_constraint(constraint: &Value, path: &str, named_regions: &mut [NamedRegion])
+-> Result<(), String>
+{
+	// a region's constraints replace whatever its attributes asked for,
+	// keeping only the attributes that are plain tags
+	let name = parse_string(&constraint["region"], &format!("{}.region", path))?;
+	let region = named_regions.iter_mut()
+		.find(|region| return region.name == name)
+		.ok_or(format!("{}.region: there's no region called {}", path, name))?;
+
+	let class_name = parse_string(&constraint["class"], &format!("{}.class", path))?;
+	let class = PerformanceClass::from_name(&class_name)
+		.ok_or(format!("{}.class: unknown class {}", path, class_name))?;
+	let dma32 = &constraint["dma32"];
+	if !dma32.is_null() && !dma32.is_boolean() {
+		return Err(format!("{}.dma32: expected a boolean", path))
+	}
+	let mut align = 1;
+	if !constraint["align"].is_null() {
+		align = parse_hex(&constraint["align"], &format!("{}.align", path))?;
+	}
+	let mut fixed = None;
+	if !constraint["at"].is_null() {
+		fixed = Some(parse_hex(&constraint["at"], &format!("{}.at", path))?);
+	}
+	let mut access = None;
+	if !constraint["access"].is_null() {
+		let access_path = format!("{}.access", path);
+		let text = parse_string(&constraint["access"], &access_path)?;
+		access = Some(Access::parse(&text)
+			.map_err(|error| return format!("{}: {}", access_path, error))?);
+	}
+	let tags = parse_strings(&constraint["attributes"], &format!("{}.attributes", path))?;
+
+	let constraints = regions::Constraints {
+		align,
+		fixed,
+		class,
+		dma32: dma32.as_bool().unwrap_or(false),
+		access,
+	};
+	region.attributes = constraints.attributes();
+	region.attributes.extend(tags.into_iter()
+		.filter(|tag| return !regions::is_constraint(tag)));
+
+	// the same checks as for attributes, e.g. that align is a power of two
+	regions::constraints(region).map_err(|error| return format!("{}: {}", path, error))?;
+
+	return Ok(())
+}
+
+pub fn import_plan(plan: &Value, board: &mut MPFS) -> Result<BoardPlan, String>
+{
+	// this mirrors the published schema, it does not use a general purpose
+	// validator, so keep the two in sync
+	if !plan.is_object() {
+		return Err("plan: expected an object".to_string())
+	}
+
+	let version = plan["plan-version"].as_u64()
+		.ok_or("plan-version: expected an integer")?;
+	if version != PLAN_VERSION {
+		return Err(format!("plan-version: unsupported version {}", version))
+	}
+
+	parse_string(&plan["provenance"]["tool"], "provenance.tool")?;
+	parse_string(&plan["provenance"]["tool-version"], "provenance.tool-version")?;
+
+	board.total_system_memory = parse_hex(&plan["total-system-memory"],
+					      "total-system-memory")?;
+
+	let banks = board.banks();
+	let apertures = plan["apertures"].as_array()
+		.ok_or("apertures: expected an array")?;
+	for (index, aperture) in apertures.iter().enumerate() {
+		let path = format!("apertures[{}]", index);
+		let reg_name = parse_string(&aperture["reg-name"], &format!("{}.reg-name", path))?;
+		let hardware_addr = parse_hex(&aperture["hardware-addr"],
+					      &format!("{}.hardware-addr", path))?;
+		parse_hex(&aperture["bus-addr"], &format!("{}.bus-addr", path))?;
+		parse_hex(&aperture["aperture-size"], &format!("{}.aperture-size", path))?;
+		parse_hex(&aperture["seg"], &format!("{}.seg", path))?;
+
+		let memory_aperture = board.memory_apertures.iter_mut()
+			.find(|memory_aperture| return memory_aperture.reg_name == reg_name)
+			.ok_or(format!("{}.reg-name: unknown register {}", path, reg_name))?;
+		memory_aperture.set_hw_start_addr(&banks, hardware_addr)
+			.map_err(|error| return format!("{}.hardware-addr: {}", path, error))?;
+	}
+
+	let mut named_regions: Vec<NamedRegion> = Vec::new();
+	for (index, region) in parse_array(&plan["regions"], "regions")?.iter().enumerate() {
+		let path = format!("regions[{}]", index);
+		let attributes = parse_strings(&region["attributes"],
+					       &format!("{}.attributes", path))?;
+
+		let size = parse_hex(&region["size"], &format!("{}.size", path))?;
+		if size == 0 {
+			return Err(format!("{}.size: a region can't be empty", path))
+		}
+
+		named_regions.push(NamedRegion {
+			name: parse_string(&region["name"], &format!("{}.name", path))?,
+			size,
+			attributes,
+			hardware_addr: None,
+		});
+	}
+
+	for (index, constraint) in parse_array(&plan["constraints"], "constraints")?
+				   .iter().enumerate() {
+		apply_constraint(constraint, &format!("constraints[{}]", index),
+				 &mut named_regions)?;
+	}
+
+	let mut memory_nodes: Vec<MemoryNode> = Vec::new();
+	for (index, node) in parse_array(&plan["memory-nodes"], "memory-nodes")?.iter().enumerate() {
+		let path = format!("memory-nodes[{}]", index);
+		memory_nodes.push(MemoryNode {
+			label: parse_string(&node["label"], &format!("{}.label", path))?,
+			regions: parse_regions(&node["regions"], &format!("{}.regions", path))?,
+			enabled: node["enabled"].as_bool().unwrap_or(true),
+		});
+	}
+
+	let mut reserved_nodes: Vec<ReservedMemoryNode> = Vec::new();
+	for (index, node) in parse_array(&plan["reserved-memory"], "reserved-memory")?
+			     .iter().enumerate() {
+		let path = format!("reserved-memory[{}]", index);
+		reserved_nodes.push(ReservedMemoryNode {
+			label: parse_string(&node["label"], &format!("{}.label", path))?,
+			regions: parse_regions(&node["regions"], &format!("{}.regions", path))?,
+			no_map: node["no-map"].as_bool().unwrap_or(false),
+			reusable: node["reusable"].as_bool().unwrap_or(false),
+		});
+	}
+
+	let mut plan_nodes = None;
+	if !memory_nodes.is_empty() {
+		plan_nodes = Some(memory_nodes);
+	}
+
+	return Ok(BoardPlan {
+		memory_nodes: plan_nodes,
+		reserved_nodes,
+		named_regions,
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn named_region(name: &str, size: u64, attributes: &[&str]) -> NamedRegion
+	{
+		return NamedRegion {
+			name: name.to_string(),
+			size,
+			attributes: attributes.iter().map(|attribute| return attribute.to_string())
+				.collect(),
+			hardware_addr: None,
+		}
+	}
+
+	#[test]
+	fn plan_roundtrips()
+	{
+		let mut board = MPFS { total_system_memory: 0x8000_0000, ..Default::default() };
+		let banks = board.banks();
+		board.memory_apertures[1].set_hw_start_addr(&banks, 0x4000_0000)
+			.unwrap();
+		let named_regions = vec![
+			named_region("linux", 0x4000_0000, &["cached"]),
+			named_region("fabric-dma", 0x100_0000,
+				     &["non-cached", "dma32", "align=1M", "access=rw", "shared"]),
+			named_region("hss-scratch", 0x10_0000, &["at=0x7ff00000", "no-map"]),
+		];
+		let provenance = Provenance { config: None, dtb: None };
+		let exported = export_plan(&board, &None, &[], &named_regions, provenance);
+		assert_eq!(exported["constraints"].as_array().unwrap().len(), named_regions.len());
+
+		let mut imported_board = MPFS::default();
+		let imported = import_plan(&exported, &mut imported_board).unwrap();
+		assert_eq!(imported_board.total_system_memory, board.total_system_memory);
+		for (imported, exported) in imported_board.memory_apertures.iter()
+					    .zip(board.memory_apertures.iter()) {
+			assert_eq!(imported.hardware_addr, exported.hardware_addr);
+		}
+
+		for (imported, exported) in imported.named_regions.iter().zip(named_regions.iter()) {
+			assert_eq!(imported.name, exported.name);
+			assert_eq!(imported.size, exported.size);
+			let imported_constraints = regions::constraints(imported).unwrap();
+			let exported_constraints = regions::constraints(exported).unwrap();
+			assert_eq!(imported_constraints.attributes(),
+				   exported_constraints.attributes());
+		}
+		assert!(imported.named_regions[1].attributes.contains(&"shared".to_string()));
+		assert!(imported.named_regions[2].attributes.contains(&"no-map".to_string()));
+
+		let reexported = export_plan(&imported_board, &None, &[], &imported.named_regions,
+					     Provenance { config: None, dtb: None });
+		assert_eq!(reexported["constraints"], exported["constraints"]);
+		assert_eq!(reexported["apertures"], exported["apertures"]);
+	}
+
+	#[test]
+	fn constraints_for_unknown_regions_are_refused()
+	{
+		let board = MPFS::default();
+		let named_regions = vec![named_region("linux", 0x4000_0000, &["cached"])];
+		let mut plan = export_plan(&board, &None, &[], &named_regions,
+					   Provenance { config: None, dtb: None });
+		plan["constraints"][0]["region"] = json!("u-boot");
+
+		let error = import_plan(&plan, &mut MPFS::default()).err().unwrap();
+		assert!(error.starts_with("constraints[0].region"), "{}", error);
+	}
+}
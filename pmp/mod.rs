@@ -0,0 +1,164 @@
+// SPDX-License-Identifier: MIT or GPL-2.0
+
+#![allow(unused_variables)]
+#![allow(dead_code)]
+#![deny(clippy::implicit_return)]
+#![allow(clippy::needless_return)]
+
+use crate::contexts;
+use crate::soc;
+use crate::soc::{PerformanceClass, SoC};
+
+// the u54s each have 16 pmp entries
+pub const PMP_ENTRIES_PER_HART: usize = 16;
+
+const PMP_R: u8 = 1 << 0;
+const PMP_W: u8 = 1 << 1;
+const PMP_X: u8 = 1 << 2;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PmpMode {
+	Off,
+	Tor,
+	Napot,
+}
+
+impl PmpMode {
+	pub fn name(&self) -> &'static str
+	{
+		match self {
+			PmpMode::Off => return "off",
+			PmpMode::Tor => return "tor",
+			PmpMode::Napot => return "napot",
+		}
+	}
+
+	fn cfg_bits(&self) -> u8
+	{
+		// the A field of pmpcfg
+		match self {
+			PmpMode::Off => return 0,
+			PmpMode::Tor => return 1 << 3,
+			PmpMode::Napot => return 3 << 3,
+		}
+	}
+}
+
+// one pmpcfg/pmpaddr pair, along with the region of (bus) address space it
+// covers. an off entry only exists to hold the base of the tor entry after it
+#[derive(Debug, Clone)]
+pub struct PmpEntry {
+	pub mode: PmpMode,
+	pub cfg: u8,
+	pub addr: u64,
+	pub base: u64,
+	pub size: u64,
+}
+
+impl PmpEntry {
+	pub fn access(&self) -> soc::Access
+	{
+		return soc::Access {
+			read: self.cfg & PMP_R != 0,
+			write: self.cfg & PMP_W != 0,
+			execute: self.cfg & PMP_X != 0,
+		}
+	}
+}
+
+fn napot_entry(base: u64, size: u64, permissions: u8) -> PmpEntry
+{
+	return PmpEntry {
+		mode: PmpMode::Napot,
+		cfg: PmpMode::Napot.cfg_bits() | permissions,
+		addr: (base >> 2) | ((size >> 3) - 1),
+		base,
+		size,
+	}
+}
+
+fn napot_pieces(base: u64, size: u64) -> Vec<(u64, u64)>
+{
+	// the fewest naturally aligned power of two pieces that make up the
+	// region, biggest first from wherever the last one left off
+	let mut pieces: Vec<(u64, u64)> = Vec::new();
+	let mut start = base;
+	let end = base + size;
+	while start < end {
+		let mut piece = 1u64 << (63 - (end - start).leading_zeros());
+		if start != 0 {
+			piece = piece.min(1 << start.trailing_zeros());
+		}
+		pieces.push((start, piece));
+		start += piece;
+	}
+
+	return pieces
+}
+
+pub fn permissions(access: &soc::Access) -> u8
+{
+	let mut permissions = 0;
+	let bits = [(access.read, PMP_R), (access.write, PMP_W), (access.execute, PMP_X)];
+	for (allowed, bit) in bits {
+		if allowed {
+			permissions |= bit;
+		}
+	}
+
+	return permissions
+}
+
+pub fn region_entries(base: u64, size: u64, access: &soc::Access) -> Vec<PmpEntry>
+{
+	// napot needs at least 8 bytes & everything 4 byte aligned, which the
+	// 16 MiB granularity of the seg registers more than takes care of. if
+	// a region can't be done with a couple of napot entries, a tor pair
+	// is cheaper
+	let permissions = permissions(access);
+	if size < 8 || base & 3 != 0 || size & 3 != 0 {
+		return Vec::new()
+	}
+
+	let pieces = napot_pieces(base, size);
+	if pieces.len() <= 2 && pieces.iter().all(|(_, piece)| return *piece >= 8) {
+		return pieces.iter()
+			.map(|(start, piece)| return napot_entry(*start, *piece, permissions))
+			.collect()
+	}
+
+	return vec![
+		PmpEntry {
+			mode: PmpMode::Off,
+			cfg: PmpMode::Off.cfg_bits(),
+			addr: base >> 2,
+			base,
+			size: 0,
+		},
+		PmpEntry {
+			mode: PmpMode::Tor,
+			cfg: PmpMode::Tor.cfg_bits() | permissions,
+			addr: (base + size) >> 2,
+			base,
+			size,
+		},
+	]
+}
+
+pub fn context_entries(board: &soc::MPFS, context: &contexts::Context) -> Vec<PmpEntry>
+{
+	// the harts see memory at its bus address, so that's what the entries
+	// have to cover, i.e. the context's memory through the apertures, with
+	// whatever access each of those apertures allows
+	let (banks, unreachable) = contexts::context_banks(board, context);
+
+	return banks.iter()
+		.flat_map(|bank| {
+			let access = board.apertures().iter()
+				.find(|aperture| return aperture.reg_name == bank.reg_name)
+				.map(|aperture| return aperture.access)
+				.unwrap_or(soc::Access::default_for(PerformanceClass::CachedAxi));
+			return region_entries(bank.bus_addr, bank.size, &access)
+		})
+		.collect()
+}
@@ -0,0 +1,93 @@
+// SPDX-License-Identifier: MIT or GPL-2.0
+
+#![allow(unused_variables)]
+#![allow(dead_code)]
+#![deny(clippy::implicit_return)]
+#![allow(clippy::needless_return)]
+
+use crate::soc;
+
+fn parse_hex(word: &str) -> Option<u64>
+{
+	// dumps tend to leave the 0x off, so everything is taken to be hex
+	let digits = word.trim_start_matches("0x").trim_start_matches("0X");
+	if digits.is_empty() || digits.len() > 16 {
+		return None
+	}
+
+	return u64::from_str_radix(digits, 16).ok()
+}
+
+pub fn parse_register_dump(contents: &str, reg_names: &[String])
+-> Result<Vec<(String, u64)>, String>
+{
+	// reads the seg registers back out of a dump of a running board, as any
+	// of (a line at a time):
+	// - "md.l"/"mdw" style "<address>: <word> <word> ...", with anything
+	//   after the words, like an ascii column, ignored
+	// - "<register>: <value>" or "<register> = <value>"
+	// - bare values, which are taken to be the registers in order
+	// anything else, like the prompt & command that produced the dump, is
+	// skipped over
+	let mut segs: Vec<(String, u64)> = Vec::new();
+	let mut next_bare = 0;
+
+	for (line_number, line) in contents.lines().enumerate() {
+		let line = line.trim();
+		if line.is_empty() || line.starts_with('#') {
+			continue;
+		}
+
+		let split = line.split_once(':').or_else(|| return line.split_once('='));
+		if split.is_none() {
+			let values: Option<Vec<u64>> = line.split_whitespace()
+				.map(parse_hex)
+				.collect();
+			for value in values.unwrap_or_default() {
+				let reg_name = reg_names.get(next_bare)
+					.ok_or(format!("line {}: more values than there are \
+						       seg registers", line_number + 1))?;
+				segs.push((reg_name.clone(), value));
+				next_bare += 1;
+			}
+			continue;
+		}
+
+		let (first, rest) = split.unwrap();
+		let first = first.trim();
+		if let Some(reg_name) = reg_names.iter().find(|name| return name.as_str() == first) {
+			let value = rest.split_whitespace()
+				.next()
+				.and_then(parse_hex)
+				.ok_or(format!("line {}: no value for {}", line_number + 1,
+					       reg_name))?;
+			segs.push((reg_name.clone(), value));
+			continue;
+		}
+
+		let addr = parse_hex(first);
+		if addr.is_none() {
+			continue;
+		}
+
+		let mut addr = addr.unwrap();
+		// the words of a dump are all the same width, the first one that
+		// isn't is the start of whatever gets printed after them
+		let words = rest.split_whitespace()
+			.take_while(|word| return word.len() == 8 && parse_hex(word).is_some());
+		for word in words {
+			let reg_name = reg_names.iter()
+				.find(|name| return soc::seg_reg_addr(name) == Some(addr));
+			if let Some(reg_name) = reg_name {
+				segs.push((reg_name.clone(), parse_hex(word).unwrap_or(0)));
+			}
+			addr += 4;
+		}
+	}
+
+	if segs.is_empty() {
+		return Err("no seg register values found".to_string())
+	}
+
+	return Ok(segs)
+}
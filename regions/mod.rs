@@ -0,0 +1,568 @@
+// SPDX-License-Identifier: MIT or GPL-2.0
+
+#![allow(unused_variables)]
+#![allow(dead_code)]
+#![deny(clippy::implicit_return)]
+#![allow(clippy::needless_return)]
+
+use std::fs;
+use std::path::Path;
+
+use crate::dt::{MemoryRegion, ReservedMemoryNode};
+use crate::soc;
+use crate::soc::{Access, MemoryAperture, PerformanceClass, SoC};
+use crate::units;
+
+// anything that a 32-bit dma engine has to reach needs a bus address below this
+const DMA32_LIMIT: u64 = 1 << 32;
+
+#[derive(Clone, Debug)]
+pub struct NamedRegion {
+	pub name: String,
+	pub size: u64,
+	pub attributes: Vec<String>,
+	pub hardware_addr: Option<u64>,
+}
+
+fn split_csv_line(line: &str) -> Vec<String>
+{
+	// spreadsheets only quote a field when it has a comma in it, so a
+	// quote toggles whether commas split fields and "" is a literal quote
+	let mut fields: Vec<String> = Vec::new();
+	let mut field = String::new();
+	let mut in_quotes = false;
+	let mut chars = line.chars().peekable();
+
+	while let Some(c) = chars.next() {
+		match c {
+			'"' if in_quotes && chars.peek() == Some(&'"') => {
+				field.push('"');
+				chars.next();
+			}
+			'"' => {
+				in_quotes = !in_quotes;
+			}
+			',' if !in_quotes => {
+				fields.push(field.trim().to_string());
+				field.clear();
+			}
+			_ => {
+				field.push(c);
+			}
+		}
+	}
+	fields.push(field.trim().to_string());
+
+	return fields
+}
+
+pub fn regions_from_csv(csv: &str) -> Result<Vec<NamedRegion>, Box<dyn std::error::Error>>
+{
+	let mut regions: Vec<NamedRegion> = Vec::new();
+
+	for (line_number, line) in csv.lines().enumerate() {
+		let line = line.trim();
+		if line.is_empty() || line.starts_with('#') {
+			continue;
+		}
+
+		let fields = split_csv_line(line);
+		if fields.len() < 2 {
+			return Err(format!("line {}: expected at least a name and a size",
+					   line_number + 1).into())
+		}
+
+		let size = units::parse_size(&fields[1]);
+		if size.is_err() {
+			// the first row is usually the column headings
+			if regions.is_empty() && line_number == 0 {
+				continue;
+			}
+
+			return Err(format!("line {}: invalid size \"{}\"",
+					   line_number + 1, fields[1]).into())
+		}
+		if matches!(size, Ok(0)) {
+			return Err(format!("line {}: {} can't be empty", line_number + 1,
+					   fields[0]).into())
+		}
+
+		let mut attributes: Vec<String> = Vec::new();
+		for field in fields.iter().skip(2) {
+			attributes.extend(
+				field.split(|c| return c == ';' || c == ' ')
+				.filter(|attribute| return !attribute.is_empty())
+				.map(|attribute| return attribute.to_lowercase())
+			);
+		}
+
+		regions.push(NamedRegion {
+			name: fields[0].clone(),
+			size: size.unwrap(),
+			attributes,
+			hardware_addr: None,
+		});
+	}
+
+	return Ok(regions)
+}
+
+pub fn csv_get_regions(csv_file: &Path)
+-> Result<Vec<NamedRegion>, Box<dyn std::error::Error>>
+{
+	let csv = fs::read_to_string(csv_file)?;
+	return regions_from_csv(&csv)
+}
+
+fn has_attribute(region: &NamedRegion, attribute: &str) -> bool
+{
+	return region.attributes.iter().any(|other| return other == attribute)
+}
+
+fn wanted_class(region: &NamedRegion) -> PerformanceClass
+{
+	// cached unless the region's attributes ask for something else
+	for class in [PerformanceClass::NonCached, PerformanceClass::WriteCombining] {
+		if has_attribute(region, class.name()) {
+			return class
+		}
+	}
+
+	return PerformanceClass::CachedAxi
+}
+
+// what a region's attributes ask of where it goes
+pub struct Constraints {
+	pub align: u64,
+	pub fixed: Option<u64>,
+	pub class: PerformanceClass,
+	pub dma32: bool,
+	// only what the region says it needs, else whatever its aperture allows
+	pub access: Option<Access>,
+}
+
+impl Constraints {
+	// the attributes that ask for exactly these, for a region that has had
+	// its constraints given some other way, e.g. in a plan
+	pub fn attributes(&self) -> Vec<String>
+	{
+		let mut attributes: Vec<String> = Vec::new();
+		if !self.class.is_cached() {
+			attributes.push(self.class.name().to_string());
+		}
+		if self.dma32 {
+			attributes.push("dma32".to_string());
+		}
+		if self.align > 1 {
+			attributes.push(format!("align={:#x}", self.align));
+		}
+		if let Some(fixed) = self.fixed {
+			attributes.push(format!("at={:#x}", fixed));
+		}
+		if let Some(access) = self.access {
+			attributes.push(format!("access={}", access));
+		}
+
+		return attributes
+	}
+
+	fn kind(&self) -> String
+	{
+		let mut kind = self.class.name().to_string();
+		if self.dma32 {
+			kind += " 32-bit";
+		}
+
+		return kind
+	}
+}
+
+// whether constraints() makes something of the attribute, rather than it
+// being a plain tag
+pub fn is_constraint(attribute: &str) -> bool
+{
+	return attribute == "dma32" || attribute.contains('=') ||
+	       PerformanceClass::from_name(attribute).is_some()
+}
+
+pub fn constraints(region: &NamedRegion) -> Result<Constraints, String>
+{
+	// "align=<size>", "at=<address>" & "access=<rwx>" take a value,
+	// anything else is a flag
+	let mut constraints = Constraints {
+		align: 1,
+		fixed: None,
+		class: wanted_class(region),
+		dma32: has_attribute(region, "dma32"),
+		access: None,
+	};
+	if has_attribute(region, "cached") && !constraints.class.is_cached() {
+		return Err(format!("{} asks to be both cached & {}.", region.name,
+				   constraints.class.name()))
+	}
+
+	for (key, value) in region.attributes.iter().filter_map(|attribute| {
+		return attribute.split_once('=')
+	}) {
+		if key == "access" {
+			let access = Access::parse(value)
+				.map_err(|error| return format!("{}: {}.", region.name, error))?;
+			constraints.access = Some(access);
+			continue;
+		}
+
+		let parsed = units::parse_size(value)
+			.map_err(|_| return format!("{} has an invalid {} \"{}\".", region.name, key,
+						    value))?;
+		match key {
+			"align" if parsed.is_power_of_two() => constraints.align = parsed,
+			"align" => return Err(format!("{} has an alignment of {}, which isn't a \
+						      power of two.", region.name, value)),
+			"at" => constraints.fixed = Some(parsed),
+			_ => return Err(format!("{} has an unknown attribute \"{}={}\".",
+						region.name, key, value)),
+		}
+	}
+
+	return Ok(constraints)
+}
+
+// where a region ended up, & the aperture it's reached through
+#[derive(Clone, Copy, Debug)]
+pub struct Placement {
+	pub hardware_addr: u64,
+	pub aperture: usize,
+}
+
+fn pick_aperture(board: &soc::MPFS, spans: &[Option<(u64, u64)>], constraints: &Constraints,
+		 start: u64, end: u64)
+-> Option<(usize, (u64, u64))>
+{
+	// the first aperture of the right kind that can cover the region as well
+	// as whatever it already has to, trying the biggest windows first so
+	// that the 32-bit ones are left for what has to be reached through them
+	let apertures = board.apertures();
+	let mut candidates: Vec<usize> = (0..apertures.len())
+		.filter(|id| return apertures[*id].performance_class == constraints.class)
+		.filter(|id| {
+			let aperture = &apertures[*id];
+			return !constraints.dma32 ||
+				aperture.bus_addr + aperture.aperture_size <= DMA32_LIMIT
+		})
+		.collect();
+	candidates.sort_by_key(|id| return std::cmp::Reverse(apertures[*id].aperture_size));
+
+	for id in candidates {
+		let (span_start, span_end) = spans[id]
+			.map(|(span_start, span_end)| {
+				return (span_start.min(start), span_end.max(end))
+			})
+			.unwrap_or((start, end));
+		let hw_start_addr = span_start & !(soc::SEG_GRANULARITY - 1);
+		if span_end <= hw_start_addr + apertures[id].aperture_size &&
+		   soc::hw_start_addr_to_seg(hw_start_addr, apertures[id].bus_addr).is_ok() {
+			return Some((id, (span_start, span_end)))
+		}
+	}
+
+	return None
+}
+
+fn place_region(board: &soc::MPFS, region: &NamedRegion, taken: &mut Vec<(u64, u64, String)>,
+		spans: &mut [Option<(u64, u64)>])
+-> Result<Placement, String>
+{
+	let constraints = constraints(region)?;
+	let total_memory = board.total_memory();
+	let banks = board.banks();
+	let fits = |start: u64| {
+		let end = start.saturating_add(region.size);
+		return soc::in_one_bank(&banks, start, end) &&
+			taken.iter().all(|(other_start, other_end, _)| {
+				return end <= *other_start || start >= *other_end
+			})
+	};
+
+	let starts: Vec<u64> = match constraints.fixed {
+		Some(address) => {
+			let fixed = format!("{} is fixed at {:#x}", region.name, address);
+			if address % constraints.align != 0 {
+				return Err(format!("{}, which isn't aligned to {}.", fixed,
+						   units::format_size(constraints.align)))
+			}
+			if address.saturating_add(region.size) > total_memory {
+				let total_memory = units::format_size(total_memory);
+				return Err(format!("{}, which runs past the end of the {} of \
+						   memory.", fixed, total_memory))
+			}
+			if !soc::in_one_bank(&banks, address, address + region.size) {
+				return Err(format!("{}, which isn't all in one memory bank.", fixed))
+			}
+			let end = address + region.size;
+			let overlap = taken.iter().find(|(other_start, other_end, _)| {
+				return end > *other_start && address < *other_end
+			});
+			if let Some((_, _, other)) = overlap {
+				return Err(format!("{}, which overlaps {}.", fixed, other))
+			}
+			vec![address]
+		}
+		None => {
+			// first fit: the bottom of a bank or just past a region that's
+			// already been placed, whichever is lowest & works
+			let mut starts: Vec<u64> = banks.iter()
+				.map(|(start, _)| return *start)
+				.chain(taken.iter().map(|(_, end, _)| return *end))
+				.map(|start| {
+					let align = constraints.align;
+					return start.div_ceil(align).saturating_mul(align)
+				})
+				.filter(|start| return fits(*start))
+				.collect();
+			starts.sort_unstable();
+			starts.dedup();
+			if starts.is_empty() {
+				let mut size = units::format_size(region.size);
+				if constraints.align > 1 {
+					size += &format!(" aligned to {}",
+							 units::format_size(constraints.align));
+				}
+				return Err(format!("There's no room left for {} of {} in the {} of \
+						   memory.", size, region.name,
+						   units::format_size(total_memory)))
+			}
+			starts
+		}
+	};
+
+	for start in starts.iter().copied() {
+		let end = start + region.size;
+		if let Some((id, span)) = pick_aperture(board, spans, &constraints, start, end) {
+			spans[id] = Some(span);
+			taken.push((start, end, region.name.clone()));
+			return Ok(Placement { hardware_addr: start, aperture: id })
+		}
+	}
+
+	// on its own or only alongside the others
+	let empty: Vec<Option<(u64, u64)>> = vec![None; spans.len()];
+	if pick_aperture(board, &empty, &constraints, 0, 0).is_none() {
+		return Err(format!("{} asks for a {} aperture, which this board doesn't have.",
+				   region.name, constraints.kind()))
+	}
+	let alone = starts.iter().any(|start| {
+		let end = start + region.size;
+		return pick_aperture(board, &empty, &constraints, *start, end).is_some()
+	});
+	if !alone {
+		return Err(format!("No {} aperture's window can take all {} of {}.",
+				   constraints.kind(), units::format_size(region.size), region.name))
+	}
+	return Err(format!("No {} aperture can reach {} as well as the regions already behind it.",
+			   constraints.kind(), region.name))
+}
+
+pub fn solve(board: &soc::MPFS, regions: &[NamedRegion]) -> Vec<Result<Placement, String>>
+{
+	// the regions with fixed addresses go first so that the rest can fit
+	// around them, then the others in the order they were listed. a region
+	// that can't be placed is explained & skipped, leaving its space free
+	let mut order: Vec<usize> = (0..regions.len()).collect();
+	order.sort_by_key(|index| return !has_fixed_address(&regions[*index]));
+
+	let mut taken: Vec<(u64, u64, String)> = Vec::new();
+	let mut spans: Vec<Option<(u64, u64)>> = vec![None; board.apertures().len()];
+	let mut placements: Vec<Result<Placement, String>> = vec![Err(String::new()); regions.len()];
+	for index in order {
+		placements[index] = place_region(board, &regions[index], &mut taken, &mut spans);
+	}
+
+	return placements
+}
+
+fn has_fixed_address(region: &NamedRegion) -> bool
+{
+	return region.attributes.iter().any(|attribute| return attribute.starts_with("at="))
+}
+
+pub fn allocate_regions(board: &soc::MPFS, regions: &mut [NamedRegion])
+{
+	// where "place" would put each of the regions, if anywhere
+	let placements = solve(board, regions);
+	for (region, placement) in regions.iter_mut().zip(placements) {
+		region.hardware_addr = placement.ok().map(|placement| {
+			return placement.hardware_addr
+		});
+	}
+}
+
+fn nearest_start(start: u64, size: u64, align: u64, window: (u64, u64),
+		 others: &[(u64, u64)])
+-> Option<u64>
+{
+	// the closest aligned spot to start in the window, clear of the other
+	// regions: where it is, pulled into the window, or up against one of them
+	let first = window.0.div_ceil(align).saturating_mul(align);
+	let last = window.1.checked_sub(size)? / align * align;
+	if first > last {
+		return None
+	}
+
+	let mut candidates: Vec<u64> = vec![start / align * align];
+	for (other_start, other_end) in others {
+		candidates.push(other_end.div_ceil(align).saturating_mul(align));
+		candidates.push(other_start.saturating_sub(size) / align * align);
+	}
+
+	return candidates.into_iter()
+		.map(|candidate| return candidate.clamp(first, last))
+		.filter(|candidate| {
+			let end = candidate + size;
+			return others.iter().all(|(other_start, other_end)| {
+				return end <= *other_start || *candidate >= *other_end
+			})
+		})
+		.min_by_key(|candidate| return candidate.abs_diff(start))
+}
+
+pub fn check_dma32(board: &soc::MPFS, regions: &[NamedRegion], index: usize) -> Option<String>
+{
+	// whether a region that a 32-bit dma engine has to reach is behind one
+	// of the 32-bit apertures of its kind as they are now, & if not, the
+	// nearest place it could go where one of them reaches it
+	let region = &regions[index];
+	if !has_attribute(region, "dma32") {
+		return None
+	}
+	let start = region.hardware_addr?;
+	let end = start + region.size;
+	let constraints = constraints(region).ok()?;
+	let windows: Vec<(&MemoryAperture, u64, u64)> = board.hw_ranges().into_iter()
+		.filter(|(aperture, _, _)| {
+			return aperture.performance_class == constraints.class &&
+				aperture.bus_addr + aperture.aperture_size <= DMA32_LIMIT
+		})
+		.collect();
+	if windows.iter().any(|(_, window_start, window_end)| {
+		return *window_start <= start && end <= *window_end
+	}) {
+		return None
+	}
+
+	let others: Vec<(u64, u64)> = regions.iter().enumerate()
+		.filter(|(other, _)| return *other != index)
+		.filter_map(|(_, other)| {
+			return other.hardware_addr.map(|other_start| {
+				return (other_start, other_start + other.size)
+			})
+		})
+		.collect();
+	let nearest = windows.iter()
+		.filter_map(|(aperture, window_start, window_end)| {
+			let window = (*window_start, *window_end);
+			return nearest_start(start, region.size, constraints.align, window, &others)
+				.map(|nearest| return (nearest, &aperture.reg_name))
+		})
+		.min_by_key(|(nearest, _)| return nearest.abs_diff(start));
+
+	// regions are never empty, but a message isn't worth panicking over
+	let detail = format!("{:#x}-{:#x} isn't reached by any 32-bit {} aperture", start,
+			     end.saturating_sub(1), constraints.class.name());
+	match nearest {
+		Some((nearest, reg_name)) => {
+			return Some(format!("{}, the nearest place it could go is {:#x}, through {}",
+					    detail, nearest, reg_name))
+		}
+		None => {
+			return Some(format!("{}, & none of them has room for it where they are, \
+					     \"place\" can move one over it", detail))
+		}
+	}
+}
+
+fn access_warning(region: &NamedRegion, access: &Access, aperture: &MemoryAperture)
+-> Option<String>
+{
+	// the aperture was picked for its kind, not what it lets be done
+	if access.execute && aperture.performance_class == PerformanceClass::WriteCombining {
+		return Some(format!("{} is executable, but is reached through the write-combining \
+				     window {}, where code can miss stores still in the buffer.",
+				    region.name, aperture.reg_name))
+	}
+	if !aperture.access.covers(access) {
+		return Some(format!("{} needs {} access, but {} only allows {}.", region.name,
+				    access, aperture.reg_name, aperture.access))
+	}
+
+	return None
+}
+
+pub fn place_regions(board: &mut soc::MPFS, regions: &mut [NamedRegion],
+		     reserved_nodes: &mut Vec<ReservedMemoryNode>)
+-> Result<String, String>
+{
+	// each region gets a spot in ddr that meets its constraints, the
+	// apertures holding them are moved over them, & each region gets a
+	// reserved-memory node at the bus address it's reached at, replacing any
+	// from an earlier go
+	let mut placed: Vec<Placement> = Vec::new();
+	for placement in solve(board, regions) {
+		placed.push(placement?);
+	}
+
+	let mut spans: Vec<Option<(u64, u64)>> = vec![None; board.apertures().len()];
+	for (region, placement) in regions.iter_mut().zip(&placed) {
+		region.hardware_addr = Some(placement.hardware_addr);
+		let start = placement.hardware_addr;
+		let end = start + region.size;
+		spans[placement.aperture] = spans[placement.aperture]
+			.map(|(span_start, span_end)| {
+				return (span_start.min(start), span_end.max(end))
+			})
+			.or(Some((start, end)));
+	}
+
+	for (id, span) in spans.iter().enumerate() {
+		if let Some((span_start, _)) = span {
+			let reg_name = board.apertures()[id].reg_name.clone();
+			board.set_hw_start_addr_by_id(span_start & !(soc::SEG_GRANULARITY - 1), id)
+				.map_err(|error| return format!("{}: {}", reg_name, error))?;
+		}
+	}
+
+	let hosts = placed.iter().map(|placement| return placement.aperture);
+	let mut warnings: Vec<String> = Vec::new();
+	for (region, id) in regions.iter().zip(hosts) {
+		let aperture = &board.apertures()[id];
+		let access = constraints(region)?.access;
+		warnings.extend(access.and_then(|access| {
+			return access_warning(region, &access, aperture)
+		}));
+		let access = access.unwrap_or(aperture.access);
+		let hardware_addr = region.hardware_addr.unwrap_or_default();
+		let address = aperture.bus_addr + (hardware_addr - aperture.hardware_addr);
+		reserved_nodes.retain(|node| {
+			return node.label.split('@').next() != Some(region.name.as_str())
+		});
+		reserved_nodes.push(ReservedMemoryNode {
+			label: format!("{}@{:x}", region.name, address),
+			regions: vec![MemoryRegion {
+				address,
+				size: region.size,
+				aperture: Some(aperture.reg_name.clone()),
+			}],
+			// linux mustn't map anything it would then access cached, or
+			// map as ordinary memory what it can't both read & write
+			no_map: !(aperture.performance_class.is_cached() && access.read &&
+				  access.write) || has_attribute(region, "no-map"),
+			reusable: has_attribute(region, "reusable"),
+		});
+	}
+
+	let mut message = format!("Placed {} regions, \"overlay <file>\" writes their \
+				   reserved-memory nodes.", regions.len());
+	for warning in warnings {
+		message += &format!(" Warning: {}", warning);
+	}
+	return Ok(message)
+}
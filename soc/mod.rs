@@ -3,13 +3,63 @@
 
 use std::error::Error;
 use std::fmt;
-#[derive(Debug)]
-pub struct SegError {
+use tracing::debug;
+
+use crate::contexts;
+// why an address, size or seg value can't be used, so that a bad config says
+// what is wrong with it rather than ending up somewhere nonsensical
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SegError {
+	// a seg register can only move an aperture in whole SEG_GRANULARITY steps
+	Unaligned(u64),
+	BeyondMemory { addr: u64, total_system_memory: u64 },
+	// between two memory banks, where there's no memory at all
+	InHole { addr: u64, hole_start: u64, hole_end: u64 },
+	// a seg register can only take away from the bus address, and only so
+	// much of it
+	AboveBusAddr { addr: u64, bus_addr: u64 },
+	FarBelowBusAddr { addr: u64, bus_addr: u64 },
+	// the seg value would put the aperture below hardware address 0
+	SegUnderflow { seg: u64, bus_addr: u64 },
+	BadSize(u64),
+	// an address & size that add up to more than 64 bits can hold
+	Overflow { addr: u64, size: u64 },
+	// none of the apertures reach a region at this bus address
+	Unreachable(u64),
+	// more than the 15 bits that a seg register has
+	TooWide(u64),
 }
 
 impl fmt::Display for SegError {
 fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-	return write!(f, "SegError is here!")
+	match self {
+		SegError::Unaligned(addr) =>
+			return write!(f, "{:#x} is not a multiple of {:#x}", addr, SEG_GRANULARITY),
+		SegError::BeyondMemory { addr, total_system_memory } =>
+			return write!(f, "{:#x} is beyond the end of memory ({:#x})", addr,
+				      total_system_memory),
+		SegError::InHole { addr, hole_start, hole_end } =>
+			return write!(f, "{:#x} is in the hole between memory banks from {:#x} to \
+				      {:#x}", addr, hole_start, hole_end),
+		SegError::AboveBusAddr { addr, bus_addr } =>
+			return write!(f, "{:#x} is above the bus address ({:#x}), a seg register \
+				      can only move an aperture down", addr, bus_addr),
+		SegError::FarBelowBusAddr { addr, bus_addr } =>
+			return write!(f, "{:#x} is too far below the bus address ({:#x}) for a \
+				      seg register to reach", addr, bus_addr),
+		SegError::SegUnderflow { seg, bus_addr } =>
+			return write!(f, "seg {:#x} would move the aperture at bus address \
+				      {:#x} below hardware address 0", seg, bus_addr),
+		SegError::BadSize(size) =>
+			return write!(f, "{:#x} is not a non-zero multiple of {:#x}", size,
+				      SEG_GRANULARITY),
+		SegError::Overflow { addr, size } =>
+			return write!(f, "{:#x} + {:#x} doesn't fit in 64 bits", addr, size),
+		SegError::Unreachable(addr) =>
+			return write!(f, "no aperture reaches {:#x}", addr),
+		SegError::TooWide(seg) =>
+			return write!(f, "{:#x} doesn't fit in the 15 bits of a seg register", seg),
+	}
 }
 }
 
@@ -19,84 +69,327 @@ impl Error for SegError {
 }
 }
 
+// the banks are the (start, end) of each, lowest first, as from MPFS::banks()
 pub trait Aperture {
 	fn get_hw_start_addr
-	(&self, total_system_memory: u64) -> Result<u64, SegError>;
+	(&self, banks: &[(u64, u64)]) -> Result<u64, SegError>;
 
 	fn get_hw_end_addr
-	(&self, total_system_memory: u64) -> Result<u64, SegError>;
+	(&self, banks: &[(u64, u64)]) -> Result<u64, SegError>;
+
+	fn get_hw_end_addrs
+	(&self, banks: &[(u64, u64)]) -> Result<HwEnd, SegError>;
 
 	fn set_hw_start_addr
-	(&mut self, total_system_memory: u64, new_start_addr: u64) -> Result<(), SegError>;
+	(&mut self, banks: &[(u64, u64)], new_start_addr: u64) -> Result<(), SegError>;
 
 	fn set_hw_start_addr_from_seg
-	(&mut self, total_system_memory: u64, seg_value: u64) -> Result<(), SegError>;
+	(&mut self, banks: &[(u64, u64)], seg_value: u64) -> Result<(), SegError>;
 
 	fn check_region_in_aperture
-	(&mut self, region_start: u64, region_size: u64) -> bool;
+	(&self, region_start: u64, region_size: u64) -> bool;
 
 	fn get_region_hw_start_addr
-	(&mut self, region_start: u64, region_size: u64) -> Option<u64>;
+	(&self, region_start: u64, region_size: u64) -> Option<u64>;
+}
+
+// where an aperture would end in hardware, and where it actually does once
+// whatever lies beyond the end of memory has been cut off
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HwEnd {
+	pub full: u64,
+	pub clamped: u64,
+}
+
+impl HwEnd {
+	pub fn is_clamped(&self) -> bool
+	{
+		return self.clamped < self.full
+	}
+}
+
+// rough bandwidth/latency class of the path an aperture takes to DDR, so that
+// people placing DMA buffers can tell the fast windows from the slow ones
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PerformanceClass {
+	CachedAxi,
+	NonCached,
+	WriteCombining,
+}
+
+impl fmt::Display for PerformanceClass {
+fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+	match self {
+		PerformanceClass::CachedAxi => return write!(f, "high bw/low lat"),
+		PerformanceClass::NonCached => return write!(f, "low bw/high lat"),
+		PerformanceClass::WriteCombining => return write!(f, "high bw writes"),
+	}
+}
+}
+
+impl PerformanceClass {
+	pub fn is_cached(&self) -> bool
+	{
+		return *self == PerformanceClass::CachedAxi
+	}
+
+	pub fn name(&self) -> &'static str
+	{
+		match self {
+			PerformanceClass::CachedAxi => return "cached",
+			PerformanceClass::NonCached => return "non-cached",
+			PerformanceClass::WriteCombining => return "write-combining",
+		}
+	}
+
+	pub fn from_name(name: &str) -> Option<PerformanceClass>
+	{
+		return [PerformanceClass::CachedAxi, PerformanceClass::NonCached,
+			PerformanceClass::WriteCombining]
+			.into_iter()
+			.find(|class| return class.name() == name)
+	}
+}
+
+// how wide the addresses that reach an aperture are, the 32-bit windows being
+// the ones that sit below 4 GiB for masters that can't reach any higher
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BusWidth {
+	Bits32,
+	Bits64,
+}
+
+impl fmt::Display for BusWidth {
+fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+	match self {
+		BusWidth::Bits32 => return write!(f, "32-bit"),
+		BusWidth::Bits64 => return write!(f, "64-bit"),
+	}
+}
+}
+
+impl BusWidth {
+	pub fn bits(&self) -> u32
+	{
+		match self {
+			BusWidth::Bits32 => return 32,
+			BusWidth::Bits64 => return 64,
+		}
+	}
+}
+
+// what MemoryAperture::matches knows how to pick apertures by
+pub const APERTURE_FILTERS: [&str; 6] = [
+	"32-bit", "64-bit", "cached", "non-cached", "write-combining", "wcb",
+];
+
+// what may be done to memory through an aperture, or to a region of it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Access {
+	pub read: bool,
+	pub write: bool,
+	pub execute: bool,
+}
+
+impl fmt::Display for Access {
+fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+	let flag = |allowed: bool, c: char| {
+		match allowed {
+			true => return c,
+			false => return '-',
+		}
+	};
+	return write!(f, "{}{}{}", flag(self.read, 'r'), flag(self.write, 'w'),
+		      flag(self.execute, 'x'))
+}
+}
+
+impl Access {
+	pub const RWX: Access = Access { read: true, write: true, execute: true };
+	pub const RW: Access = Access { read: true, write: true, execute: false };
+
+	pub fn parse(text: &str) -> Result<Access, String>
+	{
+		// "rwx", "rw", "r-x" or the like, in any order, & "-" for none
+		let mut access = Access { read: false, write: false, execute: false };
+		for c in text.trim().chars() {
+			match c.to_ascii_lowercase() {
+				'r' => access.read = true,
+				'w' => access.write = true,
+				'x' => access.execute = true,
+				'-' => {}
+				_ => return Err(format!("\"{}\" isn't an access, e.g. rwx or rw",
+							text)),
+			}
+		}
+
+		return Ok(access)
+	}
+
+	pub fn default_for(class: PerformanceClass) -> Access
+	{
+		// code only runs from the cached windows, the others are for data
+		match class.is_cached() {
+			true => return Access::RWX,
+			false => return Access::RW,
+		}
+	}
+
+	// whether everything the other allows, this does too
+	pub fn covers(&self, other: &Access) -> bool
+	{
+		return (self.read || !other.read) && (self.write || !other.write) &&
+			(self.execute || !other.execute)
+	}
 }
 
 #[derive(Debug)]
 pub struct MemoryApertureError;
 #[derive(Debug, Clone)]
 pub struct MemoryAperture {
-	pub description: String,
+	pub bus_width: BusWidth,
 	pub bus_addr: u64,
 	pub hardware_addr: u64,
 	pub aperture_size: u64,
-	pub reg_name: String
+	pub reg_name: String,
+	pub performance_class: PerformanceClass,
+	pub access: Access,
+	// reg names of the other apertures that the design lets see the same
+	// ddr, through different attributes, e.g. the 32-bit cached window at
+	// 0x80000000 & the 32-bit non-cached one at 0xc0000000
+	pub aliases_with: Vec<String>,
+}
+
+impl MemoryAperture {
+	pub fn seg(&self) -> Result<u64, SegError>
+	{
+		return hw_start_addr_to_seg(self.hardware_addr, self.bus_addr)
+	}
+
+	// whether the design has the two seeing the same ddr, which an aperture
+	// does with itself
+	pub fn aliases(&self, other: &MemoryAperture) -> bool
+	{
+		return self.reg_name == other.reg_name || self.aliases_with.contains(&other.reg_name)
+	}
+
+	// left at its bus address, by 0x0 or a value treated as it, & that's
+	// beyond the end of memory. that's how an aperture that isn't wanted is
+	// turned off, rather than a mistake
+	pub fn is_unused(&self, banks: &[(u64, u64)]) -> bool
+	{
+		return self.hardware_addr == self.bus_addr &&
+		       self.get_hw_start_addr(banks).is_err()
+	}
+
+	// e.g. "32-bit cached", worked out from the width & class rather than
+	// kept alongside them, so the two can't disagree
+	pub fn description(&self) -> String
+	{
+		let class = match self.performance_class {
+			PerformanceClass::WriteCombining => "WCB",
+			class => class.name(),
+		};
+
+		return format!("{} {}", self.bus_width, class)
+	}
+
+	// whether the aperture is one of those asked for by e.g. "cached",
+	// "64-bit" or "wcb"
+	pub fn matches(&self, filter: &str) -> bool
+	{
+		let filter = filter.to_ascii_lowercase();
+		let wcb = self.performance_class == PerformanceClass::WriteCombining;
+		return filter == self.bus_width.to_string() ||
+			filter == self.performance_class.name() || (filter == "wcb" && wcb)
+	}
 }
 
 impl Aperture for MemoryAperture {
 
-	fn get_hw_start_addr(&self, total_system_memory: u64) -> Result<u64, SegError>
+	fn get_hw_start_addr(&self, banks: &[(u64, u64)]) -> Result<u64, SegError>
 	{
-		if self.hardware_addr > total_system_memory {
-			return Err(SegError {})
-		}
+		containing_bank(banks, self.hardware_addr)?;
 		return Ok(self.hardware_addr)
 	}
 
-	fn get_hw_end_addr(&self, total_system_memory: u64) -> Result<u64, SegError>
+	fn get_hw_end_addr(&self, banks: &[(u64, u64)]) -> Result<u64, SegError>
+	{
+		return self.get_hw_end_addrs(banks)
+			.map(|end| return end.clamped)
+	}
+
+	fn get_hw_end_addrs(&self, banks: &[(u64, u64)]) -> Result<HwEnd, SegError>
 	{
 		// the last hardware addr decided by whichever is lower:
-		// - the addr of the "highest" physical memory on the system
+		// - the end of the bank that the aperture's end falls in, or of the
+		//   highest one below it if it ends in a hole or past them all
 		// - the and of the aperture into memory on this part of the bus
+		// an aperture can carry on across a hole into the next bank, so it's
+		// only its end that gets cut back
 
-		let aperture_max = self.hardware_addr + self.aperture_size;
-		if aperture_max > total_system_memory {
-			return Ok(total_system_memory)
-		} else {
-			return Ok(aperture_max)
+		let aperture_max = self.hardware_addr.checked_add(self.aperture_size)
+			.ok_or(SegError::Overflow { addr: self.hardware_addr, size: self.aperture_size })?;
+		let in_a_bank = banks.iter()
+			.any(|(start, end)| return *start < aperture_max && aperture_max <= *end);
+		if in_a_bank {
+			return Ok(HwEnd { full: aperture_max, clamped: aperture_max })
 		}
+
+		let clamped = banks.iter()
+			.map(|(_, end)| return *end)
+			.filter(|end| return *end < aperture_max)
+			.max()
+			.unwrap_or(0)
+			.max(self.hardware_addr);
+		return Ok(HwEnd { full: aperture_max, clamped })
 	}
 
 	fn set_hw_start_addr
-	(&mut self, total_system_memory: u64, new_start_addr: u64) -> Result<(), SegError>
+	(&mut self, banks: &[(u64, u64)], new_start_addr: u64) -> Result<(), SegError>
 	{
-		if new_start_addr == self.bus_addr || new_start_addr < total_system_memory {
+		if new_start_addr == self.bus_addr {
+			debug!("{}: hardware address {:#x}, same as the bus address",
+			       self.reg_name, new_start_addr);
 			self.hardware_addr = new_start_addr;
 			return Ok(())
-		} else {
-			return Err(SegError {})
 		}
+
+		// anything in between two multiples of the granularity can't be
+		// encoded, the seg register would decode to some other address
+		if !is_seg_aligned(new_start_addr) {
+			debug!("{}: hardware address {:#x} can't be encoded",
+			       self.reg_name, new_start_addr);
+			return Err(SegError::Unaligned(new_start_addr))
+		}
+
+		if let Err(error) = containing_bank(banks, new_start_addr) {
+			debug!("{}: hardware address {:#x} has no memory behind it: {}",
+			       self.reg_name, new_start_addr, error);
+			return Err(error)
+		}
+
+		let seg = hw_start_addr_to_seg(new_start_addr, self.bus_addr)?;
+		debug!("{}: hardware address {:#x} -> seg {:#x}", self.reg_name, new_start_addr,
+		       seg);
+		self.hardware_addr = new_start_addr;
+		return Ok(())
 	}
 
 	fn set_hw_start_addr_from_seg
-	(&mut self, total_system_memory: u64, seg_value: u64) -> Result<(), SegError>
+	(&mut self, banks: &[(u64, u64)], seg_value: u64) -> Result<(), SegError>
 	{
-		let new_start_addr = seg_to_hw_start_addr(seg_value, self.bus_addr);
-		return self.set_hw_start_addr(total_system_memory, new_start_addr)
+		let new_start_addr = seg_to_hw_start_addr(seg_value, self.bus_addr)?;
+		debug!("{}: seg {:#x} -> hardware address {:#x}", self.reg_name, seg_value,
+		       new_start_addr);
+		return self.set_hw_start_addr(banks, new_start_addr)
 	}
 
 	fn check_region_in_aperture
-	(&mut self, region_start: u64, region_size: u64) -> bool
+	(&self, region_start: u64, region_size: u64) -> bool
 	{
 		if region_start >= self.bus_addr &&
-		   region_start < (self.bus_addr + self.aperture_size) {
+		   region_start < self.bus_addr.saturating_add(self.aperture_size) {
 			return true;
 		}
 
@@ -104,7 +397,7 @@ impl Aperture for MemoryAperture {
 	}
 
 	fn get_region_hw_start_addr
-	(&mut self, region_start: u64, region_size: u64) -> Option<u64>
+	(&self, region_start: u64, region_size: u64) -> Option<u64>
 	{
 		if !self.check_region_in_aperture(region_start, region_size) {
 			return None
@@ -112,44 +405,714 @@ impl Aperture for MemoryAperture {
 
 		let offset = region_start - self.bus_addr;
 
-		return Some(self.hardware_addr + offset)
+		return self.hardware_addr.checked_add(offset)
+	}
+}
+
+// the bank that addr is in, or why it isn't in one: it's either past the end
+// of them all or in a hole between two of them
+pub fn containing_bank(banks: &[(u64, u64)], addr: u64) -> Result<(u64, u64), SegError>
+{
+	let bank = banks.iter().find(|(start, end)| return *start <= addr && addr < *end);
+	if let Some(bank) = bank {
+		return Ok(*bank)
 	}
+
+	let total_system_memory = banks.iter().map(|(_, end)| return *end).max().unwrap_or(0);
+	if addr >= total_system_memory {
+		return Err(SegError::BeyondMemory { addr, total_system_memory })
+	}
+
+	let hole_start = banks.iter()
+		.map(|(_, end)| return *end)
+		.filter(|end| return *end <= addr)
+		.max()
+		.unwrap_or(0);
+	let hole_end = banks.iter()
+		.map(|(start, _)| return *start)
+		.filter(|start| return *start > addr)
+		.min()
+		.unwrap_or(total_system_memory);
+	return Err(SegError::InHole { addr, hole_start, hole_end })
+}
+
+// whether all of start to end is in the one bank, as a region has to be to
+// be any use
+pub fn in_one_bank(banks: &[(u64, u64)], start: u64, end: u64) -> bool
+{
+	return containing_bank(banks, start)
+		.map(|(_, bank_end)| return end <= bank_end)
+		.unwrap_or(false)
 }
 
 pub trait SoC {
 	fn get_hw_start_addr_by_id
-	(&self, total_system_memory: u64, id: usize) -> Result<u64, SegError>;
+	(&self, id: usize) -> Result<u64, SegError>;
 	fn get_hw_end_addr_by_id
-	(&self, total_system_memory: u64, id: usize) -> Result<u64, SegError>;
+	(&self, id: usize) -> Result<u64, SegError>;
 	fn set_hw_start_addr_by_id
 	(&mut self, new_start_addr: u64, id: usize) -> Result<(), SegError>;
+	fn set_aperture_size_by_id
+	(&mut self, new_size: u64, id: usize) -> Result<(), SegError>;
+
+	fn aperture_count(&self) -> usize;
+	fn apertures(&self) -> &[MemoryAperture];
+	fn aperture_by_name(&self, reg_name: &str) -> Option<&MemoryAperture>;
+	fn total_memory(&self) -> u64;
+}
+
+// one aperture being moved as part of a reflow, kept separate from actually
+// applying it so that the user can look before they leap
+#[derive(Debug, Clone)]
+pub struct ReflowStep {
+	pub id: usize,
+	pub old_hw_start_addr: u64,
+	pub new_hw_start_addr: u64,
+}
+
+// a summary of how much of ddr the apertures reach, and how
+#[derive(Debug, Clone)]
+pub struct CoverageStats {
+	// the memory that's really there, leaving out any holes between banks
+	pub total_memory: u64,
+	// reg name & the bytes of ddr reachable through it
+	pub reachable: Vec<(String, u64)>,
+	// bytes reachable through at least one aperture of that class
+	pub covered_by_class: Vec<(PerformanceClass, u64)>,
+	// bytes reachable through more than one aperture, other than through
+	// aliases of each other (unless aliases aren't allowed)
+	pub overlap: u64,
+	// bytes reachable through more than one alias of the same aperture
+	pub aliased: u64,
+	// bus master name & the bytes of ddr it can reach
+	pub reachable_by_masters: Vec<(String, u64)>,
+	pub unreachable: u64,
+}
+
+// something other than the harts that masters onto the axi switch, e.g. a dma
+// engine in the fabric behind fic0. it goes through the same apertures, so it
+// is described by the bus addresses it can put out
+#[derive(Debug, Clone)]
+pub struct BusMaster {
+	pub name: String,
+	// (bus start, size)
+	pub windows: Vec<(u64, u64)>,
+}
+
+// the ddr behind one aperture as seen through another that it aliases with,
+// & how far the other's bus address for it is from the first's
+#[derive(Debug, Clone, PartialEq)]
+pub struct AliasView {
+	pub id: usize,
+	pub start: u64,
+	pub end: u64,
+	pub bus_offset: i64,
+}
+
+// one stretch of physical memory, e.g. the ddr4 or the lpddr4 on a design
+// that has both, where it sits in the (hardware) addresses the apertures map
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemoryBank {
+	pub name: String,
+	pub base: u64,
+	pub size: u64,
+}
+
+impl MemoryBank {
+	// a bank that would overflow is refused when the config is parsed
+	pub fn end(&self) -> u64
+	{
+		return self.base + self.size
+	}
 }
 
 pub struct MPFS {
 	pub total_system_memory: u64,
+	// empty for the usual single bank from 0 up to the total system memory.
+	// otherwise the total is where the highest bank ends, & anything below
+	// that between the banks is a hole with no memory in it
+	pub memory_banks: Vec<MemoryBank>,
 	pub memory_apertures: Vec<MemoryAperture>,
-	pub current_aperture_id: Option<usize>
+	pub current_aperture_id: Option<usize>,
+	pub pending_reflow: Option<Vec<ReflowStep>>,
+	// an aperture id & the aligned address offered in place of an unaligned
+	// one that was entered for it
+	pub pending_snap: Option<(usize, u64)>,
+	// dt node label & the aperture it was picked to go through, for when
+	// more than one of them could host it
+	pub node_apertures: Vec<(String, String)>,
+	pub contexts: Vec<contexts::Context>,
+	pub bus_masters: Vec<BusMaster>,
+	// some designs don't allow the same ddr to be mapped more than once,
+	// even deliberately, so aliases are conflicts like any other overlap
+	pub forbid_aliases: bool,
+	// don't complain about a total memory size that no real ddr would have
+	pub any_memory_size: bool,
+}
+
+pub const SEG_GRANULARITY: u64 = 1 << 24;
+
+impl MPFS {
+	pub fn propose_reflow(&self, changed_id: usize) -> Result<Vec<ReflowStep>, SegError>
+	{
+		// shuffle everything that starts after the changed aperture up
+		// just far enough that nothing overlaps, keeping the order the
+		// apertures were already in
+		let changed = &self.memory_apertures[changed_id];
+		let mut next_free = changed.hardware_addr.checked_add(changed.aperture_size)
+			.ok_or(SegError::Overflow { addr: changed.hardware_addr,
+						    size: changed.aperture_size })?;
+		let mut subsequent: Vec<(usize, &MemoryAperture)> = self.memory_apertures
+			.iter()
+			.enumerate()
+			.filter(|(id, aperture)|
+				return *id != changed_id
+				       && aperture.hardware_addr > changed.hardware_addr
+			)
+			.collect();
+		subsequent.sort_by_key(|(id, aperture)| return aperture.hardware_addr);
+
+		let mut steps: Vec<ReflowStep> = Vec::new();
+		for (id, aperture) in subsequent {
+			let mut new_start = aperture.hardware_addr;
+			if new_start < next_free {
+				new_start = seg_align_up(next_free)?;
+				steps.push(ReflowStep {
+					id,
+					old_hw_start_addr: aperture.hardware_addr,
+					new_hw_start_addr: new_start,
+				});
+			}
+			let end = new_start.checked_add(aperture.aperture_size)
+				.ok_or(SegError::Overflow { addr: new_start,
+							    size: aperture.aperture_size })?;
+			next_free = next_free.max(end);
+		}
+
+		return Ok(steps)
+	}
+
+	pub fn nudge(&mut self, id: usize, granules: i64) -> Result<u64, SegError>
+	{
+		// move an aperture by whole seg granules from wherever it is now,
+		// snapping it first if it's somewhere a seg register can't encode.
+		// going down stops at 0, going up stops wherever the seg does
+		let start = snap_to_seg_granularity(self.memory_apertures[id].hardware_addr);
+		let step = SEG_GRANULARITY.saturating_mul(granules.unsigned_abs());
+		let mut new_start = start.saturating_add(step);
+		if granules < 0 {
+			new_start = start.saturating_sub(step);
+		}
+
+		self.set_hw_start_addr_by_id(new_start, id)?;
+		return Ok(new_start)
+	}
+
+	pub fn apply_reflow(&mut self, steps: &[ReflowStep]) -> Result<(), SegError>
+	{
+		for step in steps {
+			self.set_hw_start_addr_by_id(step.new_hw_start_addr, step.id)?;
+		}
+
+		return Ok(())
+	}
+
+	pub fn banks(&self) -> Vec<(u64, u64)>
+	{
+		// the (start, end) of each bank, lowest first. without any banks
+		// in the config, memory is the one bank of the total system memory
+		if self.memory_banks.is_empty() {
+			return vec![(0, self.total_system_memory)]
+		}
+
+		let mut banks: Vec<(u64, u64)> = self.memory_banks.iter()
+			.map(|bank| return (bank.base, bank.end()))
+			.filter(|(start, end)| return start < end)
+			.collect();
+		banks.sort();
+
+		return banks
+	}
+
+	pub fn holes(&self) -> Vec<(u64, u64)>
+	{
+		// the gaps between the banks, as (start, end), where there's
+		// nothing for an aperture to reach
+		let mut holes: Vec<(u64, u64)> = Vec::new();
+		let mut next_start = 0;
+		for (start, end) in self.banks() {
+			if start > next_start {
+				holes.push((next_start, start));
+			}
+			next_start = next_start.max(end);
+		}
+
+		return holes
+	}
+
+	pub fn is_hole(&self, start: u64, end: u64) -> bool
+	{
+		// whether all of start to end is in the holes
+		return self.memory_in(start, end) == 0
+	}
+
+	pub fn memory_in(&self, start: u64, end: u64) -> u64
+	{
+		// how many bytes of start to end really have memory behind them
+		return self.banks()
+			.iter()
+			.map(|(bank_start, bank_end)| {
+				return end.min(*bank_end).saturating_sub(start.max(*bank_start))
+			})
+			.sum()
+	}
+
+	pub fn installed_memory(&self) -> u64
+	{
+		return self.memory_in(0, self.total_memory())
+	}
+
+	pub fn unmapped_regions(&self) -> Vec<(u64, u64)>
+	{
+		// the parts of ddr that no aperture reaches, as (start, end) with
+		// the end being exclusive. holes aren't memory, so aren't in it
+		let mut covered: Vec<(u64, u64)> = self.hw_ranges()
+			.iter()
+			.map(|(_, start, end)| return (*start, *end))
+			.collect();
+		covered.extend(self.holes());
+		covered.sort();
+
+		let mut unmapped: Vec<(u64, u64)> = Vec::new();
+		let mut next_unmapped = 0;
+		for (start, end) in covered {
+			if start > next_unmapped {
+				unmapped.push((next_unmapped, start));
+			}
+			next_unmapped = next_unmapped.max(end);
+		}
+
+		let total_memory = self.total_memory();
+		if next_unmapped < total_memory {
+			unmapped.push((next_unmapped, total_memory));
+		}
+
+		return unmapped
+	}
+
+	pub fn bus_banks(&self, class: PerformanceClass) -> Vec<(u64, u64)>
+	{
+		// the (bus address, size) of the part of each aperture of a class
+		// that lands in ddr, i.e. the memory that software can actually use
+		// through it. an aperture across a hole between memory banks gives
+		// a bank either side of it
+		let mut banks: Vec<(u64, u64)> = Vec::new();
+		let ranges = self.hw_ranges();
+		let ranges = ranges.iter()
+			.filter(|(aperture, _, _)| return aperture.performance_class == class);
+		for (aperture, start, end) in ranges {
+			for (bank_start, bank_end) in self.banks() {
+				let (start, end) = ((*start).max(bank_start), (*end).min(bank_end));
+				if start < end {
+					let bus_addr = aperture.bus_addr
+						       + (start - aperture.hardware_addr);
+					banks.push((bus_addr, end - start));
+				}
+			}
+		}
+		banks.sort();
+
+		return banks
+	}
+
+	pub fn alias_of(&self, id: usize) -> Option<usize>
+	{
+		// apertures that the design has seeing the same ddr, & that reach
+		// some of the same part of it, are deliberately mapping that memory
+		// twice, e.g. a cached & a non-cached view of it, rather than being
+		// misplaced. the first of them is the one the rest are aliases of
+		return self.alias_views(id).iter()
+			.map(|view| return view.id)
+			.filter(|other| return *other < id)
+			.min()
+	}
+
+	pub fn aliases_with(&self, id: usize) -> Vec<usize>
+	{
+		// the ids of the apertures that the design has seeing the same ddr as
+		// this one, whether or not they're currently set up to
+		let aliases_with = &self.memory_apertures[id].aliases_with;
+		return self.memory_apertures.iter()
+			.enumerate()
+			.filter(|(_, other)| return aliases_with.contains(&other.reg_name))
+			.map(|(other, _)| return other)
+			.collect()
+	}
+
+	pub fn alias_views(&self, id: usize) -> Vec<AliasView>
+	{
+		// where the ddr behind an aperture is also reached through those it
+		// aliases with, which is only wherever their windows overlap in ddr
+		let banks = self.banks();
+		let in_ddr = |aperture: &MemoryAperture| {
+			let start = aperture.get_hw_start_addr(&banks).ok()?;
+			let end = aperture.get_hw_end_addr(&banks).ok()?;
+			return Some((start, end))
+		};
+		let aperture = &self.memory_apertures[id];
+		let (start, end) = match in_ddr(aperture) {
+			Some(range) => range,
+			None => return Vec::new(),
+		};
+
+		// bus addresses top out well below 2^63, so the offset fits
+		let bus_to_hw = |aperture: &MemoryAperture| {
+			return aperture.bus_addr as i64 - aperture.hardware_addr as i64
+		};
+		return self.aliases_with(id).into_iter()
+			.filter_map(|other| {
+				let alias = &self.memory_apertures[other];
+				let (alias_start, alias_end) = in_ddr(alias)?;
+				let (start, end) = (start.max(alias_start), end.min(alias_end));
+				if start >= end {
+					return None
+				}
+
+				let bus_offset = bus_to_hw(alias) - bus_to_hw(aperture);
+				return Some(AliasView { id: other, start, end, bus_offset })
+			})
+			.collect()
+	}
+
+	pub fn alias_stack(&self) -> Vec<usize>
+	{
+		// the aperture ids with each one's aliases straight after it, so that
+		// views of the same ddr can be drawn side by side
+		let mut stack: Vec<usize> = Vec::new();
+		for id in 0..self.memory_apertures.len() {
+			if stack.contains(&id) {
+				continue;
+			}
+
+			// in the order the design lists them, which puts the views of
+			// the same width next to each other
+			stack.push(id);
+			let aliases: Vec<usize> = self.memory_apertures[id].aliases_with.iter()
+				.filter_map(|reg_name| {
+					return self.memory_apertures.iter()
+						.position(|other| return other.reg_name == *reg_name)
+				})
+				.filter(|other| return !stack.contains(other))
+				.collect();
+			stack.extend(aliases);
+		}
+
+		return stack
+	}
+
+	pub fn alias_errors(&self) -> Vec<String>
+	{
+		if !self.forbid_aliases {
+			return Vec::new()
+		}
+
+		return (0..self.memory_apertures.len())
+			.filter_map(|id| {
+				let first = self.alias_of(id)?;
+				return Some(format!("{} maps the same memory as {}, but aliases \
+						     are not allowed.",
+						    self.memory_apertures[id].reg_name,
+						    self.memory_apertures[first].reg_name))
+			})
+			.collect()
+	}
+
+	pub fn master_hw_ranges(&self, master: &BusMaster) -> Vec<(&MemoryAperture, u64, u64)>
+	{
+		// the ddr that a bus master reaches, as (start, end) hardware
+		// addresses, for each aperture that one of its windows overlaps
+		let mut ranges: Vec<(&MemoryAperture, u64, u64)> = Vec::new();
+		for (window_start, window_size) in &master.windows {
+			let window_end = window_start.saturating_add(*window_size);
+			for (aperture, hw_start, hw_end) in self.hw_ranges() {
+				let bus_start = aperture.bus_addr.max(*window_start);
+				let bus_end = (aperture.bus_addr + (hw_end - hw_start)).min(window_end);
+				if bus_start >= bus_end {
+					continue;
+				}
+
+				ranges.push((aperture, hw_start + (bus_start - aperture.bus_addr),
+					     hw_start + (bus_end - aperture.bus_addr)));
+			}
+		}
+
+		return ranges
+	}
+
+	pub fn check_bus_masters(&self) -> Vec<String>
+	{
+		let mut problems: Vec<String> = Vec::new();
+		for master in &self.bus_masters {
+			for (window_start, window_size) in &master.windows {
+				let window = BusMaster {
+					name: master.name.clone(),
+					windows: vec![(*window_start, *window_size)],
+				};
+				if self.master_hw_ranges(&window).is_empty() {
+					problems.push(format!("{}'s window at {:#x} doesn't reach ddr \
+							       through any aperture.",
+							      master.name, window_start));
+				}
+			}
+		}
+
+		return problems
+	}
+
+	pub fn hw_ranges(&self) -> Vec<(&MemoryAperture, u64, u64)>
+	{
+		// every aperture that lands in ddr, with the (exclusive) end of the
+		// part of it that does
+		let banks = self.banks();
+		return self.memory_apertures
+			.iter()
+			.filter_map(|aperture| {
+				let start = aperture.get_hw_start_addr(&banks).ok()?;
+				let end = aperture.get_hw_end_addr(&banks).ok()?;
+				return Some((aperture, start, end))
+			})
+			.filter(|(_, start, end)| return start < end)
+			.collect()
+	}
+
+	pub fn hw_pieces(&self) -> Vec<(u64, u64, Vec<&MemoryAperture>)>
+	{
+		// all of ddr chopped up at every aperture & bank boundary, with
+		// whichever apertures reach each piece, so the gaps show up as pieces
+		// that nothing reaches. holes are pieces of their own, see is_hole()
+		let ranges = self.hw_ranges();
+		let mut boundaries: Vec<u64> = ranges.iter()
+			.flat_map(|(_, start, end)| return [*start, *end])
+			.chain(self.banks().into_iter().flat_map(|(start, end)| return [start, end]))
+			.collect();
+		boundaries.push(0);
+		boundaries.push(self.total_memory());
+		boundaries.sort_unstable();
+		boundaries.dedup();
+
+		return boundaries.windows(2)
+			.map(|piece| {
+				let (start, end) = (piece[0], piece[1]);
+				let hosts: Vec<&MemoryAperture> = ranges.iter()
+					.filter(|(_, host_start, host_end)| return *host_start <= start
+										&& end <= *host_end)
+					.map(|(aperture, _, _)| return *aperture)
+					.collect();
+				return (start, end, hosts)
+			})
+			.collect()
+	}
+
+	pub fn coverage_stats(&self) -> CoverageStats
+	{
+		let ranges = self.hw_ranges();
+
+		// chop ddr up at every aperture boundary, so that each piece is
+		// either entirely inside or entirely outside of any given aperture
+		let mut boundaries: Vec<u64> = ranges.iter()
+			.flat_map(|(_, start, end)| return [*start, *end])
+			.chain(self.banks().into_iter().flat_map(|(start, end)| return [start, end]))
+			.collect();
+		boundaries.sort_unstable();
+		boundaries.dedup();
+
+		let classes = [
+			PerformanceClass::CachedAxi,
+			PerformanceClass::NonCached,
+			PerformanceClass::WriteCombining
+		];
+		let mut covered_by_class: Vec<(PerformanceClass, u64)> = classes.iter()
+			.map(|class| return (*class, 0))
+			.collect();
+		let mut overlap = 0;
+		let mut aliased = 0;
+		let mut covered = 0;
+
+		for piece in boundaries.windows(2) {
+			let (start, end) = (piece[0], piece[1]);
+			let hosts: Vec<&MemoryAperture> = ranges.iter()
+				.filter(|(_, host_start, host_end)| return *host_start <= start
+									&& end <= *host_end)
+				.map(|(aperture, _, _)| return *aperture)
+				.collect();
+
+			// a hole has nothing in it to reach
+			if hosts.is_empty() || self.is_hole(start, end) {
+				continue;
+			}
+			covered += end - start;
+			// every host reaches all of the piece, so they're aliases of each
+			// other if the design says they see the same ddr
+			let all_aliases = hosts.iter().all(|host| {
+				return hosts.iter().all(|other| return host.aliases(other))
+			});
+			if hosts.len() > 1 && all_aliases && !self.forbid_aliases {
+				aliased += end - start;
+			} else if hosts.len() > 1 {
+				overlap += end - start;
+			}
+
+			for (class, bytes) in covered_by_class.iter_mut() {
+				if hosts.iter().any(|host| return host.performance_class == *class) {
+					*bytes += end - start;
+				}
+			}
+		}
+
+		return CoverageStats {
+			total_memory: self.installed_memory(),
+			reachable: self.memory_apertures.iter()
+				.map(|aperture| {
+					let bytes = ranges.iter()
+						.find(|(host, _, _)| return host.reg_name == aperture.reg_name)
+						.map_or(0, |(_, start, end)| {
+							return self.memory_in(*start, *end)
+						});
+					return (aperture.reg_name.clone(), bytes)
+				})
+				.collect(),
+			covered_by_class,
+			overlap,
+			aliased,
+			reachable_by_masters: self.bus_masters.iter()
+				.map(|master| {
+					let mut ranges: Vec<(u64, u64)> = self.master_hw_ranges(master)
+						.iter()
+						.map(|(_, start, end)| return (*start, *end))
+						.collect();
+					ranges.sort();
+
+					// the same ddr can be reached through several windows
+					let mut bytes = 0;
+					let mut counted_to = 0;
+					for (start, end) in ranges {
+						let start = start.max(counted_to);
+						if end > start {
+							bytes += self.memory_in(start, end);
+						}
+						counted_to = counted_to.max(end);
+					}
+					return (master.name.clone(), bytes)
+				})
+				.collect(),
+			unreachable: self.installed_memory().saturating_sub(covered),
+		}
+	}
+
+	pub fn propose_auto_layout(&self) -> Result<Vec<ReflowStep>, String>
+	{
+		// every aperture, back to back from the bottom of memory in the
+		// order they're listed. the last one can hang off the end of memory
+		// but none of them can start there, & one that would start in a
+		// hole between banks starts at the next bank instead
+		let banks = self.banks();
+		let mut next_free = 0;
+		let mut steps: Vec<ReflowStep> = Vec::new();
+		let mut unplaced: Vec<&str> = Vec::new();
+		for (id, aperture) in self.memory_apertures.iter().enumerate() {
+			let mut new_start = seg_align_up(next_free)
+				.map_err(|error| return error.to_string())?;
+			let bank = containing_bank(&banks, new_start);
+			if let Err(SegError::InHole { hole_end, .. }) = bank {
+				new_start = seg_align_up(hole_end)
+					.map_err(|error| return error.to_string())?;
+			}
+			if containing_bank(&banks, new_start).is_err() {
+				unplaced.push(&aperture.reg_name);
+				continue;
+			}
+
+			steps.push(ReflowStep {
+				id,
+				old_hw_start_addr: aperture.hardware_addr,
+				new_hw_start_addr: new_start,
+			});
+			next_free = new_start.checked_add(aperture.aperture_size)
+				.ok_or(SegError::Overflow { addr: new_start,
+							    size: aperture.aperture_size })
+				.map_err(|error| return error.to_string())?;
+		}
+
+		if !unplaced.is_empty() {
+			return Err(format!("{} would start beyond the end of memory, \
+					   try making some apertures smaller.",
+					   unplaced.join(", ")))
+		}
+
+		return Ok(steps)
+	}
 }
 
 impl SoC for MPFS {
 
 	fn get_hw_start_addr_by_id
-	(&self, total_system_memory: u64, id: usize) -> Result<u64, SegError>
+	(&self, id: usize) -> Result<u64, SegError>
 	{
-		return self.memory_apertures[id].get_hw_start_addr(self.total_system_memory)
+		return self.memory_apertures[id].get_hw_start_addr(&self.banks())
 	}
 
 	fn get_hw_end_addr_by_id
-	(&self, total_system_memory: u64, id: usize) -> Result<u64, SegError>
+	(&self, id: usize) -> Result<u64, SegError>
 	{
-		return self.memory_apertures[id].get_hw_end_addr(self.total_system_memory)
+		return self.memory_apertures[id].get_hw_end_addr(&self.banks())
 	}
 
 	fn set_hw_start_addr_by_id
 	(&mut self, new_start_addr: u64, id: usize) -> Result<(), SegError>
 	{
-		return self.memory_apertures[id].set_hw_start_addr(self.total_system_memory,
-								   new_start_addr);
+		let banks = self.banks();
+		return self.memory_apertures[id].set_hw_start_addr(&banks, new_start_addr);
+	}
+
+	fn set_aperture_size_by_id
+	(&mut self, new_size: u64, id: usize) -> Result<(), SegError>
+	{
+		if new_size == 0 || new_size & (SEG_GRANULARITY - 1) != 0 {
+			return Err(SegError::BadSize(new_size))
+		}
+
+		// the hardware address is never above the bus address, so if this
+		// fits then so does the end of the aperture in ddr
+		let bus_addr = self.memory_apertures[id].bus_addr;
+		if bus_addr.checked_add(new_size).is_none() {
+			return Err(SegError::Overflow { addr: bus_addr, size: new_size })
+		}
+
+		self.memory_apertures[id].aperture_size = new_size;
+		return Ok(())
+	}
+
+	fn aperture_count(&self) -> usize
+	{
+		return self.memory_apertures.len()
+	}
+
+	fn apertures(&self) -> &[MemoryAperture]
+	{
+		return &self.memory_apertures
+	}
+
+	fn aperture_by_name(&self, reg_name: &str) -> Option<&MemoryAperture>
+	{
+		return self.memory_apertures
+			.iter()
+			.find(|aperture| return aperture.reg_name == reg_name)
+	}
+
+	fn total_memory(&self) -> u64
+	{
+		// the end of the highest bank
+		return self.banks().iter().map(|(_, end)| return *end).max().unwrap_or(0)
 	}
 }
 
@@ -157,83 +1120,391 @@ impl Default for MPFS {
 	fn default() -> MPFS {
 		return MPFS {
 			total_system_memory: 0x8000_0000,
+			memory_banks: Vec::new(),
 			current_aperture_id: None,
+			pending_reflow: None,
+			pending_snap: None,
+			node_apertures: Vec::new(),
+			contexts: Vec::new(),
+			bus_masters: Vec::new(),
+			forbid_aliases: false,
+			any_memory_size: false,
 			memory_apertures: vec![
 				MemoryAperture {
-					description: "32-bit cached\t".to_string(),
+					bus_width: BusWidth::Bits32,
 					reg_name: "seg0_0".to_string(),
 					bus_addr: 0x8000_0000,
 					hardware_addr: 0x0,
 					aperture_size: 0x4000_0000,
+					performance_class: PerformanceClass::CachedAxi,
+					access: Access::RWX,
+					aliases_with: reg_names(&["seg1_2", "seg1_4", "seg0_1",
+								"seg1_3", "seg1_5"]),
 				},
 				MemoryAperture {
-					description: "64-bit cached\t".to_string(),
+					bus_width: BusWidth::Bits64,
 					reg_name: "seg0_1".to_string(),
 					bus_addr: 0x10_0000_0000,
 					hardware_addr: 0x0,
 					aperture_size: 0x4_0000_0000,
+					performance_class: PerformanceClass::CachedAxi,
+					access: Access::RWX,
+					aliases_with: reg_names(&["seg1_3", "seg1_5", "seg0_0",
+								"seg1_2", "seg1_4"]),
 				},
 				MemoryAperture {
-					description: "32-bit non-cached".to_string(),
+					bus_width: BusWidth::Bits32,
 					reg_name: "seg1_2".to_string(),
 					bus_addr: 0xC000_0000,
 					hardware_addr: 0x0,
 					aperture_size: 0x1000_0000,
+					performance_class: PerformanceClass::NonCached,
+					access: Access::RW,
+					aliases_with: reg_names(&["seg0_0", "seg1_4", "seg0_1",
+								"seg1_3", "seg1_5"]),
 				},
 				MemoryAperture {
-					description: "64-bit non-cached".to_string(),
+					bus_width: BusWidth::Bits64,
 					reg_name: "seg1_3".to_string(),
 					bus_addr: 0x14_0000_0000,
 					hardware_addr: 0x0,
 					aperture_size: 0x4_0000_0000,
+					performance_class: PerformanceClass::NonCached,
+					access: Access::RW,
+					aliases_with: reg_names(&["seg0_1", "seg1_5", "seg0_0",
+								"seg1_2", "seg1_4"]),
 				},
 				MemoryAperture {
-					description: "32-bit WCB\t".to_string(),
+					bus_width: BusWidth::Bits32,
 					reg_name: "seg1_4".to_string(),
 					bus_addr: 0xD000_0000,
 					hardware_addr: 0x0,
 					aperture_size: 0x1000_0000,
+					performance_class: PerformanceClass::WriteCombining,
+					access: Access::RW,
+					aliases_with: reg_names(&["seg0_0", "seg1_2", "seg0_1",
+								"seg1_3", "seg1_5"]),
 				},
 				MemoryAperture {
-					description: "64-bit WCB\t".to_string(),
+					bus_width: BusWidth::Bits64,
 					reg_name: "seg1_5".to_string(),
 					bus_addr: 0x18_0000_0000,
 					hardware_addr: 0x0,
 					aperture_size: 0x40_0000_0000,
+					performance_class: PerformanceClass::WriteCombining,
+					access: Access::RW,
+					aliases_with: reg_names(&["seg0_1", "seg1_3", "seg0_0",
+								"seg1_2", "seg1_4"]),
 				},
 			]
 		}
 	}
 }
 
-pub fn seg_to_hw_start_addr(seg: u64, bus_addr: u64) -> u64
+fn reg_names(reg_names: &[&str]) -> Vec<String>
+{
+	return reg_names.iter().map(|reg_name| return reg_name.to_string()).collect()
+}
+
+// the mss seg registers live in two blocks of eight, segN_M being register M
+// of block N, each one a 32 bit word
+pub const SEG0_BASE_ADDR: u64 = 0x2000_5d00;
+pub const SEG1_BASE_ADDR: u64 = 0x2000_6d00;
+
+pub fn seg_reg_addr(reg_name: &str) -> Option<u64>
+{
+	let (block, index) = reg_name.strip_prefix("seg")?.split_once('_')?;
+	let index: u64 = index.parse().ok()?;
+	if index >= 8 {
+		return None
+	}
+
+	match block {
+		"0" => return Some(SEG0_BASE_ADDR + index * 4),
+		"1" => return Some(SEG1_BASE_ADDR + index * 4),
+		_ => return None,
+	}
+}
+
+// boards that lots of people use, so that they don't all have to set up the
+// same things. the seg values are those of each board's reference design and
+// the reserved regions those of its upstream devicetree. only add a board
+// once all three have been checked against the board itself, a preset that
+// is a guess is worse than none
+pub struct BoardPreset {
+	pub name: &'static str,
+	pub total_memory: u64,
+	pub segs: &'static [(&'static str, u64)],
+	// (label, bus address, size)
+	pub reserved: &'static [(&'static str, u64, u64)],
+}
+
+// the icicle kit's 2 GiB of lpddr4 appears at hardware address 0 through
+// every aperture
+const ICICLE_KIT_SEGS: [(&str, u64); 6] = [
+	("seg0_0", 0x7f80),
+	("seg0_1", 0x7000),
+	("seg1_2", 0x7f40),
+	("seg1_3", 0x6c00),
+	("seg1_4", 0x7f30),
+	("seg1_5", 0x6800),
+];
+
+pub const BOARD_PRESETS: [BoardPreset; 1] = [
+	BoardPreset {
+		name: "icicle-kit",
+		total_memory: 0x8000_0000,
+		segs: &ICICLE_KIT_SEGS,
+		reserved: &[("hss-buffer@103fc00000", 0x10_3fc0_0000, 0x40_0000)],
+	},
+];
+
+pub fn find_board_preset(name: &str) -> Option<&'static BoardPreset>
+{
+	return BOARD_PRESETS.iter().find(|preset| return preset.name == name)
+}
+
+pub fn board_preset_names() -> String
+{
+	let names: Vec<&str> = BOARD_PRESETS.iter()
+		.map(|preset| return preset.name)
+		.collect();
+
+	return names.join(", ")
+}
+
+impl MPFS {
+	pub fn from_preset(preset: &BoardPreset) -> Result<MPFS, SegError>
+	{
+		let mut board = MPFS {
+			total_system_memory: preset.total_memory,
+			..Default::default()
+		};
+
+		for (reg_name, seg) in preset.segs {
+			let aperture = board.memory_apertures
+				.iter_mut()
+				.find(|aperture| return aperture.reg_name == *reg_name);
+			if let Some(aperture) = aperture {
+				let banks = [(0, preset.total_memory)];
+				aperture.set_hw_start_addr_from_seg(&banks, *seg)?;
+			}
+		}
+
+		return Ok(board)
+	}
+}
+
+// the highest value that fits in a seg register
+pub const SEG_MAX: u64 = 0x7FFF;
+
+// defined for every seg value that fits in the register & every bus address,
+// failing only if the aperture would end up below hardware address 0
+pub fn seg_to_hw_start_addr(seg: u64, bus_addr: u64) -> Result<u64, SegError>
 {
+	if seg > SEG_MAX {
+		return Err(SegError::TooWide(seg))
+	}
+
 	let mut temp = seg;
 
 	if (temp & 0x4000) == 0 {
 	// if that bit isnt set, either this seg register is:
 	// - 0x0 (in which case the hw addr == the bus addr)
 	// - invalid (so treat as zero to match the bootloader's behaviour)
-		return bus_addr
+		return Ok(bus_addr)
 	}
 
 	temp &= 0x3FFF;
 	temp = 0x4000 - temp;
 	temp <<= 24;
-	return bus_addr - temp
+	return bus_addr.checked_sub(temp)
+		.ok_or(SegError::SegUnderflow { seg, bus_addr })
+}
+
+pub fn describe_seg(seg: u64, bus_addr: u64) -> String
+{
+	// the same steps as seg_to_hw_start_addr(), written out for checking a
+	// value against one from somewhere else, e.g. the hss source
+	if seg == 0 {
+		return format!("0x0 leaves the aperture at its bus address, {:#x}", bus_addr)
+	}
+	if seg_is_treated_as_zero(seg) {
+		return format!("{:#x} doesn't have bit 14 set, so it is treated as 0x0 & the \
+				aperture stays at its bus address, {:#x}", seg, bus_addr)
+	}
+
+	let offset = seg & 0x3FFF;
+	let shift = (0x4000 - offset) << 24;
+	let decoded = match seg_to_hw_start_addr(seg, bus_addr) {
+		Ok(hw_start_addr) => format!("{:#x}", hw_start_addr),
+		Err(_) => "below 0".to_string(),
+	};
+	return format!("bit 14 set with an offset of {:#x}, so the aperture moves down \
+			(0x4000 - {:#x}) x 16 MiB = {:#x} from bus address {:#x} to {}", offset,
+		       offset, shift, bus_addr, decoded)
+}
+
+pub fn seg_is_treated_as_zero(seg: u64) -> bool
+{
+	// see seg_to_hw_start_addr(), anything other than 0x0 without bit 14
+	// set gets quietly turned into 0x0
+	return seg != 0 && (seg & 0x4000) == 0
+}
+
+pub fn verify_roundtrip(seg: u64, bus_addr: u64) -> Result<(), String>
+{
+	// decoding a seg value & encoding the address again has to give back the
+	// same value, or 0x0 for those that are treated as it, & that value has
+	// to decode to the same address. a seg value outside of the domain of
+	// seg_to_hw_start_addr() has nothing to round trip
+	let hw_start_addr = match seg_to_hw_start_addr(seg, bus_addr) {
+		Ok(hw_start_addr) => hw_start_addr,
+		Err(_) => return Ok(()),
+	};
+
+	let context = format!("seg {:#x} at bus address {:#x}", seg, bus_addr);
+	let expected = if seg_is_treated_as_zero(seg) { 0 } else { seg };
+	let encoded = hw_start_addr_to_seg(hw_start_addr, bus_addr)
+		.map_err(|error| return format!("{} decodes to {:#x}, which doesn't encode: {}",
+						 context, hw_start_addr, error))?;
+	if encoded != expected {
+		return Err(format!("{} decodes to {:#x}, which encodes as {:#x}", context,
+				   hw_start_addr, encoded))
+	}
+
+	let decoded = seg_to_hw_start_addr(encoded, bus_addr)
+		.map_err(|error| return format!("{} encodes as {:#x}, which doesn't decode: {}",
+						 context, encoded, error))?;
+	if decoded != hw_start_addr {
+		return Err(format!("{} decodes to {:#x}, but encoding & decoding that gives {:#x}",
+				   context, hw_start_addr, decoded))
+	}
+
+	return Ok(())
+}
+
+pub fn is_seg_aligned(hw_start_addr: u64) -> bool
+{
+	return hw_start_addr & (SEG_GRANULARITY - 1) == 0
+}
+
+// the first address from here up that a seg register can encode
+fn seg_align_up(addr: u64) -> Result<u64, SegError>
+{
+	let padding = (SEG_GRANULARITY - addr % SEG_GRANULARITY) % SEG_GRANULARITY;
+	return addr.checked_add(padding)
+		.ok_or(SegError::Overflow { addr, size: padding })
+}
+
+pub fn snap_to_seg_granularity(hw_start_addr: u64) -> u64
+{
+	// the nearest address that a seg register can actually encode
+	let below = hw_start_addr & !(SEG_GRANULARITY - 1);
+	if hw_start_addr - below >= SEG_GRANULARITY / 2 {
+		return below.saturating_add(SEG_GRANULARITY)
+	}
+
+	return below
 }
 
-pub fn hw_start_addr_to_seg(hw_start_addr: u64, bus_addr: u64) -> u64
+// defined for every hardware address that is a whole number of granules, up to
+// 0x4000 of them, below the bus address. anything else would have to be
+// rounded, & so wouldn't decode back to the same address
+pub fn hw_start_addr_to_seg(hw_start_addr: u64, bus_addr: u64) -> Result<u64, SegError>
 {
 	if bus_addr == hw_start_addr {
 	// a seg register is effectively how much we need to subtract from the
 	// bus addr to get the hw addr (not /quite/, but sorta) so if they're
 	// the same, then the seg register is 0x0
-		return 0x0
+		return Ok(0x0)
 	}
 
-	let mut temp = bus_addr;
-	temp -= hw_start_addr;
+	let mut temp = bus_addr.checked_sub(hw_start_addr)
+		.ok_or(SegError::AboveBusAddr { addr: hw_start_addr, bus_addr })?;
+	if !is_seg_aligned(temp) {
+		return Err(SegError::Unaligned(hw_start_addr))
+	}
 	temp >>= 24;
-	return (0x4000 - temp) | 0x4000
+	// 14 bits is as far down as a seg register can move an aperture, and
+	// a bus address is never that high anyway
+	if temp > 0x4000 {
+		return Err(SegError::FarBelowBusAddr { addr: hw_start_addr, bus_addr })
+	}
+
+	return Ok((0x4000 - temp) | 0x4000)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use proptest::prelude::*;
+
+	proptest! {
+		#[test]
+		fn every_seg_value_roundtrips(seg in 0..=SEG_MAX, bus_addr in any::<u64>()) {
+			// the same guarantee selftest checks exhaustively for the
+			// apertures' own bus addresses, but for any bus address
+			prop_assert_eq!(verify_roundtrip(seg, bus_addr), Ok(()));
+		}
+
+		#[test]
+		fn whole_granules_below_the_bus_addr_roundtrip(granules in 0..=0x4000u64,
+							       bus_addr in any::<u64>()) {
+			let hw_start_addr = bus_addr.checked_sub(granules * SEG_GRANULARITY);
+			prop_assume!(hw_start_addr.is_some());
+			let hw_start_addr = hw_start_addr.unwrap();
+
+			let seg = hw_start_addr_to_seg(hw_start_addr, bus_addr);
+			prop_assert!(seg.is_ok(), "{:#x} at bus address {:#x}: {:?}", hw_start_addr,
+				     bus_addr, seg);
+			let seg = seg.unwrap();
+			prop_assert!(seg <= SEG_MAX);
+			prop_assert!(!seg_is_treated_as_zero(seg));
+			prop_assert_eq!(seg_to_hw_start_addr(seg, bus_addr), Ok(hw_start_addr));
+		}
+
+		#[test]
+		fn addresses_between_granules_are_refused(granules in 0..=0x4000u64,
+							   offset in 1..SEG_GRANULARITY,
+							   bus_addr in any::<u64>()) {
+			// rounding one of these to a granule would quietly move the
+			// aperture, so there's no seg value for it
+			let hw_start_addr = bus_addr.checked_sub(granules * SEG_GRANULARITY)
+				.and_then(|hw_start_addr| return hw_start_addr.checked_sub(offset));
+			prop_assume!(hw_start_addr.is_some());
+			let hw_start_addr = hw_start_addr.unwrap();
+
+			prop_assert!(hw_start_addr_to_seg(hw_start_addr, bus_addr).is_err());
+		}
+
+		#[test]
+		fn starts_in_a_hole_are_refused(granule in 0..0x80u64) {
+			// 2G of ddr at 0 & 2G more at 4G, with nothing behind the
+			// 2G in between, so there's nothing for an aperture to start on
+			let mut board = MPFS {
+				memory_banks: vec![
+					MemoryBank {
+						name: "ddr4".to_string(),
+						base: 0,
+						size: 0x8000_0000
+					},
+					MemoryBank {
+						name: "lpddr4".to_string(),
+						base: 0x1_0000_0000,
+						size: 0x8000_0000
+					},
+				],
+				..Default::default()
+			};
+			let hw_start_addr = 0x8000_0000 + granule * SEG_GRANULARITY;
+			let result = board.set_hw_start_addr_by_id(hw_start_addr, 1);
+			prop_assert_eq!(result, Err(SegError::InHole {
+				addr: hw_start_addr,
+				hole_start: 0x8000_0000,
+				hole_end: 0x1_0000_0000
+			}));
+			prop_assert_eq!(board.set_hw_start_addr_by_id(0x1_0000_0000, 1), Ok(()));
+		}
+	}
 }
@@ -1,177 +1,772 @@
 // SPDX-License-Identifier: MIT or GPL-2.0
 
-#![allow(clippy::type_complexity)]
-
+use crate::contexts;
 use crate::soc;
+use crate::soc::Aperture;
 use crate::soc::SoC;
+use crate::units;
+use crate::validate::Fix;
+use crate::commands::ParsedCommand;
+use std::path::PathBuf;
+use tracing::debug;
+
+const ASK_MEMORY_PROMPT: &str = "Enter total system memory (e.g. 0x80000000, 2G or 2048M):";
+const PICK_APERTURE_PROMPT: &str = "Enter an aperature ID to edit:";
+
+// the 32-bit non-cached window, which is all that a fabric master with only
+// 32 address bits can reach ddr through
+const FABRIC_32BIT_WINDOW: &str = "seg1_2";
+const FABRIC_64BIT_WINDOW: &str = "seg1_3";
+
+// the answers given to --wizard so far, in plain terms rather than registers
+#[derive(Clone, Debug, Default)]
+pub struct SetupAnswers {
+	pub default_memory: Option<u64>,
+	pub memory: u64,
+	pub rtos: u64,
+	pub fabric: u64,
+}
+
+// where the wizard is up to. each step carries whatever it needs to carry on
+// from there, rather than leaving it on the board for the next one to find
+#[derive(Clone, Debug)]
+pub enum Step {
+	AskMemory,
+	PickAperture,
+	SetHwStart { id: usize },
+	ConfirmSnap { id: usize, snapped: u64 },
+	ConfirmReflow { steps: Vec<soc::ReflowStep> },
+	PickFix { fixes: Vec<Fix> },
+	// --wizard, for whoever doesn't know what a seg register is
+	SetupBoard,
+	SetupMemory { answers: SetupAnswers },
+	SetupRtos { answers: SetupAnswers },
+	SetupFabric { answers: SetupAnswers },
+	SetupFabric32 { answers: SetupAnswers },
+	// questions that have to be answered before carrying on, each with
+	// wherever the wizard was up to so that it can go back there after
+	ConfirmSave { quit: bool, resume: Box<State> },
+	ConfirmOverwrite { warning: String, quit: bool, resume: Box<State> },
+	ConfirmReload { file: PathBuf, resume: Box<State> },
+	ConfirmQuit { resume: Box<State> },
+}
+
+// something that only the caller can do, e.g. writing the config, once the
+// question asking whether to has been answered
+#[derive(Clone, Debug, PartialEq)]
+pub enum Action {
+	// save, asking about the changes first unless that's been done
+	Save { quit: bool, confirmed: bool },
+	// save over whatever the overwrite check found
+	Overwrite { quit: bool },
+	Load(PathBuf),
+	Quit,
+}
+
+// something for the wizard to deal with
+#[derive(Debug)]
+pub enum Event {
+	// whatever was entered at the prompt
+	Input(String),
+	// a command, which can be entered at any prompt
+	Command(ParsedCommand),
+}
+
+// what dealing with an event does to the wizard
+#[derive(Debug)]
+pub enum Transition {
+	// on to another step, which asks its own question
+	To(Step),
+	// ask the same thing again, saying what was wrong with the answer
+	Retry(String),
+	// back to the start, saying how it went
+	Done(String),
+	// back to where it was before the question, with whatever the answer
+	// asked the caller to do
+	Resume(Box<State>, Option<Action>),
+}
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct State {
-	state_id: States,
-	previous_state_id: States,
-	pub command_text: String
+	step: Step,
+	pub command_text: String,
+	action: Option<Action>,
 }
+
 impl Default for State {
 	fn default() -> State {
 		return State {
-			state_id: States::Init,
-			previous_state_id: States::Exit,
-			command_text: "Press Enter to begin...".to_string()
+			step: Step::AskMemory,
+			command_text: ASK_MEMORY_PROMPT.to_string(),
+			action: None,
 		}
 	}
 }
 
-#[derive(Copy, Clone)]
-#[derive(PartialEq)]
-#[derive(Debug)]
-pub enum States {
-	Init,
-	SelectAperature,
-	WaitForInput,
-	SelectOperation,
-	Exit
+impl State {
+	// for when the total system memory is already known, e.g. from a dtb,
+	// so there is no need to ask for it
+	pub fn skip_init() -> State
+	{
+		return after_command(String::new())
+	}
+
+	// offer a list of fixes to pick from, for "fix" on its own
+	pub fn pick_fix(board: &soc::MPFS, fixes: Vec<Fix>) -> State
+	{
+		let step = Step::PickFix { fixes };
+		let command_text = step.prompt(board);
+		return State { step, command_text, action: None }
+	}
+
+	// the questions asked by --wizard, which finish by laying out the board
+	pub fn setup() -> State
+	{
+		return State::ask(Step::SetupBoard)
+	}
+
+	// none of the questions before saving, reloading or quitting need the
+	// board to ask them
+	fn ask(step: Step) -> State
+	{
+		let command_text = step.prompt(&soc::MPFS::default());
+		return State { step, command_text, action: None }
+	}
+
+	pub fn confirm_save(self, quit: bool) -> State
+	{
+		return State::ask(Step::ConfirmSave { quit, resume: Box::new(self) })
+	}
+
+	pub fn confirm_overwrite(self, warning: String, quit: bool) -> State
+	{
+		return State::ask(Step::ConfirmOverwrite { warning, quit, resume: Box::new(self) })
+	}
+
+	pub fn confirm_reload(self, file: PathBuf) -> State
+	{
+		return State::ask(Step::ConfirmReload { file, resume: Box::new(self) })
+	}
+
+	pub fn confirm_quit(self) -> State
+	{
+		return State::ask(Step::ConfirmQuit { resume: Box::new(self) })
+	}
+
+	// whether what's entered next answers a question rather than being a
+	// command
+	pub fn is_confirming(&self) -> bool
+	{
+		return matches!(self.step, Step::ConfirmSave { .. } | Step::ConfirmOverwrite { .. } |
+				Step::ConfirmReload { .. } | Step::ConfirmQuit { .. })
+	}
+
+	// whether the changes about to be saved should be on show
+	pub fn is_confirming_save(&self) -> bool
+	{
+		return matches!(self.step, Step::ConfirmSave { .. })
+	}
+
+	// Esc backs out of a question without answering it
+	pub fn cancel(self) -> State
+	{
+		match self.step {
+			Step::ConfirmSave { resume, .. } | Step::ConfirmOverwrite { resume, .. } |
+			Step::ConfirmReload { resume, .. } | Step::ConfirmQuit { resume } => {
+				return *resume
+			}
+			_ => return self,
+		}
+	}
+
+	// what the last answer asked to be done, if anything, only the once
+	pub fn take_action(&mut self) -> Option<Action>
+	{
+		return self.action.take()
+	}
+
+	pub fn in_setup(&self) -> bool
+	{
+		return matches!(self.step, Step::SetupBoard | Step::SetupMemory { .. } |
+				Step::SetupRtos { .. } | Step::SetupFabric { .. } |
+				Step::SetupFabric32 { .. })
+	}
+
+	// whether whatever is entered next picks the aperture to edit
+	pub fn wants_aperture_id(&self) -> bool
+	{
+		return matches!(self.step, Step::PickAperture)
+	}
+
+	pub fn handle(self, board: &mut soc::MPFS, event: Event) -> State
+	{
+		let transition = self.step.handle(board, event);
+		debug!("{:?}: {:?}", self.step, transition);
+		match transition {
+			Transition::To(step) => {
+				let command_text = step.prompt(board);
+				return State { step, command_text, action: None }
+			}
+			Transition::Retry(command_text) => return State { command_text, ..self },
+			Transition::Done(message) => return after_command(message),
+			Transition::Resume(state, action) => return State { action, ..*state },
+		}
+	}
 }
 
-fn init_handler
-(current_state: State, board: &mut soc::MPFS, input: Option<String>) -> State
+pub fn after_command(message: String) -> State
 {
-	board.total_system_memory = 0x8000_0000;
+	// a command can be entered at any prompt, afterwards drop back to the
+	// start of the wizard rather than trying to resume it part way through
+	let mut command_text = PICK_APERTURE_PROMPT.to_string();
+	if !message.is_empty() {
+		command_text = format!("{} {}", message, command_text);
+	}
 
 	return State {
-		state_id: States::WaitForInput,
-		previous_state_id: current_state.state_id,
-		command_text: "Enter total system memory in hex:".to_string()
+		step: Step::PickAperture,
+		command_text,
+		action: None,
 	}
 }
 
-fn select_aperature_handler
-(current_state: State, board: &mut soc::MPFS, input: Option<String>) -> State
-{	
-	return State {
-		state_id: States::WaitForInput,
-		previous_state_id: current_state.state_id,
-		command_text: "Enter an aperature ID to edit:".to_string()
+impl Step {
+	fn prompt(&self, board: &soc::MPFS) -> String
+	{
+		match self {
+			Step::AskMemory => return ASK_MEMORY_PROMPT.to_string(),
+			Step::PickAperture => return PICK_APERTURE_PROMPT.to_string(),
+			Step::SetHwStart { id } => {
+				return format!("Set hardware start address for {}:",
+					       board.memory_apertures[*id].description())
+			}
+			Step::ConfirmSnap { id, snapped } => {
+				return format!("A seg register can only move {} in steps of 16 MiB, \
+					       use {:#x} instead? (y/n)",
+					       board.memory_apertures[*id].reg_name, snapped)
+			}
+			Step::ConfirmReflow { steps } => {
+				let mut prompt = "Resizing created overlaps, proposed reflow:".to_string();
+				for step in steps {
+					prompt += &format!(
+						" {}: {:#x} -> {:#x};",
+						board.memory_apertures[step.id].reg_name,
+						step.old_hw_start_addr,
+						step.new_hw_start_addr
+					);
+				}
+				return prompt + " Apply? (y/n)"
+			}
+			Step::PickFix { fixes } => {
+				let mut prompt = "Suggested fixes:".to_string();
+				for (number, fix) in fixes.iter().enumerate() {
+					prompt += &format!(" {}: {};", number + 1, fix.describe(board));
+				}
+				return prompt + " Apply which? (a number, all or n)"
+			}
+			Step::SetupBoard => {
+				return format!("Which board is it? One of {}, or \"other\":",
+					       soc::board_preset_names())
+			}
+			Step::SetupMemory { answers } => {
+				let prompt = "How much DDR memory does it have? (e.g. 2G)";
+				match answers.default_memory {
+					Some(memory) => {
+						return format!("{} Press enter for {}:", prompt,
+							       units::format_size(memory))
+					}
+					None => return format!("{}:", prompt),
+				}
+			}
+			Step::SetupRtos { .. } => {
+				return "Is anything running alongside Linux, e.g. an RTOS on the \
+					fourth core? If so how much memory does it need (e.g. \
+					256M), otherwise press enter:".to_string()
+			}
+			Step::SetupFabric { .. } => {
+				return "How much memory do buffers shared with the FPGA fabric \
+					need? (e.g. 64M, in steps of 16M) Press enter for none:"
+					.to_string()
+			}
+			Step::SetupFabric32 { .. } => {
+				return "Does anything in the fabric that uses them only have 32-bit \
+					addresses? (y/n)".to_string()
+			}
+			Step::ConfirmSave { .. } => return "Save these changes? (y/n)".to_string(),
+			Step::ConfirmOverwrite { warning, .. } => {
+				return format!("{} Overwrite it? (y/n)", warning)
+			}
+			Step::ConfirmReload { file, .. } => {
+				return format!("Discard changes & reload {}? (y/n)", file.display())
+			}
+			Step::ConfirmQuit { .. } => return "Save changes? (y/n/cancel)".to_string(),
+		}
+	}
+
+	pub fn handle(&self, board: &mut soc::MPFS, event: Event) -> Transition
+	{
+		let input = match event {
+			Event::Input(input) => input,
+			Event::Command(command) => return run_command(board, command),
+		};
+
+		match self {
+			Step::AskMemory => return ask_memory(board, &input),
+			Step::PickAperture => return pick_aperture(board, &input),
+			Step::SetHwStart { id } => return set_hw_start(board, *id, &input),
+			Step::ConfirmSnap { id, snapped } => {
+				if !is_yes(&input) {
+					return Transition::To(Step::PickAperture)
+				}
+
+				if let Err(error) = board.set_hw_start_addr_by_id(*snapped, *id) {
+					return Transition::Done(format!("{}.", error))
+				}
+				return Transition::To(Step::PickAperture)
+			}
+			Step::ConfirmReflow { steps } => {
+				if !is_yes(&input) || board.apply_reflow(steps).is_ok() {
+					return Transition::To(Step::PickAperture)
+				}
+
+				return Transition::Done("Reflow moved an aperture past the end of system \
+							 memory, some apertures were not moved.".to_string())
+			}
+			Step::PickFix { fixes } => return pick_fix(board, fixes, &input),
+			Step::SetupBoard => return setup_board(&input),
+			Step::SetupMemory { answers } => return setup_memory(answers, &input),
+			Step::SetupRtos { answers } => return setup_rtos(answers, &input),
+			Step::SetupFabric { answers } => return setup_fabric(board, answers, &input),
+			Step::SetupFabric32 { answers } => {
+				return setup_layout(board, answers, is_yes(&input))
+			}
+			Step::ConfirmSave { quit, resume } => {
+				let action = Action::Save { quit: *quit, confirmed: true };
+				return resume_if_yes(resume, action, &input)
+			}
+			Step::ConfirmOverwrite { quit, resume, .. } => {
+				let action = Action::Overwrite { quit: *quit };
+				return resume_if_yes(resume, action, &input)
+			}
+			Step::ConfirmReload { file, resume } => {
+				return resume_if_yes(resume, Action::Load(file.clone()), &input)
+			}
+			Step::ConfirmQuit { resume } => return confirm_quit(resume, &input),
+		}
 	}
 }
 
-fn wait_for_input_handler
-(current_state: State, board: &mut soc::MPFS, input: Option<String>) -> State
-{	
-	let mut next_state = State {
-		state_id: States::WaitForInput,
-		previous_state_id: current_state.previous_state_id,
-		command_text: current_state.command_text
-	};
+fn is_yes(answer: &str) -> bool
+{
+	return answer.trim().eq_ignore_ascii_case("y")
+}
+
+fn resume_if_yes(resume: &State, action: Action, input: &str) -> Transition
+{
+	let action = Some(action).filter(|_| return is_yes(input));
+	return Transition::Resume(Box::new(resume.clone()), action)
+}
 
-	if input.is_none() {
-		return next_state;
+fn confirm_quit(resume: &State, input: &str) -> Transition
+{
+	// saving goes through the usual save, so that the changes & anything
+	// it would overwrite still get asked about. anything else cancels
+	let resume = Box::new(resume.clone());
+	match input.trim().to_ascii_lowercase().as_str() {
+		"y" => return Transition::Resume(resume, Some(Action::Save { quit: true,
+									   confirmed: false })),
+		"n" => return Transition::Resume(resume, Some(Action::Quit)),
+		_ => return Transition::Resume(resume, None),
 	}
+}
 
-	if current_state.previous_state_id == States::Init {
-		let memory_raw: String = input.unwrap();
-		let memory_trimmed = memory_raw.trim_start_matches("0x");
-		let memory = u64::from_str_radix(memory_trimmed, 16);
-		if memory.is_err() {
-			next_state.command_text = format!(
-					"Invalid amount of system memory ({}). \
-					Please enter a hex number",
-					memory_raw
-				);
-			next_state.state_id = States::WaitForInput;
-			return next_state;
-		}
+fn ask_memory(board: &mut soc::MPFS, input: &str) -> Transition
+{
+	let memory = units::parse_size(input);
+	if memory.is_err() {
+		return Transition::Retry(format!(
+			"Invalid amount of system memory ({}). \
+			Please enter a size, e.g. 0x80000000 or 2G",
+			input
+		))
+	}
 
-		board.total_system_memory = memory.unwrap();
+	board.total_system_memory = memory.unwrap();
+	return Transition::To(Step::PickAperture)
+}
 
-		next_state.state_id = States::SelectAperature;
-		return next_state;
+fn pick_aperture(board: &mut soc::MPFS, input: &str) -> Transition
+{
+	let id = u64::from_str_radix(input.trim_start_matches("0x"), 16);
+	if id.is_err() {
+		return Transition::Retry("Invalid aperature ID. Please enter a hex number:"
+			.to_string())
 	}
 
-	if current_state.previous_state_id == States::SelectAperature {
-		let aperature_id_raw: String = input.unwrap();
-		let aperature_id_trimmed = aperature_id_raw.trim_start_matches("0x");
-		let aperature_id = u64::from_str_radix(aperature_id_trimmed, 16);
-		if aperature_id.is_err() {
-			next_state.command_text = "Invalid address. Please enter a hex number"
-				.to_string();
-			next_state.state_id = States::SelectOperation;
-			return next_state;
-		}
-		let id = aperature_id.unwrap();
-		if id as usize >= board.memory_apertures.len() {
-			next_state.state_id = States::SelectAperature;
-			next_state.command_text = "Invalid aperature ID".to_string();
-			return next_state;
+	let id = id.unwrap() as usize;
+	if id >= board.memory_apertures.len() {
+		return Transition::Retry("Invalid aperature ID. Please enter another:".to_string())
+	}
+
+	// remembered on the board as well, as + & - move whichever was last picked
+	board.current_aperture_id = Some(id);
+	return Transition::To(Step::SetHwStart { id })
+}
+
+fn set_hw_start(board: &mut soc::MPFS, id: usize, input: &str) -> Transition
+{
+	let addr = units::parse_size(input);
+	if addr.is_err() {
+		return Transition::Retry("Invalid address. Please enter an address, \
+			e.g. 0x40000000 or 1G".to_string())
+	}
+
+	let addr = addr.unwrap();
+	if addr != board.memory_apertures[id].bus_addr && !soc::is_seg_aligned(addr) {
+		let snapped = soc::snap_to_seg_granularity(addr);
+		return Transition::To(Step::ConfirmSnap { id, snapped })
+	}
+
+	if let Err(error) = board.set_hw_start_addr_by_id(addr, id) {
+		return Transition::Retry(format!("Hardware start address {}. Please enter a new \
+			hex number:", error))
+	}
+
+	return Transition::To(Step::PickAperture)
+}
+
+fn pick_fix(board: &mut soc::MPFS, fixes: &[Fix], input: &str) -> Transition
+{
+	let input = input.trim();
+	if input.eq_ignore_ascii_case("n") {
+		return Transition::To(Step::PickAperture)
+	}
+
+	// each is applied as it was suggested, without checking again in
+	// between, so one that no longer makes sense just fails
+	let picked: Vec<&Fix> = match input.eq_ignore_ascii_case("all") {
+		true => fixes.iter().collect(),
+		false => match input.parse::<usize>() {
+			Ok(number) if number >= 1 && number <= fixes.len() => vec![&fixes[number - 1]],
+			_ => {
+				return Transition::Retry(format!("Invalid fix. Please enter 1 to {}, \
+								 all or n:", fixes.len()))
+			}
+		},
+	};
+
+	let mut applied = 0;
+	for fix in &picked {
+		if fix.apply(board).is_ok() {
+			applied += 1;
 		}
-		
-		board.current_aperture_id = Some(id as usize);
+	}
+
+	return Transition::Done(format!("Applied {} of {} fixes.", applied, picked.len()))
+}
 
-		next_state.state_id = States::SelectOperation;
-		return next_state;
+fn optional_size(input: &str) -> Result<u64, String>
+{
+	// nothing at all is as good as a 0
+	if input.trim().is_empty() {
+		return Ok(0)
 	}
 
-	if current_state.previous_state_id == States::SelectOperation {
-		let addr_raw: String = input.unwrap();
-		let addr_trimmed = addr_raw.trim_start_matches("0x");
-		let addr = u64::from_str_radix(addr_trimmed, 16);
-		if addr.is_err() {
-			next_state.command_text = "Invalid address. Please enter a hex number"
-				.to_string();
-			next_state.state_id = States::SelectOperation;
-			return next_state;
+	return units::parse_size(input)
+		.map_err(|_| return format!("Invalid size ({}). Please enter a size, e.g. 256M, or \
+					    press enter for none:", input))
+}
+
+fn setup_board(input: &str) -> Transition
+{
+	let input = input.trim();
+	let mut answers = SetupAnswers::default();
+	if !input.eq_ignore_ascii_case("other") {
+		let preset = soc::find_board_preset(input);
+		if preset.is_none() {
+			return Transition::Retry(format!("Unknown board ({}). Please enter one of \
+							 {}, or \"other\":", input,
+							 soc::board_preset_names()))
 		}
+		answers.default_memory = preset.map(|preset| return preset.total_memory);
+	}
 
-		let current_aperture_id = board.current_aperture_id.unwrap();
-		if board.set_hw_start_addr_by_id(addr.unwrap(), current_aperture_id).is_err() {
-			next_state.command_text = "Hardware start address was greater than the \
-				total system memory. Please enter a new hex number:".to_string();
-			next_state.state_id = current_state.state_id;
-			next_state.previous_state_id = States::SelectOperation;
+	return Transition::To(Step::SetupMemory { answers })
+}
 
-			return next_state;
+fn setup_memory(answers: &SetupAnswers, input: &str) -> Transition
+{
+	let memory = match (input.trim().is_empty(), answers.default_memory) {
+		(true, Some(memory)) => Ok(memory),
+		_ => units::parse_size(input),
+	};
+	match memory {
+		Ok(memory) if memory > 0 => {
+			let answers = SetupAnswers { memory, ..answers.clone() };
+			return Transition::To(Step::SetupRtos { answers })
+		}
+		_ => {
+			return Transition::Retry(format!("Invalid amount of memory ({}). Please \
+							 enter a size, e.g. 2G:", input))
 		}
-	
-		next_state.state_id = States::SelectAperature;
-		return next_state;
+	}
+}
+
+fn setup_rtos(answers: &SetupAnswers, input: &str) -> Transition
+{
+	let rtos = match optional_size(input) {
+		Ok(rtos) => rtos,
+		Err(error) => return Transition::Retry(error),
+	};
+	if rtos >= answers.memory {
+		return Transition::Retry(format!("That leaves nothing for Linux out of the {}. \
+						 Please enter a smaller size:",
+						 units::format_size(answers.memory)))
 	}
 
-	return next_state
+	let answers = SetupAnswers { rtos, ..answers.clone() };
+	return Transition::To(Step::SetupFabric { answers })
 }
 
-fn select_operation_handler
-(current_state: State, board: &mut soc::MPFS, input: Option<String>) -> State
-{	
-	let current_aperture_id = board.current_aperture_id.unwrap();
+fn setup_fabric(board: &mut soc::MPFS, answers: &SetupAnswers, input: &str) -> Transition
+{
+	let fabric = match optional_size(input) {
+		Ok(fabric) => fabric,
+		Err(error) => return Transition::Retry(error),
+	};
+	if !soc::is_seg_aligned(fabric) {
+		let rounded = fabric.div_ceil(soc::SEG_GRANULARITY) * soc::SEG_GRANULARITY;
+		return Transition::Retry(format!("The buffers have to be a multiple of 16M, e.g. \
+						 {}. Please enter another size:",
+						 units::format_size(rounded)))
+	}
+	if answers.rtos + fabric >= answers.memory {
+		return Transition::Retry(format!("That leaves nothing for Linux out of the {}. \
+						 Please enter a smaller size:",
+						 units::format_size(answers.memory)))
+	}
+
+	let answers = SetupAnswers { fabric, ..answers.clone() };
+	if fabric == 0 {
+		return setup_layout(board, &answers, false)
+	}
+	return Transition::To(Step::SetupFabric32 { answers })
+}
+
+fn setup_layout(board: &mut soc::MPFS, answers: &SetupAnswers, fabric_32bit: bool) -> Transition
+{
+	// the same scheme as the reference designs: every aperture starts at
+	// the bottom of ddr, so they're all views of the same memory & none of
+	// them overlap, & the memory is shared out by the contexts. the fabric
+	// buffers go at the bottom, where a non-cached window reaches them, the
+	// rtos at the top & linux gets the rest
+	let defaults = soc::MPFS::default();
+	board.memory_apertures = defaults.memory_apertures;
+	board.total_system_memory = answers.memory;
+	for id in 0..board.memory_apertures.len() {
+		if board.set_hw_start_addr_by_id(0, id).is_err() {
+			return Transition::Retry(format!("{} can't start at 0x0.",
+							 board.memory_apertures[id].reg_name))
+		}
+	}
+
+	// the 32-bit window if it'll do, to leave the 64-bit one alone
+	let window = |reg_name: &str| {
+		return board.apertures().iter()
+			.find(|aperture| return aperture.reg_name == reg_name)
+			.map(|aperture| return (aperture.bus_addr, aperture.aperture_size))
+			.unwrap_or_default()
+	};
+	let narrow = window(FABRIC_32BIT_WINDOW);
+	let (bus_addr, window_size) = match fabric_32bit || answers.fabric <= narrow.1 {
+		true => narrow,
+		false => window(FABRIC_64BIT_WINDOW),
+	};
+	if answers.fabric > window_size {
+		return Transition::Retry(format!("Only {} can be reached with 32-bit addresses. \
+						 Answer n, or start again with smaller buffers:",
+						 units::format_size(window_size)))
+	}
 
-	let next_state = State {
-		state_id: States::WaitForInput,
-		previous_state_id: current_state.state_id,
-		command_text: format!(
-			"Set hardware start address for {}:", 
-			board.memory_apertures[current_aperture_id].description
-		)
+	let linux_harts = match answers.rtos {
+		0 => vec!["u54_1", "u54_2", "u54_3", "u54_4"],
+		_ => vec!["u54_1", "u54_2", "u54_3"],
 	};
+	let rtos_start = answers.memory - answers.rtos;
+	board.contexts = vec![contexts::Context {
+		name: "linux".to_string(),
+		harts: linux_harts.iter().map(|hart| return hart.to_string()).collect(),
+		memory: vec![(answers.fabric, rtos_start - answers.fabric)],
+	}];
+	if answers.rtos > 0 {
+		board.contexts.push(contexts::Context {
+			name: "rtos".to_string(),
+			harts: vec!["u54_4".to_string()],
+			memory: vec![(rtos_start, answers.rtos)],
+		});
+	}
 
-	return next_state
+	board.bus_masters = Vec::new();
+	if answers.fabric > 0 {
+		board.bus_masters.push(soc::BusMaster {
+			name: "fabric".to_string(),
+			windows: vec![(bus_addr, answers.fabric)],
+		});
+	}
+
+	return Transition::Done(format!("Laid out {} for {} contexts.",
+					units::format_size(answers.memory), board.contexts.len()))
 }
 
-fn exit_handler(current_state: State, board: &mut soc::MPFS, input: Option<String>) -> State
+fn find_aperture_id(board: &soc::MPFS, reg_name: &str) -> Option<usize>
 {
-	std::process::exit(0)
+	return board.memory_apertures
+		.iter()
+		.position(|aperture| return aperture.reg_name == reg_name)
 }
 
-const STATE_HANDLERS: [fn(State, &mut soc::MPFS, input: Option<String>) -> State; 5] = [
-	init_handler,
-	select_aperature_handler,
-	wait_for_input_handler,
-	select_operation_handler,
-	exit_handler
-];
+pub fn apply_command(board: &mut soc::MPFS, command: ParsedCommand) -> Result<String, String>
+{
+	// the board side of a command, shared by the tui and batch mode. a
+	// resize that needs a reflow leaves it in board.pending_reflow, and an
+	// unaligned address leaves the aligned alternative in board.pending_snap
+	match command {
+		ParsedCommand::Set { reg_name, addr } => {
+			let id = find_aperture_id(board, &reg_name)
+				.ok_or(format!("Unknown register {}.", reg_name))?;
+
+			if addr != board.memory_apertures[id].bus_addr && !soc::is_seg_aligned(addr) {
+				let snapped = soc::snap_to_seg_granularity(addr);
+				board.pending_snap = Some((id, snapped));
+				return Err(format!(
+					"{:#x} is not 16 MiB aligned, the nearest valid address is {:#x}.",
+					addr, snapped
+				))
+			}
+
+			if let Err(error) = board.set_hw_start_addr_by_id(addr, id) {
+				return Err(format!("Can't set {} to {:#x}: {}.", reg_name, addr,
+						   error))
+			}
+
+			return Ok(format!("{} set to {:#x}.", reg_name, addr))
+		}
+		ParsedCommand::SetSeg { reg_name, seg } => {
+			let id = find_aperture_id(board, &reg_name)
+				.ok_or(format!("Unknown register {}.", reg_name))?;
+			let banks = board.banks();
+			let aperture = &mut board.memory_apertures[id];
+			aperture.set_hw_start_addr_from_seg(&banks, seg)
+				.map_err(|error| return format!("Can't set {} to seg {:#x}: {}.",
+								 reg_name, seg, error))?;
+
+			return Ok(format!("{} set to seg {:#x}: {}.", reg_name, seg,
+					  soc::describe_seg(seg, aperture.bus_addr)))
+		}
+		ParsedCommand::Size { reg_name, size } => {
+			let id = find_aperture_id(board, &reg_name)
+				.ok_or(format!("Unknown register {}.", reg_name))?;
+
+			let old_size = board.apertures()[id].aperture_size;
+			if board.set_aperture_size_by_id(size, id).is_err() {
+				return Err("Invalid size, it must be a multiple \
+					of 16 MiB.".to_string())
+			}
+
+			// nothing can be moved up far enough to make room, so the
+			// size it was is kept
+			let reflow = board.propose_reflow(id);
+			if let Err(error) = reflow {
+				board.set_aperture_size_by_id(old_size, id)
+					.map_err(|error| return error.to_string())?;
+				return Err(format!("{} can't be resized to {:#x}: {}", reg_name,
+						   size, error))
+			}
+
+			let reflow = reflow.unwrap();
+			if !reflow.is_empty() {
+				board.pending_reflow = Some(reflow);
+			}
 
-pub fn get_next_state(current_state: State, board: &mut soc::MPFS, input: Option<String>) -> State 
+			return Ok(format!("{} resized to {:#x}.", reg_name, size))
+		}
+		ParsedCommand::Mem(size) => {
+			if !board.memory_banks.is_empty() {
+				return Err("The memory banks in the config say how much memory \
+					    there is.".to_string())
+			}
+			board.total_system_memory = size;
+			return Ok(format!("Total system memory set to {:#x}.", size))
+		}
+		ParsedCommand::Reset => {
+			let defaults = soc::MPFS::default();
+			board.memory_apertures = defaults.memory_apertures;
+			board.current_aperture_id = None;
+			board.pending_reflow = None;
+			return Ok("Apertures reset to their defaults.".to_string())
+		}
+		ParsedCommand::Via { label, reg_name } => {
+			find_aperture_id(board, &reg_name)
+				.ok_or(format!("Unknown register {}.", reg_name))?;
+
+			board.node_apertures.retain(|(node, _)| return *node != label);
+			board.node_apertures.push((label.clone(), reg_name.clone()));
+			return Ok(format!("{} now goes through {}.", label, reg_name))
+		}
+		ParsedCommand::Auto(size) => {
+			let total_system_memory = board.total_system_memory;
+			if size.is_some() && !board.memory_banks.is_empty() {
+				return Err("The memory banks in the config say how much memory \
+					    there is, so auto can't be given a size.".to_string())
+			}
+			if let Some(size) = size {
+				board.total_system_memory = size;
+			}
+
+			let layout = board.propose_auto_layout();
+			if let Err(error) = layout {
+				board.total_system_memory = total_system_memory;
+				return Err(error)
+			}
+
+			let layout = layout.unwrap();
+			if board.apply_reflow(&layout).is_err() {
+				return Err("Failed to lay out the apertures.".to_string())
+			}
+
+			return Ok(format!("Laid out {} apertures back to back.", layout.len()))
+		}
+		// anything touching files or the clipboard is dealt with by the
+		// caller, as the state machine doesn't know about them
+		ParsedCommand::Save { .. } | ParsedCommand::Load(_) | ParsedCommand::Dtb(_) |
+		ParsedCommand::Copy | ParsedCommand::Export { .. } | ParsedCommand::Node { .. } |
+		ParsedCommand::RemoveNode(_) | ParsedCommand::Overlay(_) => {
+			return Ok(String::new())
+		}
+		// as is fixing, as problems with dt nodes need the nodes, & placing
+		// the named regions, which live alongside them
+		ParsedCommand::Fix(_) | ParsedCommand::Place => return Ok(String::new()),
+	}
+}
+
+fn run_command(board: &mut soc::MPFS, command: ParsedCommand) -> Transition
 {
-	let state_id = current_state.state_id as usize;
-	let next_state = STATE_HANDLERS[state_id](current_state, board, input);
+	debug!("running {:?}", command);
+	let result = apply_command(board, command);
+	debug!("{:?}", result);
+
+	// anything left on the board to be confirmed is taken up by the step
+	// that asks about it
+	if let Some((id, snapped)) = board.pending_snap.take() {
+		return Transition::To(Step::ConfirmSnap { id, snapped })
+	}
 
-	return next_state
-}
\ No newline at end of file
+	if let Some(steps) = board.pending_reflow.take() {
+		return Transition::To(Step::ConfirmReflow { steps })
+	}
+
+	match result {
+		Ok(message) => return Transition::Done(message),
+		Err(message) => return Transition::Done(message),
+	}
+}
@@ -0,0 +1,75 @@
+// SPDX-License-Identifier: MIT or GPL-2.0
+
+#![allow(unused_variables)]
+#![allow(dead_code)]
+#![deny(clippy::implicit_return)]
+#![allow(clippy::needless_return)]
+
+use std::fs;
+use std::io;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::sync::OnceLock;
+
+// "-" in place of a file name reads stdin or writes stdout, so that the tool
+// can sit in the middle of a pipeline. stdin can only be read the once, but
+// e.g. the config gets read again when saving, so whatever came in is kept
+pub const STDIO: &str = "-";
+
+static STDIN: OnceLock<Vec<u8>> = OnceLock::new();
+
+pub fn is_stdio(file: impl AsRef<Path>) -> bool
+{
+	return file.as_ref() == Path::new(STDIO)
+}
+
+fn stdin() -> io::Result<&'static [u8]>
+{
+	if let Some(contents) = STDIN.get() {
+		return Ok(contents)
+	}
+
+	let mut contents = Vec::new();
+	io::stdin().read_to_end(&mut contents)?;
+	return Ok(STDIN.get_or_init(|| return contents))
+}
+
+pub fn read(file: impl AsRef<Path>) -> io::Result<Vec<u8>>
+{
+	if is_stdio(&file) {
+		return Ok(stdin()?.to_vec())
+	}
+
+	return fs::read(file)
+}
+
+pub fn read_to_string(file: impl AsRef<Path>) -> io::Result<String>
+{
+	return String::from_utf8(read(file)?)
+		.map_err(|error| return io::Error::new(io::ErrorKind::InvalidData, error))
+}
+
+pub fn write(file: impl AsRef<Path>, contents: &[u8]) -> io::Result<()>
+{
+	if is_stdio(&file) {
+		let mut stdout = io::stdout();
+		stdout.write_all(contents)?;
+		return stdout.flush()
+	}
+
+	return fs::write(file, contents)
+}
+
+pub fn check_one_stdin(files: &[(&str, Option<&Path>)]) -> Result<(), String>
+{
+	// there's only the one stdin to go around
+	let from_stdin: Vec<&str> = files.iter()
+		.filter(|(_, file)| return file.map(is_stdio).unwrap_or(false))
+		.map(|(option, _)| return *option)
+		.collect();
+	if from_stdin.len() > 1 {
+		return Err(format!("only one of {} can be read from stdin", from_stdin.join(" & ")))
+	}
+
+	return Ok(())
+}
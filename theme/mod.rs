@@ -0,0 +1,271 @@
+// SPDX-License-Identifier: MIT or GPL-2.0
+
+#![allow(unused_variables)]
+#![allow(dead_code)]
+#![deny(clippy::implicit_return)]
+#![allow(clippy::needless_return)]
+
+use serde_yaml::Value;
+use std::cell::RefCell;
+use tui::style::{Color, Modifier, Style};
+
+// the colours that everything on screen is drawn with. they're needed by
+// nearly every bit of drawing code, so rather than pass them all around they
+// live here like the messages do
+#[derive(Clone, Debug)]
+pub struct Theme {
+	// apertures & dt nodes, in order, wrapping around if there are more
+	pub palette: Vec<Color>,
+	pub highlight: Style,
+	pub border: Color,
+	// disabled & no-map nodes, & memory that nothing reaches
+	pub dim: Color,
+	// anything that's wrong, like unmapped reserved memory
+	pub warning: Color,
+}
+
+pub const PRESET_NAMES: [&str; 3] = ["default", "colour-blind", "monochrome"];
+
+thread_local! {
+	static THEME: RefCell<Theme> = RefCell::new(preset("default").unwrap());
+	// a --theme on the command line wins over anything in a config
+	static FORCED: RefCell<bool> = const { RefCell::new(false) };
+}
+
+pub fn preset(name: &str) -> Option<Theme>
+{
+	let reversed = Style::default().add_modifier(Modifier::REVERSED);
+
+	match name {
+		"default" => return Some(Theme {
+			palette: vec![
+				Color::LightRed,
+				Color::LightGreen,
+				Color::LightMagenta,
+				Color::LightYellow,
+				Color::LightCyan,
+				Color::LightBlue,
+			],
+			highlight: reversed,
+			border: Color::Reset,
+			dim: Color::DarkGray,
+			warning: Color::Red,
+		}),
+		// okabe & ito's palette, which stays distinguishable with the
+		// common kinds of colour blindness
+		"colour-blind" => return Some(Theme {
+			palette: vec![
+				Color::Rgb(230, 159, 0),
+				Color::Rgb(86, 180, 233),
+				Color::Rgb(0, 158, 115),
+				Color::Rgb(240, 228, 66),
+				Color::Rgb(0, 114, 178),
+				Color::Rgb(213, 94, 0),
+				Color::Rgb(204, 121, 167),
+			],
+			highlight: reversed.add_modifier(Modifier::BOLD),
+			border: Color::Reset,
+			dim: Color::DarkGray,
+			warning: Color::Rgb(213, 94, 0),
+		}),
+		// for terminals without colour, everything is left to the
+		// terminal's own foreground & the labels have to do the work
+		"monochrome" => return Some(Theme {
+			palette: vec![Color::Reset],
+			highlight: reversed,
+			border: Color::Reset,
+			dim: Color::Reset,
+			warning: Color::Reset,
+		}),
+		_ => return None,
+	}
+}
+
+fn unknown_preset(name: &str) -> String
+{
+	return format!("unknown theme {}, expected one of {}", name, PRESET_NAMES.join(", "))
+}
+
+pub fn parse_colour(colour: &str) -> Result<Color, String>
+{
+	// a name, "#rrggbb" or the index of one of the terminal's 256 colours
+	let name = colour.trim().to_ascii_lowercase().replace(['_', ' '], "-");
+	if let Some(hex) = name.strip_prefix('#') {
+		let rgb = u32::from_str_radix(hex, 16)
+			.ok()
+			.filter(|_| return hex.len() == 6)
+			.ok_or(format!("{} is not a #rrggbb colour", colour))?;
+		return Ok(Color::Rgb((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8))
+	}
+
+	if let Ok(index) = name.parse::<u8>() {
+		return Ok(Color::Indexed(index))
+	}
+
+	match name.as_str() {
+		"reset" | "default" => return Ok(Color::Reset),
+		"black" => return Ok(Color::Black),
+		"red" => return Ok(Color::Red),
+		"green" => return Ok(Color::Green),
+		"yellow" => return Ok(Color::Yellow),
+		"blue" => return Ok(Color::Blue),
+		"magenta" => return Ok(Color::Magenta),
+		"cyan" => return Ok(Color::Cyan),
+		"gray" | "grey" => return Ok(Color::Gray),
+		"dark-gray" | "dark-grey" => return Ok(Color::DarkGray),
+		"light-red" => return Ok(Color::LightRed),
+		"light-green" => return Ok(Color::LightGreen),
+		"light-yellow" => return Ok(Color::LightYellow),
+		"light-blue" => return Ok(Color::LightBlue),
+		"light-magenta" => return Ok(Color::LightMagenta),
+		"light-cyan" => return Ok(Color::LightCyan),
+		"white" => return Ok(Color::White),
+		_ => return Err(format!("{} is not a colour", colour)),
+	}
+}
+
+fn parse_colour_value(value: &Value, key: &str) -> Result<Option<Color>, String>
+{
+	if value.is_null() {
+		return Ok(None)
+	}
+
+	// unquoted numbers have already been turned into numbers by the parser
+	let colour = value.as_str()
+		.map(|colour| return colour.to_string())
+		.or(value.as_u64().map(|index| return index.to_string()))
+		.ok_or(format!("theme: {}: {:?} is not a colour", key, value))?;
+
+	return parse_colour(&colour)
+		.map(Some)
+		.map_err(|error| return format!("theme: {}: {}", key, error))
+}
+
+pub fn parse_theme(value: &Value) -> Result<Theme, String>
+{
+	// theme:
+	//   preset: colour-blind
+	//   palette: [light-red, "#e69f00", 208]
+	//   highlight: yellow
+	//   border: dark-gray
+	let name = value["preset"].as_str().unwrap_or("default");
+	let mut theme = preset(name).ok_or(format!("theme: {}", unknown_preset(name)))?;
+
+	if let Some(palette) = value["palette"].as_sequence() {
+		let mut colours: Vec<Color> = Vec::new();
+		for colour in palette {
+			colours.push(parse_colour_value(colour, "palette")?.unwrap_or(Color::Reset));
+		}
+		if colours.is_empty() {
+			return Err("theme: palette needs at least one colour".to_string())
+		}
+		theme.palette = colours;
+	}
+
+	if let Some(highlight) = parse_colour_value(&value["highlight"], "highlight")? {
+		theme.highlight = Style::default().fg(highlight).add_modifier(Modifier::REVERSED);
+	}
+	if let Some(border) = parse_colour_value(&value["border"], "border")? {
+		theme.border = border;
+	}
+	if let Some(dim) = parse_colour_value(&value["dim"], "dim")? {
+		theme.dim = dim;
+	}
+	if let Some(warning) = parse_colour_value(&value["warning"], "warning")? {
+		theme.warning = warning;
+	}
+
+	return Ok(theme)
+}
+
+pub fn force_preset(name: &str) -> Result<(), String>
+{
+	let theme = preset(name).ok_or(unknown_preset(name))?;
+	THEME.with(|current| return *current.borrow_mut() = theme);
+	FORCED.with(|forced| return *forced.borrow_mut() = true);
+	return Ok(())
+}
+
+pub fn apply_config(value: &Value) -> Result<(), String>
+{
+	// a config without a theme goes back to the default, so that loading
+	// one config after another doesn't leave the last one's colours behind
+	let theme = parse_theme(value)?;
+	if FORCED.with(|forced| return *forced.borrow()) {
+		return Ok(())
+	}
+
+	THEME.with(|current| return *current.borrow_mut() = theme);
+	return Ok(())
+}
+
+pub fn palette_colour(index: usize) -> Color
+{
+	return THEME.with(|theme| {
+		let palette = &theme.borrow().palette;
+		return palette[index % palette.len()]
+	})
+}
+
+pub fn highlight() -> Style
+{
+	return THEME.with(|theme| return theme.borrow().highlight)
+}
+
+pub fn border() -> Style
+{
+	return THEME.with(|theme| return Style::default().fg(theme.borrow().border))
+}
+
+pub fn dim() -> Color
+{
+	return THEME.with(|theme| return theme.borrow().dim)
+}
+
+pub fn warning() -> Color
+{
+	return THEME.with(|theme| return theme.borrow().warning)
+}
+
+pub fn css_colour(colour: Color) -> String
+{
+	// for drawing outside of the terminal, e.g. an svg, where "the
+	// terminal's own colour" isn't a thing so reset is taken to be black
+	match colour {
+		Color::Rgb(red, green, blue) => return format!("#{:02x}{:02x}{:02x}", red, green, blue),
+		Color::Reset | Color::Black => return "black".to_string(),
+		Color::Red => return "#cd0000".to_string(),
+		Color::Green => return "#00cd00".to_string(),
+		Color::Yellow => return "#cdcd00".to_string(),
+		Color::Blue => return "#0000ee".to_string(),
+		Color::Magenta => return "#cd00cd".to_string(),
+		Color::Cyan => return "#00cdcd".to_string(),
+		Color::Gray => return "#a0a0a0".to_string(),
+		Color::DarkGray => return "#7f7f7f".to_string(),
+		Color::LightRed => return "#ff5555".to_string(),
+		Color::LightGreen => return "#55dd55".to_string(),
+		Color::LightYellow => return "#e6c800".to_string(),
+		Color::LightBlue => return "#5c5cff".to_string(),
+		Color::LightMagenta => return "#ff55ff".to_string(),
+		Color::LightCyan => return "#33cccc".to_string(),
+		Color::White => return "#e5e5e5".to_string(),
+		// the 6x6x6 cube & the greys of the 256 colour palette, with the
+		// first 16 being near enough to grey that it doesn't matter
+		Color::Indexed(index) if index >= 232 => {
+			let level = 8 + (index - 232) as u32 * 10;
+			return format!("#{:02x}{:02x}{:02x}", level, level, level)
+		}
+		Color::Indexed(index) if index >= 16 => {
+			let cube = (index - 16) as u32;
+			let level = |value: u32| {
+				if value == 0 {
+					return 0
+				}
+				return 55 + value * 40
+			};
+			return format!("#{:02x}{:02x}{:02x}", level(cube / 36), level(cube / 6 % 6),
+				       level(cube % 6))
+		}
+		Color::Indexed(_) => return "gray".to_string(),
+	}
+}
@@ -0,0 +1,127 @@
+// SPDX-License-Identifier: MIT or GPL-2.0
+
+#![allow(unused_variables)]
+#![allow(dead_code)]
+#![deny(clippy::implicit_return)]
+#![allow(clippy::needless_return)]
+
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug)]
+pub struct ParseSizeError {
+	input: String,
+}
+
+impl fmt::Display for ParseSizeError {
+fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+	return write!(f, "\"{}\" is not a valid size or address", self.input)
+}
+}
+
+impl Error for ParseSizeError {
+	fn source(&self) -> Option<&(dyn Error + 'static)> {
+	return None
+}
+}
+
+const UNITS: [(&str, u64); 4] = [
+	("k", 1 << 10),
+	("m", 1 << 20),
+	("g", 1 << 30),
+	("t", 1 << 40),
+];
+
+pub fn parse_size(input: &str) -> Result<u64, ParseSizeError>
+{
+	// accepts "0x" prefixed hex, plain decimal, or a (possibly fractional)
+	// decimal followed by a binary unit, e.g. 0x80000000, 2147483648, 2G,
+	// 1536M or 1.5GiB
+	let error = ParseSizeError { input: input.to_string() };
+	let trimmed = input.trim().replace('_', "");
+
+	if let Some(hex) = trimmed.strip_prefix("0x").or(trimmed.strip_prefix("0X")) {
+		return u64::from_str_radix(hex, 16).or(Err(error))
+	}
+
+	let lowercase = trimmed.to_lowercase();
+	let without_suffix = lowercase
+		.trim_end_matches('b')
+		.trim_end_matches('i');
+
+	for (unit, multiplier) in UNITS {
+		if let Some(number) = without_suffix.strip_suffix(unit) {
+			let number = number.trim();
+			if let Ok(whole) = number.parse::<u64>() {
+				return whole.checked_mul(multiplier).ok_or(error)
+			}
+
+			let fraction = number.parse::<f64>();
+			if fraction.is_err() || fraction.as_ref().unwrap().is_sign_negative() {
+				return Err(error)
+			}
+
+			let size = fraction.unwrap() * multiplier as f64;
+			if size.fract() != 0.0 || size > u64::MAX as f64 {
+				return Err(error)
+			}
+			return Ok(size as u64)
+		}
+	}
+
+	return lowercase.parse::<u64>().or(Err(error))
+}
+
+// how numbers get displayed, cycled through with a single key in the tui
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Radix {
+	Hex,
+	Decimal,
+	Units,
+}
+
+impl Default for Radix {
+	fn default() -> Radix {
+		return Radix::Hex
+	}
+}
+
+impl Radix {
+	pub fn next(self) -> Radix
+	{
+		match self {
+			Radix::Hex => return Radix::Decimal,
+			Radix::Decimal => return Radix::Units,
+			Radix::Units => return Radix::Hex,
+		}
+	}
+}
+
+pub fn format_size(value: u64) -> String
+{
+	// the biggest unit that the value is a whole number of, falling back to
+	// a couple of decimal places of the biggest unit that fits at all
+	for (unit, multiplier) in UNITS.iter().rev() {
+		if value >= *multiplier && value & (multiplier - 1) == 0 {
+			return format!("{} {}iB", value / multiplier, unit.to_uppercase())
+		}
+	}
+
+	for (unit, multiplier) in UNITS.iter().rev() {
+		if value >= *multiplier {
+			return format!("{:.2} {}iB", value as f64 / *multiplier as f64,
+				       unit.to_uppercase())
+		}
+	}
+
+	return format!("{} B", value)
+}
+
+pub fn format_number(value: u64, radix: Radix, hex_width: usize) -> String
+{
+	match radix {
+		Radix::Hex => return format!("{:#0width$x}", value, width = hex_width),
+		Radix::Decimal => return value.to_string(),
+		Radix::Units => return format_size(value),
+	}
+}
@@ -0,0 +1,728 @@
+// SPDX-License-Identifier: MIT or GPL-2.0
+
+#![allow(unused_variables)]
+#![allow(dead_code)]
+#![deny(clippy::implicit_return)]
+#![allow(clippy::needless_return)]
+
+use serde_json::{json, Value};
+
+use crate::dt::{MemoryNode, MemoryRegion, ReservedMemoryNode};
+use crate::plan::hex;
+use crate::regions;
+use crate::regions::NamedRegion;
+use crate::soc;
+use crate::soc::{Aperture, MemoryAperture, PerformanceClass, SegError, SoC};
+
+// fixing one thing can break another, so fixing everything gives up after
+// this many goes rather than chasing its tail
+const MAX_FIX_ROUNDS: usize = 32;
+
+// the smallest ddr that's fitted in practice, & the most the mpfs ddr
+// controller can address. real memory is a power of two multiple of the first
+const MIN_DDR_SIZE: u64 = 512 << 20;
+const MAX_DDR_SIZE: u64 = 16 << 30;
+
+pub const REPORT_VERSION: u64 = 1;
+
+// how "check" prints what it finds, for people or for other tools
+#[derive(clap::ArgEnum, Clone, Copy, Debug, PartialEq)]
+pub enum ReportFormat {
+	Text,
+	Json,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ProblemKind {
+	Overlap,
+	InvalidStart,
+	UnalignedStart,
+	ForbiddenAlias,
+	ShadowedWindow,
+	UnmappedNode,
+	UnmappedReserved,
+	InMemoryHole,
+	AmbiguousNode,
+	UncachedNode,
+	UnreachableWindow,
+	UnreachableDma32,
+	ExecutableWcb,
+	UnusualMemorySize,
+}
+
+// in the order they're summed up in
+const KINDS: [ProblemKind; 14] = [
+	ProblemKind::Overlap,
+	ProblemKind::InvalidStart,
+	ProblemKind::UnalignedStart,
+	ProblemKind::ForbiddenAlias,
+	ProblemKind::ShadowedWindow,
+	ProblemKind::UnmappedNode,
+	ProblemKind::UnmappedReserved,
+	ProblemKind::InMemoryHole,
+	ProblemKind::AmbiguousNode,
+	ProblemKind::UncachedNode,
+	ProblemKind::UnreachableWindow,
+	ProblemKind::UnreachableDma32,
+	ProblemKind::ExecutableWcb,
+	ProblemKind::UnusualMemorySize,
+];
+
+impl ProblemKind {
+	// singular & plural, for counting them up
+	fn names(&self) -> (&'static str, &'static str)
+	{
+		match self {
+			ProblemKind::Overlap => return ("overlap", "overlaps"),
+			ProblemKind::InvalidStart => return ("invalid start", "invalid starts"),
+			ProblemKind::UnalignedStart => return ("unaligned start", "unaligned starts"),
+			ProblemKind::ForbiddenAlias => return ("forbidden alias", "forbidden aliases"),
+			ProblemKind::ShadowedWindow => return ("shadowed 32-bit window",
+							       "shadowed 32-bit windows"),
+			ProblemKind::UnmappedNode => return ("node unmapped", "nodes unmapped"),
+			ProblemKind::UnmappedReserved => return ("reserved node unmapped",
+								 "reserved nodes unmapped"),
+			ProblemKind::InMemoryHole => return ("in memory hole", "in memory holes"),
+			ProblemKind::AmbiguousNode => return ("ambiguous node", "ambiguous nodes"),
+			ProblemKind::UncachedNode => return ("uncached node", "uncached nodes"),
+			ProblemKind::UnreachableWindow => return ("window unreachable",
+								  "windows unreachable"),
+			ProblemKind::UnreachableDma32 => return ("dma32 region unreachable",
+								 "dma32 regions unreachable"),
+			ProblemKind::ExecutableWcb => return ("executable wcb window",
+							      "executable wcb windows"),
+			ProblemKind::UnusualMemorySize => return ("unusual memory size",
+								  "unusual memory sizes"),
+		}
+	}
+
+	pub fn name(&self) -> &'static str
+	{
+		return self.names().0
+	}
+
+	// the name as it appears in the json report, e.g. "node-unmapped"
+	pub fn key(&self) -> String
+	{
+		return self.name().replace(' ', "-")
+	}
+
+	fn count(&self, count: usize) -> String
+	{
+		let (singular, plural) = self.names();
+		if count == 1 {
+			return format!("1 {}", singular)
+		}
+
+		return format!("{} {}", count, plural)
+	}
+}
+
+// a change that would put a problem right, if there's an obvious one
+#[derive(Clone, Debug, PartialEq)]
+pub enum Fix {
+	Move { id: usize, hw_start_addr: u64 },
+	Resize { id: usize, size: u64 },
+}
+
+impl Fix {
+	pub fn describe(&self, board: &soc::MPFS) -> String
+	{
+		match self {
+			Fix::Move { id, hw_start_addr } => {
+				return format!("move {} to {:#x}", board.apertures()[*id].reg_name,
+					       hw_start_addr)
+			}
+			Fix::Resize { id, size } => {
+				return format!("resize {} to {:#x}", board.apertures()[*id].reg_name,
+					       size)
+			}
+		}
+	}
+
+	pub fn json(&self, board: &soc::MPFS) -> Value
+	{
+		match self {
+			Fix::Move { id, hw_start_addr } => return json!({
+				"action": "move",
+				"reg-name": board.apertures()[*id].reg_name,
+				"hardware-addr": hex(*hw_start_addr),
+				"description": self.describe(board),
+			}),
+			Fix::Resize { id, size } => return json!({
+				"action": "resize",
+				"reg-name": board.apertures()[*id].reg_name,
+				"aperture-size": hex(*size),
+				"description": self.describe(board),
+			}),
+		}
+	}
+
+	pub fn apply(&self, board: &mut soc::MPFS) -> Result<(), SegError>
+	{
+		match self {
+			Fix::Move { id, hw_start_addr } => {
+				return board.set_hw_start_addr_by_id(*hw_start_addr, *id)
+			}
+			Fix::Resize { id, size } => return board.set_aperture_size_by_id(*size, *id),
+		}
+	}
+}
+
+// one thing wrong with the configuration, & the register or node at fault
+#[derive(Clone, Debug)]
+pub struct Problem {
+	pub kind: ProblemKind,
+	pub subject: String,
+	pub detail: String,
+	pub fix: Option<Fix>,
+}
+
+fn align_up(addr: u64) -> u64
+{
+	return addr.div_ceil(soc::SEG_GRANULARITY).saturating_mul(soc::SEG_GRANULARITY)
+}
+
+fn align_down(addr: u64) -> u64
+{
+	return addr & !(soc::SEG_GRANULARITY - 1)
+}
+
+fn move_to(board: &soc::MPFS, id: usize, hw_start_addr: u64) -> Option<Fix>
+{
+	// only worth suggesting if a seg register can actually do it
+	let aperture = &board.apertures()[id];
+	if hw_start_addr >= board.total_memory() ||
+	   soc::hw_start_addr_to_seg(hw_start_addr, aperture.bus_addr).is_err() {
+		return None
+	}
+
+	return Some(Fix::Move { id, hw_start_addr })
+}
+
+fn find_gap(board: &soc::MPFS, id: usize) -> Option<Fix>
+{
+	// the lowest place the aperture fits in ddr without landing on any of
+	// the others, trying the start of each bank & just after each of the
+	// others. it has to start in a bank, not in a hole between them
+	let total = board.total_memory();
+	let banks = board.banks();
+	let size = board.apertures()[id].aperture_size;
+	let others: Vec<(u64, u64)> = board.hw_ranges()
+		.into_iter()
+		.filter(|(aperture, _, _)| return aperture.reg_name != board.apertures()[id].reg_name)
+		.map(|(_, start, end)| return (start, end))
+		.collect();
+
+	let mut candidates: Vec<u64> = others.iter().map(|(_, end)| return align_up(*end)).collect();
+	candidates.extend(banks.iter().map(|(start, _)| return align_up(*start)));
+	candidates.sort_unstable();
+
+	return candidates.into_iter()
+		.filter(|start| return soc::containing_bank(&banks, *start).is_ok())
+		.filter(|start| return start.checked_add(size).map(|end| return end <= total)
+			.unwrap_or(false))
+		.filter(|start| return others.iter()
+			.all(|(other_start, other_end)| return start + size <= *other_start ||
+							  start >= other_end))
+		.find_map(|start| return move_to(board, id, start))
+}
+
+fn fix_region(board: &soc::MPFS, region: &MemoryRegion) -> Option<Fix>
+{
+	let apertures = board.apertures();
+	let host = region.host_aperture(apertures);
+	if let Some(host) = host {
+		// reachable, but past the end of memory or in a hole between
+		// banks, so bring the aperture down far enough for the region to
+		// fit in the highest bank that it can
+		let id = apertures.iter().position(|aperture| return aperture.reg_name == host.reg_name)?;
+		let offset = region.address - host.bus_addr;
+		return board.banks().iter().rev()
+			.filter_map(|(start, end)| {
+				let latest = end.checked_sub(offset)?.checked_sub(region.size)?;
+				let latest = align_down(latest);
+				return Some(latest).filter(|latest| return latest + offset >= *start)
+			})
+			.find_map(|latest| return move_to(board, id, latest))
+	}
+
+	// out of reach of every aperture, so grow whichever starts closest
+	// below it on the bus until it covers the region
+	let (id, aperture) = apertures.iter()
+		.enumerate()
+		.filter(|(_, aperture)| return aperture.bus_addr <= region.address)
+		.max_by_key(|(_, aperture)| return aperture.bus_addr)?;
+	let size = align_up(region.address.checked_add(region.size)? - aperture.bus_addr);
+	return Some(Fix::Resize { id, size })
+}
+
+fn region_mapped(region: &MemoryRegion, apertures: &[MemoryAperture], banks: &[(u64, u64)])
+-> bool
+{
+	// like MemoryRegion::is_mapped, but quietly, as this runs on every edit
+	return region.host_aperture(apertures)
+		.and_then(|aperture| return aperture.get_region_hw_start_addr(region.address,
+									      region.size))
+		.map(|start| return soc::in_one_bank(banks, start,
+						     start.saturating_add(region.size)))
+		.unwrap_or(false)
+}
+
+fn check_apertures(board: &soc::MPFS, problems: &mut Vec<Problem>)
+{
+	let banks = board.banks();
+	let apertures = board.apertures();
+	for (id, aperture) in apertures.iter().enumerate() {
+		if aperture.is_unused(&banks) {
+			continue;
+		}
+
+		let start = aperture.get_hw_start_addr(&banks);
+		if let Err(error) = aperture.seg().and(start) {
+			problems.push(Problem {
+				kind: ProblemKind::InvalidStart,
+				subject: aperture.reg_name.clone(),
+				detail: error.to_string(),
+				fix: None,
+			});
+			continue;
+		}
+
+		if aperture.hardware_addr != aperture.bus_addr &&
+		   !soc::is_seg_aligned(aperture.hardware_addr) {
+			problems.push(Problem {
+				kind: ProblemKind::UnalignedStart,
+				subject: aperture.reg_name.clone(),
+				detail: format!("{:#x} can't be encoded in a seg register, the \
+						 nearest that can is {:#x}",
+						aperture.hardware_addr,
+						soc::snap_to_seg_granularity(aperture.hardware_addr)),
+				fix: move_to(board, id,
+					     soc::snap_to_seg_granularity(aperture.hardware_addr)),
+			});
+		}
+	}
+
+	// apertures that see the same ddr are aliases where they overlap, which
+	// are only a problem if they've been forbidden
+	let ranges = board.hw_ranges();
+	for (index, (aperture, start, end)) in ranges.iter().enumerate() {
+		for (other, other_start, other_end) in &ranges[index + 1..] {
+			if aperture.aliases(other) || start >= other_end || other_start >= end {
+				continue;
+			}
+
+			// the one that starts later gets moved out of the way, or if
+			// there's nowhere for it to go the earlier one is cut short
+			let (earlier, later, later_start) = match start < other_start {
+				true => (aperture, other, other_start),
+				false => (other, aperture, start),
+			};
+			let id_of = |reg_name: &String| return apertures.iter()
+				.position(|aperture| return aperture.reg_name == *reg_name);
+			let size = align_down(later_start - start.min(other_start));
+			let shrink = id_of(&earlier.reg_name)
+				.filter(|_| return size > 0)
+				.map(|id| return Fix::Resize { id, size });
+			problems.push(Problem {
+				kind: ProblemKind::Overlap,
+				subject: aperture.reg_name.clone(),
+				detail: format!("overlaps {} from {:#x} to {:#x}", other.reg_name,
+						start.max(other_start), end.min(other_end)),
+				fix: id_of(&later.reg_name)
+					.and_then(|id| return find_gap(board, id))
+					.or(shrink),
+			});
+		}
+	}
+
+	for (id, aperture) in apertures.iter().enumerate() {
+		if let Some(first) = board.alias_of(id).filter(|_| return board.forbid_aliases) {
+			problems.push(Problem {
+				kind: ProblemKind::ForbiddenAlias,
+				subject: aperture.reg_name.clone(),
+				detail: format!("maps the same memory as {}", apertures[first].reg_name),
+				fix: None,
+			});
+		}
+	}
+}
+
+fn check_shadowed_window(board: &soc::MPFS, problems: &mut Vec<Problem>)
+{
+	// the 32-bit cached window is there for whatever can't put out a 64-bit
+	// address, so it has to start where the 64-bit one does for the low
+	// memory linux has through the 64-bit window to be the same ddr through
+	// both. otherwise a buffer handed to a 32-bit dma master is somewhere else
+	let banks = board.banks();
+	let apertures = board.apertures();
+	let cached = |wide: bool| return apertures.iter()
+		.position(|aperture| {
+			return aperture.performance_class == PerformanceClass::CachedAxi
+			       && (aperture.bus_addr >= 1 << 32) == wide
+			       && soc::containing_bank(&banks, aperture.hardware_addr).is_ok()
+		});
+	let (narrow, wide) = match (cached(false), cached(true)) {
+		(Some(narrow), Some(wide)) => (&apertures[narrow], wide),
+		_ => return,
+	};
+
+	let aperture = &apertures[wide];
+	if aperture.hardware_addr == narrow.hardware_addr {
+		return
+	}
+
+	problems.push(Problem {
+		kind: ProblemKind::ShadowedWindow,
+		subject: narrow.reg_name.clone(),
+		detail: format!("starts at {:#x} in ddr but {} starts at {:#x}, so {:#x} + n & \
+				 {:#x} + n are different memory, & 32-bit dma into memory that's \
+				 been given out through {} lands somewhere else. give {} the same \
+				 hardware address as {}, making the two aliases",
+				narrow.hardware_addr, aperture.reg_name, aperture.hardware_addr,
+				narrow.bus_addr, aperture.bus_addr, aperture.reg_name,
+				aperture.reg_name, narrow.reg_name),
+		fix: move_to(board, wide, narrow.hardware_addr),
+	});
+}
+
+fn check_nodes(board: &soc::MPFS, nodes: &[MemoryNode], reserved_nodes: &[ReservedMemoryNode],
+	       problems: &mut Vec<Problem>)
+{
+	let banks = board.banks();
+	let apertures = board.apertures();
+	for node in nodes.iter().filter(|node| return node.enabled) {
+		for region in &node.regions {
+			if !region_mapped(region, apertures, &banks) {
+				problems.push(Problem {
+					kind: ProblemKind::UnmappedNode,
+					subject: node.label.clone(),
+					detail: format!("{:#x} (size {:#x}) doesn't land in ddr",
+							region.address, region.size),
+					fix: fix_region(board, region),
+				});
+			}
+		}
+	}
+
+	for node in reserved_nodes {
+		for region in &node.regions {
+			if !region_mapped(region, apertures, &banks) {
+				problems.push(Problem {
+					kind: ProblemKind::UnmappedReserved,
+					subject: node.label.clone(),
+					detail: format!("{:#x} (size {:#x}) doesn't land in ddr",
+							region.address, region.size),
+					fix: fix_region(board, region),
+				});
+			}
+		}
+	}
+
+	for node in nodes.iter().filter(|node| return node.enabled) {
+		let ambiguous = node.regions.iter()
+			.filter(|region| return region.is_ambiguous(apertures));
+		for region in ambiguous {
+			problems.push(Problem {
+				kind: ProblemKind::AmbiguousNode,
+				subject: node.label.clone(),
+				detail: format!("could go through {}, pick one with \"via\"",
+						region.hosting_apertures(apertures).join(" or ")),
+				fix: None,
+			});
+		}
+	}
+
+	for node in nodes.iter().filter(|node| return node.enabled) {
+		let uncached = node.regions.iter()
+			.filter(|region| return region.is_uncached(apertures));
+		for region in uncached {
+			if let Some(aperture) = region.host_aperture(apertures) {
+				problems.push(Problem {
+					kind: ProblemKind::UncachedNode,
+					subject: node.label.clone(),
+					detail: format!("goes through {}, which is {}", aperture.reg_name,
+							aperture.performance_class.name()),
+					fix: None,
+				});
+			}
+		}
+	}
+}
+
+fn check_bus_masters(board: &soc::MPFS, problems: &mut Vec<Problem>)
+{
+	for master in &board.bus_masters {
+		for (window_start, window_size) in &master.windows {
+			let window = soc::BusMaster {
+				name: master.name.clone(),
+				windows: vec![(*window_start, *window_size)],
+			};
+			if board.master_hw_ranges(&window).is_empty() {
+				problems.push(Problem {
+					kind: ProblemKind::UnreachableWindow,
+					subject: master.name.clone(),
+					detail: format!("the window at {:#x} doesn't reach ddr through \
+							 any aperture", window_start),
+					fix: None,
+				});
+			}
+		}
+	}
+}
+
+fn check_access(board: &soc::MPFS, problems: &mut Vec<Problem>)
+{
+	// instruction fetches through the write combining buffer can see stores
+	// that haven't been drained to ddr yet, so code has no business there
+	for aperture in board.apertures() {
+		if aperture.performance_class == PerformanceClass::WriteCombining &&
+		   aperture.access.execute {
+			problems.push(Problem {
+				kind: ProblemKind::ExecutableWcb,
+				subject: aperture.reg_name.clone(),
+				detail: format!("has {} access, but code run through the \
+						 write-combining window can miss stores still in \
+						 the buffer", aperture.access),
+				fix: None,
+			});
+		}
+	}
+}
+
+pub fn check_regions(board: &soc::MPFS, regions: &[NamedRegion]) -> Vec<Problem>
+{
+	// the named regions only exist with --regions, so unlike everything
+	// else these are checked separately, by whatever has them
+	let mut problems: Vec<Problem> = Vec::new();
+	for (index, region) in regions.iter().enumerate() {
+		if let Some(detail) = regions::check_dma32(board, regions, index) {
+			problems.push(Problem {
+				kind: ProblemKind::UnreachableDma32,
+				subject: region.name.clone(),
+				detail,
+				fix: None,
+			});
+		}
+	}
+
+	return problems
+}
+
+fn check_memory_size(board: &soc::MPFS, problems: &mut Vec<Problem>)
+{
+	// most likely a typo, or a size in the wrong units, but emulators &
+	// the like can have whatever they want
+	if board.any_memory_size {
+		return
+	}
+
+	// with several banks it's each of them that's real ddr, not the total
+	let mut sizes = vec![("total memory".to_string(), board.total_memory())];
+	if !board.memory_banks.is_empty() {
+		sizes = board.memory_banks.iter()
+			.map(|bank| return (bank.name.clone(), bank.size))
+			.collect();
+	}
+
+	for (subject, size) in sizes {
+		let detail = if size > MAX_DDR_SIZE {
+			format!("{:#x} is more than the 16 GiB the ddr controller supports", size)
+		} else if size < MIN_DDR_SIZE || !size.is_power_of_two() {
+			format!("{:#x} isn't a power of two multiple of 512 MiB, as real ddr \
+				 would be", size)
+		} else {
+			continue;
+		};
+
+		problems.push(Problem {
+			kind: ProblemKind::UnusualMemorySize,
+			subject,
+			detail,
+			fix: None,
+		});
+	}
+}
+
+fn check_memory_holes(board: &soc::MPFS, nodes: &[MemoryNode],
+		      reserved_nodes: &[ReservedMemoryNode], problems: &mut Vec<Problem>)
+{
+	// an aperture can span a hole between banks, as long as there's some
+	// memory for it to reach, but nothing can be put in one
+	let holes = board.holes();
+	if holes.is_empty() {
+		return
+	}
+
+	for (aperture, start, end) in board.hw_ranges() {
+		if board.is_hole(start, end) {
+			problems.push(Problem {
+				kind: ProblemKind::InMemoryHole,
+				subject: aperture.reg_name.clone(),
+				detail: format!("its window, {:#x} - {:#x}, is all between memory \
+						 banks, so reaches no memory", start, end),
+				fix: None,
+			});
+		}
+	}
+
+	let regions = nodes.iter()
+		.filter(|node| return node.enabled)
+		.flat_map(|node| return node.regions.iter()
+			  .map(|region| return (&node.label, region)))
+		.chain(reserved_nodes.iter()
+		       .flat_map(|node| return node.regions.iter()
+				 .map(|region| return (&node.label, region))));
+	for (label, region) in regions {
+		let start = match region.get_hw_start_addr(board.apertures()) {
+			Ok(start) => start,
+			Err(_) => continue,
+		};
+		let end = start.saturating_add(region.size);
+		let hole = holes.iter().find(|(hole_start, hole_end)| {
+			return start < *hole_end && *hole_start < end
+		});
+		if let Some((hole_start, hole_end)) = hole {
+			problems.push(Problem {
+				kind: ProblemKind::InMemoryHole,
+				subject: label.clone(),
+				detail: format!("{:#x} - {:#x} of it is between memory banks, where \
+						 there's no memory", start.max(*hole_start),
+						end.min(*hole_end)),
+				fix: None,
+			});
+		}
+	}
+}
+
+pub fn check(board: &soc::MPFS, nodes: &[MemoryNode], reserved_nodes: &[ReservedMemoryNode])
+-> Vec<Problem>
+{
+	let mut problems: Vec<Problem> = Vec::new();
+	check_memory_size(board, &mut problems);
+	check_apertures(board, &mut problems);
+	check_shadowed_window(board, &mut problems);
+	check_nodes(board, nodes, reserved_nodes, &mut problems);
+	check_memory_holes(board, nodes, reserved_nodes, &mut problems);
+	check_bus_masters(board, &mut problems);
+	check_access(board, &mut problems);
+
+	problems.sort_by_key(|problem| return KINDS.iter()
+		.position(|kind| return *kind == problem.kind));
+	return problems
+}
+
+pub fn summary(problems: &[Problem]) -> String
+{
+	// e.g. "2 overlaps, 1 node unmapped, 1 unaligned start"
+	if problems.is_empty() {
+		return "no problems".to_string()
+	}
+
+	return KINDS.iter()
+		.map(|kind| return (kind, problems.iter()
+			.filter(|problem| return problem.kind == *kind)
+			.count()))
+		.filter(|(_, count)| return *count > 0)
+		.map(|(kind, count)| return kind.count(count))
+		.collect::<Vec<String>>()
+		.join(", ")
+}
+
+pub fn fixes(problems: &[Problem]) -> Vec<Fix>
+{
+	// several problems can share a fix, e.g. both sides of an overlap
+	let mut fixes: Vec<Fix> = Vec::new();
+	for fix in problems.iter().filter_map(|problem| return problem.fix.clone()) {
+		if !fixes.contains(&fix) {
+			fixes.push(fix);
+		}
+	}
+
+	return fixes
+}
+
+pub fn fix_all
+(board: &mut soc::MPFS, nodes: &[MemoryNode], reserved_nodes: &[ReservedMemoryNode]) -> Vec<Fix>
+{
+	// one at a time, checking again after each, as fixing one problem can
+	// change what the others need
+	let mut applied: Vec<Fix> = Vec::new();
+	let mut tried: Vec<Fix> = Vec::new();
+	for _ in 0..MAX_FIX_ROUNDS {
+		let problems = check(board, nodes, reserved_nodes);
+		let fix = fixes(&problems).into_iter().find(|fix| return !tried.contains(fix));
+		let fix = match fix {
+			Some(fix) => fix,
+			None => break,
+		};
+
+		if fix.apply(board).is_ok() {
+			applied.push(fix.clone());
+		}
+		tried.push(fix);
+	}
+
+	return applied
+}
+
+fn memory_map(board: &soc::MPFS) -> Value
+{
+	return board.hw_pieces()
+		.iter()
+		.map(|(start, end, hosts)| {
+			let hosts: Vec<&str> = hosts.iter()
+				.map(|aperture| return aperture.reg_name.as_str())
+				.collect();
+			return json!({
+				"start": hex(*start),
+				"end": hex(*end),
+				"apertures": hosts,
+			})
+		})
+		.collect()
+}
+
+pub fn report(board: &soc::MPFS, problems: &[Problem], fixed: &[Fix]) -> Value
+{
+	// everything "check" works out, for tools that would rather not scrape
+	// the text. addresses are hex strings, as in a plan
+	let total = board.total_memory();
+	let banks = board.banks();
+	let apertures: Value = board.apertures().iter()
+		.map(|aperture| return json!({
+			"reg-name": aperture.reg_name,
+			"description": aperture.description(),
+			"bus-width": aperture.bus_width.bits(),
+			"seg": aperture.seg().ok().map(hex),
+			"bus-addr": hex(aperture.bus_addr),
+			"aperture-size": hex(aperture.aperture_size),
+			"hardware-addr": aperture.get_hw_start_addr(&banks).ok().map(hex),
+			"hardware-end": aperture.get_hw_end_addr(&banks).ok().map(hex),
+			"performance-class": aperture.performance_class.to_string(),
+		}))
+		.collect();
+
+	let diagnostics: Value = problems.iter()
+		.map(|problem| return json!({
+			"kind": problem.kind.key(),
+			"subject": problem.subject,
+			"detail": problem.detail,
+			"fix": problem.fix.as_ref().map(|fix| return fix.json(board)),
+		}))
+		.collect();
+
+	return json!({
+		"report-version": REPORT_VERSION,
+		"tool": env!("CARGO_PKG_NAME"),
+		"tool-version": env!("CARGO_PKG_VERSION"),
+		"total-system-memory": hex(total),
+		"apertures": apertures,
+		"memory-map": memory_map(board),
+		"fixed": fixed.iter().map(|fix| return fix.json(board)).collect::<Value>(),
+		"problems": diagnostics,
+		"summary": summary(problems),
+		"ok": problems.is_empty(),
+	})
+}